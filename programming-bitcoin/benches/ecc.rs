@@ -0,0 +1,114 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use num_bigint::BigUint;
+use primitive_types::U256;
+use programming_bitcoin::ecc::batch_verifier::BatchVerifier;
+use programming_bitcoin::ecc::private_key::PrivateKey;
+use programming_bitcoin::ecc::s256point::S256Point;
+use programming_bitcoin::ecc::scalar::Scalar;
+use programming_bitcoin::ecc::secp256k1_params::S256Params;
+use programming_bitcoin::utils::hash256::hash256;
+
+// Compares the Jacobian/wNAF arithmetic against the original affine
+// double-and-add, which is kept as S256Point::multiply_affine.
+fn scalar_multiplication(c: &mut Criterion) {
+    let g = S256Params::g();
+    let coefficient = U256::from_big_endian(&hash256(b"scalar"));
+
+    let mut group = c.benchmark_group("scalar_multiplication");
+    group.sample_size(10);
+    group.bench_function("affine", |b| {
+        b.iter(|| S256Point::multiply_affine(black_box(&g), black_box(coefficient)))
+    });
+    group.bench_function("jacobian_wnaf", |b| {
+        b.iter(|| S256Point::multiply(black_box(&g), black_box(coefficient)))
+    });
+    group.bench_function("generator_table", |b| {
+        b.iter(|| S256Point::multiply_generator(black_box(coefficient)))
+    });
+    group.finish();
+}
+
+fn signature_verification(c: &mut Criterion) {
+    let private_key = PrivateKey::new(U256::from(12345));
+    let z = hash256(b"Programming Bitcoin!");
+    let signature = private_key.sign(z.clone());
+    let point = private_key.point();
+
+    let mut group = c.benchmark_group("signature_verification");
+    group.sample_size(10);
+    group.bench_function("affine", |b| {
+        b.iter(|| {
+            // The original verify: u*G and v*P computed separately in affine coordinates
+            let n = BigUint::from_bytes_be(&S256Params::n().to_big_endian());
+            let s = BigUint::from_bytes_be(&signature.s().to_big_endian());
+            let s_inv = s.modpow(&(&n - BigUint::from(2u8)), &n);
+            let u = (BigUint::from_bytes_be(&z) * &s_inv) % &n;
+            let v = (BigUint::from_bytes_be(&signature.r().to_big_endian()) * &s_inv) % &n;
+            let u_g = S256Point::multiply_affine(&S256Params::g(), U256::from_big_endian(&u.to_bytes_be()));
+            let v_p = S256Point::multiply_affine(&point, U256::from_big_endian(&v.to_bytes_be()));
+            let total = &u_g + &v_p;
+            total.coordinates().unwrap().0 == signature.r()
+        })
+    });
+    group.bench_function("jacobian", |b| {
+        b.iter(|| point.clone().verify(black_box(z.clone()), black_box(signature.clone())))
+    });
+    group.finish();
+}
+
+// Modular inversion mod n, as done by every sign and verify
+fn scalar_inversion(c: &mut Criterion) {
+    let value = U256::from_big_endian(&hash256(b"inverse"));
+
+    let mut group = c.benchmark_group("scalar_inversion");
+    group.bench_function("biguint", |b| {
+        b.iter(|| {
+            let n = BigUint::from_bytes_be(&S256Params::n().to_big_endian());
+            let a = BigUint::from_bytes_be(&black_box(value).to_big_endian());
+            let inverse = a.modpow(&(&n - BigUint::from(2u8)), &n);
+            U256::from_big_endian(&inverse.to_bytes_be())
+        })
+    });
+    group.bench_function("scalar", |b| {
+        b.iter(|| Scalar::reduce(black_box(value)).invert())
+    });
+    group.finish();
+}
+
+// 64 Schnorr signatures checked one at a time, as one batch on one thread,
+// and split between all cores
+fn batch_verification(c: &mut Criterion) {
+    let mut batch = BatchVerifier::new();
+    for i in 0..64u32 {
+        let private_key = PrivateKey::new(U256::from(1000 + i));
+        let msg = hash256(&i.to_le_bytes());
+        let signature = private_key.sign_schnorr(&msg, [0u8; 32]);
+        batch.add_schnorr(private_key.xonly_public_key(), msg, signature);
+    }
+    let single: Vec<BatchVerifier> = (0..64)
+        .map(|i| {
+            let mut one = BatchVerifier::new();
+            let private_key = PrivateKey::new(U256::from(1000 + i as u32));
+            let msg = hash256(&(i as u32).to_le_bytes());
+            let signature = private_key.sign_schnorr(&msg, [0u8; 32]);
+            one.add_schnorr(private_key.xonly_public_key(), msg, signature);
+            one
+        })
+        .collect();
+
+    let mut group = c.benchmark_group("batch_verification");
+    group.sample_size(10);
+    group.bench_function("individual", |b| {
+        b.iter(|| single.iter().all(|one| one.verify_with_threads(1).is_ok()))
+    });
+    group.bench_function("batch_single_thread", |b| {
+        b.iter(|| black_box(&batch).verify_with_threads(1))
+    });
+    group.bench_function("batch_all_cores", |b| {
+        b.iter(|| black_box(&batch).verify())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, scalar_multiplication, signature_verification, scalar_inversion, batch_verification);
+criterion_main!(benches);
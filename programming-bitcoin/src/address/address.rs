@@ -1,4 +1,6 @@
-use crate::utils::base58::encode_base58_checksum;
+use crate::script::script::{Command, Script};
+use crate::utils::base58::{decode_base58_checksum, encode_base58_checksum};
+use crate::utils::bech32::{decode_segwit_address, encode_segwit_address};
 
 pub fn h160_to_p2sh_address(h160: &str, testnet: bool) -> String {
     let network_prefix: u8 = match testnet {
@@ -19,3 +21,56 @@ pub fn h160_to_p2pkh(h160: &str, testnet: bool) -> String {
     h160_vec.insert(0, network_prefix);
     encode_base58_checksum(&h160_vec)
 }
+
+/// Returns the script_pubkey an address pays to, along with whether it is a
+/// testnet address. Handles base58 P2PKH/P2SH and bech32/bech32m segwit.
+pub fn address_to_script_pubkey(address: &str) -> Result<(Script, bool), String> {
+    let lower = address.to_lowercase();
+    for (hrp, testnet) in [("bc", false), ("tb", true), ("bcrt", true)] {
+        if lower.starts_with(&format!("{}1", hrp)) {
+            let (version, program) = decode_segwit_address(hrp, address)?;
+            // OP_0 is 0x00 and OP_1 to OP_16 are 0x51 to 0x60
+            let version_op = if version == 0 { 0x00 } else { 0x50 + version };
+            return Ok((Script::new(vec![Command::Op(version_op), Command::Data(program)]), testnet));
+        }
+    }
+
+    let payload = decode_base58_checksum(address)?;
+    if payload.len() != 21 {
+        return Err(format!("Invalid base58 address length: {}", payload.len()));
+    }
+    let h160 = payload[1..].to_vec();
+    match payload[0] {
+        0x00 => Ok((Script::p2pkh_script(h160), false)),
+        0x6f => Ok((Script::p2pkh_script(h160), true)),
+        0x05 => Ok((Script::p2sh_script(h160), false)),
+        0xc4 => Ok((Script::p2sh_script(h160), true)),
+        version => Err(format!("Unknown address version byte: {:#04x}", version)),
+    }
+}
+
+/// Returns the address of a script_pubkey, or None if it has no standard
+/// address form (bare multisig, P2PK, OP_RETURN and so on)
+pub fn script_pubkey_to_address(script_pubkey: &Script, testnet: bool) -> Option<String> {
+    let hrp = if testnet { "tb" } else { "bc" };
+    match script_pubkey.commands.as_slice() {
+        // OP_DUP OP_HASH160 <20 bytes> OP_EQUALVERIFY OP_CHECKSIG
+        [Command::Op(0x76), Command::Op(0xa9), Command::Data(h160), Command::Op(0x88), Command::Op(0xac)] if h160.len() == 20 => {
+            let prefix = if testnet { 0x6f } else { 0x00 };
+            Some(encode_base58_checksum(&[&[prefix][..], h160].concat()))
+        }
+        // OP_HASH160 <20 bytes> OP_EQUAL
+        [Command::Op(0xa9), Command::Data(h160), Command::Op(0x87)] if h160.len() == 20 => {
+            let prefix = if testnet { 0xc4 } else { 0x05 };
+            Some(encode_base58_checksum(&[&[prefix][..], h160].concat()))
+        }
+        // OP_0 with a 20 or 32 byte program, or OP_1 to OP_16 with 2 to 40 bytes
+        [Command::Op(0x00), Command::Data(program)] if program.len() == 20 || program.len() == 32 => {
+            Some(encode_segwit_address(hrp, 0, program))
+        }
+        [Command::Op(version @ 0x51..=0x60), Command::Data(program)] if (2..=40).contains(&program.len()) => {
+            Some(encode_segwit_address(hrp, version - 0x50, program))
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,7 @@
+pub mod checksum;
+// Named like script::script and address::address: the module holding the
+// type its parent is named after
+#[allow(clippy::module_inception)]
+pub mod descriptor;
+pub mod error;
+pub mod key;
@@ -0,0 +1,63 @@
+use crate::descriptor::error::DescriptorError;
+
+// The BIP380 checksum is a BCH code like bech32's, but over the characters
+// descriptors use. Each character is a symbol for its position in its group
+// of 32, and every three characters add a symbol for their three groups.
+const INPUT_CHARSET: &str = "0123456789()[],'/*abcdefgh@:$%{}IJKLMNOPQRSTUVWXYZ&+-.;<=>?!^_|~ijklmnopqrstuvwxyzABCDEFGH`#\"\\ ";
+const CHECKSUM_CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const GENERATOR: [u64; 5] = [0xf5dee51989, 0xa9fdca3312, 0x1bab10e32d, 0x3706b1677a, 0x644d626ffd];
+
+fn polymod(c: u64, value: u64) -> u64 {
+    let c0 = c >> 35;
+    let mut c = ((c & 0x7_ffff_ffff) << 5) ^ value;
+    for (i, generator) in GENERATOR.iter().enumerate() {
+        if (c0 >> i) & 1 == 1 {
+            c ^= generator;
+        }
+    }
+    c
+}
+
+/// Returns the eight character checksum of a descriptor, given without the
+/// #checksum suffix
+pub fn descriptor_checksum(descriptor: &str) -> Result<String, DescriptorError> {
+    let mut c = 1;
+    let mut class = 0;
+    let mut class_count = 0;
+    for ch in descriptor.chars() {
+        let position = INPUT_CHARSET.find(ch).ok_or(DescriptorError::InvalidCharacter(ch))? as u64;
+        c = polymod(c, position & 31);
+        class = class * 3 + (position >> 5);
+        class_count += 1;
+        if class_count == 3 {
+            c = polymod(c, class);
+            class = 0;
+            class_count = 0;
+        }
+    }
+    if class_count > 0 {
+        c = polymod(c, class);
+    }
+    for _ in 0..8 {
+        c = polymod(c, 0);
+    }
+    c ^= 1;
+    Ok((0..8).map(|i| CHECKSUM_CHARSET[((c >> (5 * (7 - i))) & 31) as usize] as char).collect())
+}
+
+/// Splits off and verifies the checksum, if the descriptor has one.
+/// Returns the descriptor without it.
+pub fn strip_checksum(descriptor: &str) -> Result<&str, DescriptorError> {
+    match descriptor.split_once('#') {
+        Some((body, checksum)) => {
+            if descriptor_checksum(body)? != checksum {
+                return Err(DescriptorError::InvalidChecksum);
+            }
+            Ok(body)
+        }
+        None => {
+            descriptor_checksum(descriptor)?;
+            Ok(descriptor)
+        }
+    }
+}
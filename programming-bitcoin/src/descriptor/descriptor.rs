@@ -0,0 +1,377 @@
+use std::fmt;
+use std::io::Cursor;
+use std::ops::Range;
+use sha2::{Digest, Sha256};
+
+use crate::address::address::{address_to_script_pubkey, script_pubkey_to_address};
+use crate::descriptor::checksum::{descriptor_checksum, strip_checksum};
+use crate::descriptor::error::DescriptorError;
+use crate::descriptor::key::DescriptorKey;
+use crate::script::op::encode_num;
+use crate::script::script::{Command, Script};
+use crate::taproot::spend_info::TaprootSpendInfo;
+use crate::taproot::tap_tree::TapTree;
+use crate::utils::hash160::hash160;
+use crate::utils::varint::encode_varint;
+
+/// An output script descriptor (BIP380-386), such as
+/// wpkh([d34db33f/84h/0h/0h]xpub.../0/*). A descriptor whose keys have
+/// wildcards stands for one script per index.
+#[derive(Debug)]
+pub enum Descriptor {
+    // <key> OP_CHECKSIG
+    Pk(DescriptorKey),
+    Pkh(DescriptorKey),
+    Wpkh(DescriptorKey),
+    // multi(k,KEY,...) and sortedmulti(k,KEY,...). sortedmulti puts the keys
+    // in the script in the order of their SEC serializations.
+    Multi { threshold: usize, keys: Vec<DescriptorKey>, sorted: bool },
+    Sh(Box<Descriptor>),
+    Wsh(Box<Descriptor>),
+    Tr { internal_key: DescriptorKey, tree: Option<TapTreeDescriptor> },
+    Addr(String),
+    Raw(Vec<u8>),
+}
+
+/// The script tree of a tr() descriptor, written {left,right}
+#[derive(Debug)]
+pub enum TapTreeDescriptor {
+    Leaf(Box<Descriptor>),
+    Branch(Box<TapTreeDescriptor>, Box<TapTreeDescriptor>),
+}
+
+// The largest script a single push can hold
+const MAX_REDEEM_SCRIPT_SIZE: usize = 520;
+
+// Where a script expression appears, which decides the expressions and
+// keys it can use
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Context {
+    Top,
+    Sh,
+    // Inside wsh(), and the key of wpkh()
+    Segwit,
+    // Leaves of a tr() tree, and the internal key
+    Tap,
+}
+
+impl Descriptor {
+    /// Parses a descriptor. The #checksum suffix is optional, but is
+    /// verified when present.
+    pub fn parse(s: &str) -> Result<Self, DescriptorError> {
+        let mut parser = Parser { input: strip_checksum(s)?, position: 0 };
+        let descriptor = parser.script(Context::Top)?;
+        if parser.position != parser.input.len() {
+            return Err(DescriptorError::Syntax("end of descriptor".to_string(), parser.position));
+        }
+        Ok(descriptor)
+    }
+
+    /// The name of the script expression, e.g. "wpkh"
+    pub fn name(&self) -> &'static str {
+        match self {
+            Descriptor::Pk(_) => "pk",
+            Descriptor::Pkh(_) => "pkh",
+            Descriptor::Wpkh(_) => "wpkh",
+            Descriptor::Multi { sorted: false, .. } => "multi",
+            Descriptor::Multi { sorted: true, .. } => "sortedmulti",
+            Descriptor::Sh(_) => "sh",
+            Descriptor::Wsh(_) => "wsh",
+            Descriptor::Tr { .. } => "tr",
+            Descriptor::Addr(_) => "addr",
+            Descriptor::Raw(_) => "raw",
+        }
+    }
+
+    /// True if any key has a wildcard, so that the scripts depend on the
+    /// index
+    pub fn has_wildcard(&self) -> bool {
+        match self {
+            Descriptor::Pk(key) | Descriptor::Pkh(key) | Descriptor::Wpkh(key) => key.has_wildcard(),
+            Descriptor::Multi { keys, .. } => keys.iter().any(|key| key.has_wildcard()),
+            Descriptor::Sh(inner) | Descriptor::Wsh(inner) => inner.has_wildcard(),
+            Descriptor::Tr { internal_key, tree } => {
+                internal_key.has_wildcard() || tree.as_ref().is_some_and(|tree| tree.has_wildcard())
+            }
+            Descriptor::Addr(_) | Descriptor::Raw(_) => false,
+        }
+    }
+
+    /// Returns the script_pubkey at this index
+    pub fn script_pubkey(&self, index: u32) -> Result<Script, DescriptorError> {
+        match self {
+            Descriptor::Pk(key) => Ok(Script::new(vec![Command::Data(key.sec(index)?), Command::Op(0xac)])),
+            Descriptor::Pkh(key) => Ok(Script::p2pkh_script(hash160(&key.sec(index)?))),
+            Descriptor::Wpkh(key) => Ok(Script::p2wpkh_script(hash160(&key.sec(index)?))),
+            Descriptor::Multi { threshold, keys, sorted } => {
+                let mut secs = keys.iter().map(|key| key.sec(index)).collect::<Result<Vec<_>, _>>()?;
+                if *sorted {
+                    secs.sort();
+                }
+                // <k> <keys> <n> OP_CHECKMULTISIG
+                let n = push_count(secs.len());
+                let mut commands = vec![push_count(*threshold)];
+                commands.extend(secs.into_iter().map(Command::Data));
+                commands.push(n);
+                commands.push(Command::Op(0xae));
+                Ok(Script::new(commands))
+            }
+            Descriptor::Sh(inner) => Ok(Script::p2sh_script(hash160(&inner.script_pubkey(index)?.raw_serialize()))),
+            Descriptor::Wsh(inner) => {
+                let witness_script = inner.script_pubkey(index)?;
+                Ok(Script::p2wsh_script(Sha256::digest(witness_script.raw_serialize()).to_vec()))
+            }
+            Descriptor::Tr { .. } => Ok(self.spend_info(index)?.unwrap().script_pubkey()),
+            Descriptor::Addr(address) => {
+                let (script_pubkey, _) = address_to_script_pubkey(address).map_err(DescriptorError::InvalidAddress)?;
+                Ok(script_pubkey)
+            }
+            Descriptor::Raw(raw) => parse_raw_script(raw),
+        }
+    }
+
+    /// Returns the script_pubkeys of a range of indexes, as a watch-only
+    /// wallet would scan for
+    pub fn script_pubkeys(&self, indexes: Range<u32>) -> Result<Vec<Script>, DescriptorError> {
+        indexes.map(|index| self.script_pubkey(index)).collect()
+    }
+
+    /// Returns the redeem script a P2SH spend reveals, for sh() descriptors
+    pub fn redeem_script(&self, index: u32) -> Result<Option<Script>, DescriptorError> {
+        match self {
+            Descriptor::Sh(inner) => Ok(Some(inner.script_pubkey(index)?)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Returns the witness script a P2WSH spend reveals, for wsh() and
+    /// sh(wsh()) descriptors
+    pub fn witness_script(&self, index: u32) -> Result<Option<Script>, DescriptorError> {
+        match self {
+            Descriptor::Wsh(inner) => Ok(Some(inner.script_pubkey(index)?)),
+            Descriptor::Sh(inner) => inner.witness_script(index),
+            _ => Ok(None),
+        }
+    }
+
+    /// Returns the taproot output of a tr() descriptor at this index
+    pub fn spend_info(&self, index: u32) -> Result<Option<TaprootSpendInfo>, DescriptorError> {
+        let Descriptor::Tr { internal_key, tree } = self else { return Ok(None) };
+        let tree = tree.as_ref().map(|tree| tree.tap_tree(index)).transpose()?;
+        let spend_info = TaprootSpendInfo::new(internal_key.xonly_public_key(index)?, tree).map_err(DescriptorError::Taproot)?;
+        Ok(Some(spend_info))
+    }
+
+    /// Returns the address at this index
+    pub fn address(&self, index: u32, testnet: bool) -> Result<String, DescriptorError> {
+        script_pubkey_to_address(&self.script_pubkey(index)?, testnet).ok_or(DescriptorError::NoAddress)
+    }
+
+    /// Returns the canonical form of the descriptor, without a checksum
+    pub fn to_string_without_checksum(&self) -> String {
+        let keys = |keys: &[DescriptorKey]| keys.iter().map(|key| key.to_string()).collect::<Vec<_>>().join(",");
+        match self {
+            Descriptor::Pk(key) | Descriptor::Pkh(key) | Descriptor::Wpkh(key) => format!("{}({})", self.name(), key),
+            Descriptor::Multi { threshold, keys: multi_keys, .. } => format!("{}({},{})", self.name(), threshold, keys(multi_keys)),
+            Descriptor::Sh(inner) | Descriptor::Wsh(inner) => format!("{}({})", self.name(), inner.to_string_without_checksum()),
+            Descriptor::Tr { internal_key, tree: None } => format!("tr({})", internal_key),
+            Descriptor::Tr { internal_key, tree: Some(tree) } => format!("tr({},{})", internal_key, tree),
+            Descriptor::Addr(address) => format!("addr({})", address),
+            Descriptor::Raw(raw) => format!("raw({})", hex::encode(raw)),
+        }
+    }
+}
+
+// The canonical form followed by its checksum
+impl fmt::Display for Descriptor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let descriptor = self.to_string_without_checksum();
+        let checksum = descriptor_checksum(&descriptor).map_err(|_| fmt::Error)?;
+        write!(f, "{}#{}", descriptor, checksum)
+    }
+}
+
+impl TapTreeDescriptor {
+    pub fn has_wildcard(&self) -> bool {
+        match self {
+            TapTreeDescriptor::Leaf(leaf) => leaf.has_wildcard(),
+            TapTreeDescriptor::Branch(left, right) => left.has_wildcard() || right.has_wildcard(),
+        }
+    }
+
+    /// Returns the script tree at this index
+    pub fn tap_tree(&self, index: u32) -> Result<TapTree, DescriptorError> {
+        match self {
+            TapTreeDescriptor::Leaf(leaf) => match leaf.as_ref() {
+                // Tapscript keys are x-only
+                Descriptor::Pk(key) => Ok(TapTree::leaf(Script::new(vec![Command::Data(key.xonly_public_key(index)?.serialize()), Command::Op(0xac)]))),
+                Descriptor::Pkh(key) => Ok(TapTree::leaf(Script::p2pkh_script(hash160(&key.xonly_public_key(index)?.serialize())))),
+                leaf => Err(DescriptorError::InvalidNesting(leaf.name().to_string())),
+            },
+            TapTreeDescriptor::Branch(left, right) => Ok(TapTree::branch(left.tap_tree(index)?, right.tap_tree(index)?)),
+        }
+    }
+}
+
+impl fmt::Display for TapTreeDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TapTreeDescriptor::Leaf(leaf) => write!(f, "{}", leaf.to_string_without_checksum()),
+            TapTreeDescriptor::Branch(left, right) => write!(f, "{{{},{}}}", left, right),
+        }
+    }
+}
+
+// Counts up to 16 are pushed as OP_1 to OP_16, the larger ones a wsh()
+// multi can have as script numbers
+fn push_count(n: usize) -> Command {
+    match n {
+        0..=16 => Command::Op(0x50 + n as u8),
+        _ => Command::Data(encode_num(n as i32)),
+    }
+}
+
+// Scripts are parsed with their length prefix
+fn parse_raw_script(raw: &[u8]) -> Result<Script, DescriptorError> {
+    let mut serialized = encode_varint(raw.len() as u64);
+    serialized.extend_from_slice(raw);
+    Script::parse(&mut Cursor::new(serialized)).map_err(|_| DescriptorError::InvalidScript)
+}
+
+// A recursive descent parser over the descriptor without its checksum
+struct Parser<'a> {
+    input: &'a str,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.input.as_bytes().get(self.position).copied()
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), DescriptorError> {
+        if self.peek() != Some(c) {
+            return Err(DescriptorError::Syntax(format!("'{}'", c as char), self.position));
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    // Everything up to the next separator: a key, a number, an address or
+    // hex
+    fn token(&mut self) -> &'a str {
+        let rest = &self.input[self.position..];
+        let length = rest.find([',', '(', ')', '{', '}']).unwrap_or(rest.len());
+        self.position += length;
+        &rest[..length]
+    }
+
+    fn script(&mut self, context: Context) -> Result<Descriptor, DescriptorError> {
+        let start = self.position;
+        let name = self.token();
+        if self.peek() != Some(b'(') {
+            return Err(DescriptorError::Syntax("script expression".to_string(), start));
+        }
+        self.position += 1;
+
+        let allowed = match name {
+            "pk" => true,
+            "pkh" => true,
+            "multi" | "sortedmulti" => context != Context::Tap,
+            "wpkh" | "wsh" => context == Context::Top || context == Context::Sh,
+            "sh" | "tr" | "addr" | "raw" => context == Context::Top,
+            _ => return Err(DescriptorError::UnknownFunction(name.to_string())),
+        };
+        if !allowed {
+            return Err(DescriptorError::InvalidNesting(name.to_string()));
+        }
+
+        let descriptor = match name {
+            "pk" => Descriptor::Pk(self.key(context)?),
+            "pkh" => Descriptor::Pkh(self.key(context)?),
+            "wpkh" => Descriptor::Wpkh(self.key(Context::Segwit)?),
+            "multi" | "sortedmulti" => self.multi(context, name == "sortedmulti")?,
+            "sh" => {
+                let inner = self.script(Context::Sh)?;
+                // The redeem script is pushed by the script_sig, so it has
+                // to fit in one push. Its size doesn't depend on the index.
+                let size = inner.script_pubkey(0)?.raw_serialize().len();
+                if size > MAX_REDEEM_SCRIPT_SIZE {
+                    return Err(DescriptorError::ScriptTooLarge(size));
+                }
+                Descriptor::Sh(Box::new(inner))
+            }
+            "wsh" => Descriptor::Wsh(Box::new(self.script(Context::Segwit)?)),
+            "tr" => {
+                let internal_key = self.key(Context::Tap)?;
+                let tree = match self.peek() {
+                    Some(b',') => {
+                        self.position += 1;
+                        Some(self.tree()?)
+                    }
+                    _ => None,
+                };
+                Descriptor::Tr { internal_key, tree }
+            }
+            "addr" => {
+                let address = self.token();
+                address_to_script_pubkey(address).map_err(DescriptorError::InvalidAddress)?;
+                Descriptor::Addr(address.to_string())
+            }
+            _ => {
+                let raw = hex::decode(self.token()).map_err(|_| DescriptorError::InvalidScript)?;
+                parse_raw_script(&raw)?;
+                Descriptor::Raw(raw)
+            }
+        };
+        self.expect(b')')?;
+        Ok(descriptor)
+    }
+
+    fn key(&mut self, context: Context) -> Result<DescriptorKey, DescriptorError> {
+        let token = self.token();
+        let key = DescriptorKey::parse(token)?;
+        if key.is_uncompressed() && (context == Context::Segwit || context == Context::Tap) {
+            return Err(DescriptorError::UncompressedKey(token.to_string()));
+        }
+        if key.is_xonly() && context != Context::Tap {
+            return Err(DescriptorError::XOnlyKey(token.to_string()));
+        }
+        Ok(key)
+    }
+
+    fn multi(&mut self, context: Context, sorted: bool) -> Result<Descriptor, DescriptorError> {
+        let start = self.position;
+        let threshold = self
+            .token()
+            .parse::<usize>()
+            .map_err(|_| DescriptorError::Syntax("threshold".to_string(), start))?;
+        let mut keys = vec![];
+        while self.peek() == Some(b',') {
+            self.position += 1;
+            keys.push(self.key(context)?);
+        }
+        // wsh() scripts aren't bound by the P2SH push limit, and BIP383
+        // allows 20 keys there as CHECKMULTISIG does
+        let max_keys = if context == Context::Segwit { 20 } else { 16 };
+        if keys.len() > max_keys {
+            return Err(DescriptorError::TooManyKeys(keys.len()));
+        }
+        if threshold == 0 || threshold > keys.len() {
+            return Err(DescriptorError::InvalidThreshold(threshold));
+        }
+        Ok(Descriptor::Multi { threshold, keys, sorted })
+    }
+
+    fn tree(&mut self) -> Result<TapTreeDescriptor, DescriptorError> {
+        if self.peek() != Some(b'{') {
+            return Ok(TapTreeDescriptor::Leaf(Box::new(self.script(Context::Tap)?)));
+        }
+        self.position += 1;
+        let left = self.tree()?;
+        self.expect(b',')?;
+        let right = self.tree()?;
+        self.expect(b'}')?;
+        Ok(TapTreeDescriptor::Branch(Box::new(left), Box::new(right)))
+    }
+}
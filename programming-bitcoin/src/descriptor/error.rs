@@ -0,0 +1,73 @@
+use std::fmt;
+use crate::hd::error::Bip32Error;
+
+/// Why a descriptor could not be parsed or expanded
+#[derive(Debug, Clone, PartialEq)]
+pub enum DescriptorError {
+    // A character outside the set that BIP380 checksums cover
+    InvalidCharacter(char),
+    // The part after # is not the checksum of the descriptor
+    InvalidChecksum,
+    // The descriptor is not well formed. Holds what was expected and the
+    // byte offset where it was expected.
+    Syntax(String, usize),
+    UnknownFunction(String),
+    // A script expression where BIP380-386 don't allow it, e.g. wpkh inside
+    // wsh or tr anywhere but the top level
+    InvalidNesting(String),
+    // A key expression that can't be parsed
+    InvalidKey(String),
+    // Segwit and taproot scripts only take compressed keys
+    UncompressedKey(String),
+    // x-only keys can only be used in tr()
+    XOnlyKey(String),
+    // A hardened step or wildcard after an xpub, which needs the private key
+    HardenedFromPublic(String),
+    // The threshold of a multi is not between 1 and the number of keys
+    InvalidThreshold(usize),
+    // multi() takes at most 16 keys, or 20 inside wsh()
+    TooManyKeys(usize),
+    // The redeem script of sh() is over the 520 byte push limit
+    ScriptTooLarge(usize),
+    // Indexes for a wildcard must be below 2^31
+    InvalidIndex(u32),
+    InvalidAddress(String),
+    InvalidScript,
+    // The descriptor's script has no address, as for pk() and multi()
+    NoAddress,
+    Bip32(Bip32Error),
+    Taproot(String),
+}
+
+impl fmt::Display for DescriptorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DescriptorError::InvalidCharacter(c) => write!(f, "invalid character in descriptor: {:?}", c),
+            DescriptorError::InvalidChecksum => write!(f, "invalid descriptor checksum"),
+            DescriptorError::Syntax(expected, offset) => write!(f, "expected {} at offset {}", expected, offset),
+            DescriptorError::UnknownFunction(name) => write!(f, "unknown script expression: {}", name),
+            DescriptorError::InvalidNesting(name) => write!(f, "{} is not allowed here", name),
+            DescriptorError::InvalidKey(key) => write!(f, "invalid key expression: {}", key),
+            DescriptorError::UncompressedKey(key) => write!(f, "uncompressed key not allowed here: {}", key),
+            DescriptorError::XOnlyKey(key) => write!(f, "x-only key outside tr(): {}", key),
+            DescriptorError::HardenedFromPublic(key) => write!(f, "hardened derivation from a public key: {}", key),
+            DescriptorError::InvalidThreshold(threshold) => write!(f, "invalid multisig threshold: {}", threshold),
+            DescriptorError::TooManyKeys(count) => write!(f, "too many keys in multisig: {}", count),
+            DescriptorError::ScriptTooLarge(size) => write!(f, "redeem script too large: {} bytes", size),
+            DescriptorError::InvalidIndex(index) => write!(f, "invalid wildcard index: {}", index),
+            DescriptorError::InvalidAddress(error) => write!(f, "invalid address: {}", error),
+            DescriptorError::InvalidScript => write!(f, "invalid raw script"),
+            DescriptorError::NoAddress => write!(f, "descriptor has no address"),
+            DescriptorError::Bip32(error) => write!(f, "{}", error),
+            DescriptorError::Taproot(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for DescriptorError {}
+
+impl From<Bip32Error> for DescriptorError {
+    fn from(error: Bip32Error) -> Self {
+        DescriptorError::Bip32(error)
+    }
+}
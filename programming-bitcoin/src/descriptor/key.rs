@@ -0,0 +1,244 @@
+use std::fmt;
+use crate::descriptor::error::DescriptorError;
+use crate::ecc::point::Point;
+use crate::ecc::private_key::{PrivateKey, WifMetadata};
+use crate::ecc::xonly_public_key::XOnlyPublicKey;
+use crate::hd::derivation_path::DerivationPath;
+use crate::hd::extended_key::{ExtendedPrivateKey, ExtendedPublicKey, HARDENED};
+
+/// The fingerprint of the master key and the path from it to a key, written
+/// [d34db33f/84h/0h/0h] in front of the key. Signers use it to find their
+/// keys; it doesn't change the scripts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyOrigin {
+    pub fingerprint: [u8; 4],
+    pub path: DerivationPath,
+}
+
+/// Whether an extended key's path ends in a wildcard, which the index the
+/// descriptor is expanded at fills in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wildcard {
+    None,
+    // /*
+    Unhardened,
+    // /*' or /*h
+    Hardened,
+}
+
+#[derive(Debug)]
+pub enum ExtendedKey {
+    Public(ExtendedPublicKey),
+    Private(ExtendedPrivateKey),
+}
+
+/// The key of a key expression
+#[derive(Debug)]
+pub enum KeySource {
+    // A hex SEC public key
+    Public { key: Point, compressed: bool },
+    // A 32 byte hex key, only allowed in tr()
+    XOnly(XOnlyPublicKey),
+    // A WIF private key
+    Private { key: PrivateKey, metadata: WifMetadata },
+    // An xpub or xprv followed by a path, which may end in a wildcard
+    Extended { key: ExtendedKey, path: DerivationPath, wildcard: Wildcard },
+}
+
+/// A key expression (BIP380): a key with an optional origin
+#[derive(Debug)]
+pub struct DescriptorKey {
+    origin: Option<KeyOrigin>,
+    source: KeySource,
+}
+
+impl DescriptorKey {
+    pub fn new(origin: Option<KeyOrigin>, source: KeySource) -> Self {
+        Self { origin, source }
+    }
+
+    /// Parses a key expression such as
+    /// [d34db33f/84'/0'/0']xpub6DJ2dNUysrn5Vt36jH2KLBT2i1auw1tTSSomg8PhqNiUtx8QX2SvC9nrHu81fT41fvDUnhMjEzQgXnQjKEu3oaqMSzhSrHMxyyoEAmUHQbY/0/*
+    pub fn parse(s: &str) -> Result<Self, DescriptorError> {
+        let invalid = || DescriptorError::InvalidKey(s.to_string());
+        let (origin, key) = match s.strip_prefix('[') {
+            Some(rest) => {
+                let (origin, key) = rest.split_once(']').ok_or_else(invalid)?;
+                (Some(parse_origin(origin).ok_or_else(invalid)?), key)
+            }
+            None => (None, s),
+        };
+
+        let is_hex = key.bytes().all(|b| b.is_ascii_hexdigit());
+        let source = if is_hex && key.len() == 64 {
+            KeySource::XOnly(XOnlyPublicKey::parse(&hex::decode(key).unwrap()).map_err(|_| invalid())?)
+        } else if is_hex && (key.len() == 66 || key.len() == 130) {
+            let sec = hex::decode(key).unwrap();
+            // from_sec also takes the hybrid 06 and 07 prefixes, which
+            // descriptors don't
+            if !matches!((sec[0], sec.len()), (0x02 | 0x03, 33) | (0x04, 65)) {
+                return Err(invalid());
+            }
+            KeySource::Public {
+                key: Point::from_sec(&sec).map_err(|_| invalid())?,
+                compressed: sec.len() == 33,
+            }
+        } else if let Ok((key, metadata)) = PrivateKey::from_wif(key) {
+            KeySource::Private { key, metadata }
+        } else {
+            parse_extended(key).ok_or_else(invalid)?
+        };
+
+        if let KeySource::Extended { key: ExtendedKey::Public(_), path, wildcard } = &source {
+            if *wildcard == Wildcard::Hardened || path.indexes().iter().any(|index| *index >= HARDENED) {
+                return Err(DescriptorError::HardenedFromPublic(s.to_string()));
+            }
+        }
+        Ok(Self { origin, source })
+    }
+
+    pub fn origin(&self) -> Option<&KeyOrigin> {
+        self.origin.as_ref()
+    }
+
+    pub fn source(&self) -> &KeySource {
+        &self.source
+    }
+
+    pub fn has_wildcard(&self) -> bool {
+        matches!(self.source, KeySource::Extended { wildcard: Wildcard::Unhardened | Wildcard::Hardened, .. })
+    }
+
+    /// True for hex and WIF keys given in uncompressed form
+    pub fn is_uncompressed(&self) -> bool {
+        match &self.source {
+            KeySource::Public { compressed, .. } => !compressed,
+            KeySource::Private { metadata, .. } => !metadata.compressed,
+            _ => false,
+        }
+    }
+
+    pub fn is_xonly(&self) -> bool {
+        matches!(self.source, KeySource::XOnly(_))
+    }
+
+    /// Returns the public key at this index. The index is only used by keys
+    /// with a wildcard.
+    pub fn public_key(&self, index: u32) -> Result<Point, DescriptorError> {
+        match &self.source {
+            KeySource::Public { key, .. } => Ok(key.clone()),
+            KeySource::XOnly(key) => Ok(key.to_point()),
+            KeySource::Private { key, .. } => Ok(key.public_point().clone()),
+            KeySource::Extended { key, path, wildcard } => {
+                if *wildcard != Wildcard::None && index >= HARDENED {
+                    return Err(DescriptorError::InvalidIndex(index));
+                }
+                let path = match wildcard {
+                    Wildcard::None => path.clone(),
+                    Wildcard::Unhardened => path.child(index),
+                    Wildcard::Hardened => path.child(index + HARDENED),
+                };
+                match key {
+                    ExtendedKey::Public(xpub) => Ok(xpub.derive_path(&path)?.public_key().clone()),
+                    ExtendedKey::Private(xprv) => Ok(xprv.derive_path(&path)?.public_key()),
+                }
+            }
+        }
+    }
+
+    /// Returns the SEC serialization of the public key at this index, which
+    /// is compressed unless the key was given uncompressed
+    pub fn sec(&self, index: u32) -> Result<Vec<u8>, DescriptorError> {
+        Ok(self.public_key(index)?.sec(!self.is_uncompressed()))
+    }
+
+    pub fn xonly_public_key(&self, index: u32) -> Result<XOnlyPublicKey, DescriptorError> {
+        Ok(XOnlyPublicKey::from_point(&self.public_key(index)?))
+    }
+}
+
+// Hex keys are written compressed or not as they were given, and hardened
+// steps are written with h, as Bitcoin Core does
+impl fmt::Display for DescriptorKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(origin) = &self.origin {
+            write!(f, "[{}", hex::encode(origin.fingerprint))?;
+            write_path(f, &origin.path)?;
+            write!(f, "]")?;
+        }
+        match &self.source {
+            KeySource::Public { key, compressed } => write!(f, "{}", hex::encode(key.clone().sec(*compressed))),
+            KeySource::XOnly(key) => write!(f, "{}", hex::encode(key.serialize())),
+            KeySource::Private { key, metadata } => write!(f, "{}", key.wif(metadata.compressed, metadata.testnet)),
+            KeySource::Extended { key, path, wildcard } => {
+                match key {
+                    ExtendedKey::Public(xpub) => write!(f, "{}", xpub.to_base58())?,
+                    ExtendedKey::Private(xprv) => write!(f, "{}", xprv.to_base58())?,
+                }
+                write_path(f, path)?;
+                match wildcard {
+                    Wildcard::None => Ok(()),
+                    Wildcard::Unhardened => write!(f, "/*"),
+                    Wildcard::Hardened => write!(f, "/*h"),
+                }
+            }
+        }
+    }
+}
+
+fn write_path(f: &mut fmt::Formatter, path: &DerivationPath) -> fmt::Result {
+    for index in path.indexes() {
+        if *index >= HARDENED {
+            write!(f, "/{}h", index - HARDENED)?;
+        } else {
+            write!(f, "/{}", index)?;
+        }
+    }
+    Ok(())
+}
+
+// Parses steps a/b'/c (without the leading m/) with DerivationPath
+fn parse_steps(steps: Option<&str>) -> Option<DerivationPath> {
+    match steps {
+        Some(steps) => DerivationPath::parse(&format!("m/{}", steps)).ok(),
+        None => Some(DerivationPath::master()),
+    }
+}
+
+// fingerprint[/path], with the fingerprint as eight hex digits
+fn parse_origin(origin: &str) -> Option<KeyOrigin> {
+    let (fingerprint, steps) = match origin.split_once('/') {
+        Some((fingerprint, steps)) => (fingerprint, Some(steps)),
+        None => (origin, None),
+    };
+    if fingerprint.len() != 8 {
+        return None;
+    }
+    Some(KeyOrigin {
+        fingerprint: hex::decode(fingerprint).ok()?.try_into().ok()?,
+        path: parse_steps(steps)?,
+    })
+}
+
+// xpub or xprv, then /-separated steps that may end in * or *' (or *h)
+fn parse_extended(key: &str) -> Option<KeySource> {
+    let (base58, steps) = match key.split_once('/') {
+        Some((base58, steps)) => (base58, Some(steps)),
+        None => (key, None),
+    };
+    let (steps, wildcard) = match steps {
+        Some("*") => (None, Wildcard::Unhardened),
+        Some("*'") | Some("*h") => (None, Wildcard::Hardened),
+        Some(steps) => match steps.rsplit_once('/') {
+            Some((steps, "*")) => (Some(steps), Wildcard::Unhardened),
+            Some((steps, "*'")) | Some((steps, "*h")) => (Some(steps), Wildcard::Hardened),
+            _ => (Some(steps), Wildcard::None),
+        },
+        None => (None, Wildcard::None),
+    };
+    let key = match ExtendedPublicKey::from_base58(base58) {
+        Ok(xpub) => ExtendedKey::Public(xpub),
+        Err(_) => ExtendedKey::Private(ExtendedPrivateKey::from_base58(base58).ok()?),
+    };
+    Some(KeySource::Extended { key, path: parse_steps(steps)?, wildcard })
+}
@@ -1,8 +1,18 @@
+pub mod adaptor;
+pub mod batch_verifier;
 pub mod field_element;
+pub mod frost;
+pub mod generator_table;
+pub mod jacobian_point;
 pub mod mod_exp;
+pub mod nonce_reuse;
 pub mod point;
 pub mod private_key;
+pub mod recoverable_signature;
 pub mod s256field_element;
 pub mod s256point;
+pub mod scalar;
+pub mod schnorr_signature;
 pub mod secp256k1_params;
 pub mod signature;
+pub mod xonly_public_key;
@@ -0,0 +1,275 @@
+use std::fmt;
+use primitive_types::U256;
+use crate::ecc::generator_table::GeneratorTable;
+use crate::ecc::jacobian_point::JacobianPoint;
+use crate::ecc::point::Point;
+use crate::ecc::private_key::PrivateKey;
+use crate::ecc::scalar::Scalar;
+use crate::ecc::schnorr_signature::SchnorrSignature;
+use crate::ecc::signature::Signature;
+use crate::ecc::xonly_public_key::{challenge, XOnlyPublicKey};
+use crate::utils::tagged_hash::tagged_hash;
+use subtle::Choice;
+
+// Adaptor signatures, also called verifiably encrypted signatures. A
+// pre-signature is a signature encrypted under an adaptor point T = t * G:
+// anyone can check that it will become a valid signature once decrypted,
+// decrypting it takes the secret t, and whoever sees both the pre-signature
+// and the final signature learns t. That last property is what makes
+// scriptless atomic swaps and DLCs work.
+
+/// Why an adaptor signature operation failed
+#[derive(Debug, Clone, PartialEq)]
+pub enum AdaptorError {
+    // The encryption key is the point at infinity
+    InvalidEncryptionKey,
+    // The decryption key is zero or at least n
+    InvalidDecryptionKey,
+    // The signature is not the decryption of this pre-signature
+    SecretNotFound,
+}
+
+impl fmt::Display for AdaptorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AdaptorError::InvalidEncryptionKey => write!(f, "invalid encryption key"),
+            AdaptorError::InvalidDecryptionKey => write!(f, "invalid decryption key"),
+            AdaptorError::SecretNotFound => write!(f, "signature was not decrypted from this pre-signature"),
+        }
+    }
+}
+
+impl std::error::Error for AdaptorError {}
+
+/// An ECDSA signature encrypted under an adaptor point Y (the one-time
+/// verifiably encrypted signature scheme). The signer uses the nonce k * y,
+/// publishing R = k * Y and R_a = k * G with a proof that both have the same
+/// discrete log k. Decrypting divides s by y.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EcdsaPreSignature {
+    r: (U256, U256),
+    r_a: (U256, U256),
+    s: Scalar,
+    // DLEQ proof that log_G(R_a) = log_Y(R)
+    proof: (Scalar, Scalar),
+}
+
+impl EcdsaPreSignature {
+    /// Encrypts an ECDSA signature of z under the encryption key. aux_rand is
+    /// fresh randomness that is mixed into the nonce, as with BIP340.
+    pub fn sign(private_key: &PrivateKey, z: Vec<u8>, encryption_key: &Point, aux_rand: [u8; 32]) -> Result<Self, AdaptorError> {
+        let y = jacobian(encryption_key)?;
+        let (yx, yy) = affine(&y);
+        let d = Scalar::reduce(private_key.secret());
+        let z = Scalar::reduce_bytes(&z);
+
+        let nonce_data = [&masked_secret(d, &aux_rand)[..], &compressed((yx, yy)), &z.to_bytes()].concat();
+        let k = nonzero(Scalar::reduce_bytes(&tagged_hash("ECDSAadaptor/non", &nonce_data)));
+
+        let r_a = GeneratorTable::multiply_ct(k.to_u256());
+        let r = y.multiply_ct(k.to_u256());
+        let proof = dleq_prove(k, &r_a, &y, &r, &aux_rand);
+
+        // s' = (z + r * d) / k
+        let r = affine(&r);
+        let s = (z + Scalar::reduce(r.0) * d) * k.invert().unwrap();
+        Ok(Self { r, r_a: affine(&r_a), s, proof })
+    }
+
+    /// The r of the signature this pre-signature decrypts to
+    pub fn r(&self) -> U256 {
+        Scalar::reduce(self.r.0).to_u256()
+    }
+
+    /// Checks that the pre-signature decrypts, with the discrete log of the
+    /// encryption key, to a valid signature of z under the public key
+    pub fn verify(&self, public_key: &Point, z: Vec<u8>, encryption_key: &Point) -> bool {
+        let (y, p) = match (jacobian(encryption_key), jacobian(public_key)) {
+            (Ok(y), Ok(p)) => (y, p),
+            _ => return false,
+        };
+        let r_a = JacobianPoint::from_affine(self.r_a.0, self.r_a.1);
+        let r = JacobianPoint::from_affine(self.r.0, self.r.1);
+        if !dleq_verify(self.proof, &r_a, &y, &r) {
+            return false;
+        }
+        let s_inv = match self.s.invert() {
+            Some(s_inv) => s_inv,
+            None => return false,
+        };
+        // R_a = (z * G + r * P) / s'
+        let u = Scalar::reduce_bytes(&z) * s_inv;
+        let v = Scalar::reduce(self.r.0) * s_inv;
+        equal(&GeneratorTable::multiply(u.to_u256()).add(&p.multiply(v.to_u256())), &r_a)
+    }
+
+    /// Decrypts to a (low S) signature, s = s' / y
+    pub fn decrypt(&self, decryption_key: &PrivateKey) -> Result<Signature, AdaptorError> {
+        let y = decryption_scalar(decryption_key)?;
+        let s = self.s * y.invert().unwrap();
+        Ok(Signature::new(self.r(), s.to_u256()).normalize_s())
+    }
+
+    /// Recovers the decryption key from the signature it was decrypted to,
+    /// y = s' / s (or its negation, if s was normalized)
+    pub fn recover(&self, signature: &Signature, encryption_key: &Point) -> Result<PrivateKey, AdaptorError> {
+        let y_point = jacobian(encryption_key)?;
+        let s = Scalar::from_u256(signature.s())
+            .and_then(|s| s.invert())
+            .ok_or(AdaptorError::SecretNotFound)?;
+        if signature.r() != self.r() {
+            return Err(AdaptorError::SecretNotFound);
+        }
+        let y = self.s * s;
+        for candidate in [y, -y] {
+            if equal(&GeneratorTable::multiply(candidate.to_u256()), &y_point) {
+                return Ok(PrivateKey::new(candidate.to_u256()));
+            }
+        }
+        Err(AdaptorError::SecretNotFound)
+    }
+}
+
+/// A BIP340 signature encrypted under an adaptor point T. The nonce point of
+/// the final signature is R = R' + T, but the pre-signature only knows the
+/// discrete log of R', so s' is off by t until decrypted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchnorrPreSignature {
+    // R = R' + T, with its y coordinate. If y is odd the final nonce is -R,
+    // so t is subtracted instead of added.
+    r: (U256, U256),
+    s: Scalar,
+}
+
+impl SchnorrPreSignature {
+    /// Encrypts a BIP340 signature of msg under the encryption key
+    pub fn sign(private_key: &PrivateKey, msg: &[u8], encryption_key: &Point, aux_rand: [u8; 32]) -> Result<Self, AdaptorError> {
+        let t = jacobian(encryption_key)?;
+        // Use the secret whose public key has an even y
+        let (px, py) = private_key.public_point().coordinates().unwrap();
+        let secret = Scalar::reduce(private_key.secret());
+        let d = secret.negate_if(Choice::from(py.bit(0) as u8));
+
+        let nonce_data = [&masked_secret(d, &aux_rand)[..], &compressed(affine(&t)), &px.to_big_endian(), msg].concat();
+        let k_prime = nonzero(Scalar::reduce_bytes(&tagged_hash("SchnorrAdaptor/nonce", &nonce_data)));
+
+        // R = k' * G + T can only be infinity if T = -k' * G, which would mean
+        // we hashed our way to the encryption key's secret
+        let r = affine(&GeneratorTable::multiply_ct(k_prime.to_u256()).add(&t));
+        let k = k_prime.negate_if(Choice::from(r.1.bit(0) as u8));
+
+        // s' = k + e * d
+        let s = k + challenge(r.0, px, msg) * d;
+        Ok(Self { r, s })
+    }
+
+    /// Checks that the pre-signature decrypts, with the discrete log of the
+    /// encryption key, to a valid BIP340 signature of msg
+    pub fn verify(&self, public_key: &XOnlyPublicKey, msg: &[u8], encryption_key: &Point) -> bool {
+        let t = match jacobian(encryption_key) {
+            Ok(t) => t,
+            Err(_) => return false,
+        };
+        let (px, py) = XOnlyPublicKey::lift_x(public_key.x()).unwrap();
+        let e = challenge(self.r.0, px, msg);
+
+        // R' = s' * G - e * P must be R - T, or T - R if R has an odd y
+        let r_prime = GeneratorTable::multiply(self.s.to_u256())
+            .add(&JacobianPoint::from_affine(px, py).multiply((-e).to_u256()));
+        let r_prime = if self.r.1.bit(0) { r_prime.negate() } else { r_prime };
+        let expected = JacobianPoint::from_affine(self.r.0, self.r.1).add(&t.negate());
+        equal(&r_prime, &expected)
+    }
+
+    /// Decrypts to a BIP340 signature, s = s' + t (or s' - t)
+    pub fn decrypt(&self, decryption_key: &PrivateKey) -> Result<SchnorrSignature, AdaptorError> {
+        let t = decryption_scalar(decryption_key)?;
+        let s = if self.r.1.bit(0) { self.s - t } else { self.s + t };
+        Ok(SchnorrSignature::new(self.r.0, s.to_u256()))
+    }
+
+    /// Recovers the decryption key from the signature it was decrypted to
+    pub fn recover(&self, signature: &SchnorrSignature, encryption_key: &Point) -> Result<PrivateKey, AdaptorError> {
+        let t_point = jacobian(encryption_key)?;
+        let s = Scalar::from_u256(signature.s()).ok_or(AdaptorError::SecretNotFound)?;
+        if signature.r() != self.r.0 {
+            return Err(AdaptorError::SecretNotFound);
+        }
+        let t = if self.r.1.bit(0) { self.s - s } else { s - self.s };
+        if t.is_zero() || !equal(&GeneratorTable::multiply(t.to_u256()), &t_point) {
+            return Err(AdaptorError::SecretNotFound);
+        }
+        Ok(PrivateKey::new(t.to_u256()))
+    }
+}
+
+// A non-interactive Chaum-Pedersen proof that A = x * G and B = x * Y for
+// the same x, as the challenge and response (e, z)
+fn dleq_prove(x: Scalar, a: &JacobianPoint, y: &JacobianPoint, b: &JacobianPoint, aux_rand: &[u8; 32]) -> (Scalar, Scalar) {
+    let nonce_data = [&masked_secret(x, aux_rand)[..], &compressed(affine(a)), &compressed(affine(b))].concat();
+    let k = nonzero(Scalar::reduce_bytes(&tagged_hash("DLEQ/nonce", &nonce_data)));
+    let r_1 = GeneratorTable::multiply_ct(k.to_u256());
+    let r_2 = y.multiply_ct(k.to_u256());
+    let e = dleq_challenge(a, y, b, &r_1, &r_2);
+    (e, k + e * x)
+}
+
+// Recomputes R1 = z * G - e * A and R2 = z * Y - e * B, and checks they hash
+// to the challenge
+fn dleq_verify((e, z): (Scalar, Scalar), a: &JacobianPoint, y: &JacobianPoint, b: &JacobianPoint) -> bool {
+    let r_1 = GeneratorTable::multiply(z.to_u256()).add(&a.multiply((-e).to_u256()));
+    let r_2 = y.multiply(z.to_u256()).add(&b.multiply((-e).to_u256()));
+    if r_1.is_infinity() || r_2.is_infinity() {
+        return false;
+    }
+    dleq_challenge(a, y, b, &r_1, &r_2) == e
+}
+
+fn dleq_challenge(a: &JacobianPoint, y: &JacobianPoint, b: &JacobianPoint, r_1: &JacobianPoint, r_2: &JacobianPoint) -> Scalar {
+    let data: Vec<u8> = [a, y, b, r_1, r_2].iter().flat_map(|point| compressed(affine(point))).collect();
+    Scalar::reduce_bytes(&tagged_hash("DLEQ", &data))
+}
+
+// bytes(d) xor hash(aux_rand), as BIP340 masks the secret before hashing it
+// into the nonce
+fn masked_secret(d: Scalar, aux_rand: &[u8; 32]) -> Vec<u8> {
+    let aux_hash = tagged_hash("BIP0340/aux", aux_rand);
+    d.to_bytes().iter().zip(aux_hash.iter()).map(|(a, b)| a ^ b).collect()
+}
+
+// A hashed nonce is zero with negligible probability
+fn nonzero(k: Scalar) -> Scalar {
+    if k.is_zero() {
+        panic!("adaptor nonce is zero");
+    }
+    k
+}
+
+fn decryption_scalar(decryption_key: &PrivateKey) -> Result<Scalar, AdaptorError> {
+    Scalar::from_u256(decryption_key.secret())
+        .filter(|k| !k.is_zero())
+        .ok_or(AdaptorError::InvalidDecryptionKey)
+}
+
+fn jacobian(point: &Point) -> Result<JacobianPoint, AdaptorError> {
+    if point.is_infinity() {
+        return Err(AdaptorError::InvalidEncryptionKey);
+    }
+    Ok(JacobianPoint::from_point(point))
+}
+
+// Points built here are products of non-zero scalars with non-infinite
+// points, so never infinity
+fn affine(point: &JacobianPoint) -> (U256, U256) {
+    point.to_affine().expect("unexpected point at infinity")
+}
+
+fn equal(a: &JacobianPoint, b: &JacobianPoint) -> bool {
+    a.add(&b.negate()).is_infinity()
+}
+
+fn compressed((x, y): (U256, U256)) -> Vec<u8> {
+    let mut result = vec![if y.bit(0) { 0x03 } else { 0x02 }];
+    result.extend_from_slice(&x.to_big_endian());
+    result
+}
@@ -0,0 +1,177 @@
+use std::thread;
+use sha2::{Digest, Sha256};
+use crate::ecc::generator_table::GeneratorTable;
+use crate::ecc::jacobian_point::JacobianPoint;
+use crate::ecc::point::Point;
+use crate::ecc::scalar::Scalar;
+use crate::ecc::schnorr_signature::SchnorrSignature;
+use crate::ecc::signature::Signature;
+use crate::ecc::xonly_public_key::{challenge, XOnlyPublicKey};
+use crate::utils::tagged_hash::tagged_hash;
+
+/// A signature check waiting to be verified
+#[derive(Debug, Clone)]
+pub enum BatchEntry {
+    Ecdsa {
+        public_key: Box<Point>,
+        z: Vec<u8>,
+        signature: Signature,
+    },
+    Schnorr {
+        public_key: XOnlyPublicKey,
+        msg: Vec<u8>,
+        signature: SchnorrSignature,
+    },
+}
+
+impl BatchEntry {
+    // Verifies the entry on its own
+    fn verify(&self) -> bool {
+        match self {
+            BatchEntry::Ecdsa { public_key, z, signature } => {
+                (**public_key).clone().verify(z.clone(), signature.clone())
+            }
+            BatchEntry::Schnorr { public_key, msg, signature } => public_key.verify_schnorr(msg, signature),
+        }
+    }
+}
+
+/// Collects signature checks and verifies them together. The entries are
+/// split between threads. ECDSA signatures are checked one by one, while
+/// each thread's Schnorr signatures are first checked with a single random
+/// linear combination (as described in BIP340), falling back to individual
+/// checks only to find which one is invalid.
+#[derive(Debug, Clone, Default)]
+pub struct BatchVerifier {
+    entries: Vec<BatchEntry>,
+}
+
+impl BatchVerifier {
+    pub fn new() -> Self {
+        Self { entries: vec![] }
+    }
+
+    /// Adds an ECDSA check, returning the index of the entry
+    pub fn add_ecdsa(&mut self, public_key: Point, z: Vec<u8>, signature: Signature) -> usize {
+        self.entries.push(BatchEntry::Ecdsa { public_key: Box::new(public_key), z, signature });
+        self.entries.len() - 1
+    }
+
+    /// Adds a BIP340 check, returning the index of the entry
+    pub fn add_schnorr(&mut self, public_key: XOnlyPublicKey, msg: Vec<u8>, signature: SchnorrSignature) -> usize {
+        self.entries.push(BatchEntry::Schnorr { public_key, msg, signature });
+        self.entries.len() - 1
+    }
+
+    pub fn entries(&self) -> &[BatchEntry] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Verifies every entry, using as many threads as there are cores.
+    /// Returns the index of the first invalid entry on failure.
+    pub fn verify(&self) -> Result<(), usize> {
+        let threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+        self.verify_with_threads(threads)
+    }
+
+    /// Verifies every entry, splitting them between at most this many
+    /// threads. Returns the index of the first invalid entry on failure.
+    pub fn verify_with_threads(&self, threads: usize) -> Result<(), usize> {
+        if self.entries.is_empty() {
+            return Ok(());
+        }
+        let chunk_size = self.entries.len().div_ceil(threads.max(1));
+        let first_failure = thread::scope(|scope| {
+            let handles: Vec<_> = self
+                .entries
+                .chunks(chunk_size)
+                .enumerate()
+                .map(|(chunk_index, chunk)| {
+                    scope.spawn(move || verify_chunk(chunk).map(|index| chunk_index * chunk_size + index))
+                })
+                .collect();
+            // Chunks are in order, so the first failing chunk has the lowest index
+            handles.into_iter().filter_map(|handle| handle.join().unwrap()).next()
+        });
+        match first_failure {
+            Some(index) => Err(index),
+            None => Ok(()),
+        }
+    }
+}
+
+// Returns the index within the chunk of the first invalid entry
+fn verify_chunk(chunk: &[BatchEntry]) -> Option<usize> {
+    let schnorr: Vec<&BatchEntry> = chunk
+        .iter()
+        .filter(|entry| matches!(entry, BatchEntry::Schnorr { .. }))
+        .collect();
+    let schnorr_valid = schnorr.is_empty() || verify_schnorr_batch(&schnorr);
+
+    chunk.iter().position(|entry| match entry {
+        BatchEntry::Ecdsa { .. } => !entry.verify(),
+        // The batch only tells us that one of them is invalid
+        BatchEntry::Schnorr { .. } => !schnorr_valid && !entry.verify(),
+    })
+}
+
+// BIP340 batch verification: with random a_0 = 1, a_1, ..., a_u-1, checks
+// (a_0 s_0 + ... + a_u-1 s_u-1) G = a_0 R_0 + a_0 e_0 P_0 + ... , which only
+// holds for all the a_i if every signature is valid
+fn verify_schnorr_batch(entries: &[&BatchEntry]) -> bool {
+    // The coefficients are derived from everything being verified, so they
+    // can't be predicted by whoever made the signatures
+    let mut seed = Sha256::new();
+    for entry in entries {
+        if let BatchEntry::Schnorr { public_key, msg, signature } = entry {
+            seed.update(public_key.serialize());
+            seed.update(Sha256::digest(msg));
+            seed.update(signature.serialize());
+        }
+    }
+    let seed = seed.finalize().to_vec();
+
+    let mut s_sum = Scalar::zero();
+    let mut total = JacobianPoint::infinity();
+    for (i, entry) in entries.iter().enumerate() {
+        let (public_key, msg, signature) = match entry {
+            BatchEntry::Schnorr { public_key, msg, signature } => (public_key, msg, signature),
+            BatchEntry::Ecdsa { .. } => unreachable!(),
+        };
+        let (px, py) = match XOnlyPublicKey::lift_x(public_key.x()) {
+            Some(point) => point,
+            None => return false,
+        };
+        let (rx, ry) = match XOnlyPublicKey::lift_x(signature.r()) {
+            Some(point) => point,
+            None => return false,
+        };
+        let s = match Scalar::from_u256(signature.s()) {
+            Some(s) => s,
+            None => return false,
+        };
+        let a = if i == 0 {
+            Scalar::one()
+        } else {
+            let data = [seed.clone(), (i as u64).to_be_bytes().to_vec()].concat();
+            Scalar::reduce_bytes(&tagged_hash("BIP0340/batch", &data))
+        };
+        let e = challenge(signature.r(), public_key.x(), msg);
+
+        s_sum = s_sum + a * s;
+        // a_i R_i + a_i e_i P_i
+        let r_point = JacobianPoint::from_affine(rx, ry);
+        let term = r_point.multiply_add(a.to_u256(), &JacobianPoint::from_affine(px, py), (a * e).to_u256());
+        total = total.add(&term);
+    }
+    GeneratorTable::multiply(s_sum.to_u256()).add(&total.negate()).is_infinity()
+}
+
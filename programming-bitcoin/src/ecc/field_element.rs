@@ -1,26 +1,95 @@
 use crate::ecc::mod_exp::mod_exp;
-use num_bigint::BigUint;
-use primitive_types::U256;
+use crate::ecc::s256field_element::S256Field;
+use primitive_types::{U256, U512};
 use std::fmt;
+use std::marker::PhantomData;
 use std::ops::{Add, Div, Mul, Sub};
 
+/// The prime field a FieldElement lives in. The prime is part of the type,
+/// so elements of different fields can't be mixed by accident: adding a
+/// FieldElement<Fp<13>> to a FieldElement<Fp<19>> doesn't compile.
+///
+/// ```
+/// use primitive_types::U256;
+/// use programming_bitcoin::ecc::field_element::{FieldElement, Fp};
+///
+/// let a = FieldElement::<Fp<13>>::new(U256::from(3));
+/// let b = FieldElement::<Fp<13>>::new(U256::from(12));
+/// assert_eq!((&a + &b).num(), U256::from(2));
+/// ```
+///
+/// ```compile_fail
+/// use primitive_types::U256;
+/// use programming_bitcoin::ecc::field_element::{FieldElement, Fp};
+///
+/// let a = FieldElement::<Fp<13>>::new(U256::from(3));
+/// let b = FieldElement::<Fp<19>>::new(U256::from(3));
+/// let _ = &a + &b;
+/// ```
+///
+/// The arithmetic works on values that are already reduced mod PRIME. The
+/// default implementations go through 512-bit intermediates and work for any
+/// prime; a field with a faster reduction can override them.
+pub trait FieldParams: Clone + fmt::Debug {
+    const PRIME: U256;
+
+    fn add(a: U256, b: U256) -> U256 {
+        let sum = U512::from(a) + U512::from(b);
+        reduce_wide::<Self>(sum)
+    }
+
+    fn sub(a: U256, b: U256) -> U256 {
+        if a >= b {
+            a - b
+        } else {
+            Self::PRIME - (b - a)
+        }
+    }
+
+    fn mul(a: U256, b: U256) -> U256 {
+        reduce_wide::<Self>(a.full_mul(b))
+    }
+
+    fn pow(base: U256, exp: U256) -> U256 {
+        mod_exp(base, exp, Self::PRIME)
+    }
+}
+
+fn reduce_wide<F: FieldParams>(wide: U512) -> U256 {
+    U256::try_from(wide % U512::from(F::PRIME)).expect("value reduced mod a 256-bit prime")
+}
+
+/// A small prime field for the book's teaching examples, e.g. Fp<223> for
+/// the curve y^2 = x^3 + 7 over F_223
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fp<const P: u64>;
+
+impl<const P: u64> FieldParams for Fp<P> {
+    const PRIME: U256 = U256([P, 0, 0, 0]);
+}
+
 #[derive(Debug, Clone)]
-pub struct FieldElement {
+pub struct FieldElement<F: FieldParams = S256Field> {
     num: U256,
-    prime: U256,
+    field: PhantomData<F>,
 }
 
-impl FieldElement {
-    pub fn new(num: U256, prime: U256) -> Self {
-        if num >= prime {
-            panic!("num not in range 0 to {}", prime - 1);
+impl<F: FieldParams> FieldElement<F> {
+    pub fn new(num: U256) -> Self {
+        if num >= F::PRIME {
+            panic!("num not in range 0 to {}", F::PRIME - 1);
         }
-        FieldElement { num, prime }
+        FieldElement { num, field: PhantomData }
     }
 
-    pub fn mod_inverse(&self) -> FieldElement {
+    // Skips the range check, for results of the field arithmetic
+    fn from_reduced(num: U256) -> Self {
+        FieldElement { num, field: PhantomData }
+    }
+
+    pub fn mod_inverse(&self) -> FieldElement<F> {
         // Fermat's Little Theorem: a^(p-1) ≡ 1 (mod p), so a^(p-2) is the inverse
-        self.pow(self.prime - 2)
+        self.pow(F::PRIME - 2)
     }
 
     pub fn is_zero(&self) -> bool {
@@ -31,149 +100,71 @@ impl FieldElement {
         self.num
     }
 
-pub fn sqrt(&self) -> Self {
-    // Convert prime to BigUint
-    let p = BigUint::from_bytes_be(&self.prime.to_big_endian());
-    let exp = (p.clone() + BigUint::from(1u32)) / BigUint::from(4u32);
-    
-    // Convert num to BigUint and perform power operation
-    let a = BigUint::from_bytes_be(&self.num.to_big_endian());
-    let result = a.modpow(&exp, &p);
-    
-    // Convert result back to U256
-    let num = U256::from_big_endian(&result.to_bytes_be());
-    
-    Self::new(num, self.prime)
-}
+    pub fn prime(&self) -> U256 {
+        F::PRIME
+    }
 
+    // Only valid for primes with p ≡ 3 (mod 4), where a^((p+1)/4) is a
+    // square root of a if one exists
+    pub fn sqrt(&self) -> Self {
+        self.pow((F::PRIME + 1) / 4)
+    }
 }
 
-impl PartialEq for &FieldElement {
+impl<F: FieldParams> PartialEq for &FieldElement<F> {
     fn eq(&self, other: &Self) -> bool {
-        self.num == other.num && self.prime == other.prime
+        self.num == other.num
     }
 }
 
-impl fmt::Display for FieldElement {
+impl<F: FieldParams> fmt::Display for FieldElement<F> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
             "FieldElement_num_{:064x}_prime_{:064x}",
-            self.num, self.prime
+            self.num, F::PRIME
         )
     }
 }
 
-impl Add for &FieldElement {
-    type Output = FieldElement;
+impl<F: FieldParams> Add for &FieldElement<F> {
+    type Output = FieldElement<F>;
 
-    fn add(self, other: Self) -> FieldElement {
-        if self.prime != other.prime {
-            panic!("Cannot add two numbers in different fields")
-        }
-        let num = (self.num + other.num) % self.prime;
-        FieldElement {
-            num,
-            prime: self.prime,
-        }
+    fn add(self, other: Self) -> FieldElement<F> {
+        FieldElement::from_reduced(F::add(self.num, other.num))
     }
 }
 
-impl Sub for &FieldElement {
-    type Output = FieldElement;
+impl<F: FieldParams> Sub for &FieldElement<F> {
+    type Output = FieldElement<F>;
 
-    fn sub(self, other: Self) -> FieldElement {
-        if self.prime != other.prime {
-            panic!("Cannot subtract two numbers in different fields")
-        }
-        
-        // Convert to BigUint
-        let a = BigUint::from_bytes_be(&self.num.to_big_endian());
-        let b = BigUint::from_bytes_be(&other.num.to_big_endian());
-        let p = BigUint::from_bytes_be(&self.prime.to_big_endian());
-        
-        // Perform subtraction and modulo
-        let result = if a >= b {
-            (a - b) % p
-        } else {
-            (p.clone() + a - b) % p
-        };
-        
-        // Convert back to U256
-        let num = U256::from_big_endian(&result.to_bytes_be());
-        
-        FieldElement {
-            num,
-            prime: self.prime,
-        }
+    fn sub(self, other: Self) -> FieldElement<F> {
+        FieldElement::from_reduced(F::sub(self.num, other.num))
     }
 }
 
-impl Mul for &FieldElement {
-    type Output = FieldElement;
+impl<F: FieldParams> Mul for &FieldElement<F> {
+    type Output = FieldElement<F>;
 
-    fn mul(self, other: Self) -> FieldElement {
-        if self.prime != other.prime {
-            panic!("Cannot multiply two numbers in different fields")
-        }
-        
-        // Convert to BigUint
-        let a = BigUint::from_bytes_be(&self.num.to_big_endian());
-        let b = BigUint::from_bytes_be(&other.num.to_big_endian());
-        let p = BigUint::from_bytes_be(&self.prime.to_big_endian());
-        
-        // Perform multiplication and modulo
-        let result = (a * b) % p;
-        
-        // Convert back to U256
-        let num = U256::from_big_endian(&result.to_bytes_be());
-        
-        FieldElement {
-            num,
-            prime: self.prime,
-        }
+    fn mul(self, other: Self) -> FieldElement<F> {
+        FieldElement::from_reduced(F::mul(self.num, other.num))
     }
 }
 
-impl Mul<u32> for &FieldElement {
-    type Output = FieldElement;
+impl<F: FieldParams> Mul<u32> for &FieldElement<F> {
+    type Output = FieldElement<F>;
 
-    fn mul(self, other: u32) -> FieldElement {
-        let p = BigUint::from_bytes_be(&self.prime.to_big_endian());
-        let a = BigUint::from_bytes_be(&self.num.to_big_endian());
-        let b = BigUint::from(other);
-        
-        let result = (a * b) % p;
-        let num = U256::from_big_endian(&result.to_bytes_be());
-        
-        FieldElement { num, prime: self.prime }
+    fn mul(self, other: u32) -> FieldElement<F> {
+        let other = reduce_wide::<F>(U512::from(other));
+        FieldElement::from_reduced(F::mul(self.num, other))
     }
 }
 
-impl Div for &FieldElement {
-    type Output = FieldElement;
+impl<F: FieldParams> Div for &FieldElement<F> {
+    type Output = FieldElement<F>;
 
-    fn div(self, other: Self) -> FieldElement {
-        if self.prime != other.prime {
-            panic!("Cannot divide two numbers in different fields");
-        }
-        let inverse = other.mod_inverse();
-        
-        // Convert to BigUint
-        let a = BigUint::from_bytes_be(&self.num.to_big_endian());
-        let b = BigUint::from_bytes_be(&inverse.num.to_big_endian());
-        let p = BigUint::from_bytes_be(&self.prime.to_big_endian());
-        
-        // Perform multiplication and modulo
-        let result = (a * b) % p;
-        
-        // Convert back to U256
-        let num = U256::from_big_endian(&result.to_bytes_be());
-        
-        FieldElement {
-            num,
-            prime: self.prime,
-        }
+    fn div(self, other: Self) -> FieldElement<F> {
+        FieldElement::from_reduced(F::mul(self.num, other.mod_inverse().num))
     }
 }
 
@@ -183,14 +174,10 @@ pub trait Pow {
     fn pow(self, exp: U256) -> Self::Output;
 }
 
-impl Pow for &FieldElement {
-    type Output = FieldElement;
+impl<F: FieldParams> Pow for &FieldElement<F> {
+    type Output = FieldElement<F>;
 
-    fn pow(self, exp: U256) -> FieldElement {
-        let num = mod_exp(self.num, exp, self.prime);
-        FieldElement {
-            num,
-            prime: self.prime,
-        }
+    fn pow(self, exp: U256) -> FieldElement<F> {
+        FieldElement::from_reduced(F::pow(self.num, exp))
     }
 }
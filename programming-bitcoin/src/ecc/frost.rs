@@ -0,0 +1,800 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use primitive_types::U256;
+use crate::ecc::generator_table::GeneratorTable;
+use crate::ecc::jacobian_point::JacobianPoint;
+use crate::ecc::point::Point;
+use crate::ecc::s256point::S256Point;
+use crate::ecc::scalar::Scalar;
+use crate::ecc::schnorr_signature::SchnorrSignature;
+use crate::ecc::xonly_public_key::{challenge, XOnlyPublicKey};
+use crate::utils::rng::get_random_u256;
+use crate::utils::tagged_hash::tagged_hash;
+use zeroize::Zeroize;
+
+// FROST (Flexible Round-Optimized Schnorr Threshold signatures, RFC 9591)
+// over secp256k1, adapted to produce BIP340 signatures: the group key and
+// the group nonce are negated when their y is odd, as with single-signer
+// BIP340. Any t of the n participants can sign; fewer learn nothing about
+// the group secret. Participants are numbered 1..=n by their identifier.
+
+/// Why a FROST operation failed. Where a participant can be blamed, the
+/// variant carries their identifier.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FrostError {
+    // The threshold is zero or above the number of participants
+    InvalidThreshold,
+    // The group secret is zero, which has no public key
+    InvalidSecret,
+    // Identifiers are 1..=n
+    InvalidIdentifier(u16),
+    DuplicateIdentifier(u16),
+    // A secret share does not match the sender's commitment
+    InvalidSecretShare(u16),
+    // A DKG participant did not prove knowledge of their secret
+    InvalidProofOfKnowledge(u16),
+    // A participant's commitment has the wrong number of coefficients
+    InvalidCommitment(u16),
+    // A package from this participant is missing
+    MissingParticipant(u16),
+    // Fewer signers than the threshold
+    NotEnoughSigners,
+    // The signer is not in the signing package
+    SignerNotInPackage(u16),
+    InvalidSignatureShare(u16),
+}
+
+impl fmt::Display for FrostError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FrostError::InvalidThreshold => write!(f, "threshold must be between 1 and the number of participants"),
+            FrostError::InvalidSecret => write!(f, "the group secret must not be zero"),
+            FrostError::InvalidIdentifier(id) => write!(f, "invalid identifier: {}", id),
+            FrostError::DuplicateIdentifier(id) => write!(f, "duplicate identifier: {}", id),
+            FrostError::InvalidSecretShare(id) => write!(f, "invalid secret share from participant {}", id),
+            FrostError::InvalidProofOfKnowledge(id) => write!(f, "invalid proof of knowledge from participant {}", id),
+            FrostError::InvalidCommitment(id) => write!(f, "invalid commitment from participant {}", id),
+            FrostError::MissingParticipant(id) => write!(f, "missing package from participant {}", id),
+            FrostError::NotEnoughSigners => write!(f, "not enough signers to meet the threshold"),
+            FrostError::SignerNotInPackage(id) => write!(f, "participant {} is not in the signing package", id),
+            FrostError::InvalidSignatureShare(id) => write!(f, "invalid signature share from participant {}", id),
+        }
+    }
+}
+
+impl std::error::Error for FrostError {}
+
+/// Commitments A_k = a_k * G to the coefficients of a secret polynomial
+/// f(x) = a_0 + a_1 x + ... + a_t-1 x^t-1. They let anyone check a share
+/// f(i) without learning it (Feldman's verifiable secret sharing).
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifiableCommitment(Vec<(U256, U256)>);
+
+impl VerifiableCommitment {
+    fn from_coefficients(coefficients: &[Scalar]) -> Self {
+        Self(coefficients.iter().map(|a| affine(&mul_g(*a))).collect())
+    }
+
+    /// The commitment to the secret itself, A_0
+    pub fn public_key(&self) -> Point {
+        to_point(self.0[0])
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    // Returns f(i) * G = A_0 + i A_1 + i^2 A_2 + ...
+    fn evaluate(&self, identifier: u16) -> JacobianPoint {
+        let x = to_scalar(identifier);
+        let mut result = JacobianPoint::infinity();
+        // Horner's rule, from the highest coefficient down
+        for (ax, ay) in self.0.iter().rev() {
+            result = result.multiply(x.to_u256()).add_affine(*ax, *ay);
+        }
+        result
+    }
+}
+
+/// A share f(i) of a secret, along with the commitment it can be checked
+/// against
+#[derive(Clone)]
+pub struct SecretShare {
+    identifier: u16,
+    value: Scalar,
+    commitment: VerifiableCommitment,
+}
+
+impl SecretShare {
+    pub fn identifier(&self) -> u16 {
+        self.identifier
+    }
+
+    pub fn commitment(&self) -> &VerifiableCommitment {
+        &self.commitment
+    }
+
+    /// Checks that f(i) * G matches the commitment
+    pub fn verify(&self) -> Result<(), FrostError> {
+        let expected = self.commitment.evaluate(self.identifier);
+        if !equal(&mul_g(self.value), &expected) {
+            return Err(FrostError::InvalidSecretShare(self.identifier));
+        }
+        Ok(())
+    }
+
+    /// Verifies the share and turns it into the participant's key package
+    pub fn into_key_package(self) -> Result<KeyPackage, FrostError> {
+        self.verify()?;
+        Ok(KeyPackage {
+            identifier: self.identifier,
+            signing_share: self.value,
+            verifying_share: affine(&mul_g(self.value)),
+            group_public_key: self.commitment.0[0],
+            threshold: self.commitment.len(),
+        })
+    }
+}
+
+/// Everything a participant needs to sign: their share of the group secret
+/// and the group key
+#[derive(Clone)]
+pub struct KeyPackage {
+    identifier: u16,
+    signing_share: Scalar,
+    verifying_share: (U256, U256),
+    group_public_key: (U256, U256),
+    threshold: usize,
+}
+
+impl KeyPackage {
+    pub fn identifier(&self) -> u16 {
+        self.identifier
+    }
+
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    /// The public key of this participant's share, s_i * G
+    pub fn verifying_share(&self) -> Point {
+        to_point(self.verifying_share)
+    }
+
+    pub fn group_public_key(&self) -> XOnlyPublicKey {
+        XOnlyPublicKey::from_point(&to_point(self.group_public_key))
+    }
+}
+
+/// The public keys of every participant's share and the group key. This is
+/// all a coordinator needs to check signature shares and aggregate them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PublicKeyPackage {
+    verifying_shares: BTreeMap<u16, (U256, U256)>,
+    group_public_key: (U256, U256),
+    threshold: usize,
+}
+
+impl PublicKeyPackage {
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    pub fn verifying_share(&self, identifier: u16) -> Option<Point> {
+        self.verifying_shares.get(&identifier).map(|share| to_point(*share))
+    }
+
+    /// The group key, including its y coordinate
+    pub fn group_point(&self) -> Point {
+        to_point(self.group_public_key)
+    }
+
+    /// The key that FROST signatures verify against with BIP340
+    pub fn group_public_key(&self) -> XOnlyPublicKey {
+        XOnlyPublicKey::from_point(&self.group_point())
+    }
+}
+
+/// Splits a random group secret into shares for participants 1..=n, any
+/// threshold of which can sign. The dealer learns the secret, so this is for
+/// when the dealer is trusted (or is the one setting up the custody).
+pub fn trusted_dealer_keygen(threshold: usize, participants: u16) -> Result<(Vec<SecretShare>, PublicKeyPackage), FrostError> {
+    trusted_dealer_keygen_with_secret(random_scalar(), threshold, participants)
+}
+
+/// Like trusted_dealer_keygen, but splits an existing secret
+pub fn trusted_dealer_keygen_with_secret(
+    secret: Scalar,
+    threshold: usize,
+    participants: u16,
+) -> Result<(Vec<SecretShare>, PublicKeyPackage), FrostError> {
+    check_threshold(threshold, participants)?;
+    if secret.is_zero() {
+        return Err(FrostError::InvalidSecret);
+    }
+    let mut coefficients = vec![secret];
+    coefficients.extend((1..threshold).map(|_| random_scalar()));
+    let commitment = VerifiableCommitment::from_coefficients(&coefficients);
+
+    let shares: Vec<SecretShare> = (1..=participants)
+        .map(|identifier| SecretShare {
+            identifier,
+            value: evaluate_polynomial(&coefficients, identifier),
+            commitment: commitment.clone(),
+        })
+        .collect();
+    let verifying_shares = shares
+        .iter()
+        .map(|share| (share.identifier, affine(&mul_g(share.value))))
+        .collect();
+    let public_key_package = PublicKeyPackage { verifying_shares, group_public_key: commitment.0[0], threshold };
+    Ok((shares, public_key_package))
+}
+
+/// A participant's secret state between the rounds of distributed key
+/// generation
+pub struct DkgRound1Secret {
+    identifier: u16,
+    coefficients: Vec<Scalar>,
+    participants: u16,
+}
+
+/// Broadcast to every other participant in the first round of DKG: the
+/// commitment to the participant's polynomial, and a Schnorr proof that
+/// they know its constant term (which stops rogue key attacks)
+#[derive(Debug, Clone)]
+pub struct DkgRound1Package {
+    identifier: u16,
+    commitment: VerifiableCommitment,
+    proof_of_knowledge: ((U256, U256), Scalar),
+}
+
+impl DkgRound1Package {
+    pub fn identifier(&self) -> u16 {
+        self.identifier
+    }
+
+    pub fn commitment(&self) -> &VerifiableCommitment {
+        &self.commitment
+    }
+}
+
+/// Sent privately from one participant to another in the second round of
+/// DKG: the sender's polynomial evaluated at the recipient's identifier
+#[derive(Clone)]
+pub struct DkgRound2Package {
+    sender: u16,
+    recipient: u16,
+    share: Scalar,
+}
+
+impl DkgRound2Package {
+    pub fn sender(&self) -> u16 {
+        self.sender
+    }
+
+    pub fn recipient(&self) -> u16 {
+        self.recipient
+    }
+}
+
+/// First round of distributed key generation (Pedersen DKG as in the FROST
+/// paper). Every participant picks a random polynomial, and the group
+/// secret is the sum of their constant terms, which nobody ever learns.
+pub fn dkg_part1(identifier: u16, threshold: usize, participants: u16) -> Result<(DkgRound1Secret, DkgRound1Package), FrostError> {
+    check_threshold(threshold, participants)?;
+    if identifier == 0 || identifier > participants {
+        return Err(FrostError::InvalidIdentifier(identifier));
+    }
+    let coefficients: Vec<Scalar> = (0..threshold).map(|_| random_scalar()).collect();
+    let commitment = VerifiableCommitment::from_coefficients(&coefficients);
+
+    // Prove knowledge of a_0: R = k * G, z = k + a_0 * c
+    let k = random_scalar();
+    let r = affine(&mul_g(k));
+    let c = dkg_challenge(identifier, commitment.0[0], r);
+    let z = k + coefficients[0] * c;
+
+    let package = DkgRound1Package { identifier, commitment, proof_of_knowledge: (r, z) };
+    let secret = DkgRound1Secret { identifier, coefficients, participants };
+    Ok((secret, package))
+}
+
+/// Second round of DKG: checks everyone else's proof of knowledge, then
+/// returns the shares to send privately to each of the other participants
+pub fn dkg_part2(secret: &DkgRound1Secret, round1_packages: &[DkgRound1Package]) -> Result<Vec<DkgRound2Package>, FrostError> {
+    let others = other_packages(secret, round1_packages)?;
+    for package in others.values() {
+        if package.commitment.len() != secret.coefficients.len() {
+            return Err(FrostError::InvalidCommitment(package.identifier));
+        }
+        // R = z * G - c * A_0
+        let (r, z) = package.proof_of_knowledge;
+        let c = dkg_challenge(package.identifier, package.commitment.0[0], r);
+        let a_0 = package.commitment.0[0];
+        let expected = mul_g(z).add(&JacobianPoint::from_affine(a_0.0, a_0.1).multiply((-c).to_u256()));
+        if !equal(&expected, &JacobianPoint::from_affine(r.0, r.1)) {
+            return Err(FrostError::InvalidProofOfKnowledge(package.identifier));
+        }
+    }
+    Ok(others
+        .keys()
+        .map(|recipient| DkgRound2Package {
+            sender: secret.identifier,
+            recipient: *recipient,
+            share: evaluate_polynomial(&secret.coefficients, *recipient),
+        })
+        .collect())
+}
+
+/// Final round of DKG: checks the shares received against their senders'
+/// commitments and sums them into this participant's signing share. Every
+/// participant ends up with the same PublicKeyPackage.
+pub fn dkg_part3(
+    secret: &DkgRound1Secret,
+    round1_packages: &[DkgRound1Package],
+    round2_packages: &[DkgRound2Package],
+) -> Result<(KeyPackage, PublicKeyPackage), FrostError> {
+    let others = other_packages(secret, round1_packages)?;
+    let mut signing_share = evaluate_polynomial(&secret.coefficients, secret.identifier);
+    for sender in others.keys() {
+        let package = round2_packages
+            .iter()
+            .find(|package| package.sender == *sender && package.recipient == secret.identifier)
+            .ok_or(FrostError::MissingParticipant(*sender))?;
+        let share = SecretShare {
+            identifier: secret.identifier,
+            value: package.share,
+            commitment: others[sender].commitment.clone(),
+        };
+        share.verify().map_err(|_| FrostError::InvalidSecretShare(*sender))?;
+        signing_share = signing_share + package.share;
+    }
+
+    // The group's polynomial is the sum of everyone's, so its commitment is
+    // the sum of their commitments
+    let own_commitment = VerifiableCommitment::from_coefficients(&secret.coefficients);
+    let commitments: Vec<&VerifiableCommitment> = others
+        .values()
+        .map(|package| &package.commitment)
+        .chain(std::iter::once(&own_commitment))
+        .collect();
+    let group_public_key = commitments
+        .iter()
+        .fold(JacobianPoint::infinity(), |sum, commitment| sum.add_affine(commitment.0[0].0, commitment.0[0].1));
+    let verifying_shares = (1..=secret.participants)
+        .map(|identifier| {
+            let share = commitments
+                .iter()
+                .fold(JacobianPoint::infinity(), |sum, commitment| sum.add(&commitment.evaluate(identifier)));
+            (identifier, affine(&share))
+        })
+        .collect();
+
+    let key_package = KeyPackage {
+        identifier: secret.identifier,
+        signing_share,
+        verifying_share: affine(&mul_g(signing_share)),
+        group_public_key: affine(&group_public_key),
+        threshold: secret.coefficients.len(),
+    };
+    let public_key_package = PublicKeyPackage {
+        verifying_shares,
+        group_public_key: affine(&group_public_key),
+        threshold: secret.coefficients.len(),
+    };
+    Ok((key_package, public_key_package))
+}
+
+/// A signer's secret nonces for one signing session: the hiding nonce d
+/// and the binding nonce e. They must only ever be used once, so they can't
+/// be cloned and sign takes them by value.
+pub struct SigningNonces {
+    hiding: Scalar,
+    binding: Scalar,
+}
+
+/// The public commitments D = d * G and E = e * G to a signer's nonces,
+/// sent to the coordinator in the first round of signing
+#[derive(Debug, Clone, PartialEq)]
+pub struct SigningCommitments {
+    identifier: u16,
+    hiding: (U256, U256),
+    binding: (U256, U256),
+}
+
+impl SigningCommitments {
+    pub fn identifier(&self) -> u16 {
+        self.identifier
+    }
+}
+
+/// First round of signing: makes fresh nonces and their commitments. The
+/// nonces are derived from fresh randomness hashed with the signing share,
+/// so bad randomness alone doesn't leak the share.
+pub fn commit(key_package: &KeyPackage) -> (SigningNonces, SigningCommitments) {
+    let nonce = |tag: u8| {
+        let mut data = get_random_u256().to_big_endian().to_vec();
+        data.extend_from_slice(&key_package.signing_share.to_bytes());
+        data.push(tag);
+        let k = Scalar::reduce_bytes(&tagged_hash("FROST/nonce", &data));
+        if k.is_zero() { Scalar::one() } else { k }
+    };
+    let nonces = SigningNonces { hiding: nonce(0), binding: nonce(1) };
+    let commitments = SigningCommitments {
+        identifier: key_package.identifier,
+        hiding: affine(&mul_g(nonces.hiding)),
+        binding: affine(&mul_g(nonces.binding)),
+    };
+    (nonces, commitments)
+}
+
+/// What the coordinator sends to the chosen signers in the second round:
+/// the message and every signer's commitments
+#[derive(Debug, Clone)]
+pub struct SigningPackage {
+    // Keyed by identifier, so always in the same order
+    commitments: BTreeMap<u16, SigningCommitments>,
+    message: Vec<u8>,
+}
+
+impl SigningPackage {
+    pub fn new(commitments: Vec<SigningCommitments>, message: &[u8]) -> Result<Self, FrostError> {
+        let mut by_identifier = BTreeMap::new();
+        for commitment in commitments {
+            if commitment.identifier == 0 {
+                return Err(FrostError::InvalidIdentifier(0));
+            }
+            let identifier = commitment.identifier;
+            if by_identifier.insert(identifier, commitment).is_some() {
+                return Err(FrostError::DuplicateIdentifier(identifier));
+            }
+        }
+        Ok(Self { commitments: by_identifier, message: message.to_vec() })
+    }
+
+    pub fn message(&self) -> &[u8] {
+        &self.message
+    }
+
+    pub fn signers(&self) -> Vec<u16> {
+        self.commitments.keys().copied().collect()
+    }
+
+    // rho_i = H(Y || H(m) || H(commitments) || i). Binding each signer's
+    // nonce to the message and to everyone's commitments is what stops
+    // the Drijvers attack on concurrent sessions.
+    fn binding_factors(&self, group_public_key: (U256, U256)) -> BTreeMap<u16, Scalar> {
+        let mut encoded_commitments = vec![];
+        for commitment in self.commitments.values() {
+            encoded_commitments.extend_from_slice(&commitment.identifier.to_be_bytes());
+            encoded_commitments.extend_from_slice(&compressed(commitment.hiding));
+            encoded_commitments.extend_from_slice(&compressed(commitment.binding));
+        }
+        let mut prefix = compressed(group_public_key).to_vec();
+        prefix.extend(tagged_hash("FROST/msg", &self.message));
+        prefix.extend(tagged_hash("FROST/com", &encoded_commitments));
+        self.commitments
+            .keys()
+            .map(|identifier| {
+                let data = [prefix.as_slice(), &identifier.to_be_bytes()].concat();
+                (*identifier, Scalar::reduce_bytes(&tagged_hash("FROST/rho", &data)))
+            })
+            .collect()
+    }
+
+    // The values every signer and the coordinator derive from the package:
+    // the binding factors, R = sum of D_i + rho_i E_i, and the challenge
+    fn session(&self, group_public_key: (U256, U256)) -> SigningSession {
+        let binding_factors = self.binding_factors(group_public_key);
+        let mut r = JacobianPoint::infinity();
+        for (identifier, commitment) in &self.commitments {
+            r = r.add(&self.signer_commitment(commitment, binding_factors[identifier]));
+        }
+        // R can only be infinity if the commitments were chosen to cancel out,
+        // and then there is nothing to sign with
+        let r = r.to_affine().unwrap_or((U256::zero(), U256::zero()));
+        let c = challenge(r.0, group_public_key.0, &self.message);
+        SigningSession { binding_factors, r, c }
+    }
+
+    // D_i + rho_i * E_i
+    fn signer_commitment(&self, commitment: &SigningCommitments, rho: Scalar) -> JacobianPoint {
+        let binding = JacobianPoint::from_affine(commitment.binding.0, commitment.binding.1).multiply(rho.to_u256());
+        binding.add_affine(commitment.hiding.0, commitment.hiding.1)
+    }
+
+    fn lagrange_coefficient(&self, identifier: u16) -> Scalar {
+        lagrange_coefficient(identifier, &self.signers())
+    }
+}
+
+struct SigningSession {
+    binding_factors: BTreeMap<u16, Scalar>,
+    r: (U256, U256),
+    c: Scalar,
+}
+
+/// A signer's share z_i of the final signature
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignatureShare {
+    identifier: u16,
+    z: Scalar,
+}
+
+impl SignatureShare {
+    pub fn identifier(&self) -> u16 {
+        self.identifier
+    }
+}
+
+/// Second round of signing: z_i = d_i + e_i rho_i + lambda_i s_i c, with the
+/// nonces negated if R has an odd y and the share negated if the group key
+/// does, so the result is a BIP340 signature
+pub fn sign(signing_package: &SigningPackage, nonces: SigningNonces, key_package: &KeyPackage) -> Result<SignatureShare, FrostError> {
+    let identifier = key_package.identifier;
+    if signing_package.commitments.len() < key_package.threshold {
+        return Err(FrostError::NotEnoughSigners);
+    }
+    let own_commitment = signing_package
+        .commitments
+        .get(&identifier)
+        .ok_or(FrostError::SignerNotInPackage(identifier))?;
+    // The coordinator must have passed on our actual commitments
+    if own_commitment.hiding != affine(&mul_g(nonces.hiding)) || own_commitment.binding != affine(&mul_g(nonces.binding)) {
+        return Err(FrostError::InvalidSignatureShare(identifier));
+    }
+
+    let session = signing_package.session(key_package.group_public_key);
+    let (d, e) = if session.r.1.bit(0) { (-nonces.hiding, -nonces.binding) } else { (nonces.hiding, nonces.binding) };
+    let s = if key_package.group_public_key.1.bit(0) { -key_package.signing_share } else { key_package.signing_share };
+    let lambda = signing_package.lagrange_coefficient(identifier);
+    let z = d + e * session.binding_factors[&identifier] + lambda * s * session.c;
+    Ok(SignatureShare { identifier, z })
+}
+
+/// Checks a signature share against the signer's verifying share:
+/// z_i * G = R_i + c * lambda_i * Y_i, with the same negations as sign
+pub fn verify_signature_share(signing_package: &SigningPackage, share: &SignatureShare, public_key_package: &PublicKeyPackage) -> bool {
+    let (commitment, verifying_share) = match (
+        signing_package.commitments.get(&share.identifier),
+        public_key_package.verifying_shares.get(&share.identifier),
+    ) {
+        (Some(commitment), Some(verifying_share)) => (commitment, *verifying_share),
+        _ => return false,
+    };
+    let session = signing_package.session(public_key_package.group_public_key);
+    verify_share(signing_package, &session, share, commitment, verifying_share, public_key_package.group_public_key)
+}
+
+/// Sums the signature shares into a BIP340 signature for the group key. If
+/// the signature doesn't verify, the first invalid share is blamed.
+pub fn aggregate(
+    signing_package: &SigningPackage,
+    shares: &[SignatureShare],
+    public_key_package: &PublicKeyPackage,
+) -> Result<SchnorrSignature, FrostError> {
+    // Too few signers can't produce a valid signature, however honest
+    if shares.is_empty() || signing_package.commitments.len() < public_key_package.threshold {
+        return Err(FrostError::NotEnoughSigners);
+    }
+    for identifier in signing_package.commitments.keys() {
+        if !shares.iter().any(|share| share.identifier == *identifier) {
+            return Err(FrostError::MissingParticipant(*identifier));
+        }
+    }
+    let session = signing_package.session(public_key_package.group_public_key);
+    let z = shares.iter().fold(Scalar::zero(), |sum, share| sum + share.z);
+    let signature = SchnorrSignature::new(session.r.0, z.to_u256());
+    if public_key_package.group_public_key().verify_schnorr(&signing_package.message, &signature) {
+        return Ok(signature);
+    }
+    for share in shares {
+        let valid = match (
+            signing_package.commitments.get(&share.identifier),
+            public_key_package.verifying_shares.get(&share.identifier),
+        ) {
+            (Some(commitment), Some(verifying_share)) => {
+                verify_share(signing_package, &session, share, commitment, *verifying_share, public_key_package.group_public_key)
+            }
+            _ => false,
+        };
+        if !valid {
+            return Err(FrostError::InvalidSignatureShare(share.identifier));
+        }
+    }
+    // Every share is valid, but a share may have been given twice
+    Err(FrostError::InvalidSignatureShare(shares[0].identifier))
+}
+
+fn verify_share(
+    signing_package: &SigningPackage,
+    session: &SigningSession,
+    share: &SignatureShare,
+    commitment: &SigningCommitments,
+    verifying_share: (U256, U256),
+    group_public_key: (U256, U256),
+) -> bool {
+    let r_i = signing_package.signer_commitment(commitment, session.binding_factors[&share.identifier]);
+    let r_i = if session.r.1.bit(0) { r_i.negate() } else { r_i };
+    let y_i = JacobianPoint::from_affine(verifying_share.0, verifying_share.1);
+    let y_i = if group_public_key.1.bit(0) { y_i.negate() } else { y_i };
+    let lambda = signing_package.lagrange_coefficient(share.identifier);
+    let expected = r_i.add(&y_i.multiply((session.c * lambda).to_u256()));
+    equal(&mul_g(share.z), &expected)
+}
+
+/// lambda_i = product over the other signers j of x_j / (x_j - x_i). The
+/// group secret is the sum of lambda_i * s_i over any threshold of signers.
+pub fn lagrange_coefficient(identifier: u16, signers: &[u16]) -> Scalar {
+    let x_i = to_scalar(identifier);
+    let mut numerator = Scalar::one();
+    let mut denominator = Scalar::one();
+    for j in signers.iter().filter(|j| **j != identifier) {
+        let x_j = to_scalar(*j);
+        numerator = numerator * x_j;
+        denominator = denominator * (x_j - x_i);
+    }
+    numerator * denominator.invert().unwrap()
+}
+
+// Shares and nonces are never printed, and are wiped when dropped
+
+impl fmt::Debug for SecretShare {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SecretShare")
+            .field("identifier", &self.identifier)
+            .field("value", &"<redacted>")
+            .field("commitment", &self.commitment)
+            .finish()
+    }
+}
+
+impl Drop for SecretShare {
+    fn drop(&mut self) {
+        self.value.zeroize();
+    }
+}
+
+impl fmt::Debug for KeyPackage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("KeyPackage")
+            .field("identifier", &self.identifier)
+            .field("signing_share", &"<redacted>")
+            .field("verifying_share", &self.verifying_share)
+            .field("group_public_key", &self.group_public_key)
+            .field("threshold", &self.threshold)
+            .finish()
+    }
+}
+
+impl Drop for KeyPackage {
+    fn drop(&mut self) {
+        self.signing_share.zeroize();
+    }
+}
+
+impl fmt::Debug for DkgRound1Secret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DkgRound1Secret")
+            .field("identifier", &self.identifier)
+            .field("coefficients", &"<redacted>")
+            .field("participants", &self.participants)
+            .finish()
+    }
+}
+
+impl Drop for DkgRound1Secret {
+    fn drop(&mut self) {
+        self.coefficients.iter_mut().for_each(Zeroize::zeroize);
+    }
+}
+
+impl fmt::Debug for DkgRound2Package {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DkgRound2Package")
+            .field("sender", &self.sender)
+            .field("recipient", &self.recipient)
+            .field("share", &"<redacted>")
+            .finish()
+    }
+}
+
+impl Drop for DkgRound2Package {
+    fn drop(&mut self) {
+        self.share.zeroize();
+    }
+}
+
+impl fmt::Debug for SigningNonces {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("SigningNonces(<redacted>)")
+    }
+}
+
+impl Drop for SigningNonces {
+    fn drop(&mut self) {
+        self.hiding.zeroize();
+        self.binding.zeroize();
+    }
+}
+
+fn check_threshold(threshold: usize, participants: u16) -> Result<(), FrostError> {
+    if threshold == 0 || threshold > participants as usize {
+        return Err(FrostError::InvalidThreshold);
+    }
+    Ok(())
+}
+
+// The round 1 packages of everyone but us, keyed by identifier. There must
+// be exactly one from each of the other participants.
+fn other_packages<'a>(secret: &DkgRound1Secret, packages: &'a [DkgRound1Package]) -> Result<BTreeMap<u16, &'a DkgRound1Package>, FrostError> {
+    let mut others = BTreeMap::new();
+    for package in packages.iter().filter(|package| package.identifier != secret.identifier) {
+        if package.identifier == 0 || package.identifier > secret.participants {
+            return Err(FrostError::InvalidIdentifier(package.identifier));
+        }
+        if others.insert(package.identifier, package).is_some() {
+            return Err(FrostError::DuplicateIdentifier(package.identifier));
+        }
+    }
+    for identifier in (1..=secret.participants).filter(|id| *id != secret.identifier) {
+        if !others.contains_key(&identifier) {
+            return Err(FrostError::MissingParticipant(identifier));
+        }
+    }
+    Ok(others)
+}
+
+// c = H(i || A_0 || R) for the DKG proof of knowledge
+fn dkg_challenge(identifier: u16, a_0: (U256, U256), r: (U256, U256)) -> Scalar {
+    let data = [&identifier.to_be_bytes()[..], &compressed(a_0), &compressed(r)].concat();
+    Scalar::reduce_bytes(&tagged_hash("FROST/dkg", &data))
+}
+
+fn evaluate_polynomial(coefficients: &[Scalar], identifier: u16) -> Scalar {
+    let x = to_scalar(identifier);
+    coefficients.iter().rev().fold(Scalar::zero(), |result, a| result * x + *a)
+}
+
+fn random_scalar() -> Scalar {
+    loop {
+        let k = Scalar::reduce(get_random_u256());
+        if !k.is_zero() {
+            return k;
+        }
+    }
+}
+
+fn to_scalar(identifier: u16) -> Scalar {
+    Scalar::reduce(U256::from(identifier))
+}
+
+// Constant time, as most of what is multiplied here is a share or a nonce
+fn mul_g(k: Scalar) -> JacobianPoint {
+    GeneratorTable::multiply_ct(k.to_u256())
+}
+
+// Every point handled here comes from multiplying by a non-zero scalar or
+// summing independent random points, so it is never infinity
+fn affine(point: &JacobianPoint) -> (U256, U256) {
+    point.to_affine().expect("unexpected point at infinity")
+}
+
+fn equal(a: &JacobianPoint, b: &JacobianPoint) -> bool {
+    a.add(&b.negate()).is_infinity()
+}
+
+fn to_point((x, y): (U256, U256)) -> Point {
+    S256Point::new_s256_point_unchecked(x, y)
+}
+
+fn compressed((x, y): (U256, U256)) -> Vec<u8> {
+    let mut result = vec![if y.bit(0) { 0x03 } else { 0x02 }];
+    result.extend_from_slice(&x.to_big_endian());
+    result
+}
@@ -0,0 +1,84 @@
+use std::sync::OnceLock;
+use primitive_types::U256;
+use crate::ecc::jacobian_point::{select_affine, JacobianPoint};
+use subtle::ConstantTimeEq;
+use crate::ecc::scalar::Scalar;
+use crate::ecc::secp256k1_params::S256Params;
+
+// The scalar is split into 64 windows of 4 bits. For window i the table holds
+// the affine points j * 16^i * G for j in 1..=15, so multiplying G by any
+// scalar takes at most 64 mixed additions and no doublings at all.
+const WINDOW_BITS: usize = 4;
+const WINDOWS: usize = 256 / WINDOW_BITS;
+const ENTRIES: usize = (1 << WINDOW_BITS) - 1;
+
+type Window = [(U256, U256); ENTRIES];
+
+static TABLE: OnceLock<Vec<Window>> = OnceLock::new();
+
+/// Precomputed multiples of the secp256k1 generator point. The table is
+/// built the first time it is used and shared by every thread afterwards.
+pub struct GeneratorTable;
+
+impl GeneratorTable {
+    /// Returns coefficient * G
+    pub fn multiply(coefficient: U256) -> JacobianPoint {
+        let table = Self::table();
+        let coef = Scalar::reduce(coefficient).to_u256();
+        let mut result = JacobianPoint::infinity();
+        for (i, window) in table.iter().enumerate() {
+            let digit = ((coef >> (i * WINDOW_BITS)).low_u64() & ENTRIES as u64) as usize;
+            if digit != 0 {
+                let (x, y) = window[digit - 1];
+                result = result.add_affine(x, y);
+            }
+        }
+        result
+    }
+
+    /// Returns coefficient * G for a secret coefficient, such as a private
+    /// key or a nonce. Every window does one addition and reads its whole row
+    /// of the table. The sum starts at G (subtracted at the end) so that zero
+    /// leading windows don't leave it at infinity, but it can still reach
+    /// infinity or equal the point being added: with a coefficient of n - 1
+    /// the last window brings it to n * G. The result is correct regardless,
+    /// as add_affine and add handle infinity and doubling; only those rare
+    /// additions take a different path. See JacobianPoint::multiply_ct.
+    pub fn multiply_ct(coefficient: U256) -> JacobianPoint {
+        let table = Self::table();
+        let coef = Scalar::reduce(coefficient).to_u256();
+        let g = JacobianPoint::from_affine(S256Params::gx(), S256Params::gy());
+        let mut result = g;
+        for (i, window) in table.iter().enumerate() {
+            let digit = ((coef >> (i * WINDOW_BITS)).low_u64() & ENTRIES as u64) as u8;
+            let (x, y) = select_affine(window, digit);
+            let sum = result.add_affine(x, y);
+            result = JacobianPoint::conditional_select(&result, &sum, !digit.ct_eq(&0));
+        }
+        result.add(&g.negate())
+    }
+
+    fn table() -> &'static Vec<Window> {
+        TABLE.get_or_init(Self::build)
+    }
+
+    fn build() -> Vec<Window> {
+        let mut points = Vec::with_capacity(WINDOWS * ENTRIES);
+        // base is 16^i * G for the current window
+        let mut base = JacobianPoint::from_affine(S256Params::gx(), S256Params::gy());
+        for _ in 0..WINDOWS {
+            let mut current = base;
+            for _ in 0..ENTRIES {
+                points.push(current);
+                current = current.add(&base);
+            }
+            // current has reached 16 * base
+            base = current;
+        }
+        let affine = JacobianPoint::batch_to_affine(&points);
+        affine
+            .chunks(ENTRIES)
+            .map(|chunk| chunk.try_into().unwrap())
+            .collect()
+    }
+}
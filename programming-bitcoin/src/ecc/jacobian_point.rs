@@ -0,0 +1,310 @@
+use primitive_types::U256;
+use crate::ecc::point::Point;
+use crate::ecc::s256field_element::S256FieldElement as Fe;
+use crate::ecc::s256point::S256Point;
+use crate::ecc::scalar::{select_u256, Scalar};
+use crate::ecc::secp256k1_params::S256Params;
+use subtle::{Choice, ConstantTimeEq};
+
+// Width of the wNAF window. Each point multiplication precomputes the
+// 2^(WINDOW - 2) odd multiples P, 3P, 5P, ... of the point it multiplies.
+const WINDOW: usize = 5;
+const TABLE_SIZE: usize = 1 << (WINDOW - 2);
+
+// multiply_ct uses fixed 4 bit windows, with a table of P..=15P
+const CT_WINDOW_BITS: usize = 4;
+const CT_ENTRIES: usize = (1 << CT_WINDOW_BITS) - 1;
+
+/// A secp256k1 point in Jacobian coordinates, where (X, Y, Z) represents the
+/// affine point (X / Z^2, Y / Z^3). Working in these coordinates means adding
+/// and doubling never need a modular inversion; only the final conversion
+/// back to affine does. The point at infinity has Z = 0.
+#[derive(Debug, Clone, Copy)]
+pub struct JacobianPoint {
+    x: U256,
+    y: U256,
+    z: U256,
+}
+
+impl JacobianPoint {
+    pub fn infinity() -> Self {
+        Self {
+            x: U256::one(),
+            y: U256::one(),
+            z: U256::zero(),
+        }
+    }
+
+    pub fn from_affine(x: U256, y: U256) -> Self {
+        Self { x, y, z: U256::one() }
+    }
+
+    pub fn from_point(point: &Point) -> Self {
+        match point.coordinates() {
+            Some((x, y)) => Self::from_affine(x, y),
+            None => Self::infinity(),
+        }
+    }
+
+    pub fn is_infinity(&self) -> bool {
+        self.z.is_zero()
+    }
+
+    /// Converts back to affine (x, y) with a single inversion. Returns None
+    /// for the point at infinity.
+    pub fn to_affine(&self) -> Option<(U256, U256)> {
+        if self.is_infinity() {
+            return None;
+        }
+        let z_inv = Fe::inverse(self.z);
+        let z_inv_2 = Fe::square(z_inv);
+        let z_inv_3 = Fe::mul(z_inv_2, z_inv);
+        Some((Fe::mul(self.x, z_inv_2), Fe::mul(self.y, z_inv_3)))
+    }
+
+    /// Converts many points to affine at once using Montgomery's trick, so
+    /// the whole batch costs one inversion instead of one per point. Every
+    /// point must be finite.
+    pub fn batch_to_affine(points: &[Self]) -> Vec<(U256, U256)> {
+        // prefix[i] is the product of the first i Zs
+        let mut prefix = Vec::with_capacity(points.len() + 1);
+        prefix.push(U256::one());
+        for point in points {
+            prefix.push(Fe::mul(prefix[prefix.len() - 1], point.z));
+        }
+        let mut inverse = Fe::inverse(prefix[points.len()]);
+        let mut result = vec![(U256::zero(), U256::zero()); points.len()];
+        for i in (0..points.len()).rev() {
+            // inverse is currently 1 / (z_0 * ... * z_i)
+            let z_inv = Fe::mul(inverse, prefix[i]);
+            inverse = Fe::mul(inverse, points[i].z);
+            let z_inv_2 = Fe::square(z_inv);
+            let z_inv_3 = Fe::mul(z_inv_2, z_inv);
+            result[i] = (Fe::mul(points[i].x, z_inv_2), Fe::mul(points[i].y, z_inv_3));
+        }
+        result
+    }
+
+    pub fn to_point(&self) -> Point {
+        match self.to_affine() {
+            Some((x, y)) => S256Point::new_s256_point_unchecked(x, y),
+            None => S256Point::new_s256_infinity(),
+        }
+    }
+
+    pub fn negate(&self) -> Self {
+        Self {
+            x: self.x,
+            y: Fe::neg(self.y),
+            z: self.z,
+        }
+    }
+
+    // dbl-2009-l from the Explicit-Formulas Database, specialised for a = 0
+    pub fn double(&self) -> Self {
+        if self.is_infinity() || self.y.is_zero() {
+            return Self::infinity();
+        }
+        let a = Fe::square(self.x);
+        let b = Fe::square(self.y);
+        let c = Fe::square(b);
+        let x_plus_b = Fe::add(self.x, b);
+        let d = Fe::sub(Fe::sub(Fe::square(x_plus_b), a), c);
+        let d = Fe::add(d, d);
+        let e = Fe::add(Fe::add(a, a), a);
+        let f = Fe::square(e);
+        let x3 = Fe::sub(f, Fe::add(d, d));
+        let c8 = Fe::add(c, c);
+        let c8 = Fe::add(c8, c8);
+        let c8 = Fe::add(c8, c8);
+        let y3 = Fe::sub(Fe::mul(e, Fe::sub(d, x3)), c8);
+        let y_z = Fe::mul(self.y, self.z);
+        let z3 = Fe::add(y_z, y_z);
+        Self { x: x3, y: y3, z: z3 }
+    }
+
+    pub fn add(&self, other: &Self) -> Self {
+        if self.is_infinity() {
+            return *other;
+        }
+        if other.is_infinity() {
+            return *self;
+        }
+        let z1_2 = Fe::square(self.z);
+        let z2_2 = Fe::square(other.z);
+        let u1 = Fe::mul(self.x, z2_2);
+        let u2 = Fe::mul(other.x, z1_2);
+        let s1 = Fe::mul(self.y, Fe::mul(z2_2, other.z));
+        let s2 = Fe::mul(other.y, Fe::mul(z1_2, self.z));
+        let z3 = Fe::mul(self.z, other.z);
+        Self::add_inner(u1, u2, s1, s2, z3, self)
+    }
+
+    /// Adds an affine point (Z = 1), which saves several multiplications
+    pub fn add_affine(&self, x2: U256, y2: U256) -> Self {
+        if self.is_infinity() {
+            return Self::from_affine(x2, y2);
+        }
+        let z1_2 = Fe::square(self.z);
+        let u2 = Fe::mul(x2, z1_2);
+        let s2 = Fe::mul(y2, Fe::mul(z1_2, self.z));
+        Self::add_inner(self.x, u2, self.y, s2, self.z, self)
+    }
+
+    // Shared tail of the addition formulas once both points have been scaled
+    // to a common denominator. z is the product of the input Zs.
+    fn add_inner(u1: U256, u2: U256, s1: U256, s2: U256, z: U256, p1: &Self) -> Self {
+        if u1 == u2 {
+            // Same x coordinate: either the same point or inverses of each other
+            return if s1 == s2 { p1.double() } else { Self::infinity() };
+        }
+        let h = Fe::sub(u2, u1);
+        let r = Fe::sub(s2, s1);
+        let h_2 = Fe::square(h);
+        let h_3 = Fe::mul(h_2, h);
+        let u1_h_2 = Fe::mul(u1, h_2);
+        let x3 = Fe::sub(Fe::sub(Fe::square(r), h_3), Fe::add(u1_h_2, u1_h_2));
+        let y3 = Fe::sub(Fe::mul(r, Fe::sub(u1_h_2, x3)), Fe::mul(s1, h_3));
+        let z3 = Fe::mul(h, z);
+        Self { x: x3, y: y3, z: z3 }
+    }
+
+    /// Scalar multiplication using a width-5 window non-adjacent form
+    pub fn multiply(&self, coefficient: U256) -> Self {
+        let table = self.odd_multiples();
+        let naf = wnaf(Scalar::reduce(coefficient).to_u256());
+        let mut result = Self::infinity();
+        for digit in naf.iter().rev() {
+            result = result.double();
+            result = add_digit(&result, &table, *digit);
+        }
+        result
+    }
+
+    /// Computes u * self + v * other in one pass (Shamir's trick / Straus'
+    /// algorithm), sharing the doublings between both multiplications.
+    pub fn multiply_add(&self, u: U256, other: &Self, v: U256) -> Self {
+        let table_1 = self.odd_multiples();
+        let table_2 = other.odd_multiples();
+        let mut naf_1 = wnaf(Scalar::reduce(u).to_u256());
+        let mut naf_2 = wnaf(Scalar::reduce(v).to_u256());
+        let length = naf_1.len().max(naf_2.len());
+        naf_1.resize(length, 0);
+        naf_2.resize(length, 0);
+
+        let mut result = Self::infinity();
+        for i in (0..length).rev() {
+            result = result.double();
+            result = add_digit(&result, &table_1, naf_1[i]);
+            result = add_digit(&result, &table_2, naf_2[i]);
+        }
+        result
+    }
+
+    /// Returns b if choice is set and a otherwise, without branching on choice
+    pub fn conditional_select(a: &Self, b: &Self, choice: Choice) -> Self {
+        Self {
+            x: select_u256(&a.x, &b.x, choice),
+            y: select_u256(&a.y, &b.y, choice),
+            z: select_u256(&a.z, &b.z, choice),
+        }
+    }
+
+    /// Scalar multiplication for secret scalars. Every 4 bit window costs
+    /// four doublings and one addition whatever its digit, and the table is
+    /// read with a full scan, so the sequence of operations and memory
+    /// accesses doesn't depend on the scalar. The accumulator starts at G
+    /// rather than infinity, so the addition formulas don't take their
+    /// infinity shortcut while the leading windows are zero; 2^256 * G is
+    /// subtracted again at the end. The U256 arithmetic underneath comes from
+    /// primitive-types, which makes no timing guarantees of its own.
+    pub fn multiply_ct(&self, coefficient: U256) -> Self {
+        if self.is_infinity() {
+            return *self;
+        }
+        // [P, 2P, ..., 15P]
+        let mut multiples = [*self; CT_ENTRIES];
+        for i in 1..CT_ENTRIES {
+            multiples[i] = multiples[i - 1].add(self);
+        }
+        let table = Self::batch_to_affine(&multiples);
+
+        let coef = Scalar::reduce(coefficient).to_u256();
+        let g = Self::from_affine(S256Params::gx(), S256Params::gy());
+        let mut result = g;
+        for i in (0..256 / CT_WINDOW_BITS).rev() {
+            for _ in 0..CT_WINDOW_BITS {
+                result = result.double();
+            }
+            let digit = ((coef >> (i * CT_WINDOW_BITS)).low_u64() & CT_ENTRIES as u64) as u8;
+            let (x, y) = select_affine(&table, digit);
+            let sum = result.add_affine(x, y);
+            result = Self::conditional_select(&result, &sum, !digit.ct_eq(&0));
+        }
+        // The starting G has been doubled 256 times. 2^256 = 2^256 - 1 + 1,
+        // and 2^256 - 1 reduces to a public scalar, so this is variable time
+        // on public data only.
+        let offset = Scalar::reduce(U256::MAX) + Scalar::one();
+        result.add(&g.multiply(offset.to_u256()).negate())
+    }
+
+    // Returns [P, 3P, 5P, ..., (2 * TABLE_SIZE - 1)P]
+    fn odd_multiples(&self) -> [Self; TABLE_SIZE] {
+        let mut table = [*self; TABLE_SIZE];
+        let twice = self.double();
+        for i in 1..TABLE_SIZE {
+            table[i] = table[i - 1].add(&twice);
+        }
+        table
+    }
+}
+
+/// Reads table[digit - 1] by scanning the whole table, so the memory access
+/// pattern doesn't reveal the digit. Digit 0 gives table[0]; callers discard
+/// the result in that case.
+pub(crate) fn select_affine(table: &[(U256, U256)], digit: u8) -> (U256, U256) {
+    let (mut x, mut y) = table[0];
+    for (i, (entry_x, entry_y)) in table.iter().enumerate() {
+        let choice = (i as u8 + 1).ct_eq(&digit);
+        x = select_u256(&x, entry_x, choice);
+        y = select_u256(&y, entry_y, choice);
+    }
+    (x, y)
+}
+
+// Adds digit * P to result, where the table holds the odd multiples of P
+fn add_digit(result: &JacobianPoint, table: &[JacobianPoint], digit: i8) -> JacobianPoint {
+    match digit {
+        0 => *result,
+        d if d > 0 => result.add(&table[(d as usize) / 2]),
+        d => result.add(&table[(d.unsigned_abs() as usize) / 2].negate()),
+    }
+}
+
+/// Returns the width-WINDOW non-adjacent form of k, least significant digit
+/// first. Every non-zero digit is odd and lies in (-2^(WINDOW-1), 2^(WINDOW-1)),
+/// and any two non-zero digits are at least WINDOW positions apart.
+pub fn wnaf(k: U256) -> Vec<i8> {
+    let mut k = k;
+    let mut naf = Vec::with_capacity(257);
+    let modulus = 1u64 << WINDOW;
+    while !k.is_zero() {
+        if k.bit(0) {
+            let mut digit = (k.low_u64() % modulus) as i64;
+            if digit >= (modulus / 2) as i64 {
+                digit -= modulus as i64;
+            }
+            // k is below n, which is far enough from 2^256 that this never overflows
+            if digit > 0 {
+                k -= U256::from(digit as u64);
+            } else {
+                k += U256::from(digit.unsigned_abs());
+            }
+            naf.push(digit as i8);
+        } else {
+            naf.push(0);
+        }
+        k >>= 1;
+    }
+    naf
+}
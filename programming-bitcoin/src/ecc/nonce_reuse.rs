@@ -0,0 +1,78 @@
+use std::fmt;
+use crate::ecc::point::Point;
+use crate::ecc::private_key::PrivateKey;
+use crate::ecc::scalar::Scalar;
+use crate::ecc::signature::Signature;
+
+// Two ECDSA signatures made with the same nonce k give away the private key.
+// With s1 = (z1 + r * e) / k and s2 = (z2 + r * e) / k, subtracting gives
+// k = (z1 - z2) / (s1 - s2), and then e = (s1 * k - z1) / r. Each s is only
+// known up to sign, since n - s verifies as well, so there are two candidate
+// keys and the public key decides between them.
+
+/// Why a private key could not be recovered from two signatures
+#[derive(Debug, Clone, PartialEq)]
+pub enum NonceReuseError {
+    // The signatures have different r values, so they don't share a nonce
+    DifferentNonce,
+    // Both signatures are over the same message hash, which reveals nothing
+    SameMessage,
+    // r or s is zero or not below n
+    InvalidSignature,
+    // No candidate key belongs to the public key
+    KeyMismatch,
+}
+
+impl fmt::Display for NonceReuseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NonceReuseError::DifferentNonce => write!(f, "signatures do not share a nonce"),
+            NonceReuseError::SameMessage => write!(f, "signatures are over the same message hash"),
+            NonceReuseError::InvalidSignature => write!(f, "invalid signature"),
+            NonceReuseError::KeyMismatch => write!(f, "recovered key does not match the public key"),
+        }
+    }
+}
+
+impl std::error::Error for NonceReuseError {}
+
+/// Returns the private keys consistent with two signatures of different
+/// message hashes z1 and z2 that share a nonce. There are at most two.
+pub fn candidate_keys(z1: &[u8], sig1: &Signature, z2: &[u8], sig2: &Signature) -> Result<Vec<PrivateKey>, NonceReuseError> {
+    if sig1.r() != sig2.r() {
+        return Err(NonceReuseError::DifferentNonce);
+    }
+    let (r, s1, s2) = match (Scalar::from_u256(sig1.r()), Scalar::from_u256(sig1.s()), Scalar::from_u256(sig2.s())) {
+        (Some(r), Some(s1), Some(s2)) if !r.is_zero() && !s1.is_zero() && !s2.is_zero() => (r, s1, s2),
+        _ => return Err(NonceReuseError::InvalidSignature),
+    };
+    let z1 = Scalar::reduce_bytes(z1);
+    let z2 = Scalar::reduce_bytes(z2);
+    if z1 == z2 {
+        return Err(NonceReuseError::SameMessage);
+    }
+    let r_inv = r.invert().ok_or(NonceReuseError::InvalidSignature)?;
+
+    let mut candidates = vec![];
+    for s2 in [s2, -s2] {
+        // s1 = s2 would need z1 = z2, so this only skips the other sign of s2
+        let k = match (s1 - s2).invert() {
+            Some(inverse) => (z1 - z2) * inverse,
+            None => continue,
+        };
+        let secret = (s1 * k - z1) * r_inv;
+        if !secret.is_zero() {
+            candidates.push(PrivateKey::new(secret.to_u256()));
+        }
+    }
+    Ok(candidates)
+}
+
+/// Recovers the private key behind public_key from two signatures of
+/// different message hashes that share a nonce
+pub fn recover_private_key(z1: &[u8], sig1: &Signature, z2: &[u8], sig2: &Signature, public_key: &Point) -> Result<PrivateKey, NonceReuseError> {
+    candidate_keys(z1, sig1, z2, sig2)?
+        .into_iter()
+        .find(|candidate| candidate.public_point().coordinates() == public_key.coordinates())
+        .ok_or(NonceReuseError::KeyMismatch)
+}
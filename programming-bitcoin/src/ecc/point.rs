@@ -1,29 +1,82 @@
 use crate::utils::base58::encode_base58_checksum;
 use crate::ecc::field_element::*;
-use num_bigint::BigUint;
 use primitive_types::U256;
+use std::fmt;
 use std::ops::{Add, Mul};
 use crate::ecc::secp256k1_params::S256Params;
 use crate::ecc::s256point::S256Point;
+use crate::ecc::jacobian_point::JacobianPoint;
+use crate::ecc::scalar::Scalar;
 use crate::ecc::signature::Signature;
+use crate::ecc::schnorr_signature::SchnorrSignature;
+use crate::ecc::xonly_public_key::XOnlyPublicKey;
 use crate::utils::hash160;
-use crate::ecc::s256point;
+use crate::ecc::s256field_element::{S256Field, S256FieldElement as Fe, P};
 
+/// Why a point could not be built or parsed
+#[derive(Debug, Clone, PartialEq)]
+pub enum PointError {
+    // Only one of x and y was given
+    MissingCoordinate,
+    // A coordinate is not below the field prime
+    CoordinateOutOfRange,
+    // The coordinates do not satisfy the curve equation
+    NotOnCurve,
+    // The SEC encoding has the wrong length for its prefix
+    InvalidSecLength(usize),
+    // The SEC prefix is not 0x02, 0x03 or 0x04
+    InvalidSecPrefix(u8),
+    // There is no y with y^2 = x^3 + 7 for the x of a compressed SEC key
+    InvalidXCoordinate,
+}
+
+impl fmt::Display for PointError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PointError::MissingCoordinate => write!(f, "a point needs both or neither of x and y"),
+            PointError::CoordinateOutOfRange => write!(f, "coordinate is not below the field prime"),
+            PointError::NotOnCurve => write!(f, "point is not on the curve"),
+            PointError::InvalidSecLength(length) => write!(f, "invalid SEC length: {}", length),
+            PointError::InvalidSecPrefix(prefix) => write!(f, "invalid SEC prefix: {:#04x}", prefix),
+            PointError::InvalidXCoordinate => write!(f, "x is not the x coordinate of a point on the curve"),
+        }
+    }
+}
+
+impl std::error::Error for PointError {}
+
+/// A point on the curve y^2 = x^3 + ax + b over the field F. Without a
+/// field parameter this is a point over the secp256k1 base field, which is
+/// where the SEC, address and signature methods live.
 #[derive(Debug, Clone)]
-pub struct Point {
-    x: Option<FieldElement>,
-    y: Option<FieldElement>,
-    a: FieldElement,
-    b: FieldElement,
+pub struct Point<F: FieldParams = S256Field> {
+    x: Option<FieldElement<F>>,
+    y: Option<FieldElement<F>>,
+    a: FieldElement<F>,
+    b: FieldElement<F>,
 }
 
-impl Point {
+impl<F: FieldParams> Point<F> {
+    /// Panics if the point is not on the curve; use try_new to handle that
     pub fn new(
-        x: Option<FieldElement>,
-        y: Option<FieldElement>,
-        a: FieldElement,
-        b: FieldElement,
+        x: Option<FieldElement<F>>,
+        y: Option<FieldElement<F>>,
+        a: FieldElement<F>,
+        b: FieldElement<F>,
     ) -> Self {
+        match Self::try_new(x, y, a, b) {
+            Ok(point) => point,
+            Err(PointError::NotOnCurve) => panic!("Point is not on the curve"),
+            Err(_) => panic!("Invalid parameters to Point::new()"),
+        }
+    }
+
+    pub fn try_new(
+        x: Option<FieldElement<F>>,
+        y: Option<FieldElement<F>>,
+        a: FieldElement<F>,
+        b: FieldElement<F>,
+    ) -> Result<Self, PointError> {
         match (x, y) {
             (Some(x), Some(y)) => {
                 let x_cubed = x.pow(U256::from(3));
@@ -32,31 +85,56 @@ impl Point {
                 let y_squared = y.pow(U256::from(2));
 
                 if &y_squared != &right_side {
-                    panic!("({x:?}, {y:?}) is not on the curve")
+                    return Err(PointError::NotOnCurve);
                 }
-                Self {
+                Ok(Self {
                     x: Some(x),
                     y: Some(y),
                     a,
                     b,
-                }
+                })
             }
-            (None, None) => Self {
+            (None, None) => Ok(Self {
                 x: None,
                 y: None,
                 a,
                 b,
-            },
-            _ => {
-                panic!("Invalid parameters to Point::new()")
-            }
+            }),
+            _ => Err(PointError::MissingCoordinate),
         }
     }
 
-    pub fn x(self) -> Option<FieldElement> {
+    // Builds a point without checking that it is on the curve
+    pub(crate) fn new_unchecked(
+        x: Option<FieldElement<F>>,
+        y: Option<FieldElement<F>>,
+        a: FieldElement<F>,
+        b: FieldElement<F>,
+    ) -> Self {
+        Self { x, y, a, b }
+    }
+
+    pub fn x(self) -> Option<FieldElement<F>> {
         self.x
     }
 
+    // Returns the affine (x, y) coordinates, or None for the point at infinity
+    pub fn coordinates(&self) -> Option<(U256, U256)> {
+        match (&self.x, &self.y) {
+            (Some(x), Some(y)) => Some((x.num(), y.num())),
+            _ => None,
+        }
+    }
+
+    // Returns the a and b parameters of the curve the point is on
+    pub fn curve(&self) -> (&FieldElement<F>, &FieldElement<F>) {
+        (&self.a, &self.b)
+    }
+
+    pub fn is_infinity(&self) -> bool {
+        self.x.is_none()
+    }
+
     // Returns the point at infinity
     pub fn new_infinity(&self) -> Self {
         Self {
@@ -67,43 +145,69 @@ impl Point {
         }
     }
 
+    // Scalar multiplication using binary expansion
+    fn multiply_binary(&self, coefficient: U256) -> Self {
+        let mut coef = coefficient;
+        // current represents the point that’s at the current bit. The first
+        // time through the loop it represents 1 × self; the second time it will
+        // be 2 × self, the third time 4 × self, then 8 × self, and so on. We
+        // double the point each time. In binary the coefficients are 1, 10,
+        // 100, 1000, 10000, etc.
+        let mut current = self.clone();
+        // We start the result at 0, or the point at infinity.
+        let mut result = self.new_infinity();
+
+        while coef > U256::from(0u32) {
+            // We are looking at whether the rightmost bit is a 1. If it is,
+            // then we add the value of the current bit.
+            if coef & U256::from(1u32) == U256::from(1u32) {
+                result = &result + &current.clone();
+            }
+            // We need to double the point until we’re past how big the
+            // coefficient can be.
+            current = &current.clone() + &current.clone();
+            // We bit-shift the coefficient to the right.
+            coef >>= 1;
+        }
+
+        result
+    }
+}
+
+impl Point {
     pub fn verify(self: Point, z: Vec<u8>, sig: Signature) -> bool {
-        // Convert inputs to BigUint
-        let z_biguint = BigUint::from_bytes_be(&z);
-        let n = BigUint::from_bytes_be(&S256Params::n().to_big_endian());
-        let s_biguint = BigUint::from_bytes_be(&sig.s().to_big_endian());
-        let r_biguint = BigUint::from_bytes_be(&sig.r().to_big_endian());
-        
-        // Calculate s_inv using Fermat's little theorem
-        let two = BigUint::from(2u8);
-        let s_inv = s_biguint.modpow(&(n.clone() - two), &n);
-        
-        // Calculate u = z * s_inv mod N
-        let u = (&z_biguint * &s_inv) % &n;
-        
-        // Calculate v = r * s_inv mod N
-        let v = (&r_biguint * &s_inv) % &n;
-        
-        // Convert back to U256 for point multiplication
-        let u_bytes = u.to_bytes_be();
-        let v_bytes = v.to_bytes_be();
-        let u_u256 = U256::from_big_endian(&u_bytes);
-        let v_u256 = U256::from_big_endian(&v_bytes);
-        
-        // previous implementation
-        // Calculate u*G + v*P where G is generator point and P is public key point
-        let g = S256Point::new_s256_point(Some(S256Params::gx()), Some(S256Params::gy()));
-        let u_g = S256Point::multiply(&g, u_u256);
-        let v_p = S256Point::multiply(&self, v_u256);
-        let total = &u_g + &v_p;
-        
-        // Verify x coordinate equals r
-        match total.x {
-            Some(x) => x.num() == sig.r(),
+        // r and s must both be in [1, n)
+        let (r, s) = match (Scalar::from_u256(sig.r()), Scalar::from_u256(sig.s())) {
+            (Some(r), Some(s)) if !r.is_zero() => (r, s),
+            _ => return false,
+        };
+        let s_inv = match s.invert() {
+            Some(s_inv) => s_inv,
+            None => return false,
+        };
+
+        // u = z / s, v = r / s
+        let u = Scalar::reduce_bytes(&z) * s_inv;
+        let v = r * s_inv;
+
+        // Calculate u*G + v*P where G is generator point and P is public key point,
+        // sharing the doublings between both multiplications
+        let g = JacobianPoint::from_affine(S256Params::gx(), S256Params::gy());
+        let p = JacobianPoint::from_point(&self);
+        let total = g.multiply_add(u.to_u256(), &p, v.to_u256());
+
+        // Verify the x coordinate, reduced mod n, equals r
+        match total.to_affine() {
+            Some((x, _)) => Scalar::reduce(x) == r,
             None => false
         }
     }
 
+    // Verifies a BIP340 Schnorr signature against the x-only form of this point
+    pub fn verify_schnorr(&self, msg: &[u8], sig: &SchnorrSignature) -> bool {
+        XOnlyPublicKey::from_point(self).verify_schnorr(msg, sig)
+    }
+
     // Returns the point in Standards for Efficient Cryptography (SEC) format
     pub fn sec(self, compressed: bool) -> Vec<u8> {
         if compressed {
@@ -124,71 +228,46 @@ impl Point {
         }
     }
 
-    // Returns a point based on sec formatted pubkey
+    // Returns a point based on sec formatted pubkey. Panics on an invalid
+    // key; use from_sec for keys that come from untrusted input.
     pub fn parse_to_s256_point(sec: Vec<u8>) -> Self {
-        let compressed = !matches!(sec[0], 0x04);
-
-        if compressed {
-            let is_even = sec[0] == 0x02;
-            let x = U256::from_big_endian(&sec[1..=32]);
-            
-            // Calculate right side of y² = x³ + 7
-            let x_field = FieldElement::new(x, S256Params::p());
-            let alpha = &x_field.pow(U256::from(3)) + &FieldElement::new(S256Params::b(), S256Params::p());
-            let beta = alpha.sqrt();
-            
-            // Get even and odd possibilities for y
-            let (even_beta, odd_beta) = if beta.num() % 2 == U256::zero() {
-                (beta.clone(), FieldElement::new(S256Params::p() - beta.num(), S256Params::p()))
-            } else {
-                (FieldElement::new(S256Params::p() - beta.num(), S256Params::p()), beta.clone())
-            };
-            
-            // Choose y based on prefix
-            let y = if is_even { even_beta.num() } else { odd_beta.num() };
-            
-            s256point::S256Point::new_s256_point(Some(x), Some(y))
-        } else {
-            let x = U256::from_big_endian(&sec[1..=32]);
-            let y = U256::from_big_endian(&sec[33..=64]);
-            s256point::S256Point::new_s256_point(Some(x), Some(y))
-        }
+        Self::from_sec(&sec).expect("Invalid SEC public key")
     }
 
     // Returns a point from a byte vector representing serialized SEC
     pub fn parse(self, sec_bin: Vec<u8>) -> Self {
-        if sec_bin[0] == 4 {
-            let x = Some(U256::from_big_endian(&sec_bin[1..33]));
-            let y = Some(U256::from_big_endian(&sec_bin[33..65]));
-            return S256Point::new_s256_point(x, y);
-        }
-        
-        let is_even = sec_bin[0] == 2u8;
-        let x_field = FieldElement::new(
-            U256::from_big_endian(&sec_bin[1..]),
-            S256Params::p()
-        );
-        
-        // Calculate right side of y^2 = x^3 + 7
-        let alpha = &x_field.pow(U256::from(3)) + &FieldElement::new(S256Params::b(), S256Params::p());
-        let beta = alpha.sqrt();
-        
-        let (even_beta, odd_beta) = if beta.num() % 2 == U256::zero() {
-            (
-                beta.clone(),
-                FieldElement::new(S256Params::p() - beta.num(), S256Params::p())
-            )
-        } else {
-            (
-                FieldElement::new(S256Params::p() - beta.num(), S256Params::p()),
-                beta.clone()
-            )
+        Self::parse_to_s256_point(sec_bin)
+    }
+
+    /// Parses a compressed (0x02/0x03 prefix, 33 bytes) or uncompressed
+    /// (0x04 prefix, 65 bytes) SEC public key
+    pub fn from_sec(sec: &[u8]) -> Result<Self, PointError> {
+        let prefix = *sec.first().ok_or(PointError::InvalidSecLength(0))?;
+        let expected_length = match prefix {
+            0x02 | 0x03 => 33,
+            0x04 => 65,
+            _ => return Err(PointError::InvalidSecPrefix(prefix)),
         };
-        
-        S256Point::new_s256_point(
-            Some(x_field.num()),
-            Some(if is_even { even_beta.num() } else { odd_beta.num() })
-        )
+        if sec.len() != expected_length {
+            return Err(PointError::InvalidSecLength(sec.len()));
+        }
+        let x = U256::from_big_endian(&sec[1..33]);
+        if x >= P {
+            return Err(PointError::CoordinateOutOfRange);
+        }
+        if prefix == 0x04 {
+            let y = U256::from_big_endian(&sec[33..65]);
+            return S256Point::try_new_s256_point(Some(x), Some(y));
+        }
+
+        // Solve y^2 = x^3 + 7, then pick the y with the parity of the prefix
+        let alpha = Fe::add(Fe::mul(Fe::square(x), x), S256Params::b());
+        let beta = Fe::sqrt(alpha);
+        if Fe::square(beta) != alpha {
+            return Err(PointError::InvalidXCoordinate);
+        }
+        let y = if beta.bit(0) == (prefix == 0x03) { beta } else { Fe::neg(beta) };
+        Ok(S256Point::new_s256_point_unchecked(x, y))
     }
 
     fn hash160(self, compressed: bool) -> Vec<u8> {
@@ -207,7 +286,7 @@ impl Point {
     }
 }
 
-impl PartialEq for &Point {
+impl<F: FieldParams> PartialEq for &Point<F> {
     fn eq(&self, other: &Self) -> bool {
         let x_eq = match (&self.x, &other.x) {
             (Some(x1), Some(x2)) => x1 == x2,
@@ -225,10 +304,10 @@ impl PartialEq for &Point {
     }
 }
 
-impl Add for &Point {
-    type Output = Point;
+impl<F: FieldParams> Add for &Point<F> {
+    type Output = Point<F>;
 
-    fn add(self, other: Self) -> Point {
+    fn add(self, other: Self) -> Point<F> {
         if (&self.a != &other.a) | (&self.b != &other.b) {
             panic!("Points {self:?}, {other:?} are not on the same curve.");
         }
@@ -300,32 +379,19 @@ impl Add for &Point {
 impl Mul<U256> for &Point {
     type Output = Point;
 
-    // Scalar multiplication using binary expansion
     fn mul(self, coefficient: U256) -> Self::Output {
-        // TODO here we can conditionally set coef to coefficient % N if a, b and p match the params of the secp256k1 curve
-        let mut coef = coefficient;
-        // current represents the point that’s at the current bit. The first
-        // time through the loop it represents 1 × self; the second time it will
-        // be 2 × self, the third time 4 × self, then 8 × self, and so on. We
-        // double the point each time. In binary the coefficients are 1, 10,
-        // 100, 1000, 10000, etc.
-        let mut current = self.clone();
-        // We start the result at 0, or the point at infinity.
-        let mut result = self.new_infinity();
-
-        while coef > U256::from(0u32) {
-            // We are looking at whether the rightmost bit is a 1. If it is,
-            // then we add the value of the current bit.
-            if coef & U256::from(1u32) == U256::from(1u32) {
-                result = &result + &current.clone();
-            }
-            // We need to double the point until we’re past how big the
-            // coefficient can be.
-            current = &current.clone() + &current.clone();
-            // We bit-shift the coefficient to the right.
-            coef >>= 1;
+        // secp256k1 points take the fast path, which also mods the coefficient by N
+        if S256Point::is_s256_point(self) {
+            return S256Point::multiply(self, coefficient);
         }
+        self.multiply_binary(coefficient)
+    }
+}
 
-        result
+impl<const P: u64> Mul<U256> for &Point<Fp<P>> {
+    type Output = Point<Fp<P>>;
+
+    fn mul(self, coefficient: U256) -> Self::Output {
+        self.multiply_binary(coefficient)
     }
 }
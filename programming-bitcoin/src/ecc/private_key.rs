@@ -1,12 +1,34 @@
-use num_bigint::BigUint;
+use std::fmt;
 use primitive_types::U256;
-use crate::utils::base58::encode_base58_checksum;
+use crate::utils::base58::{decode_base58_checksum, encode_base58_checksum};
 use crate::{ecc::point::Point, ecc::signature::Signature};
 use crate::ecc::secp256k1_params::S256Params;
-use crate::ecc::s256point::S256Point;
+use crate::ecc::generator_table::GeneratorTable;
+use crate::ecc::jacobian_point::JacobianPoint;
+use crate::ecc::adaptor::{AdaptorError, EcdsaPreSignature, SchnorrPreSignature};
+use crate::ecc::recoverable_signature::RecoverableSignature;
+use crate::ecc::scalar::Scalar;
+use crate::ecc::schnorr_signature::SchnorrSignature;
+use crate::ecc::xonly_public_key::{challenge, XOnlyPublicKey};
+use crate::utils::tagged_hash::tagged_hash;
 use hmac::{Hmac, Mac};
-use sha2::Sha256;
+use rand::rngs::OsRng;
+use rand::{CryptoRng, RngCore};
+use sha2::{Digest, Sha256};
+use subtle::Choice;
+use zeroize::Zeroize;
 
+/// What a WIF string says about its key besides the secret: whether the
+/// public key is meant to be serialized compressed, and the network
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WifMetadata {
+    pub compressed: bool,
+    pub testnet: bool,
+}
+
+/// A secp256k1 private key. The secret is wiped from memory when the key is
+/// dropped, is never printed by Debug or Display, and is only multiplied
+/// with the constant-time multiply_ct.
 pub struct PrivateKey {
     secret: U256,
     point: Point
@@ -14,62 +36,187 @@ pub struct PrivateKey {
 
 impl PrivateKey {
     pub fn new(secret: U256) -> PrivateKey {
-        let g = S256Params::g();
         Self {
             secret,
-            point: S256Point::multiply(&g, secret)
+            point: GeneratorTable::multiply_ct(secret).to_point()
+        }
+    }
+
+    /// Generates a new key from the operating system's CSPRNG
+    pub fn generate() -> PrivateKey {
+        Self::generate_with_rng(&mut OsRng)
+    }
+
+    /// Generates a new key from the given CSPRNG. Candidates outside [1, n)
+    /// are rejected rather than reduced, so every key is equally likely.
+    pub fn generate_with_rng<R: RngCore + CryptoRng>(rng: &mut R) -> PrivateKey {
+        let mut bytes = [0u8; 32];
+        loop {
+            rng.fill_bytes(&mut bytes);
+            let mut candidate = U256::from_big_endian(&bytes);
+            if !candidate.is_zero() && candidate < S256Params::n() {
+                bytes.zeroize();
+                return Self::new(candidate);
+            }
+            candidate.0.zeroize();
         }
     }
 
     pub fn point(self) -> Point {
-        self.point
+        self.point.clone()
+    }
+
+    pub(crate) fn secret(&self) -> U256 {
+        self.secret
+    }
+
+    pub(crate) fn public_point(&self) -> &Point {
+        &self.point
     }
 
     pub fn sign(&self, z: Vec<u8>) -> Signature {
-        // Generate random k between 0 and N
-        // Should be using cryptographic randomness here
-        let z = U256::from_big_endian(&z);
-        let k = self.deterministic_k(z);
-        
-        // Calculate r = (k*G).x
-        let g = S256Params::g();
-        let k_times_g = &g * k;
-        let r = k_times_g.x().unwrap();
-        
-        // Calculate k_inv using Fermat's little theorem
-        // let k_inv = k.pow(S256Params::n() - U256::from(2)) % S256Params::n();
-        let k_big = BigUint::from_bytes_be(&k.to_big_endian());
-        let n_minus_2 = BigUint::from_bytes_be(&(S256Params::n() - U256::from(2)).to_big_endian());
-        let n = BigUint::from_bytes_be(&S256Params::n().to_big_endian());
-        let k_inv_big = k_big.modpow(&n_minus_2, &n);
-        let k_inv = U256::from_big_endian(&k_inv_big.to_bytes_be());
-        
-        // Calculate s = (z + r*secret) * k_inv % N
-        // let mut s = ((z + r.num() * self.secret) * k_inv) % S256Params::n();
+        self.sign_recoverable(z).signature().clone()
+    }
 
-        // Convert existing values to BigUint
-        let z_big = BigUint::from_bytes_be(&z.to_big_endian());
-        let r_big = BigUint::from_bytes_be(&r.num().to_big_endian());
-        let secret_big = BigUint::from_bytes_be(&self.secret.to_big_endian());
-        let k_inv_big = BigUint::from_bytes_be(&k_inv.to_big_endian());
-        let n_big = BigUint::from_bytes_be(&S256Params::n().to_big_endian());
+    /// Signs like sign, but mixes extra entropy (such as 32 fresh random
+    /// bytes) into the nonce as in RFC 6979 section 3.6. This matches
+    /// libsecp256k1's signing with nonce data.
+    pub fn sign_with_extra_entropy(&self, z: Vec<u8>, extra_entropy: &[u8; 32]) -> Signature {
+        self.sign_recoverable_with_extra_entropy(z, extra_entropy).signature().clone()
+    }
 
-        // Calculate s using BigUint operations
-        let s_big = ((&z_big + &r_big * &secret_big) * &k_inv_big) % &n_big;
+    /// Signs like sign, but grinds the nonce until r is below 2^255, as
+    /// Bitcoin Core does. r then needs no padding byte in DER, so the
+    /// signature is at most 70 bytes (71 with the sighash type). Each retry
+    /// uses a counter as extra entropy, the same way as Core and libsecp256k1,
+    /// so the signatures are identical to theirs. Two tries are needed on
+    /// average.
+    pub fn sign_low_r(&self, z: Vec<u8>) -> Signature {
+        let mut signature = self.sign(z.clone());
+        let mut counter: u32 = 0;
+        while signature.r().bit(255) {
+            counter += 1;
+            let mut extra_entropy = [0u8; 32];
+            extra_entropy[..4].copy_from_slice(&counter.to_le_bytes());
+            signature = self.sign_with_extra_entropy(z.clone(), &extra_entropy);
+        }
+        signature
+    }
 
-        // Convert back to U256
-        let mut s = U256::from_big_endian(&s_big.to_bytes_be());
-        
-        // If s > N/2, set s = N - s (to ensure low S values)
-        if s > S256Params::n() / 2 {
-            s = S256Params::n() - s;
+    /// Signs like sign, but also returns the recovery id that lets a
+    /// verifier recover this key's point from the signature
+    pub fn sign_recoverable(&self, z: Vec<u8>) -> RecoverableSignature {
+        self.sign_recoverable_with_extra_entropy(z, &[])
+    }
+
+    fn sign_recoverable_with_extra_entropy(&self, z: Vec<u8>, extra_entropy: &[u8]) -> RecoverableSignature {
+        // The nonce comes from RFC 6979, so signing doesn't depend on the
+        // quality of a random number generator
+        let z = Scalar::reduce_bytes(&z);
+        let mut k = self.deterministic_k_with_extra_entropy(z.to_u256(), extra_entropy);
+
+        // Calculate r = (k*G).x mod N
+        let (rx, ry) = GeneratorTable::multiply_ct(k).to_affine().unwrap();
+        let r = Scalar::reduce(rx);
+        let mut recovery_id = (ry.bit(0) as u8) | (((rx != r.to_u256()) as u8) << 1);
+
+        // Calculate s = (z + r*secret) / k. k is in [1, N) so it has an inverse.
+        let mut k_inv = Scalar::reduce(k).invert().unwrap();
+        let mut secret = Scalar::reduce(self.secret);
+        let mut s = (z + r * secret) * k_inv;
+        k.0.zeroize();
+        k_inv.zeroize();
+        secret.zeroize();
+
+        // If s > N/2, set s = N - s (to ensure low S values). This is the
+        // same as signing with -k, whose R has the opposite y parity.
+        let high = Choice::from(s.is_high() as u8);
+        s = s.negate_if(high);
+        recovery_id ^= high.unwrap_u8();
+
+        RecoverableSignature::new(Signature::new(r.to_u256(), s.to_u256()), recovery_id).unwrap()
+    }
+
+    pub fn xonly_public_key(&self) -> XOnlyPublicKey {
+        XOnlyPublicKey::from_point(&self.point)
+    }
+
+    /// Signs a message with BIP340 Schnorr. aux_rand is fresh randomness
+    /// that is mixed into the nonce; all zeros still gives a valid signature.
+    pub fn sign_schnorr(&self, msg: &[u8], aux_rand: [u8; 32]) -> SchnorrSignature {
+        // Use the secret whose public key has an even y
+        let (px, py) = self.point.coordinates().unwrap();
+        let mut d = Scalar::reduce(self.secret).negate_if(Choice::from(py.bit(0) as u8));
+
+        // t = bytes(d) xor hash_BIP0340/aux(a)
+        let aux_hash = tagged_hash("BIP0340/aux", &aux_rand);
+        let t: Vec<u8> = d.to_bytes()
+            .iter()
+            .zip(aux_hash.iter())
+            .map(|(a, b)| a ^ b)
+            .collect();
+
+        // k' = int(hash_BIP0340/nonce(t || bytes(P) || m)) mod n
+        let mut nonce_data = t;
+        nonce_data.extend_from_slice(&px.to_big_endian());
+        nonce_data.extend_from_slice(msg);
+        let mut k_prime = Scalar::reduce_bytes(&tagged_hash("BIP0340/nonce", &nonce_data));
+        nonce_data.zeroize();
+        if k_prime.is_zero() {
+            panic!("Schnorr nonce is zero");
         }
-        
-        Signature::new(r.num(), s)
+
+        // Use the nonce whose point R has an even y
+        let (rx, ry) = GeneratorTable::multiply_ct(k_prime.to_u256()).to_affine().unwrap();
+        let mut k = k_prime.negate_if(Choice::from(ry.bit(0) as u8));
+
+        // s = (k + e * d) mod n
+        let s = k + challenge(rx, px, msg) * d;
+        k_prime.zeroize();
+        k.zeroize();
+        d.zeroize();
+
+        SchnorrSignature::new(rx, s.to_u256())
+    }
+
+    /// Elliptic curve Diffie-Hellman: both sides get the same secret from
+    /// their own private key and the other's public key. Hashed like
+    /// libsecp256k1's default ECDH, sha256(0x02 or 0x03 for the parity of y
+    /// || x) of the shared point.
+    pub fn ecdh(&self, public_key: &Point) -> Result<[u8; 32], String> {
+        if public_key.is_infinity() {
+            return Err("ECDH public key is the point at infinity".to_string());
+        }
+        let shared = JacobianPoint::from_point(public_key).multiply_ct(self.secret);
+        let (x, y) = shared.to_affine().ok_or("ECDH shared point is the point at infinity")?;
+
+        let mut hasher = Sha256::new();
+        hasher.update([if y.bit(0) { 0x03 } else { 0x02 }]);
+        hasher.update(x.to_big_endian());
+        Ok(hasher.finalize().into())
+    }
+
+    /// Signs z with ECDSA, encrypted under an adaptor point. See
+    /// EcdsaPreSignature.
+    pub fn encrypt_sign(&self, z: Vec<u8>, encryption_key: &Point, aux_rand: [u8; 32]) -> Result<EcdsaPreSignature, AdaptorError> {
+        EcdsaPreSignature::sign(self, z, encryption_key, aux_rand)
+    }
+
+    /// Signs msg with BIP340 Schnorr, encrypted under an adaptor point. See
+    /// SchnorrPreSignature.
+    pub fn encrypt_sign_schnorr(&self, msg: &[u8], encryption_key: &Point, aux_rand: [u8; 32]) -> Result<SchnorrPreSignature, AdaptorError> {
+        SchnorrPreSignature::sign(self, msg, encryption_key, aux_rand)
     }
 
     // From RFC 6979
     pub fn deterministic_k(&self, z: U256) -> U256 {
+        self.deterministic_k_with_extra_entropy(z, &[])
+    }
+
+    /// RFC 6979 with additional data k' (section 3.6), which is appended to
+    /// the secret and z when seeding the HMAC-DRBG. Empty extra entropy
+    /// gives the same k as deterministic_k.
+    pub fn deterministic_k_with_extra_entropy(&self, z: U256, extra_entropy: &[u8]) -> U256 {
         type HmacSha256 = Hmac<Sha256>;
         
         // Initialize k and v
@@ -77,58 +224,64 @@ impl PrivateKey {
         let mut v = vec![1u8; 32];
         
         // Adjust z if needed
-        let mut z = z;
-        if z > S256Params::n() {
-            z -= S256Params::n();
-        }
+        let z = Scalar::reduce(z).to_u256();
         
         // Convert values to bytes
         let z_bytes = z.to_big_endian();
-        let secret_bytes = self.secret.to_big_endian();
+        let mut secret_bytes = self.secret.to_big_endian();
         
         // First round
         let mut hmac = HmacSha256::new_from_slice(&k).unwrap();
-        hmac.update(&[&v[..], &[0u8], &secret_bytes[..], &z_bytes[..]].concat());
-        k = hmac.finalize().into_bytes().to_vec();
+        let mut data = [&v[..], &[0u8], &secret_bytes[..], &z_bytes[..], extra_entropy].concat();
+        hmac.update(&data);
+        data.zeroize();
+        k.copy_from_slice(&hmac.finalize().into_bytes());
         
         let mut hmac = HmacSha256::new_from_slice(&k).unwrap();
         hmac.update(&v);
-        v = hmac.finalize().into_bytes().to_vec();
+        v.copy_from_slice(&hmac.finalize().into_bytes());
         
         // Second round
         let mut hmac = HmacSha256::new_from_slice(&k).unwrap();
-        hmac.update(&[&v[..], &[1u8], &secret_bytes[..], &z_bytes[..]].concat());
-        k = hmac.finalize().into_bytes().to_vec();
+        let mut data = [&v[..], &[1u8], &secret_bytes[..], &z_bytes[..], extra_entropy].concat();
+        hmac.update(&data);
+        data.zeroize();
+        k.copy_from_slice(&hmac.finalize().into_bytes());
         
         let mut hmac = HmacSha256::new_from_slice(&k).unwrap();
         hmac.update(&v);
-        v = hmac.finalize().into_bytes().to_vec();
+        v.copy_from_slice(&hmac.finalize().into_bytes());
         
         // Generate k
         loop {
             let mut hmac = HmacSha256::new_from_slice(&k).unwrap();
             hmac.update(&v);
-            v = hmac.finalize().into_bytes().to_vec();
+            v.copy_from_slice(&hmac.finalize().into_bytes());
             
+            // Rejecting a candidate only reveals something about a value
+            // that is thrown away
             let candidate = U256::from_big_endian(&v);
             if candidate >= U256::one() && candidate < S256Params::n() {
+                k.zeroize();
+                v.zeroize();
+                secret_bytes.zeroize();
                 return candidate;
             }
             
             let mut hmac = HmacSha256::new_from_slice(&k).unwrap();
             hmac.update(&[&v[..], &[0u8]].concat());
-            k = hmac.finalize().into_bytes().to_vec();
+            k.copy_from_slice(&hmac.finalize().into_bytes());
             
             let mut hmac = HmacSha256::new_from_slice(&k).unwrap();
             hmac.update(&v);
-            v = hmac.finalize().into_bytes().to_vec();
+            v.copy_from_slice(&hmac.finalize().into_bytes());
         }
     }
 
     // Returns private key in Wallet Import Format (WIF)
     pub fn wif(&self, compressed: bool, testnet: bool) -> String {
         // Convert secret to big-endian bytes
-        let secret_bytes = self.secret.to_big_endian();
+        let mut secret_bytes = self.secret.to_big_endian();
         
         // Set prefix based on network
         let prefix = if testnet { vec![0xef] } else { vec![0x80] };
@@ -141,6 +294,100 @@ impl PrivateKey {
         result.extend_from_slice(&secret_bytes);
         result.extend_from_slice(&suffix);
         
-        encode_base58_checksum(&result)
+        let wif = encode_base58_checksum(&result);
+        secret_bytes.zeroize();
+        result.zeroize();
+        wif
+    }
+
+    /// Parses a key in Wallet Import Format, checking the checksum, the
+    /// network prefix and the compression flag
+    pub fn from_wif(wif: &str) -> Result<(PrivateKey, WifMetadata), String> {
+        let mut payload = decode_base58_checksum(wif)?;
+        let result = Self::parse_wif_payload(&payload);
+        payload.zeroize();
+        result
+    }
+
+    // prefix || secret || [0x01 if compressed]
+    fn parse_wif_payload(payload: &[u8]) -> Result<(PrivateKey, WifMetadata), String> {
+        let testnet = match payload.first() {
+            Some(0x80) => false,
+            Some(0xef) => true,
+            Some(prefix) => return Err(format!("Invalid WIF network prefix: {:#04x}", prefix)),
+            None => return Err("Invalid WIF length: 0".to_string()),
+        };
+        let compressed = match payload.len() {
+            33 => false,
+            34 if payload[33] == 0x01 => true,
+            34 => return Err(format!("Invalid WIF compression flag: {:#04x}", payload[33])),
+            length => return Err(format!("Invalid WIF length: {}", length)),
+        };
+        let private_key = Self::from_bytes(&payload[1..33])?;
+        Ok((private_key, WifMetadata { compressed, testnet }))
+    }
+
+    /// Parses a 32 byte big-endian secret, which must be in [1, n)
+    pub fn from_bytes(bytes: &[u8]) -> Result<PrivateKey, String> {
+        if bytes.len() != 32 {
+            return Err(format!("Invalid private key length: {}", bytes.len()));
+        }
+        let mut secret = U256::from_big_endian(bytes);
+        if secret.is_zero() || secret >= S256Params::n() {
+            secret.0.zeroize();
+            return Err("Private key is not in the range [1, n)".to_string());
+        }
+        Ok(Self::new(secret))
+    }
+
+    /// The secret as 32 big-endian bytes. The caller is responsible for
+    /// wiping them.
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.secret.to_big_endian()
+    }
+
+    /// Parses a secret from 64 hex digits
+    pub fn from_hex(hex: &str) -> Result<PrivateKey, String> {
+        let mut bytes = hex::decode(hex).map_err(|e| format!("Invalid private key hex: {}", e))?;
+        let result = Self::from_bytes(&bytes);
+        bytes.zeroize();
+        result
+    }
+
+    /// The secret as 64 lowercase hex digits
+    pub fn to_hex(&self) -> String {
+        let mut bytes = self.to_bytes();
+        let hex = hex::encode(bytes);
+        bytes.zeroize();
+        hex
+    }
+
+    fn public_key_hex(&self) -> String {
+        if self.point.is_infinity() {
+            return "infinity".to_string();
+        }
+        hex::encode(self.point.clone().sec(true))
+    }
+}
+
+impl Drop for PrivateKey {
+    fn drop(&mut self) {
+        self.secret.0.zeroize();
+    }
+}
+
+// Only the public key is shown, so keys can't leak into logs
+impl fmt::Debug for PrivateKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PrivateKey")
+            .field("secret", &"<redacted>")
+            .field("public_key", &self.public_key_hex())
+            .finish()
+    }
+}
+
+impl fmt::Display for PrivateKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<private key for {}>", self.public_key_hex())
     }
 }
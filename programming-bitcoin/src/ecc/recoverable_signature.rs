@@ -0,0 +1,115 @@
+use primitive_types::U256;
+use crate::ecc::jacobian_point::JacobianPoint;
+use crate::ecc::point::Point;
+use crate::ecc::s256field_element::{S256FieldElement as Fe, P};
+use crate::ecc::scalar::{Scalar, N};
+use crate::ecc::secp256k1_params::S256Params;
+use crate::ecc::signature::Signature;
+
+// Header bytes of the 65 byte compact format: 27 + recovery id, plus 4 when
+// the key is meant to be serialized compressed
+const COMPACT_HEADER_BASE: u8 = 27;
+const COMPACT_HEADER_COMPRESSED: u8 = 4;
+
+/// An ECDSA signature together with the recovery id needed to get the public
+/// key back out of it. Bit 0 of the recovery id is the parity of R's y
+/// coordinate and bit 1 is set when R's x coordinate was at least n (so r
+/// had been reduced).
+#[derive(Debug, Clone)]
+pub struct RecoverableSignature {
+    signature: Signature,
+    recovery_id: u8,
+}
+
+impl RecoverableSignature {
+    pub fn new(signature: Signature, recovery_id: u8) -> Result<Self, String> {
+        if recovery_id > 3 {
+            return Err(format!("Invalid recovery id: {}", recovery_id));
+        }
+        Ok(Self { signature, recovery_id })
+    }
+
+    pub fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    pub fn recovery_id(&self) -> u8 {
+        self.recovery_id
+    }
+
+    /// Returns the public key that produced this signature over z
+    pub fn recover(&self, z: Vec<u8>) -> Result<Point, String> {
+        recover_public_key(z, &self.signature, self.recovery_id)
+    }
+
+    /// Serializes to 65 bytes: a header byte, then r and s as 32 bytes each.
+    /// The header records the recovery id and whether the signer's key
+    /// should be serialized compressed, as used by signed messages.
+    pub fn serialize_compact(&self, compressed: bool) -> Vec<u8> {
+        let mut header = COMPACT_HEADER_BASE + self.recovery_id;
+        if compressed {
+            header += COMPACT_HEADER_COMPRESSED;
+        }
+        let mut result = vec![header];
+        result.extend_from_slice(&self.signature.r().to_big_endian());
+        result.extend_from_slice(&self.signature.s().to_big_endian());
+        result
+    }
+
+    /// Parses the 65 byte compact format, returning the signature and
+    /// whether the header marked the key as compressed
+    pub fn parse_compact(raw: &[u8]) -> Result<(Self, bool), String> {
+        if raw.len() != 65 {
+            return Err(format!("Invalid compact signature length: {}", raw.len()));
+        }
+        let header = raw[0];
+        if !(COMPACT_HEADER_BASE..COMPACT_HEADER_BASE + 8).contains(&header) {
+            return Err(format!("Invalid compact signature header: {}", header));
+        }
+        let header = header - COMPACT_HEADER_BASE;
+        let compressed = header & COMPACT_HEADER_COMPRESSED != 0;
+        let r = U256::from_big_endian(&raw[1..33]);
+        let s = U256::from_big_endian(&raw[33..65]);
+        let signature = Self::new(Signature::new(r, s), header & 3)?;
+        Ok((signature, compressed))
+    }
+}
+
+/// Recovers the public key Q from a signature over z, given the recovery id.
+/// R is rebuilt from r and the recovery id, then Q = r^-1 (s*R - z*G).
+pub fn recover_public_key(z: Vec<u8>, sig: &Signature, recovery_id: u8) -> Result<Point, String> {
+    if recovery_id > 3 {
+        return Err(format!("Invalid recovery id: {}", recovery_id));
+    }
+    let r = Scalar::from_u256(sig.r()).filter(|r| !r.is_zero()).ok_or("r is not in [1, n)")?;
+    let s = Scalar::from_u256(sig.s()).filter(|s| !s.is_zero()).ok_or("s is not in [1, n)")?;
+
+    // R's x coordinate is r, or r + n if it overflowed the order
+    let mut x = r.to_u256();
+    if recovery_id & 2 != 0 {
+        let (sum, overflow) = x.overflowing_add(N);
+        if overflow || sum >= P {
+            return Err("R's x coordinate is not below p".to_string());
+        }
+        x = sum;
+    }
+
+    // Solve y^2 = x^3 + 7 and pick the y with the recorded parity
+    let alpha = Fe::add(Fe::mul(Fe::square(x), x), S256Params::b());
+    let beta = Fe::sqrt(alpha);
+    if Fe::square(beta) != alpha {
+        return Err(format!("{:#x} is not the x coordinate of a point on the curve", x));
+    }
+    let y = if beta.bit(0) == (recovery_id & 1 == 1) { beta } else { Fe::neg(beta) };
+
+    // Q = (-z/r) * G + (s/r) * R
+    let r_inv = r.invert().unwrap();
+    let u1 = -(Scalar::reduce_bytes(&z) * r_inv);
+    let u2 = s * r_inv;
+    let generator = JacobianPoint::from_affine(S256Params::gx(), S256Params::gy());
+    let q = generator.multiply_add(u1.to_u256(), &JacobianPoint::from_affine(x, y), u2.to_u256());
+    if q.is_infinity() {
+        return Err("Recovered public key is the point at infinity".to_string());
+    }
+    Ok(q.to_point())
+}
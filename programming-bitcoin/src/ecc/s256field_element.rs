@@ -1,13 +1,141 @@
-use crate::ecc::field_element::FieldElement;
-use crate::ecc::secp256k1_params::S256Params;
+use crate::ecc::field_element::{FieldElement, FieldParams};
 use primitive_types::U256;
 
+// The secp256k1 prime as little-endian limbs: p = 2^256 - 2^32 - 977
+pub const P: U256 = U256([
+    0xFFFFFFFEFFFFFC2F,
+    0xFFFFFFFFFFFFFFFF,
+    0xFFFFFFFFFFFFFFFF,
+    0xFFFFFFFFFFFFFFFF,
+]);
+
+// 2^256 mod p. Because p is so close to 2^256, anything above 256 bits can be
+// folded back down by multiplying the high part by this constant.
+const C: u64 = 0x1000003D1;
+
+/// The secp256k1 base field. Its arithmetic uses the specialised reduction
+/// below rather than the generic 512-bit remainder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct S256Field;
+
+impl FieldParams for S256Field {
+    const PRIME: U256 = P;
+
+    fn add(a: U256, b: U256) -> U256 {
+        S256FieldElement::add(a, b)
+    }
+
+    fn sub(a: U256, b: U256) -> U256 {
+        S256FieldElement::sub(a, b)
+    }
+
+    fn mul(a: U256, b: U256) -> U256 {
+        S256FieldElement::mul(a, b)
+    }
+
+    fn pow(base: U256, exp: U256) -> U256 {
+        S256FieldElement::pow(base, exp)
+    }
+}
+
 #[derive(Debug)]
 pub struct S256FieldElement;
 
 impl S256FieldElement {
-    // Returns a field element without having to pass the specific p for secp256k1
-    pub fn new_s256_field(num: U256) -> FieldElement {
-        FieldElement::new(num, S256Params::p())
+    // Returns a field element in the secp256k1 base field
+    pub fn new_s256_field(num: U256) -> FieldElement<S256Field> {
+        FieldElement::new(num)
+    }
+
+    // The functions below work directly on U256 values that are already
+    // reduced mod p. They back S256Field, and are what the Jacobian point
+    // arithmetic is built on.
+
+    pub fn add(a: U256, b: U256) -> U256 {
+        let (sum, overflow) = a.overflowing_add(b);
+        if overflow {
+            // sum wrapped past 2^256, and 2^256 ≡ C (mod p)
+            sum + U256::from(C)
+        } else if sum >= P {
+            sum - P
+        } else {
+            sum
+        }
+    }
+
+    pub fn sub(a: U256, b: U256) -> U256 {
+        let (diff, borrow) = a.overflowing_sub(b);
+        if borrow {
+            // diff is a - b + 2^256, we want a - b + p
+            diff - U256::from(C)
+        } else {
+            diff
+        }
+    }
+
+    pub fn neg(a: U256) -> U256 {
+        if a.is_zero() {
+            a
+        } else {
+            P - a
+        }
+    }
+
+    pub fn mul(a: U256, b: U256) -> U256 {
+        let wide = a.full_mul(b).0;
+        Self::reduce_wide(&wide)
+    }
+
+    pub fn square(a: U256) -> U256 {
+        Self::mul(a, a)
+    }
+
+    pub fn pow(base: U256, exp: U256) -> U256 {
+        let mut result = U256::one();
+        for i in (0..exp.bits()).rev() {
+            result = Self::square(result);
+            if exp.bit(i) {
+                result = Self::mul(result, base);
+            }
+        }
+        result
+    }
+
+    // Fermat's Little Theorem: a^(p-2) is the inverse of a
+    pub fn inverse(a: U256) -> U256 {
+        Self::pow(a, P - U256::from(2))
+    }
+
+    // p ≡ 3 (mod 4), so a^((p+1)/4) is a square root of a if one exists
+    pub fn sqrt(a: U256) -> U256 {
+        Self::pow(a, (P + U256::one()) / U256::from(4))
+    }
+
+    // Reduces a 512-bit product mod p by folding the high half twice
+    fn reduce_wide(wide: &[u64; 8]) -> U256 {
+        let (folded, carry) = Self::fold(&wide[4..], &wide[..4]);
+        let (folded, carry) = Self::fold(&[carry, 0, 0, 0], &folded);
+        let mut result = U256(folded);
+        if carry != 0 {
+            // Only possible when folded is tiny, so this cannot overflow again
+            result += U256::from(C);
+        }
+        if result >= P {
+            result - P
+        } else {
+            result
+        }
+    }
+
+    // Returns lo + hi * C as four limbs plus the carry limb
+    fn fold(hi: &[u64], lo: &[u64]) -> ([u64; 4], u64) {
+        let mut out = [0u64; 4];
+        let mut carry: u128 = 0;
+        for i in 0..4 {
+            let t = (hi[i] as u128) * (C as u128) + (lo[i] as u128) + carry;
+            out[i] = t as u64;
+            carry = t >> 64;
+        }
+        (out, carry as u64)
     }
 }
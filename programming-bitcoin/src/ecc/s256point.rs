@@ -1,6 +1,8 @@
 use primitive_types::U256;
-use crate::ecc::point::Point;
-use crate::ecc::s256field_element::S256FieldElement;
+use crate::ecc::generator_table::GeneratorTable;
+use crate::ecc::jacobian_point::JacobianPoint;
+use crate::ecc::point::{Point, PointError};
+use crate::ecc::s256field_element::{S256FieldElement, P};
 use crate::ecc::secp256k1_params::S256Params;
 
 #[derive(Debug)]
@@ -13,21 +15,64 @@ impl S256Point {
 
         match x {
             Some(x) => {
-                let x = S256FieldElement::new_s256_field(x);   
-                let y = S256FieldElement::new_s256_field(y.unwrap());  
+                let x = S256FieldElement::new_s256_field(x);
+                let y = S256FieldElement::new_s256_field(y.unwrap());
                 Point::new(Some(x), Some(y), a, b)
             },
             None => Point::new(None, None, a, b)
         }
     }
 
+    // Like new_s256_point, but returns an error instead of panicking for
+    // coordinates that are out of range or not on the curve
+    pub fn try_new_s256_point(x: Option<U256>, y: Option<U256>) -> Result<Point, PointError> {
+        let a = S256FieldElement::new_s256_field(S256Params::a());
+        let b = S256FieldElement::new_s256_field(S256Params::b());
+
+        let field_element = |value: U256| {
+            if value < P {
+                Ok(S256FieldElement::new_s256_field(value))
+            } else {
+                Err(PointError::CoordinateOutOfRange)
+            }
+        };
+        match (x, y) {
+            (Some(x), Some(y)) => Point::try_new(Some(field_element(x)?), Some(field_element(y)?), a, b),
+            (None, None) => Point::try_new(None, None, a, b),
+            _ => Err(PointError::MissingCoordinate),
+        }
+    }
+
+    // Skips the on-curve check. Only for coordinates produced by our own
+    // group arithmetic, which are on the curve by construction.
+    pub(crate) fn new_s256_point_unchecked(x: U256, y: U256) -> Point {
+        let a = S256FieldElement::new_s256_field(S256Params::a());
+        let b = S256FieldElement::new_s256_field(S256Params::b());
+        let x = S256FieldElement::new_s256_field(x);
+        let y = S256FieldElement::new_s256_field(y);
+        Point::new_unchecked(Some(x), Some(y), a, b)
+    }
+
     pub fn new_s256_infinity() -> Point {
         Self::new_s256_point(None, None)
     }
 
-    // Multiplication by binary expansion. Mods the coefficient by N for efficiency
-    // TODO switch over to the overridden Mul defined on Point when possible
+    // Mods the coefficient by N, then multiplies in Jacobian coordinates
     pub fn multiply(point: &Point, coefficient: U256) -> Point {
+        JacobianPoint::from_point(point)
+            .multiply(coefficient)
+            .to_point()
+    }
+
+    // Returns coefficient * G using the precomputed generator table
+    pub fn multiply_generator(coefficient: U256) -> Point {
+        GeneratorTable::multiply(coefficient).to_point()
+    }
+
+    // Multiplication by binary expansion in affine coordinates. This was the
+    // original implementation of multiply, and is kept as a reference to test
+    // and benchmark the Jacobian arithmetic against.
+    pub fn multiply_affine(point: &Point, coefficient: U256) -> Point {
         let mut coef = coefficient % S256Params::n();
         let mut current = point.clone();
         let mut result = point.new_infinity();
@@ -42,4 +87,11 @@ impl S256Point {
 
         result
     }
+
+    // Returns true if the point's curve parameters are those of secp256k1.
+    // The field is already fixed by the type, but a and b are not.
+    pub fn is_s256_point(point: &Point) -> bool {
+        let (a, b) = point.curve();
+        a.num() == S256Params::a() && b.num() == S256Params::b()
+    }
 }
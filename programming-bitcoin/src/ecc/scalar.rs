@@ -0,0 +1,229 @@
+use std::ops::{Add, Mul, Neg, Sub};
+use primitive_types::U256;
+use subtle::{Choice, ConditionallySelectable, ConstantTimeEq};
+use zeroize::Zeroize;
+
+// The secp256k1 group order as little-endian limbs
+pub const N: U256 = U256([
+    0xBFD25E8CD0364141,
+    0xBAAEDCE6AF48A03B,
+    0xFFFFFFFFFFFFFFFE,
+    0xFFFFFFFFFFFFFFFF,
+]);
+
+// 2^256 - n, a 129 bit number. Since 2^256 ≡ NC (mod n), the high half of a
+// wide value can be folded down by multiplying it by NC.
+const NC: U256 = U256([
+    0x402DA1732FC9BEBF,
+    0x4551231950B75FC4,
+    0x0000000000000001,
+    0x0000000000000000,
+]);
+
+/// An integer mod n, the order of the secp256k1 group. Private keys, nonces
+/// and the r and s of signatures all live here. A Scalar is always fully
+/// reduced: the constructors either reduce their input or reject it, so it
+/// is explicit at each call site which of the two happens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Scalar(U256);
+
+impl Scalar {
+    pub fn zero() -> Self {
+        Self(U256::zero())
+    }
+
+    pub fn one() -> Self {
+        Self(U256::one())
+    }
+
+    /// Returns None if value is not below n
+    pub fn from_u256(value: U256) -> Option<Self> {
+        if value < N {
+            Some(Self(value))
+        } else {
+            None
+        }
+    }
+
+    /// Reduces any 256 bit value mod n
+    pub fn reduce(value: U256) -> Self {
+        // 2^256 < 2n, so one subtraction is always enough
+        if value >= N {
+            Self(value - N)
+        } else {
+            Self(value)
+        }
+    }
+
+    /// Parses 32 big-endian bytes, returning None if the value is not below n
+    pub fn from_bytes(bytes: &[u8; 32]) -> Option<Self> {
+        Self::from_u256(U256::from_big_endian(bytes))
+    }
+
+    /// Interprets up to 32 big-endian bytes (such as a hash) as an integer
+    /// and reduces it mod n
+    pub fn reduce_bytes(bytes: &[u8]) -> Self {
+        Self::reduce(U256::from_big_endian(bytes))
+    }
+
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.0.to_big_endian()
+    }
+
+    pub fn to_u256(&self) -> U256 {
+        self.0
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0.is_zero()
+    }
+
+    /// True if the scalar is above n / 2. ECDSA signatures with a high s are
+    /// malleable and are negated to their low form.
+    pub fn is_high(&self) -> bool {
+        self.0 > N >> 1
+    }
+
+    pub fn negate(&self) -> Self {
+        if self.is_zero() {
+            *self
+        } else {
+            Self(N - self.0)
+        }
+    }
+
+    /// Negates the scalar if choice is set, without branching on it
+    pub fn negate_if(&self, choice: Choice) -> Self {
+        Self::conditional_select(self, &self.negate(), choice)
+    }
+
+    pub fn square(&self) -> Self {
+        *self * *self
+    }
+
+    pub fn pow(&self, exp: U256) -> Self {
+        let mut result = Self::one();
+        for i in (0..exp.bits()).rev() {
+            result = result.square();
+            if exp.bit(i) {
+                result = result * *self;
+            }
+        }
+        result
+    }
+
+    /// Returns the multiplicative inverse, or None for zero. n is prime, so by
+    /// Fermat's Little Theorem a^(n-2) is the inverse of a.
+    pub fn invert(&self) -> Option<Self> {
+        if self.is_zero() {
+            return None;
+        }
+        Some(self.pow(N - U256::from(2)))
+    }
+
+    // Reduces a 512 bit product mod n by repeatedly folding the high half.
+    // Each fold shrinks the value by about 127 bits, so this takes at most
+    // three rounds.
+    fn reduce_wide(wide: [u64; 8]) -> Self {
+        let mut wide = wide;
+        while wide[4..].iter().any(|limb| *limb != 0) {
+            wide = Self::fold(&wide);
+        }
+        Self::reduce(U256([wide[0], wide[1], wide[2], wide[3]]))
+    }
+
+    // Returns lo + hi * NC, where lo and hi are the low and high four limbs.
+    // NC has three limbs, so the result is below 2^386 and fits in eight.
+    fn fold(wide: &[u64; 8]) -> [u64; 8] {
+        let nc = NC.0;
+        let mut out = [0u64; 8];
+        out[..4].copy_from_slice(&wide[..4]);
+        for i in 0..4 {
+            let h = wide[4 + i] as u128;
+            let mut carry: u128 = 0;
+            for j in 0..3 {
+                let t = h * (nc[j] as u128) + (out[i + j] as u128) + carry;
+                out[i + j] = t as u64;
+                carry = t >> 64;
+            }
+            let mut k = i + 3;
+            while carry != 0 {
+                let t = (out[k] as u128) + carry;
+                out[k] = t as u64;
+                carry = t >> 64;
+                k += 1;
+            }
+        }
+        out
+    }
+}
+
+impl ConditionallySelectable for Scalar {
+    fn conditional_select(a: &Self, b: &Self, choice: Choice) -> Self {
+        Self(select_u256(&a.0, &b.0, choice))
+    }
+}
+
+impl ConstantTimeEq for Scalar {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.0 .0.ct_eq(&other.0 .0)
+    }
+}
+
+impl Zeroize for Scalar {
+    fn zeroize(&mut self) {
+        self.0 .0.zeroize();
+    }
+}
+
+// Picks b if choice is set and a otherwise, limb by limb
+pub(crate) fn select_u256(a: &U256, b: &U256, choice: Choice) -> U256 {
+    let mut limbs = [0u64; 4];
+    for (i, limb) in limbs.iter_mut().enumerate() {
+        *limb = u64::conditional_select(&a.0[i], &b.0[i], choice);
+    }
+    U256(limbs)
+}
+
+impl Add for Scalar {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        let (sum, overflow) = self.0.overflowing_add(other.0);
+        if overflow || sum >= N {
+            // Subtracting n wraps back around 2^256 when the sum overflowed
+            Self(sum.overflowing_sub(N).0)
+        } else {
+            Self(sum)
+        }
+    }
+}
+
+impl Sub for Scalar {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        let (diff, borrow) = self.0.overflowing_sub(other.0);
+        if borrow {
+            Self(diff.overflowing_add(N).0)
+        } else {
+            Self(diff)
+        }
+    }
+}
+
+impl Mul for Scalar {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        Self::reduce_wide(self.0.full_mul(other.0).0)
+    }
+}
+
+impl Neg for Scalar {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        self.negate()
+    }
+}
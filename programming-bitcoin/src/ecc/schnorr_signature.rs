@@ -0,0 +1,42 @@
+use primitive_types::U256;
+
+/// A BIP340 Schnorr signature: the x coordinate of the nonce point R followed
+/// by the scalar s, serialized as 64 bytes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchnorrSignature {
+    r: U256,
+    s: U256,
+}
+
+impl SchnorrSignature {
+    pub fn new(r: U256, s: U256) -> Self {
+        Self { r, s }
+    }
+
+    pub fn r(&self) -> U256 {
+        self.r
+    }
+
+    pub fn s(&self) -> U256 {
+        self.s
+    }
+
+    // Returns the 64 byte serialization: bytes(r) || bytes(s)
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = self.r.to_big_endian().to_vec();
+        result.extend_from_slice(&self.s.to_big_endian());
+        result
+    }
+
+    /// Parses a 64 byte signature. Range checks on r and s are left to
+    /// verification, as BIP340 specifies.
+    pub fn parse(raw_sig: &[u8]) -> Result<Self, String> {
+        if raw_sig.len() != 64 {
+            return Err(format!("Invalid Schnorr signature length: {}", raw_sig.len()));
+        }
+        Ok(Self {
+            r: U256::from_big_endian(&raw_sig[..32]),
+            s: U256::from_big_endian(&raw_sig[32..]),
+        })
+    }
+}
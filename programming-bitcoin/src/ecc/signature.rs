@@ -1,4 +1,58 @@
+use std::fmt;
 use primitive_types::U256;
+use crate::ecc::scalar::{Scalar, N};
+
+/// Why a DER signature failed to parse
+#[derive(Debug, Clone, PartialEq)]
+pub enum SignatureError {
+    // The encoding is shorter or longer than it can be
+    InvalidLength(usize),
+    // The encoding does not start with the 0x30 sequence tag
+    InvalidSequenceTag,
+    // The sequence length does not match the length of the encoding
+    InvalidSequenceLength,
+    // r or s does not start with the 0x02 integer tag
+    InvalidIntegerTag,
+    // An integer is empty or its length runs past the end of the encoding
+    InvalidIntegerLength,
+    // The top bit of an integer is set, so it would be negative
+    NegativeInteger,
+    // An integer has a leading zero byte that is not needed
+    ExcessivePadding,
+    // An integer does not fit in 256 bits
+    IntegerTooLarge,
+    // s is above n / 2 where low S is required
+    HighS,
+}
+
+impl fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SignatureError::InvalidLength(length) => write!(f, "invalid signature length: {}", length),
+            SignatureError::InvalidSequenceTag => write!(f, "signature does not start with a DER sequence"),
+            SignatureError::InvalidSequenceLength => write!(f, "DER sequence length does not match the signature length"),
+            SignatureError::InvalidIntegerTag => write!(f, "expected a DER integer"),
+            SignatureError::InvalidIntegerLength => write!(f, "invalid DER integer length"),
+            SignatureError::NegativeInteger => write!(f, "DER integer is negative"),
+            SignatureError::ExcessivePadding => write!(f, "DER integer has excessive padding"),
+            SignatureError::IntegerTooLarge => write!(f, "DER integer does not fit in 256 bits"),
+            SignatureError::HighS => write!(f, "s is not low"),
+        }
+    }
+}
+
+impl std::error::Error for SignatureError {}
+
+/// How strictly DER signatures are parsed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DerMode {
+    /// Only accept the strict DER encoding required by BIP66. When used for
+    /// script evaluation, s must also be low (BIP146).
+    Strict,
+    /// Accept the encodings found in old transactions: long form lengths,
+    /// padded or negative integers and trailing data after the sequence
+    Lax,
+}
 
 #[derive(Debug, Clone)]
 pub struct Signature {
@@ -62,32 +116,144 @@ impl Signature {
         final_result
     }
     
-    /// Returns a Signature from a raw sig encoded as a byte vector (DER)
+    /// Returns a Signature from a raw sig encoded as a byte vector (DER).
+    /// Panics on malformed input; use parse_der to handle errors.
     pub fn parse(raw_sig: Vec<u8>) -> Self {
-        let r_length = raw_sig[3];
-        // check the value at index 4
-        let mut start_index = 4;
-        if raw_sig[start_index] == 00 {
-            start_index += 1;
-        }
-        // let r_end_index = start_index + 31; // should always be 31
-        let r_end_index = start_index + (r_length as usize) - 2;
-        let r = U256::from_big_endian(&raw_sig[start_index..=r_end_index]); // the final index is one more than the end of the slice
-        // can refactor
-        let marker_byte_index = r_end_index + 1;
-        let s_length_index = marker_byte_index + 1;
-        // let s_length = raw_sig[s_length_index];
-        let mut s_start_index = s_length_index + 1;
-        if raw_sig[s_start_index] == 00 {
-            s_start_index += 1;
-        }
-        let s_end_index = s_start_index + 31; // should always be 32 bytes?
-        // let s_end_index = s_start_index + (s_length as usize) - 1;
-        let s = U256::from_big_endian(&raw_sig[s_start_index..=s_end_index]);
-        Self {
-            r,
-            s
+        Self::parse_der(&raw_sig, DerMode::Lax).expect("Invalid DER signature")
+    }
+
+    /// Parses a DER signature, without the sighash type byte a signature in
+    /// a script carries
+    pub fn parse_der(raw: &[u8], mode: DerMode) -> Result<Self, SignatureError> {
+        match mode {
+            DerMode::Strict => Self::parse_der_strict(raw),
+            DerMode::Lax => Self::parse_der_lax(raw),
+        }
+    }
+
+    // The checks of BIP66's IsValidSignatureEncoding, minus the sighash byte:
+    // 0x30 [total length] 0x02 [r length] [r] 0x02 [s length] [s]
+    fn parse_der_strict(raw: &[u8]) -> Result<Self, SignatureError> {
+        let length = raw.len();
+        if !(8..=72).contains(&length) {
+            return Err(SignatureError::InvalidLength(length));
+        }
+        if raw[0] != 0x30 {
+            return Err(SignatureError::InvalidSequenceTag);
+        }
+        if raw[1] as usize != length - 2 {
+            return Err(SignatureError::InvalidSequenceLength);
+        }
+        let r_length = raw[3] as usize;
+        if 5 + r_length >= length {
+            return Err(SignatureError::InvalidIntegerLength);
+        }
+        let s_length = raw[5 + r_length] as usize;
+        if r_length + s_length + 6 != length {
+            return Err(SignatureError::InvalidSequenceLength);
+        }
+        let r = Self::strict_integer(&raw[2..4 + r_length])?;
+        let s = Self::strict_integer(&raw[4 + r_length..])?;
+        Ok(Self { r, s })
+    }
+
+    // A strict DER integer: 0x02, its length, then a minimally encoded
+    // positive big-endian value
+    fn strict_integer(raw: &[u8]) -> Result<U256, SignatureError> {
+        if raw[0] != 0x02 {
+            return Err(SignatureError::InvalidIntegerTag);
+        }
+        let value = &raw[2..];
+        if value.is_empty() {
+            return Err(SignatureError::InvalidIntegerLength);
+        }
+        if value[0] & 0x80 != 0 {
+            return Err(SignatureError::NegativeInteger);
+        }
+        if value.len() > 1 && value[0] == 0 && value[1] & 0x80 == 0 {
+            return Err(SignatureError::ExcessivePadding);
+        }
+        Self::parse_integer(value)
+    }
+
+    // Modelled on libsecp256k1's ecdsa_signature_parse_der_lax. Only the
+    // tags and the lengths of r and s have to make sense.
+    fn parse_der_lax(raw: &[u8]) -> Result<Self, SignatureError> {
+        let mut position = 0;
+        if raw.first() != Some(&0x30) {
+            return Err(SignatureError::InvalidSequenceTag);
+        }
+        position += 1;
+        // The sequence length is skipped, whatever it says
+        Self::lax_length(raw, &mut position)?;
+        let r = Self::lax_integer(raw, &mut position)?;
+        let s = Self::lax_integer(raw, &mut position)?;
+        Ok(Self { r, s })
+    }
+
+    fn lax_integer(raw: &[u8], position: &mut usize) -> Result<U256, SignatureError> {
+        if raw.get(*position) != Some(&0x02) {
+            return Err(SignatureError::InvalidIntegerTag);
+        }
+        *position += 1;
+        let length = Self::lax_length(raw, position)?;
+        let end = position.checked_add(length).ok_or(SignatureError::InvalidIntegerLength)?;
+        if end > raw.len() {
+            return Err(SignatureError::InvalidIntegerLength);
+        }
+        let value = Self::parse_integer(&raw[*position..end])?;
+        *position = end;
+        Ok(value)
+    }
+
+    // Reads a DER length, which is either one byte below 0x80, or 0x80 plus
+    // the number of big-endian bytes that follow
+    fn lax_length(raw: &[u8], position: &mut usize) -> Result<usize, SignatureError> {
+        let first = *raw.get(*position).ok_or(SignatureError::InvalidLength(raw.len()))?;
+        *position += 1;
+        if first & 0x80 == 0 {
+            return Ok(first as usize);
+        }
+        let byte_count = (first & 0x7f) as usize;
+        if *position + byte_count > raw.len() {
+            return Err(SignatureError::InvalidLength(raw.len()));
+        }
+        let mut length: usize = 0;
+        for byte in &raw[*position..*position + byte_count] {
+            length = length.checked_mul(256).ok_or(SignatureError::InvalidIntegerLength)? + *byte as usize;
+        }
+        *position += byte_count;
+        Ok(length)
+    }
+
+    // DER integers may have a leading zero byte to keep them positive
+    fn parse_integer(bytes: &[u8]) -> Result<U256, SignatureError> {
+        let start = bytes.iter().take_while(|b| **b == 0).count();
+        if bytes.len() - start > 32 {
+            return Err(SignatureError::IntegerTooLarge);
+        }
+        Ok(U256::from_big_endian(&bytes[start..]))
+    }
+
+    /// True if s is at most n / 2. Both s and n - s verify, so Bitcoin only
+    /// relays signatures with the low one to avoid malleability.
+    pub fn is_low_s(&self) -> bool {
+        self.s < N && !Scalar::reduce(self.s).is_high()
+    }
+
+    pub fn check_low_s(&self) -> Result<(), SignatureError> {
+        if self.is_low_s() {
+            Ok(())
+        } else {
+            Err(SignatureError::HighS)
+        }
+    }
+
+    /// Returns the signature with s replaced by n - s if s is high
+    pub fn normalize_s(&self) -> Self {
+        match Scalar::from_u256(self.s) {
+            Some(s) if s.is_high() => Self::new(self.r, (-s).to_u256()),
+            _ => self.clone(),
         }
     }
-    
 }
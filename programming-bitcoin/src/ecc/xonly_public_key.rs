@@ -0,0 +1,102 @@
+use primitive_types::U256;
+use crate::ecc::generator_table::GeneratorTable;
+use crate::ecc::jacobian_point::JacobianPoint;
+use crate::ecc::point::Point;
+use crate::ecc::s256field_element::{S256FieldElement as Fe, P};
+use crate::ecc::s256point::S256Point;
+use crate::ecc::scalar::Scalar;
+use crate::ecc::schnorr_signature::SchnorrSignature;
+use crate::ecc::secp256k1_params::S256Params;
+use crate::utils::tagged_hash::tagged_hash;
+
+/// A BIP340 public key. Only the x coordinate is kept; the point it stands
+/// for is the one with that x and an even y.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct XOnlyPublicKey {
+    x: U256,
+}
+
+impl XOnlyPublicKey {
+    // Drops the y coordinate of a (non-infinite) point
+    pub fn from_point(point: &Point) -> Self {
+        let (x, _) = point.coordinates().expect("point at infinity has no x-only key");
+        Self { x }
+    }
+
+    /// Parses a 32 byte x-only key, failing if x is not the x coordinate of
+    /// a point on the curve
+    pub fn parse(raw: &[u8]) -> Result<Self, String> {
+        if raw.len() != 32 {
+            return Err(format!("Invalid x-only public key length: {}", raw.len()));
+        }
+        let x = U256::from_big_endian(raw);
+        match Self::lift_x(x) {
+            Some(_) => Ok(Self { x }),
+            None => Err(format!("{:#x} is not the x coordinate of a point on the curve", x)),
+        }
+    }
+
+    pub fn x(&self) -> U256 {
+        self.x
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        self.x.to_big_endian().to_vec()
+    }
+
+    // Returns the point with this x coordinate and an even y
+    pub fn to_point(&self) -> Point {
+        let (x, y) = Self::lift_x(self.x).unwrap();
+        S256Point::new_s256_point_unchecked(x, y)
+    }
+
+    /// BIP340 lift_x: returns the point with x coordinate x and an even y,
+    /// or None if there is no such point
+    pub fn lift_x(x: U256) -> Option<(U256, U256)> {
+        if x >= P {
+            return None;
+        }
+        let c = Fe::add(Fe::mul(Fe::square(x), x), S256Params::b());
+        let y = Fe::sqrt(c);
+        if Fe::square(y) != c {
+            return None;
+        }
+        if y.bit(0) {
+            Some((x, P - y))
+        } else {
+            Some((x, y))
+        }
+    }
+
+    /// Verifies a BIP340 signature over a message of any length
+    pub fn verify_schnorr(&self, msg: &[u8], sig: &SchnorrSignature) -> bool {
+        let (px, py) = match Self::lift_x(self.x) {
+            Some(point) => point,
+            None => return false,
+        };
+        if sig.r() >= P {
+            return false;
+        }
+        let s = match Scalar::from_u256(sig.s()) {
+            Some(s) => s,
+            None => return false,
+        };
+        let e = challenge(sig.r(), self.x, msg);
+
+        // R = s*G - e*P
+        let s_g = GeneratorTable::multiply(s.to_u256());
+        let e_p = JacobianPoint::from_affine(px, py).multiply((-e).to_u256());
+        match s_g.add(&e_p).to_affine() {
+            Some((rx, ry)) => !ry.bit(0) && rx == sig.r(),
+            None => false,
+        }
+    }
+}
+
+/// Returns the BIP340 challenge e = int(hash_BIP0340/challenge(r || P || m)) mod n
+pub fn challenge(r: U256, public_key_x: U256, msg: &[u8]) -> Scalar {
+    let mut data = r.to_big_endian().to_vec();
+    data.extend_from_slice(&public_key_x.to_big_endian());
+    data.extend_from_slice(msg);
+    Scalar::reduce_bytes(&tagged_hash("BIP0340/challenge", &data))
+}
@@ -0,0 +1,6 @@
+pub mod account;
+pub mod derivation_path;
+pub mod error;
+pub mod extended_key;
+pub mod mnemonic;
+pub mod wordlist;
@@ -0,0 +1,229 @@
+use crate::ecc::point::Point;
+use crate::ecc::private_key::PrivateKey;
+use crate::ecc::xonly_public_key::XOnlyPublicKey;
+use crate::hd::derivation_path::DerivationPath;
+use crate::hd::error::Bip32Error;
+use crate::hd::extended_key::{ExtendedPrivateKey, ExtendedPublicKey};
+use crate::script::script::Script;
+use crate::taproot::tweak::tweak_public_key;
+use crate::utils::base58::encode_base58_checksum;
+use crate::utils::bech32::encode_segwit_address;
+use crate::utils::hash160::hash160;
+
+// Version bytes of account keys, as (private, public). BIP49 and BIP84
+// accounts use the SLIP-132 versions so that wallets importing them know
+// which addresses to derive. BIP44 and BIP86 accounts keep xprv/xpub.
+const XPRV_XPUB: [[u8; 4]; 2] = [[0x04, 0x88, 0xad, 0xe4], [0x04, 0x88, 0xb2, 0x1e]];
+const TPRV_TPUB: [[u8; 4]; 2] = [[0x04, 0x35, 0x83, 0x94], [0x04, 0x35, 0x87, 0xcf]];
+const YPRV_YPUB: [[u8; 4]; 2] = [[0x04, 0x9d, 0x78, 0x78], [0x04, 0x9d, 0x7c, 0xb2]];
+const UPRV_UPUB: [[u8; 4]; 2] = [[0x04, 0x4a, 0x4e, 0x28], [0x04, 0x4a, 0x52, 0x62]];
+const ZPRV_ZPUB: [[u8; 4]; 2] = [[0x04, 0xb2, 0x43, 0x0c], [0x04, 0xb2, 0x47, 0x46]];
+const VPRV_VPUB: [[u8; 4]; 2] = [[0x04, 0x5f, 0x18, 0xbc], [0x04, 0x5f, 0x1c, 0xf6]];
+
+/// The standard account structures, named after the BIP that defines each.
+/// The purpose fixes the script type of every address in the account.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Purpose {
+    // P2PKH
+    Bip44,
+    // P2WPKH nested in P2SH
+    Bip49,
+    // P2WPKH
+    Bip84,
+    // P2TR with a key path only
+    Bip86,
+}
+
+impl Purpose {
+    /// The first, hardened, child number of the account path
+    pub fn number(&self) -> u32 {
+        match self {
+            Purpose::Bip44 => 44,
+            Purpose::Bip49 => 49,
+            Purpose::Bip84 => 84,
+            Purpose::Bip86 => 86,
+        }
+    }
+
+    /// The version bytes of the account's extended private key
+    pub fn private_version(&self, testnet: bool) -> [u8; 4] {
+        self.versions(testnet)[0]
+    }
+
+    /// The version bytes of the account's extended public key
+    pub fn public_version(&self, testnet: bool) -> [u8; 4] {
+        self.versions(testnet)[1]
+    }
+
+    fn versions(&self, testnet: bool) -> [[u8; 4]; 2] {
+        match (self, testnet) {
+            (Purpose::Bip44 | Purpose::Bip86, false) => XPRV_XPUB,
+            (Purpose::Bip44 | Purpose::Bip86, true) => TPRV_TPUB,
+            (Purpose::Bip49, false) => YPRV_YPUB,
+            (Purpose::Bip49, true) => UPRV_UPUB,
+            (Purpose::Bip84, false) => ZPRV_ZPUB,
+            (Purpose::Bip84, true) => VPRV_VPUB,
+        }
+    }
+
+    /// Returns the script_pubkey that pays to a public key
+    pub fn script_pubkey(&self, public_key: &Point) -> Script {
+        match self {
+            Purpose::Bip44 => Script::p2pkh_script(hash160(&public_key.clone().sec(true))),
+            Purpose::Bip49 => Script::p2sh_script(hash160(&self.redeem_script(public_key))),
+            Purpose::Bip84 => Script::p2wpkh_script(hash160(&public_key.clone().sec(true))),
+            Purpose::Bip86 => Script::p2tr_script(taproot_output_key(public_key)),
+        }
+    }
+
+    /// Returns the address that pays to a public key
+    pub fn address(&self, public_key: &Point, testnet: bool) -> String {
+        let hrp = if testnet { "tb" } else { "bc" };
+        match self {
+            Purpose::Bip44 => {
+                let prefix = if testnet { 0x6f } else { 0x00 };
+                encode_base58_checksum(&[&[prefix][..], &hash160(&public_key.clone().sec(true))].concat())
+            }
+            Purpose::Bip49 => {
+                let prefix = if testnet { 0xc4 } else { 0x05 };
+                encode_base58_checksum(&[&[prefix][..], &hash160(&self.redeem_script(public_key))].concat())
+            }
+            Purpose::Bip84 => encode_segwit_address(hrp, 0, &hash160(&public_key.clone().sec(true))),
+            Purpose::Bip86 => encode_segwit_address(hrp, 1, &taproot_output_key(public_key)),
+        }
+    }
+
+    // The serialized P2WPKH script that a BIP49 P2SH output commits to
+    fn redeem_script(&self, public_key: &Point) -> Vec<u8> {
+        [&[0x00, 0x14][..], &hash160(&public_key.clone().sec(true))].concat()
+    }
+}
+
+// BIP86 tweaks the key with no script tree. The tweak is a hash, so it only
+// fails (is at least n, or cancels the key) with negligible probability.
+fn taproot_output_key(public_key: &Point) -> Vec<u8> {
+    let (output_key, _) = tweak_public_key(&XOnlyPublicKey::from_point(public_key), None).expect("taproot tweak failed");
+    output_key.serialize()
+}
+
+/// The two chains of an account: addresses to hand out, and addresses for
+/// the wallet's own change
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chain {
+    Receive,
+    Change,
+}
+
+impl Chain {
+    /// The child number of the chain under the account key
+    pub fn number(&self) -> u32 {
+        match self {
+            Chain::Receive => 0,
+            Chain::Change => 1,
+        }
+    }
+}
+
+/// An account at m/purpose'/coin_type'/account', where coin_type is 0 on
+/// mainnet and 1 on testnet. Addresses are at /chain/index below it.
+#[derive(Debug)]
+pub struct Account {
+    purpose: Purpose,
+    index: u32,
+    key: ExtendedPrivateKey,
+}
+
+impl Account {
+    /// Derives the account from a BIP32 seed
+    pub fn from_seed(seed: &[u8], purpose: Purpose, index: u32, testnet: bool) -> Result<Self, Bip32Error> {
+        Self::from_master(&ExtendedPrivateKey::from_seed(seed, testnet)?, purpose, index)
+    }
+
+    /// Derives the account from a master key, on the master key's network
+    pub fn from_master(master: &ExtendedPrivateKey, purpose: Purpose, index: u32) -> Result<Self, Bip32Error> {
+        let path = account_path(purpose, master.testnet(), index)?;
+        Ok(Self {
+            purpose,
+            index,
+            key: master.derive_path(&path)?,
+        })
+    }
+
+    pub fn purpose(&self) -> Purpose {
+        self.purpose
+    }
+
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    pub fn testnet(&self) -> bool {
+        self.key.testnet()
+    }
+
+    /// The path of the account key
+    pub fn path(&self) -> DerivationPath {
+        // The index was checked when the account was derived
+        account_path(self.purpose, self.testnet(), self.index).unwrap()
+    }
+
+    /// The path of the address at this index of a chain
+    pub fn address_path(&self, chain: Chain, index: u32) -> DerivationPath {
+        self.path().child(chain.number()).child(index)
+    }
+
+    pub fn extended_private_key(&self) -> &ExtendedPrivateKey {
+        &self.key
+    }
+
+    pub fn extended_public_key(&self) -> ExtendedPublicKey {
+        self.key.extended_public_key()
+    }
+
+    /// Returns the account's extended private key with the version bytes of
+    /// its purpose, e.g. zprv for BIP84
+    pub fn to_slip132_private(&self) -> String {
+        self.key.to_base58_with_version(self.purpose.private_version(self.testnet()))
+    }
+
+    /// Returns the account's extended public key with the version bytes of
+    /// its purpose, e.g. zpub for BIP84
+    pub fn to_slip132_public(&self) -> String {
+        self.extended_public_key().to_base58_with_version(self.purpose.public_version(self.testnet()))
+    }
+
+    /// Returns the private key of the address at this index. For BIP86 this
+    /// is the internal key, which has to be tweaked before it can sign for
+    /// the output.
+    pub fn private_key(&self, chain: Chain, index: u32) -> Result<PrivateKey, Bip32Error> {
+        let key = self.key.derive_child(chain.number())?.derive_child(index)?;
+        Ok(PrivateKey::new(key.private_key().secret()))
+    }
+
+    pub fn public_key(&self, chain: Chain, index: u32) -> Result<Point, Bip32Error> {
+        let key = self.extended_public_key().derive_child(chain.number())?.derive_child(index)?;
+        Ok(key.public_key().clone())
+    }
+
+    pub fn script_pubkey(&self, chain: Chain, index: u32) -> Result<Script, Bip32Error> {
+        Ok(self.purpose.script_pubkey(&self.public_key(chain, index)?))
+    }
+
+    pub fn address(&self, chain: Chain, index: u32) -> Result<String, Bip32Error> {
+        Ok(self.purpose.address(&self.public_key(chain, index)?, self.testnet()))
+    }
+
+    pub fn receive_address(&self, index: u32) -> Result<String, Bip32Error> {
+        self.address(Chain::Receive, index)
+    }
+
+    pub fn change_address(&self, index: u32) -> Result<String, Bip32Error> {
+        self.address(Chain::Change, index)
+    }
+}
+
+// Parsing the path checks that the account index can be hardened
+fn account_path(purpose: Purpose, testnet: bool, index: u32) -> Result<DerivationPath, Bip32Error> {
+    let coin_type = if testnet { 1 } else { 0 };
+    DerivationPath::parse(&format!("m/{}'/{}'/{}'", purpose.number(), coin_type, index))
+}
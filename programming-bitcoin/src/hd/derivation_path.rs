@@ -0,0 +1,78 @@
+use std::fmt;
+use crate::hd::error::Bip32Error;
+use crate::hd::extended_key::HARDENED;
+
+/// A BIP32 derivation path such as m/84'/0'/0'/0/5: the child numbers to
+/// derive, in order, starting from the master key
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct DerivationPath {
+    indexes: Vec<u32>,
+}
+
+impl DerivationPath {
+    pub fn new(indexes: Vec<u32>) -> Self {
+        Self { indexes }
+    }
+
+    /// The empty path "m", which is the master key itself
+    pub fn master() -> Self {
+        Self::default()
+    }
+
+    /// Parses a path of the form m/a/b'/c. A child number is hardened when
+    /// followed by ' or h, and must be below 2^31 either way.
+    pub fn parse(path: &str) -> Result<Self, Bip32Error> {
+        let mut components = path.split('/');
+        if components.next() != Some("m") {
+            return Err(Bip32Error::InvalidPath(path.to_string()));
+        }
+        let indexes = components
+            .map(|component| {
+                let (number, offset) = match component.strip_suffix(['\'', 'h']) {
+                    Some(number) => (number, HARDENED),
+                    None => (component, 0),
+                };
+                // u32::from_str accepts a leading +, which isn't a valid path
+                match number.parse::<u32>() {
+                    Ok(index) if index < HARDENED && number.bytes().all(|b| b.is_ascii_digit()) => Ok(index + offset),
+                    _ => Err(Bip32Error::InvalidPath(path.to_string())),
+                }
+            })
+            .collect::<Result<Vec<u32>, Bip32Error>>()?;
+        if indexes.len() > u8::MAX as usize {
+            return Err(Bip32Error::MaxDepthExceeded);
+        }
+        Ok(Self { indexes })
+    }
+
+    pub fn indexes(&self) -> &[u32] {
+        &self.indexes
+    }
+
+    /// The depth of the key the path leads to
+    pub fn depth(&self) -> usize {
+        self.indexes.len()
+    }
+
+    /// Returns the path extended by one child number
+    pub fn child(&self, index: u32) -> Self {
+        let mut indexes = self.indexes.clone();
+        indexes.push(index);
+        Self { indexes }
+    }
+}
+
+// Hardened child numbers are written with ', as in BIP32
+impl fmt::Display for DerivationPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "m")?;
+        for index in &self.indexes {
+            if *index >= HARDENED {
+                write!(f, "/{}'", index - HARDENED)?;
+            } else {
+                write!(f, "/{}", index)?;
+            }
+        }
+        Ok(())
+    }
+}
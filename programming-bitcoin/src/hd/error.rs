@@ -0,0 +1,82 @@
+use std::fmt;
+
+/// Why a BIP32 key could not be derived or parsed
+#[derive(Debug, Clone, PartialEq)]
+pub enum Bip32Error {
+    // Seeds must be between 16 and 64 bytes
+    InvalidSeedLength(usize),
+    // The seed gives a master key of zero or at least n. The odds of this
+    // are below 1 in 2^127, and BIP32 says to use another seed.
+    InvalidMasterKey,
+    // This index gives an invalid child key, and BIP32 says to skip to the
+    // next one. As unlikely as InvalidMasterKey.
+    InvalidChildKey(u32),
+    // Hardened children can only be derived from a private key
+    HardenedFromPublic(u32),
+    // Keys can be at most 255 levels deep
+    MaxDepthExceeded,
+    // A serialized key must be 78 bytes
+    InvalidLength(usize),
+    // The version bytes are not those of the kind of key being parsed
+    InvalidVersion([u8; 4]),
+    // A master key (depth 0) with a parent fingerprint
+    InvalidParentFingerprint,
+    // A master key (depth 0) with a child number
+    InvalidChildNumber,
+    // The key data is not 0x00 followed by a scalar in [1, n)
+    InvalidPrivateKey,
+    // The key data is not a compressed SEC public key
+    InvalidPublicKey,
+    Base58(String),
+    // A derivation path that isn't m followed by /-separated child numbers
+    // below 2^31, each optionally marked hardened with ' or h
+    InvalidPath(String),
+}
+
+impl fmt::Display for Bip32Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Bip32Error::InvalidSeedLength(length) => write!(f, "invalid seed length: {}", length),
+            Bip32Error::InvalidMasterKey => write!(f, "seed gives an invalid master key"),
+            Bip32Error::InvalidChildKey(index) => write!(f, "child {} is an invalid key", index),
+            Bip32Error::HardenedFromPublic(index) => write!(f, "cannot derive hardened child {} from a public key", index),
+            Bip32Error::MaxDepthExceeded => write!(f, "maximum depth of 255 exceeded"),
+            Bip32Error::InvalidLength(length) => write!(f, "invalid extended key length: {}", length),
+            Bip32Error::InvalidVersion(version) => write!(f, "invalid extended key version: {}", hex::encode(version)),
+            Bip32Error::InvalidParentFingerprint => write!(f, "master key with a parent fingerprint"),
+            Bip32Error::InvalidChildNumber => write!(f, "master key with a child number"),
+            Bip32Error::InvalidPrivateKey => write!(f, "invalid private key"),
+            Bip32Error::InvalidPublicKey => write!(f, "invalid public key"),
+            Bip32Error::Base58(error) => write!(f, "{}", error),
+            Bip32Error::InvalidPath(path) => write!(f, "invalid derivation path: {}", path),
+        }
+    }
+}
+
+impl std::error::Error for Bip32Error {}
+
+/// Why a BIP39 mnemonic could not be created or parsed
+#[derive(Debug, Clone, PartialEq)]
+pub enum Bip39Error {
+    // Entropy must be 16, 20, 24, 28 or 32 bytes
+    InvalidEntropyLength(usize),
+    // A phrase must have 12, 15, 18, 21 or 24 words
+    InvalidWordCount(usize),
+    // The word at this position is not in the wordlist. Only the position is
+    // kept, so the phrase doesn't end up in logs.
+    UnknownWord(usize),
+    InvalidChecksum,
+}
+
+impl fmt::Display for Bip39Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Bip39Error::InvalidEntropyLength(length) => write!(f, "invalid entropy length: {}", length),
+            Bip39Error::InvalidWordCount(count) => write!(f, "invalid word count: {}", count),
+            Bip39Error::UnknownWord(position) => write!(f, "word {} is not in the wordlist", position),
+            Bip39Error::InvalidChecksum => write!(f, "invalid mnemonic checksum"),
+        }
+    }
+}
+
+impl std::error::Error for Bip39Error {}
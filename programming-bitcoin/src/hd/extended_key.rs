@@ -0,0 +1,420 @@
+use std::fmt;
+use zeroize::Zeroize;
+use crate::ecc::generator_table::GeneratorTable;
+use crate::ecc::jacobian_point::JacobianPoint;
+use crate::ecc::point::Point;
+use crate::ecc::private_key::PrivateKey;
+use crate::ecc::scalar::Scalar;
+use crate::hd::derivation_path::DerivationPath;
+use crate::hd::error::Bip32Error;
+use crate::utils::base58::{decode_base58_checksum, encode_base58_checksum};
+use crate::utils::hash160::hash160;
+use crate::utils::hmac_sha512::hmac_sha512;
+
+/// Child numbers from 2^31 up are hardened
+pub const HARDENED: u32 = 0x8000_0000;
+
+// Version bytes of the serialized keys
+const XPRV: [u8; 4] = [0x04, 0x88, 0xad, 0xe4];
+const XPUB: [u8; 4] = [0x04, 0x88, 0xb2, 0x1e];
+const TPRV: [u8; 4] = [0x04, 0x35, 0x83, 0x94];
+const TPUB: [u8; 4] = [0x04, 0x35, 0x87, 0xcf];
+
+/// A BIP32 extended private key: a private key and a chain code, from which
+/// any number of child keys can be derived
+pub struct ExtendedPrivateKey {
+    private_key: PrivateKey,
+    chain_code: [u8; 32],
+    depth: u8,
+    parent_fingerprint: [u8; 4],
+    child_number: u32,
+    testnet: bool,
+}
+
+/// A BIP32 extended public key, which can derive the public keys of the
+/// non-hardened children of the matching private key
+#[derive(Debug, Clone)]
+pub struct ExtendedPublicKey {
+    public_key: Point,
+    chain_code: [u8; 32],
+    depth: u8,
+    parent_fingerprint: [u8; 4],
+    child_number: u32,
+    testnet: bool,
+}
+
+impl ExtendedPrivateKey {
+    /// Derives the master key from a seed of 16 to 64 bytes
+    pub fn from_seed(seed: &[u8], testnet: bool) -> Result<Self, Bip32Error> {
+        if !(16..=64).contains(&seed.len()) {
+            return Err(Bip32Error::InvalidSeedLength(seed.len()));
+        }
+        let mut i = hmac_sha512(b"Bitcoin seed", seed);
+        let key = parse_secret(&i[..32]).ok_or(Bip32Error::InvalidMasterKey);
+        let chain_code = i[32..].try_into().unwrap();
+        i.zeroize();
+        Ok(Self {
+            private_key: PrivateKey::new(key?.to_u256()),
+            chain_code,
+            depth: 0,
+            parent_fingerprint: [0; 4],
+            child_number: 0,
+            testnet,
+        })
+    }
+
+    /// Derives the child key at this index. Indexes from HARDENED up give
+    /// hardened children.
+    pub fn derive_child(&self, index: u32) -> Result<Self, Bip32Error> {
+        let depth = self.depth.checked_add(1).ok_or(Bip32Error::MaxDepthExceeded)?;
+        let secret = Scalar::reduce(self.private_key.secret());
+        let mut data = if index >= HARDENED {
+            [&[0u8][..], &secret.to_bytes()].concat()
+        } else {
+            self.private_key.public_point().clone().sec(true)
+        };
+        data.extend_from_slice(&index.to_be_bytes());
+        let mut i = hmac_sha512(&self.chain_code, &data);
+        data.zeroize();
+
+        // k_i = IL + k, which must not be zero
+        let child_secret = parse_secret(&i[..32])
+            .map(|tweak| tweak + secret)
+            .filter(|child_secret| !child_secret.is_zero());
+        let chain_code = i[32..].try_into().unwrap();
+        i.zeroize();
+        let child_secret = child_secret.ok_or(Bip32Error::InvalidChildKey(index))?;
+        Ok(Self {
+            private_key: PrivateKey::new(child_secret.to_u256()),
+            chain_code,
+            depth,
+            parent_fingerprint: self.fingerprint(),
+            child_number: index,
+            testnet: self.testnet,
+        })
+    }
+
+    /// Derives the key at the end of a path from this key, which is taken
+    /// to be the path's starting point
+    pub fn derive_path(&self, path: &DerivationPath) -> Result<Self, Bip32Error> {
+        let mut key = Self {
+            private_key: PrivateKey::new(self.private_key.secret()),
+            chain_code: self.chain_code,
+            depth: self.depth,
+            parent_fingerprint: self.parent_fingerprint,
+            child_number: self.child_number,
+            testnet: self.testnet,
+        };
+        for index in path.indexes() {
+            key = key.derive_child(*index)?;
+        }
+        Ok(key)
+    }
+
+    /// Returns the extended public key with the same chain code
+    pub fn extended_public_key(&self) -> ExtendedPublicKey {
+        ExtendedPublicKey {
+            public_key: self.private_key.public_point().clone(),
+            chain_code: self.chain_code,
+            depth: self.depth,
+            parent_fingerprint: self.parent_fingerprint,
+            child_number: self.child_number,
+            testnet: self.testnet,
+        }
+    }
+
+    pub fn private_key(&self) -> &PrivateKey {
+        &self.private_key
+    }
+
+    pub fn public_key(&self) -> Point {
+        self.private_key.public_point().clone()
+    }
+
+    pub fn chain_code(&self) -> [u8; 32] {
+        self.chain_code
+    }
+
+    pub fn depth(&self) -> u8 {
+        self.depth
+    }
+
+    pub fn parent_fingerprint(&self) -> [u8; 4] {
+        self.parent_fingerprint
+    }
+
+    pub fn child_number(&self) -> u32 {
+        self.child_number
+    }
+
+    pub fn testnet(&self) -> bool {
+        self.testnet
+    }
+
+    /// The hash160 of the compressed public key
+    pub fn identifier(&self) -> Vec<u8> {
+        hash160(&self.public_key().sec(true))
+    }
+
+    /// The first four bytes of the identifier, which children record as
+    /// their parent fingerprint
+    pub fn fingerprint(&self) -> [u8; 4] {
+        self.identifier()[..4].try_into().unwrap()
+    }
+
+    /// Returns the 78-byte serialization
+    pub fn serialize(&self) -> Vec<u8> {
+        self.serialize_with_version(if self.testnet { TPRV } else { XPRV })
+    }
+
+    /// Returns the 78-byte serialization with other version bytes, such as
+    /// the SLIP-132 ones for yprv and zprv
+    pub fn serialize_with_version(&self, version: [u8; 4]) -> Vec<u8> {
+        let mut key_data = [0u8; 33];
+        key_data[1..].copy_from_slice(&self.private_key.to_bytes());
+        let result = serialize(version, self.depth, self.parent_fingerprint, self.child_number, &self.chain_code, &key_data);
+        key_data.zeroize();
+        result
+    }
+
+    /// Returns the Base58Check xprv (or tprv on testnet) string
+    pub fn to_base58(&self) -> String {
+        self.to_base58_with_version(if self.testnet { TPRV } else { XPRV })
+    }
+
+    /// Returns the Base58Check string with other version bytes
+    pub fn to_base58_with_version(&self, version: [u8; 4]) -> String {
+        let mut serialized = self.serialize_with_version(version);
+        let result = encode_base58_checksum(&serialized);
+        serialized.zeroize();
+        result
+    }
+
+    /// Parses an xprv or tprv string
+    pub fn from_base58(s: &str) -> Result<Self, Bip32Error> {
+        let mut raw = RawKey::parse(s)?;
+        let testnet = match raw.version {
+            XPRV => false,
+            TPRV => true,
+            version => return Err(Bip32Error::InvalidVersion(version)),
+        };
+        let secret = match raw.key_data[0] {
+            0x00 => parse_secret(&raw.key_data[1..]).filter(|secret| !secret.is_zero()),
+            _ => None,
+        };
+        raw.key_data.zeroize();
+        let secret = secret.ok_or(Bip32Error::InvalidPrivateKey)?;
+        Ok(Self {
+            private_key: PrivateKey::new(secret.to_u256()),
+            chain_code: raw.chain_code,
+            depth: raw.depth,
+            parent_fingerprint: raw.parent_fingerprint,
+            child_number: raw.child_number,
+            testnet,
+        })
+    }
+}
+
+impl Drop for ExtendedPrivateKey {
+    fn drop(&mut self) {
+        self.chain_code.zeroize();
+    }
+}
+
+// Only the public parts are shown, so keys can't leak into logs
+impl fmt::Debug for ExtendedPrivateKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ExtendedPrivateKey")
+            .field("private_key", &self.private_key)
+            .field("chain_code", &"<redacted>")
+            .field("depth", &self.depth)
+            .field("parent_fingerprint", &hex::encode(self.parent_fingerprint))
+            .field("child_number", &self.child_number)
+            .field("testnet", &self.testnet)
+            .finish()
+    }
+}
+
+impl ExtendedPublicKey {
+    /// Derives the public key of the non-hardened child at this index
+    pub fn derive_child(&self, index: u32) -> Result<Self, Bip32Error> {
+        if index >= HARDENED {
+            return Err(Bip32Error::HardenedFromPublic(index));
+        }
+        let depth = self.depth.checked_add(1).ok_or(Bip32Error::MaxDepthExceeded)?;
+        let mut data = self.public_key.clone().sec(true);
+        data.extend_from_slice(&index.to_be_bytes());
+        let i = hmac_sha512(&self.chain_code, &data);
+
+        // K_i = IL * G + K, which must not be the point at infinity
+        let tweak = parse_secret(&i[..32]).ok_or(Bip32Error::InvalidChildKey(index))?;
+        let child = GeneratorTable::multiply(tweak.to_u256()).add(&JacobianPoint::from_point(&self.public_key));
+        if child.is_infinity() {
+            return Err(Bip32Error::InvalidChildKey(index));
+        }
+        Ok(Self {
+            public_key: child.to_point(),
+            chain_code: i[32..].try_into().unwrap(),
+            depth,
+            parent_fingerprint: self.fingerprint(),
+            child_number: index,
+            testnet: self.testnet,
+        })
+    }
+
+    /// Derives the key at the end of a path of non-hardened child numbers
+    pub fn derive_path(&self, path: &DerivationPath) -> Result<Self, Bip32Error> {
+        path.indexes().iter().try_fold(self.clone(), |key, index| key.derive_child(*index))
+    }
+
+    pub fn public_key(&self) -> &Point {
+        &self.public_key
+    }
+
+    pub fn chain_code(&self) -> [u8; 32] {
+        self.chain_code
+    }
+
+    pub fn depth(&self) -> u8 {
+        self.depth
+    }
+
+    pub fn parent_fingerprint(&self) -> [u8; 4] {
+        self.parent_fingerprint
+    }
+
+    pub fn child_number(&self) -> u32 {
+        self.child_number
+    }
+
+    pub fn testnet(&self) -> bool {
+        self.testnet
+    }
+
+    /// The hash160 of the compressed public key
+    pub fn identifier(&self) -> Vec<u8> {
+        hash160(&self.public_key.clone().sec(true))
+    }
+
+    /// The first four bytes of the identifier, which children record as
+    /// their parent fingerprint
+    pub fn fingerprint(&self) -> [u8; 4] {
+        self.identifier()[..4].try_into().unwrap()
+    }
+
+    /// Returns the 78-byte serialization
+    pub fn serialize(&self) -> Vec<u8> {
+        self.serialize_with_version(if self.testnet { TPUB } else { XPUB })
+    }
+
+    /// Returns the 78-byte serialization with other version bytes, such as
+    /// the SLIP-132 ones for ypub and zpub
+    pub fn serialize_with_version(&self, version: [u8; 4]) -> Vec<u8> {
+        let key_data: [u8; 33] = self.public_key.clone().sec(true).try_into().unwrap();
+        serialize(version, self.depth, self.parent_fingerprint, self.child_number, &self.chain_code, &key_data)
+    }
+
+    /// Returns the Base58Check xpub (or tpub on testnet) string
+    pub fn to_base58(&self) -> String {
+        encode_base58_checksum(&self.serialize())
+    }
+
+    /// Returns the Base58Check string with other version bytes
+    pub fn to_base58_with_version(&self, version: [u8; 4]) -> String {
+        encode_base58_checksum(&self.serialize_with_version(version))
+    }
+
+    /// Parses an xpub or tpub string
+    pub fn from_base58(s: &str) -> Result<Self, Bip32Error> {
+        let raw = RawKey::parse(s)?;
+        let testnet = match raw.version {
+            XPUB => false,
+            TPUB => true,
+            version => return Err(Bip32Error::InvalidVersion(version)),
+        };
+        if raw.key_data[0] != 0x02 && raw.key_data[0] != 0x03 {
+            return Err(Bip32Error::InvalidPublicKey);
+        }
+        let public_key = Point::from_sec(&raw.key_data).map_err(|_| Bip32Error::InvalidPublicKey)?;
+        Ok(Self {
+            public_key,
+            chain_code: raw.chain_code,
+            depth: raw.depth,
+            parent_fingerprint: raw.parent_fingerprint,
+            child_number: raw.child_number,
+            testnet,
+        })
+    }
+}
+
+impl PartialEq for ExtendedPublicKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.serialize() == other.serialize()
+    }
+}
+
+impl fmt::Display for ExtendedPublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_base58())
+    }
+}
+
+// Parses IL as a scalar, which BIP32 requires to be below n
+fn parse_secret(bytes: &[u8]) -> Option<Scalar> {
+    Scalar::from_bytes(bytes.try_into().ok()?)
+}
+
+fn serialize(version: [u8; 4], depth: u8, parent_fingerprint: [u8; 4], child_number: u32, chain_code: &[u8; 32], key_data: &[u8; 33]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(78);
+    result.extend_from_slice(&version);
+    result.push(depth);
+    result.extend_from_slice(&parent_fingerprint);
+    result.extend_from_slice(&child_number.to_be_bytes());
+    result.extend_from_slice(chain_code);
+    result.extend_from_slice(key_data);
+    result
+}
+
+// The fields of a serialized key, before the key data is interpreted
+struct RawKey {
+    version: [u8; 4],
+    depth: u8,
+    parent_fingerprint: [u8; 4],
+    child_number: u32,
+    chain_code: [u8; 32],
+    key_data: [u8; 33],
+}
+
+impl RawKey {
+    fn parse(s: &str) -> Result<Self, Bip32Error> {
+        let mut payload = decode_base58_checksum(s).map_err(Bip32Error::Base58)?;
+        if payload.len() != 78 {
+            let length = payload.len();
+            payload.zeroize();
+            return Err(Bip32Error::InvalidLength(length));
+        }
+        let raw = Self {
+            version: payload[0..4].try_into().unwrap(),
+            depth: payload[4],
+            parent_fingerprint: payload[5..9].try_into().unwrap(),
+            child_number: u32::from_be_bytes(payload[9..13].try_into().unwrap()),
+            chain_code: payload[13..45].try_into().unwrap(),
+            key_data: payload[45..78].try_into().unwrap(),
+        };
+        payload.zeroize();
+        if raw.depth == 0 && raw.parent_fingerprint != [0; 4] {
+            return Err(Bip32Error::InvalidParentFingerprint);
+        }
+        if raw.depth == 0 && raw.child_number != 0 {
+            return Err(Bip32Error::InvalidChildNumber);
+        }
+        Ok(raw)
+    }
+}
+
+impl Drop for RawKey {
+    fn drop(&mut self) {
+        self.key_data.zeroize();
+        self.chain_code.zeroize();
+    }
+}
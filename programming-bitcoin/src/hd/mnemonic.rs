@@ -0,0 +1,170 @@
+use std::fmt;
+use hmac::{Hmac, Mac};
+use rand::rngs::OsRng;
+use rand::{CryptoRng, RngCore};
+use sha2::{Digest, Sha256, Sha512};
+use zeroize::Zeroize;
+use crate::hd::error::{Bip32Error, Bip39Error};
+use crate::hd::extended_key::ExtendedPrivateKey;
+use crate::hd::wordlist::ENGLISH;
+
+// BIP39 seeds are PBKDF2-HMAC-SHA512 of the phrase with this many rounds
+const PBKDF2_ROUNDS: u32 = 2048;
+
+/// A BIP39 mnemonic phrase in English. It holds the entropy the words encode;
+/// every eleven bits of entropy plus checksum pick one word.
+pub struct Mnemonic {
+    entropy: Vec<u8>,
+}
+
+impl Mnemonic {
+    /// Encodes 16 to 32 bytes of entropy (in steps of 4) as 12 to 24 words
+    pub fn from_entropy(entropy: &[u8]) -> Result<Self, Bip39Error> {
+        if !(16..=32).contains(&entropy.len()) || !entropy.len().is_multiple_of(4) {
+            return Err(Bip39Error::InvalidEntropyLength(entropy.len()));
+        }
+        Ok(Self { entropy: entropy.to_vec() })
+    }
+
+    /// Generates a new phrase of 12, 15, 18, 21 or 24 words from the
+    /// operating system's CSPRNG
+    pub fn generate(word_count: usize) -> Result<Self, Bip39Error> {
+        Self::generate_with_rng(&mut OsRng, word_count)
+    }
+
+    /// Generates a new phrase from the given CSPRNG
+    pub fn generate_with_rng<R: RngCore + CryptoRng>(rng: &mut R, word_count: usize) -> Result<Self, Bip39Error> {
+        if !(12..=24).contains(&word_count) || !word_count.is_multiple_of(3) {
+            return Err(Bip39Error::InvalidWordCount(word_count));
+        }
+        // Three words carry 32 bits of entropy and 1 bit of checksum
+        let mut entropy = vec![0u8; word_count / 3 * 4];
+        rng.fill_bytes(&mut entropy);
+        let mnemonic = Self::from_entropy(&entropy);
+        entropy.zeroize();
+        mnemonic
+    }
+
+    /// Parses a phrase, checking that every word is in the wordlist and that
+    /// the checksum matches. Words can be separated by any whitespace.
+    pub fn parse(phrase: &str) -> Result<Self, Bip39Error> {
+        let words: Vec<&str> = phrase.split_whitespace().collect();
+        if !(12..=24).contains(&words.len()) || !words.len().is_multiple_of(3) {
+            return Err(Bip39Error::InvalidWordCount(words.len()));
+        }
+
+        // Pack the 11-bit word indexes into bytes
+        let mut bits = vec![0u8; (words.len() * 11).div_ceil(8)];
+        for (position, word) in words.iter().enumerate() {
+            let index = ENGLISH.binary_search(word).map_err(|_| Bip39Error::UnknownWord(position))?;
+            for bit in 0..11 {
+                if index & (1 << (10 - bit)) != 0 {
+                    let offset = position * 11 + bit;
+                    bits[offset / 8] |= 0x80 >> (offset % 8);
+                }
+            }
+        }
+
+        let entropy_length = words.len() / 3 * 4;
+        let mnemonic = Self { entropy: bits[..entropy_length].to_vec() };
+        let checksum_matches = bits[entropy_length] == mnemonic.checksum();
+        bits.zeroize();
+        if checksum_matches {
+            Ok(mnemonic)
+        } else {
+            Err(Bip39Error::InvalidChecksum)
+        }
+    }
+
+    pub fn entropy(&self) -> &[u8] {
+        &self.entropy
+    }
+
+    pub fn word_count(&self) -> usize {
+        self.entropy.len() / 4 * 3
+    }
+
+    /// Returns the words of the phrase
+    pub fn words(&self) -> Vec<&'static str> {
+        let checksum = self.checksum();
+        let bit = |offset: usize| {
+            let byte = self.entropy.get(offset / 8).copied().unwrap_or(checksum);
+            (byte >> (7 - offset % 8)) & 1
+        };
+        (0..self.word_count())
+            .map(|position| {
+                let index = (0..11).fold(0usize, |index, i| index << 1 | bit(position * 11 + i) as usize);
+                ENGLISH[index]
+            })
+            .collect()
+    }
+
+    /// Returns the words separated by single spaces
+    pub fn phrase(&self) -> String {
+        self.words().join(" ")
+    }
+
+    /// Derives the 64-byte seed. The passphrase is used as given: BIP39 asks
+    /// for it to be NFKD normalised, which only matters outside ASCII.
+    pub fn to_seed(&self, passphrase: &str) -> [u8; 64] {
+        let mut phrase = self.phrase();
+        let mut salt = format!("mnemonic{}", passphrase);
+        let seed = pbkdf2_hmac_sha512(phrase.as_bytes(), salt.as_bytes(), PBKDF2_ROUNDS);
+        phrase.zeroize();
+        salt.zeroize();
+        seed
+    }
+
+    /// Derives the BIP32 master key of the seed
+    pub fn to_extended_private_key(&self, passphrase: &str, testnet: bool) -> Result<ExtendedPrivateKey, Bip32Error> {
+        let mut seed = self.to_seed(passphrase);
+        let master = ExtendedPrivateKey::from_seed(&seed, testnet);
+        seed.zeroize();
+        master
+    }
+
+    // The first ENT / 32 bits of sha256(entropy), left aligned in a byte
+    fn checksum(&self) -> u8 {
+        let bits = self.entropy.len() / 4;
+        let hash = Sha256::digest(&self.entropy);
+        hash[0] >> (8 - bits) << (8 - bits)
+    }
+}
+
+impl Drop for Mnemonic {
+    fn drop(&mut self) {
+        self.entropy.zeroize();
+    }
+}
+
+// The words are secret, so only the length is shown
+impl fmt::Debug for Mnemonic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Mnemonic")
+            .field("word_count", &self.word_count())
+            .field("words", &"<redacted>")
+            .finish()
+    }
+}
+
+// PBKDF2 with HMAC-SHA512, for a single 64-byte output block
+fn pbkdf2_hmac_sha512(password: &[u8], salt: &[u8], rounds: u32) -> [u8; 64] {
+    let prf = Hmac::<Sha512>::new_from_slice(password).expect("HMAC accepts keys of any length");
+
+    // U_1 = PRF(password, salt || INT(1)), then U_i = PRF(password, U_i-1)
+    let mut mac = prf.clone();
+    mac.update(salt);
+    mac.update(&1u32.to_be_bytes());
+    let mut u: [u8; 64] = mac.finalize().into_bytes().into();
+    let mut result = u;
+    for _ in 1..rounds {
+        let mut mac = prf.clone();
+        mac.update(&u);
+        u = mac.finalize().into_bytes().into();
+        for (r, u) in result.iter_mut().zip(u.iter()) {
+            *r ^= u;
+        }
+    }
+    u.zeroize();
+    result
+}
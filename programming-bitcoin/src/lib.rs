@@ -3,6 +3,12 @@ pub mod transactions;
 pub mod ecc;
 pub mod script;
 pub mod address;
+pub mod taproot;
+pub mod musig;
+pub mod sign_message;
 pub mod blocks;
 pub mod network;
 pub mod spv;
+pub mod hd;
+pub mod descriptor;
+pub mod miniscript;
@@ -0,0 +1,8 @@
+pub mod decode;
+pub mod error;
+// Named like script::script and address::address: the module holding the
+// type its parent is named after
+#[allow(clippy::module_inception)]
+pub mod miniscript;
+pub mod satisfy;
+pub mod types;
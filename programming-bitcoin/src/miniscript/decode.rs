@@ -0,0 +1,298 @@
+use crate::miniscript::error::MiniscriptError;
+use crate::miniscript::miniscript::*;
+use crate::script::op::{decode_num, encode_num};
+use crate::script::script::{Command, Script};
+
+/// Decodes a script into the Miniscript it encodes, of any base type.
+/// Scripts that are not exactly what some Miniscript compiles to, such as
+/// ones with OP_EQUAL OP_VERIFY for OP_EQUALVERIFY or non-minimal numbers,
+/// are rejected.
+pub fn decode(script: &Script, context: Context) -> Result<Miniscript, MiniscriptError> {
+    // VERIFY forms are split into the opcode and OP_VERIFY, so that v:
+    // decodes the same way whatever it wraps
+    let mut tokens = vec![];
+    for (index, command) in script.commands.iter().enumerate() {
+        match VERIFY_FORMS.iter().find(|(_, verify)| *command == Command::Op(*verify)) {
+            Some((op, _)) => {
+                tokens.push(Token { command: Command::Op(*op), index });
+                tokens.push(Token { command: Command::Op(OP_VERIFY), index });
+            }
+            None => tokens.push(Token { command: command.clone(), index }),
+        }
+    }
+
+    let end = tokens.len();
+    let mut decoder = Decoder { tokens, end, context };
+    let miniscript = decoder.expression()?;
+    if decoder.end != 0 {
+        return Err(decoder.error());
+    }
+    if miniscript.to_script().commands != script.commands {
+        return Err(MiniscriptError::InvalidScript(0));
+    }
+    Ok(miniscript)
+}
+
+struct Token {
+    command: Command,
+    // The index of the command it came from
+    index: usize,
+}
+
+// Decodes from the end of the script. Every fragment ends in something that
+// tells it apart, where the start of its subexpressions can only be found
+// by decoding them.
+struct Decoder {
+    tokens: Vec<Token>,
+    // The tokens before this one are still to be decoded
+    end: usize,
+    context: Context,
+}
+
+impl Decoder {
+    fn error(&self) -> MiniscriptError {
+        let index = self.end.checked_sub(1).map_or(0, |last| self.tokens[last].index);
+        MiniscriptError::InvalidScript(index)
+    }
+
+    // The token this far back from the end
+    fn peek(&self, back: usize) -> Option<&Command> {
+        let position = self.end.checked_sub(back + 1)?;
+        Some(&self.tokens[position].command)
+    }
+
+    fn is_op(&self, back: usize, op: u8) -> bool {
+        self.peek(back) == Some(&Command::Op(op))
+    }
+
+    fn pop_op(&mut self, op: u8) -> Result<(), MiniscriptError> {
+        if !self.is_op(0, op) {
+            return Err(self.error());
+        }
+        self.end -= 1;
+        Ok(())
+    }
+
+    fn pop_number(&mut self) -> Result<usize, MiniscriptError> {
+        let number = self.peek(0).and_then(number).ok_or_else(|| self.error())?;
+        self.end -= 1;
+        Ok(number)
+    }
+
+    fn pop_key(&mut self) -> Result<Vec<u8>, MiniscriptError> {
+        let key = self
+            .peek(0)
+            .and_then(Command::data)
+            .filter(|key| validate_key(key, self.context).is_ok())
+            .ok_or_else(|| self.error())?;
+        let key = key.to_vec();
+        self.end -= 1;
+        Ok(key)
+    }
+
+    fn node(&self, node: Terminal) -> Result<Miniscript, MiniscriptError> {
+        Miniscript::from_node(node, self.context)
+    }
+
+    // A fragment, followed by V fragments before it that it is and_v'd with
+    fn expression(&mut self) -> Result<Miniscript, MiniscriptError> {
+        let y = self.single()?;
+        // These opcodes start a fragment's subexpression, and can't end one
+        let boundary = [OP_IF, OP_NOTIF, OP_ELSE, OP_TOALTSTACK, OP_SWAP];
+        if self.end == 0 || boundary.iter().any(|op| self.is_op(0, *op)) {
+            return Ok(y);
+        }
+        let x = self.expression()?;
+        self.node(Terminal::AndV(Box::new(x), Box::new(y)))
+    }
+
+    // a:X or s:X
+    fn w_expression(&mut self) -> Result<Miniscript, MiniscriptError> {
+        if self.is_op(0, OP_FROMALTSTACK) {
+            self.end -= 1;
+            let x = self.expression()?;
+            self.pop_op(OP_TOALTSTACK)?;
+            self.node(Terminal::Alt(Box::new(x)))
+        } else {
+            let x = self.single()?;
+            self.pop_op(OP_SWAP)?;
+            self.node(Terminal::Swap(Box::new(x)))
+        }
+    }
+
+    // Whether the tokens before the OP_EQUAL at the end are those of a hash
+    // fragment
+    fn is_hash(&self) -> bool {
+        let hash_ops = [OP_SHA256, OP_HASH256, OP_RIPEMD160, OP_HASH160];
+        self.peek(1).is_some_and(|hash| hash.data().is_some())
+            && hash_ops.iter().any(|op| self.is_op(2, *op))
+            && self.is_op(3, OP_VERIFY)
+            && self.is_op(4, OP_EQUAL)
+            && self.peek(5).and_then(number) == Some(32)
+            && self.is_op(6, OP_SIZE)
+    }
+
+    // A single fragment, without and_v
+    fn single(&mut self) -> Result<Miniscript, MiniscriptError> {
+        let last = match self.peek(0).ok_or_else(|| self.error())? {
+            Command::Op(op) => *op,
+            Command::Data(_) => {
+                let key = self.pop_key()?;
+                return self.node(Terminal::PkK(key));
+            }
+        };
+        let box_single = |decoder: &mut Self| decoder.single().map(Box::new);
+
+        let node = match last {
+            OP_0 => {
+                self.end -= 1;
+                Terminal::False
+            }
+            OP_1 => {
+                self.end -= 1;
+                Terminal::True
+            }
+            OP_CHECKSEQUENCEVERIFY | OP_CHECKLOCKTIMEVERIFY => {
+                self.end -= 1;
+                let n = self.pop_number()? as u32;
+                if last == OP_CHECKSEQUENCEVERIFY {
+                    Terminal::Older(n)
+                } else {
+                    Terminal::After(n)
+                }
+            }
+            // OP_DUP OP_HASH160 <hash> OP_EQUAL OP_VERIFY
+            OP_VERIFY if self.is_op(1, OP_EQUAL) && self.is_op(3, OP_HASH160) && self.is_op(4, OP_DUP) => {
+                let hash = self
+                    .peek(2)
+                    .and_then(Command::data)
+                    .and_then(|hash| <[u8; 20]>::try_from(hash).ok())
+                    .ok_or_else(|| self.error())?;
+                self.end -= 5;
+                Terminal::RawPkH(hash)
+            }
+            OP_VERIFY => {
+                self.end -= 1;
+                Terminal::Verify(box_single(self)?)
+            }
+            // OP_SIZE <32> OP_EQUAL OP_VERIFY <hash opcode> <hash> OP_EQUAL
+            OP_EQUAL if self.is_hash() => {
+                let hash = self.peek(1).and_then(Command::data).unwrap().to_vec();
+                let node = match (self.peek(2).unwrap(), hash.len()) {
+                    (&Command::Op(OP_SHA256), 32) => Terminal::Sha256(hash.try_into().unwrap()),
+                    (&Command::Op(OP_HASH256), 32) => Terminal::Hash256(hash.try_into().unwrap()),
+                    (&Command::Op(OP_RIPEMD160), 20) => Terminal::Ripemd160(hash.try_into().unwrap()),
+                    (&Command::Op(OP_HASH160), 20) => Terminal::Hash160(hash.try_into().unwrap()),
+                    _ => return Err(self.error()),
+                };
+                self.end -= 7;
+                node
+            }
+            // [X1] ([Xn] OP_ADD)* <k> OP_EQUAL
+            OP_EQUAL => {
+                self.end -= 1;
+                let k = self.pop_number()?;
+                let mut subs = vec![];
+                while self.is_op(0, OP_ADD) {
+                    self.end -= 1;
+                    subs.push(self.w_expression()?);
+                }
+                subs.push(self.single()?);
+                subs.reverse();
+                Terminal::Thresh(k, subs)
+            }
+            OP_CHECKSIG => {
+                self.end -= 1;
+                Terminal::Check(box_single(self)?)
+            }
+            OP_CHECKMULTISIG => {
+                self.end -= 1;
+                let n = self.pop_number()?;
+                let mut keys = (0..n).map(|_| self.pop_key()).collect::<Result<Vec<_>, _>>()?;
+                keys.reverse();
+                let k = self.pop_number()?;
+                Terminal::Multi(k, keys)
+            }
+            OP_NUMEQUAL => {
+                self.end -= 1;
+                let k = self.pop_number()?;
+                let mut keys = vec![];
+                while self.is_op(0, OP_CHECKSIGADD) {
+                    self.end -= 1;
+                    keys.push(self.pop_key()?);
+                }
+                self.pop_op(OP_CHECKSIG)?;
+                keys.push(self.pop_key()?);
+                keys.reverse();
+                Terminal::MultiA(k, keys)
+            }
+            OP_BOOLAND | OP_BOOLOR => {
+                self.end -= 1;
+                let y = Box::new(self.w_expression()?);
+                let x = box_single(self)?;
+                if last == OP_BOOLAND {
+                    Terminal::AndB(x, y)
+                } else {
+                    Terminal::OrB(x, y)
+                }
+            }
+            OP_0NOTEQUAL => {
+                self.end -= 1;
+                Terminal::ZeroNotEqual(box_single(self)?)
+            }
+            OP_ENDIF => {
+                self.end -= 1;
+                let a = Box::new(self.expression()?);
+                if self.is_op(0, OP_ELSE) {
+                    self.end -= 1;
+                    let b = Box::new(self.expression()?);
+                    if self.is_op(0, OP_IF) {
+                        // OP_IF [X] OP_ELSE [Z] OP_ENDIF
+                        self.end -= 1;
+                        Terminal::OrI(b, a)
+                    } else {
+                        // [X] OP_NOTIF [Z] OP_ELSE [Y] OP_ENDIF
+                        self.pop_op(OP_NOTIF)?;
+                        Terminal::AndOr(box_single(self)?, a, b)
+                    }
+                } else if self.is_op(0, OP_NOTIF) {
+                    self.end -= 1;
+                    if self.is_op(0, OP_IFDUP) {
+                        self.end -= 1;
+                        Terminal::OrD(box_single(self)?, a)
+                    } else {
+                        Terminal::OrC(box_single(self)?, a)
+                    }
+                } else {
+                    self.pop_op(OP_IF)?;
+                    if self.is_op(0, OP_DUP) {
+                        self.end -= 1;
+                        Terminal::DupIf(a)
+                    } else {
+                        self.pop_op(OP_0NOTEQUAL)?;
+                        self.pop_op(OP_SIZE)?;
+                        Terminal::NonZero(a)
+                    }
+                }
+            }
+            _ => return Err(self.error()),
+        };
+        self.node(node)
+    }
+}
+
+// A number pushed as Miniscript pushes them: OP_0 to OP_16, or a minimally
+// encoded positive script number above 16
+fn number(command: &Command) -> Option<usize> {
+    let data = match command {
+        Command::Op(OP_0) => return Some(0),
+        Command::Op(op @ 0x51..=0x60) => return Some((op - 0x50) as usize),
+        Command::Op(_) => return None,
+        Command::Data(data) => data,
+    };
+    if data.len() > 4 {
+        return None;
+    }
+    let n = decode_num(data);
+    (n > 16 && encode_num(n) == *data).then_some(n as usize)
+}
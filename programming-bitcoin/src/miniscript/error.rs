@@ -0,0 +1,62 @@
+use std::fmt;
+
+/// Why a Miniscript could not be parsed, decoded or satisfied
+#[derive(Debug, Clone, PartialEq)]
+pub enum MiniscriptError {
+    // The expression is not well formed. Holds what was expected and the
+    // byte offset where it was expected.
+    Syntax(String, usize),
+    UnknownFragment(String),
+    UnknownWrapper(char),
+    // Keys are 33 byte compressed SEC keys, or 32 byte x-only keys in
+    // tapscript
+    InvalidKey(String),
+    // sha256 and hash256 take 32 byte hashes, ripemd160 and hash160 20 byte
+    // hashes
+    InvalidHash(String),
+    // older and after take values from 1 to 2^31 - 1
+    InvalidTimelock(u32),
+    // A threshold that is not between 1 and the number of keys or
+    // subexpressions
+    InvalidThreshold(usize),
+    // multi takes at most 20 keys and multi_a at most 999
+    TooManyKeys(usize),
+    // multi is only allowed in P2WSH scripts and multi_a only in tapscript
+    InvalidContext(String),
+    // A fragment whose subexpressions don't have the types it requires.
+    // Holds the fragment's name.
+    TypeCheck(String),
+    // A complete Miniscript must have type B
+    NotTopLevel,
+    // The script is not the encoding of a Miniscript. Holds the index of
+    // the command where decoding failed.
+    InvalidScript(usize),
+    // No satisfaction can be built from the signatures, preimages and
+    // timelocks available
+    Unsatisfiable,
+    // Every satisfaction available could be changed by a third party
+    MalleableSatisfaction,
+}
+
+impl fmt::Display for MiniscriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MiniscriptError::Syntax(expected, offset) => write!(f, "expected {} at offset {}", expected, offset),
+            MiniscriptError::UnknownFragment(name) => write!(f, "unknown fragment: {}", name),
+            MiniscriptError::UnknownWrapper(wrapper) => write!(f, "unknown wrapper: {}", wrapper),
+            MiniscriptError::InvalidKey(key) => write!(f, "invalid key: {}", key),
+            MiniscriptError::InvalidHash(hash) => write!(f, "invalid hash: {}", hash),
+            MiniscriptError::InvalidTimelock(value) => write!(f, "invalid timelock: {}", value),
+            MiniscriptError::InvalidThreshold(threshold) => write!(f, "invalid threshold: {}", threshold),
+            MiniscriptError::TooManyKeys(count) => write!(f, "too many keys: {}", count),
+            MiniscriptError::InvalidContext(name) => write!(f, "{} is not allowed in this script context", name),
+            MiniscriptError::TypeCheck(name) => write!(f, "subexpressions of {} have the wrong types", name),
+            MiniscriptError::NotTopLevel => write!(f, "miniscript does not have type B"),
+            MiniscriptError::InvalidScript(index) => write!(f, "not a miniscript: failed at command {}", index),
+            MiniscriptError::Unsatisfiable => write!(f, "miniscript can't be satisfied"),
+            MiniscriptError::MalleableSatisfaction => write!(f, "only malleable satisfactions are available"),
+        }
+    }
+}
+
+impl std::error::Error for MiniscriptError {}
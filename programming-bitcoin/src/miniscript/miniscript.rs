@@ -0,0 +1,758 @@
+use std::fmt;
+use sha2::{Digest, Sha256};
+
+use crate::ecc::point::Point;
+use crate::ecc::xonly_public_key::XOnlyPublicKey;
+use crate::miniscript::decode::decode;
+use crate::miniscript::error::MiniscriptError;
+use crate::miniscript::types::{BaseType, TimelockInfo, Type};
+use crate::script::op::encode_num;
+use crate::script::script::{Command, Script};
+use crate::utils::hash160::hash160;
+
+pub(crate) const OP_0: u8 = 0x00;
+pub(crate) const OP_1: u8 = 0x51;
+pub(crate) const OP_IF: u8 = 0x63;
+pub(crate) const OP_NOTIF: u8 = 0x64;
+pub(crate) const OP_ELSE: u8 = 0x67;
+pub(crate) const OP_ENDIF: u8 = 0x68;
+pub(crate) const OP_VERIFY: u8 = 0x69;
+pub(crate) const OP_TOALTSTACK: u8 = 0x6b;
+pub(crate) const OP_FROMALTSTACK: u8 = 0x6c;
+pub(crate) const OP_IFDUP: u8 = 0x73;
+pub(crate) const OP_DUP: u8 = 0x76;
+pub(crate) const OP_SWAP: u8 = 0x7c;
+pub(crate) const OP_SIZE: u8 = 0x82;
+pub(crate) const OP_EQUAL: u8 = 0x87;
+pub(crate) const OP_EQUALVERIFY: u8 = 0x88;
+pub(crate) const OP_0NOTEQUAL: u8 = 0x92;
+pub(crate) const OP_ADD: u8 = 0x93;
+pub(crate) const OP_BOOLAND: u8 = 0x9a;
+pub(crate) const OP_BOOLOR: u8 = 0x9b;
+pub(crate) const OP_NUMEQUAL: u8 = 0x9c;
+pub(crate) const OP_NUMEQUALVERIFY: u8 = 0x9d;
+pub(crate) const OP_RIPEMD160: u8 = 0xa6;
+pub(crate) const OP_SHA256: u8 = 0xa8;
+pub(crate) const OP_HASH160: u8 = 0xa9;
+pub(crate) const OP_HASH256: u8 = 0xaa;
+pub(crate) const OP_CHECKSIG: u8 = 0xac;
+pub(crate) const OP_CHECKSIGVERIFY: u8 = 0xad;
+pub(crate) const OP_CHECKMULTISIG: u8 = 0xae;
+pub(crate) const OP_CHECKMULTISIGVERIFY: u8 = 0xaf;
+pub(crate) const OP_CHECKLOCKTIMEVERIFY: u8 = 0xb1;
+pub(crate) const OP_CHECKSEQUENCEVERIFY: u8 = 0xb2;
+pub(crate) const OP_CHECKSIGADD: u8 = 0xba;
+
+// Opcodes that have a VERIFY form, which v: uses instead of adding
+// OP_VERIFY
+pub(crate) const VERIFY_FORMS: [(u8, u8); 4] = [
+    (OP_EQUAL, OP_EQUALVERIFY),
+    (OP_CHECKSIG, OP_CHECKSIGVERIFY),
+    (OP_CHECKMULTISIG, OP_CHECKMULTISIGVERIFY),
+    (OP_NUMEQUAL, OP_NUMEQUALVERIFY),
+];
+
+/// The script a Miniscript is written for, which decides the keys and the
+/// multisig fragment it can use
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Context {
+    // P2WSH witness scripts, with 33 byte compressed keys and multi()
+    Segwit,
+    // Tapscript leaves, with 32 byte x-only keys and multi_a()
+    Tap,
+}
+
+/// A Miniscript fragment. Keys are held serialized as they are in the
+/// script, and hashes in the byte order of the script.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Terminal {
+    // 0
+    False,
+    // 1
+    True,
+    // <key>
+    PkK(Vec<u8>),
+    // OP_DUP OP_HASH160 <hash160(key)> OP_EQUALVERIFY
+    PkH(Vec<u8>),
+    // A pk_h decoded from a script, where only the hash of the key is
+    // known. Written expr_raw_pkh(hash).
+    RawPkH([u8; 20]),
+    // <n> OP_CHECKSEQUENCEVERIFY
+    Older(u32),
+    // <n> OP_CHECKLOCKTIMEVERIFY
+    After(u32),
+    // OP_SIZE <32> OP_EQUALVERIFY OP_SHA256 <hash> OP_EQUAL, and likewise
+    // for the other hashes
+    Sha256([u8; 32]),
+    Hash256([u8; 32]),
+    Ripemd160([u8; 20]),
+    Hash160([u8; 20]),
+    // a:X = OP_TOALTSTACK [X] OP_FROMALTSTACK
+    Alt(Box<Miniscript>),
+    // s:X = OP_SWAP [X]
+    Swap(Box<Miniscript>),
+    // c:X = [X] OP_CHECKSIG
+    Check(Box<Miniscript>),
+    // d:X = OP_DUP OP_IF [X] OP_ENDIF
+    DupIf(Box<Miniscript>),
+    // v:X = [X] OP_VERIFY, or the VERIFY form of X's last opcode
+    Verify(Box<Miniscript>),
+    // j:X = OP_SIZE OP_0NOTEQUAL OP_IF [X] OP_ENDIF
+    NonZero(Box<Miniscript>),
+    // n:X = [X] OP_0NOTEQUAL
+    ZeroNotEqual(Box<Miniscript>),
+    // [X] [Y]
+    AndV(Box<Miniscript>, Box<Miniscript>),
+    // [X] [Y] OP_BOOLAND
+    AndB(Box<Miniscript>, Box<Miniscript>),
+    // andor(X,Y,Z) = [X] OP_NOTIF [Z] OP_ELSE [Y] OP_ENDIF, which needs Y
+    // if X is satisfied and Z if it isn't
+    AndOr(Box<Miniscript>, Box<Miniscript>, Box<Miniscript>),
+    // [X] [Z] OP_BOOLOR
+    OrB(Box<Miniscript>, Box<Miniscript>),
+    // [X] OP_NOTIF [Z] OP_ENDIF
+    OrC(Box<Miniscript>, Box<Miniscript>),
+    // [X] OP_IFDUP OP_NOTIF [Z] OP_ENDIF
+    OrD(Box<Miniscript>, Box<Miniscript>),
+    // OP_IF [X] OP_ELSE [Z] OP_ENDIF
+    OrI(Box<Miniscript>, Box<Miniscript>),
+    // [X1] ([Xn] OP_ADD)* <k> OP_EQUAL
+    Thresh(usize, Vec<Miniscript>),
+    // <k> <key>* <n> OP_CHECKMULTISIG
+    Multi(usize, Vec<Vec<u8>>),
+    // <key1> OP_CHECKSIG (<keyn> OP_CHECKSIGADD)* <k> OP_NUMEQUAL
+    MultiA(usize, Vec<Vec<u8>>),
+}
+
+impl Terminal {
+    /// The name of the fragment, as written in expressions
+    pub fn name(&self) -> &'static str {
+        match self {
+            Terminal::False => "0",
+            Terminal::True => "1",
+            Terminal::PkK(_) => "pk_k",
+            Terminal::PkH(_) => "pk_h",
+            Terminal::RawPkH(_) => "expr_raw_pkh",
+            Terminal::Older(_) => "older",
+            Terminal::After(_) => "after",
+            Terminal::Sha256(_) => "sha256",
+            Terminal::Hash256(_) => "hash256",
+            Terminal::Ripemd160(_) => "ripemd160",
+            Terminal::Hash160(_) => "hash160",
+            Terminal::Alt(_) => "a:",
+            Terminal::Swap(_) => "s:",
+            Terminal::Check(_) => "c:",
+            Terminal::DupIf(_) => "d:",
+            Terminal::Verify(_) => "v:",
+            Terminal::NonZero(_) => "j:",
+            Terminal::ZeroNotEqual(_) => "n:",
+            Terminal::AndV(..) => "and_v",
+            Terminal::AndB(..) => "and_b",
+            Terminal::AndOr(..) => "andor",
+            Terminal::OrB(..) => "or_b",
+            Terminal::OrC(..) => "or_c",
+            Terminal::OrD(..) => "or_d",
+            Terminal::OrI(..) => "or_i",
+            Terminal::Thresh(..) => "thresh",
+            Terminal::Multi(..) => "multi",
+            Terminal::MultiA(..) => "multi_a",
+        }
+    }
+
+    /// The subexpressions, in the order they are written
+    pub fn subs(&self) -> Vec<&Miniscript> {
+        match self {
+            Terminal::Alt(x)
+            | Terminal::Swap(x)
+            | Terminal::Check(x)
+            | Terminal::DupIf(x)
+            | Terminal::Verify(x)
+            | Terminal::NonZero(x)
+            | Terminal::ZeroNotEqual(x) => vec![x],
+            Terminal::AndV(x, y)
+            | Terminal::AndB(x, y)
+            | Terminal::OrB(x, y)
+            | Terminal::OrC(x, y)
+            | Terminal::OrD(x, y)
+            | Terminal::OrI(x, y) => vec![x, y],
+            Terminal::AndOr(x, y, z) => vec![x, y, z],
+            Terminal::Thresh(_, subs) => subs.iter().collect(),
+            _ => vec![],
+        }
+    }
+}
+
+/// A type checked Miniscript expression, such as
+/// or_d(pk(A),and_v(v:pk(B),older(144))): spendable by A, or by B 144
+/// blocks after the output confirmed
+#[derive(Debug, Clone, PartialEq)]
+pub struct Miniscript {
+    node: Terminal,
+    ty: Type,
+    timelocks: TimelockInfo,
+    context: Context,
+}
+
+impl Miniscript {
+    /// Type checks a fragment whose subexpressions are already type
+    /// checked. Any base type is allowed, so this also builds
+    /// subexpressions; parse and from_script require type B.
+    pub fn from_node(node: Terminal, context: Context) -> Result<Self, MiniscriptError> {
+        if node.subs().iter().any(|sub| sub.context != context) {
+            return Err(MiniscriptError::InvalidContext(node.name().to_string()));
+        }
+        match &node {
+            Terminal::PkK(key) | Terminal::PkH(key) => validate_key(key, context)?,
+            Terminal::Older(n) | Terminal::After(n) if *n == 0 || *n >= 1 << 31 => {
+                return Err(MiniscriptError::InvalidTimelock(*n));
+            }
+            Terminal::Thresh(k, subs) => validate_threshold(*k, subs.len())?,
+            Terminal::Multi(k, keys) | Terminal::MultiA(k, keys) => {
+                let multi = matches!(node, Terminal::Multi(..));
+                if multi != (context == Context::Segwit) {
+                    return Err(MiniscriptError::InvalidContext(node.name().to_string()));
+                }
+                if keys.len() > if multi { 20 } else { 999 } {
+                    return Err(MiniscriptError::TooManyKeys(keys.len()));
+                }
+                validate_threshold(*k, keys.len())?;
+                keys.iter().try_for_each(|key| validate_key(key, context))?;
+            }
+            _ => {}
+        }
+        let ty = Type::of(&node, context)?;
+        let timelocks = TimelockInfo::of(&node);
+        Ok(Self { node, ty, timelocks, context })
+    }
+
+    /// Parses a Miniscript expression. Keys are hex: compressed SEC keys
+    /// for Segwit, x-only keys for Tap.
+    pub fn parse(s: &str, context: Context) -> Result<Self, MiniscriptError> {
+        let mut parser = Parser { input: s, position: 0, context };
+        let miniscript = parser.expression()?;
+        if parser.position != s.len() {
+            return Err(MiniscriptError::Syntax("end of expression".to_string(), parser.position));
+        }
+        miniscript.top_level()
+    }
+
+    /// Decodes a script back into the Miniscript it encodes. Hashed keys
+    /// can't be recovered, so pk_h decodes to expr_raw_pkh.
+    pub fn from_script(script: &Script, context: Context) -> Result<Self, MiniscriptError> {
+        decode(script, context)?.top_level()
+    }
+
+    pub(crate) fn top_level(self) -> Result<Self, MiniscriptError> {
+        if self.ty.base != BaseType::B {
+            return Err(MiniscriptError::NotTopLevel);
+        }
+        Ok(self)
+    }
+
+    pub fn node(&self) -> &Terminal {
+        &self.node
+    }
+
+    pub fn ty(&self) -> &Type {
+        &self.ty
+    }
+
+    pub fn timelocks(&self) -> &TimelockInfo {
+        &self.timelocks
+    }
+
+    pub fn context(&self) -> Context {
+        self.context
+    }
+
+    /// Every satisfaction needs a signature, so nobody without a key can
+    /// spend the output
+    pub fn requires_signature(&self) -> bool {
+        self.ty.safe
+    }
+
+    /// A non-malleable satisfaction always exists
+    pub fn is_non_malleable(&self) -> bool {
+        self.ty.non_malleable
+    }
+
+    /// Some branch needs both a height and a time lock of the same kind,
+    /// and can never be spent
+    pub fn has_mixed_timelocks(&self) -> bool {
+        self.timelocks.contains_combination
+    }
+
+    /// The keys of the expression, in the order they appear in the script.
+    /// Decoded pk_h fragments have no key.
+    pub fn keys(&self) -> Vec<&Vec<u8>> {
+        match &self.node {
+            Terminal::PkK(key) | Terminal::PkH(key) => vec![key],
+            Terminal::Multi(_, keys) | Terminal::MultiA(_, keys) => keys.iter().collect(),
+            node => node.subs().into_iter().flat_map(|sub| sub.keys()).collect(),
+        }
+    }
+
+    pub fn has_repeated_keys(&self) -> bool {
+        let mut keys = self.keys();
+        let count = keys.len();
+        keys.sort();
+        keys.dedup();
+        keys.len() != count
+    }
+
+    /// Whether the script is safe to pay to: every spend needs a signature,
+    /// satisfactions can't be malleated, no branch mixes heights and times,
+    /// and no key is used twice (which would let one signature satisfy
+    /// two fragments)
+    pub fn is_sane(&self) -> bool {
+        self.ty.base == BaseType::B
+            && self.requires_signature()
+            && self.is_non_malleable()
+            && !self.has_mixed_timelocks()
+            && !self.has_repeated_keys()
+    }
+
+    /// Compiles the expression to a Script
+    pub fn to_script(&self) -> Script {
+        let mut commands = vec![];
+        self.encode_into(&mut commands);
+        Script::new(commands)
+    }
+
+    /// The serialized script, without a length prefix
+    pub fn encode(&self) -> Vec<u8> {
+        self.to_script().raw_serialize()
+    }
+
+    pub fn script_size(&self) -> usize {
+        self.encode().len()
+    }
+
+    /// Returns the P2WSH script_pubkey that pays to this script
+    pub fn script_pubkey(&self) -> Result<Script, MiniscriptError> {
+        if self.context != Context::Segwit {
+            return Err(MiniscriptError::InvalidContext("wsh".to_string()));
+        }
+        Ok(Script::p2wsh_script(Sha256::digest(self.encode()).to_vec()))
+    }
+
+    fn encode_into(&self, commands: &mut Vec<Command>) {
+        let op = |commands: &mut Vec<Command>, op: u8| commands.push(Command::Op(op));
+        match &self.node {
+            Terminal::False => op(commands, OP_0),
+            Terminal::True => op(commands, OP_1),
+            Terminal::PkK(key) => commands.push(Command::Data(key.clone())),
+            Terminal::PkH(key) => encode_pkh(commands, hash160(key)),
+            Terminal::RawPkH(hash) => encode_pkh(commands, hash.to_vec()),
+            Terminal::Older(n) => {
+                commands.push(push_number(*n as usize));
+                op(commands, OP_CHECKSEQUENCEVERIFY);
+            }
+            Terminal::After(n) => {
+                commands.push(push_number(*n as usize));
+                op(commands, OP_CHECKLOCKTIMEVERIFY);
+            }
+            Terminal::Sha256(hash) | Terminal::Hash256(hash) => encode_hash(commands, self.hash_opcode(), hash),
+            Terminal::Ripemd160(hash) | Terminal::Hash160(hash) => encode_hash(commands, self.hash_opcode(), hash),
+            Terminal::Alt(x) => {
+                op(commands, OP_TOALTSTACK);
+                x.encode_into(commands);
+                op(commands, OP_FROMALTSTACK);
+            }
+            Terminal::Swap(x) => {
+                op(commands, OP_SWAP);
+                x.encode_into(commands);
+            }
+            Terminal::Check(x) => {
+                x.encode_into(commands);
+                op(commands, OP_CHECKSIG);
+            }
+            Terminal::DupIf(x) => {
+                op(commands, OP_DUP);
+                op(commands, OP_IF);
+                x.encode_into(commands);
+                op(commands, OP_ENDIF);
+            }
+            Terminal::Verify(x) => {
+                x.encode_into(commands);
+                let last = commands.last_mut().unwrap();
+                match VERIFY_FORMS.iter().find(|(op, _)| *last == Command::Op(*op)) {
+                    Some((_, verify)) => *last = Command::Op(*verify),
+                    None => op(commands, OP_VERIFY),
+                }
+            }
+            Terminal::NonZero(x) => {
+                op(commands, OP_SIZE);
+                op(commands, OP_0NOTEQUAL);
+                op(commands, OP_IF);
+                x.encode_into(commands);
+                op(commands, OP_ENDIF);
+            }
+            Terminal::ZeroNotEqual(x) => {
+                x.encode_into(commands);
+                op(commands, OP_0NOTEQUAL);
+            }
+            Terminal::AndV(x, y) => {
+                x.encode_into(commands);
+                y.encode_into(commands);
+            }
+            Terminal::AndB(x, y) => {
+                x.encode_into(commands);
+                y.encode_into(commands);
+                op(commands, OP_BOOLAND);
+            }
+            Terminal::AndOr(x, y, z) => {
+                x.encode_into(commands);
+                op(commands, OP_NOTIF);
+                z.encode_into(commands);
+                op(commands, OP_ELSE);
+                y.encode_into(commands);
+                op(commands, OP_ENDIF);
+            }
+            Terminal::OrB(x, z) => {
+                x.encode_into(commands);
+                z.encode_into(commands);
+                op(commands, OP_BOOLOR);
+            }
+            Terminal::OrC(x, z) => {
+                x.encode_into(commands);
+                op(commands, OP_NOTIF);
+                z.encode_into(commands);
+                op(commands, OP_ENDIF);
+            }
+            Terminal::OrD(x, z) => {
+                x.encode_into(commands);
+                op(commands, OP_IFDUP);
+                op(commands, OP_NOTIF);
+                z.encode_into(commands);
+                op(commands, OP_ENDIF);
+            }
+            Terminal::OrI(x, z) => {
+                op(commands, OP_IF);
+                x.encode_into(commands);
+                op(commands, OP_ELSE);
+                z.encode_into(commands);
+                op(commands, OP_ENDIF);
+            }
+            Terminal::Thresh(k, subs) => {
+                for (i, sub) in subs.iter().enumerate() {
+                    sub.encode_into(commands);
+                    if i > 0 {
+                        op(commands, OP_ADD);
+                    }
+                }
+                commands.push(push_number(*k));
+                op(commands, OP_EQUAL);
+            }
+            Terminal::Multi(k, keys) => {
+                commands.push(push_number(*k));
+                commands.extend(keys.iter().cloned().map(Command::Data));
+                commands.push(push_number(keys.len()));
+                op(commands, OP_CHECKMULTISIG);
+            }
+            Terminal::MultiA(k, keys) => {
+                for (i, key) in keys.iter().enumerate() {
+                    commands.push(Command::Data(key.clone()));
+                    op(commands, if i == 0 { OP_CHECKSIG } else { OP_CHECKSIGADD });
+                }
+                commands.push(push_number(*k));
+                op(commands, OP_NUMEQUAL);
+            }
+        }
+    }
+
+    fn hash_opcode(&self) -> u8 {
+        match self.node {
+            Terminal::Sha256(_) => OP_SHA256,
+            Terminal::Hash256(_) => OP_HASH256,
+            Terminal::Ripemd160(_) => OP_RIPEMD160,
+            _ => OP_HASH160,
+        }
+    }
+
+    // The wrapper letter this node is written with, and what it wraps.
+    // and_v(X,1), or_i(0,X) and or_i(X,0) are written t:X, l:X and u:X, and
+    // c:pk_k(K) and c:pk_h(K) are the fragments pk(K) and pkh(K).
+    fn wrapper(&self) -> Option<(char, &Miniscript)> {
+        match &self.node {
+            Terminal::Alt(x) => Some(('a', x)),
+            Terminal::Swap(x) => Some(('s', x)),
+            Terminal::Check(x) if !matches!(x.node, Terminal::PkK(_) | Terminal::PkH(_)) => Some(('c', x)),
+            Terminal::DupIf(x) => Some(('d', x)),
+            Terminal::Verify(x) => Some(('v', x)),
+            Terminal::NonZero(x) => Some(('j', x)),
+            Terminal::ZeroNotEqual(x) => Some(('n', x)),
+            Terminal::AndV(x, y) if y.node == Terminal::True => Some(('t', x)),
+            Terminal::OrI(x, z) if x.node == Terminal::False => Some(('l', z)),
+            Terminal::OrI(x, z) if z.node == Terminal::False => Some(('u', x)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Miniscript {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut node = self;
+        let mut wrappers = String::new();
+        while let Some((wrapper, inner)) = node.wrapper() {
+            wrappers.push(wrapper);
+            node = inner;
+        }
+        if !wrappers.is_empty() {
+            write!(f, "{}:", wrappers)?;
+        }
+
+        let name = node.node.name();
+        let keys = |keys: &[Vec<u8>]| keys.iter().map(hex::encode).collect::<Vec<_>>().join(",");
+        match &node.node {
+            Terminal::False | Terminal::True => write!(f, "{}", name),
+            Terminal::PkK(key) | Terminal::PkH(key) => write!(f, "{}({})", name, hex::encode(key)),
+            Terminal::RawPkH(hash) | Terminal::Ripemd160(hash) | Terminal::Hash160(hash) => {
+                write!(f, "{}({})", name, hex::encode(hash))
+            }
+            Terminal::Sha256(hash) | Terminal::Hash256(hash) => write!(f, "{}({})", name, hex::encode(hash)),
+            Terminal::Older(n) | Terminal::After(n) => write!(f, "{}({})", name, n),
+            Terminal::Check(x) => match &x.node {
+                Terminal::PkK(key) => write!(f, "pk({})", hex::encode(key)),
+                Terminal::PkH(key) => write!(f, "pkh({})", hex::encode(key)),
+                _ => unreachable!(),
+            },
+            Terminal::AndOr(x, y, z) if z.node == Terminal::False => write!(f, "and_n({},{})", x, y),
+            Terminal::AndOr(x, y, z) => write!(f, "andor({},{},{})", x, y, z),
+            Terminal::AndV(x, y)
+            | Terminal::AndB(x, y)
+            | Terminal::OrB(x, y)
+            | Terminal::OrC(x, y)
+            | Terminal::OrD(x, y)
+            | Terminal::OrI(x, y) => write!(f, "{}({},{})", name, x, y),
+            Terminal::Thresh(k, subs) => {
+                let subs = subs.iter().map(|sub| sub.to_string()).collect::<Vec<_>>().join(",");
+                write!(f, "thresh({},{})", k, subs)
+            }
+            Terminal::Multi(k, multi_keys) | Terminal::MultiA(k, multi_keys) => {
+                write!(f, "{}({},{})", name, k, keys(multi_keys))
+            }
+            // Wrappers are written above
+            _ => unreachable!(),
+        }
+    }
+}
+
+// Numbers up to 16 are pushed with OP_0 to OP_16, others as minimally
+// encoded script numbers
+pub(crate) fn push_number(n: usize) -> Command {
+    match n {
+        0 => Command::Op(OP_0),
+        1..=16 => Command::Op(0x50 + n as u8),
+        _ => Command::Data(encode_num(n as i32)),
+    }
+}
+
+fn validate_threshold(k: usize, n: usize) -> Result<(), MiniscriptError> {
+    if k == 0 || k > n {
+        return Err(MiniscriptError::InvalidThreshold(k));
+    }
+    Ok(())
+}
+
+pub(crate) fn validate_key(key: &[u8], context: Context) -> Result<(), MiniscriptError> {
+    let valid = match context {
+        Context::Segwit => key.len() == 33 && matches!(key[0], 0x02 | 0x03) && Point::from_sec(key).is_ok(),
+        Context::Tap => key.len() == 32 && XOnlyPublicKey::parse(key).is_ok(),
+    };
+    if !valid {
+        return Err(MiniscriptError::InvalidKey(hex::encode(key)));
+    }
+    Ok(())
+}
+
+// OP_DUP OP_HASH160 <hash> OP_EQUALVERIFY
+fn encode_pkh(commands: &mut Vec<Command>, hash: Vec<u8>) {
+    commands.push(Command::Op(OP_DUP));
+    commands.push(Command::Op(OP_HASH160));
+    commands.push(Command::Data(hash));
+    commands.push(Command::Op(OP_EQUALVERIFY));
+}
+
+// OP_SIZE <32> OP_EQUALVERIFY <hash opcode> <hash> OP_EQUAL, where the 32
+// is a one byte push
+fn encode_hash(commands: &mut Vec<Command>, opcode: u8, hash: &[u8]) {
+    commands.push(Command::Op(OP_SIZE));
+    commands.push(push_number(32));
+    commands.push(Command::Op(OP_EQUALVERIFY));
+    commands.push(Command::Op(opcode));
+    commands.push(Command::Data(hash.to_vec()));
+    commands.push(Command::Op(OP_EQUAL));
+}
+
+// A recursive descent parser over a Miniscript expression
+struct Parser<'a> {
+    input: &'a str,
+    position: usize,
+    context: Context,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.input.as_bytes().get(self.position).copied()
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), MiniscriptError> {
+        if self.peek() != Some(c) {
+            return Err(MiniscriptError::Syntax(format!("'{}'", c as char), self.position));
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    // Everything up to the next separator: a fragment name with its
+    // wrappers, a key, a hash or a number
+    fn token(&mut self) -> &'a str {
+        let rest = &self.input[self.position..];
+        let length = rest.find([',', '(', ')']).unwrap_or(rest.len());
+        self.position += length;
+        &rest[..length]
+    }
+
+    fn node(&self, node: Terminal) -> Result<Miniscript, MiniscriptError> {
+        Miniscript::from_node(node, self.context)
+    }
+
+    fn expression(&mut self) -> Result<Miniscript, MiniscriptError> {
+        let start = self.position;
+        let token = self.token();
+        let (wrappers, name) = token.split_once(':').unwrap_or(("", token));
+        let miniscript = match name {
+            "0" if self.peek() != Some(b'(') => self.node(Terminal::False)?,
+            "1" if self.peek() != Some(b'(') => self.node(Terminal::True)?,
+            _ => {
+                if self.peek() != Some(b'(') {
+                    return Err(MiniscriptError::Syntax("fragment".to_string(), start));
+                }
+                self.position += 1;
+                let miniscript = self.fragment(name)?;
+                self.expect(b')')?;
+                miniscript
+            }
+        };
+        // The wrapper nearest the fragment applies first
+        wrappers.chars().rev().try_fold(miniscript, |miniscript, wrapper| self.wrap(wrapper, miniscript))
+    }
+
+    fn wrap(&self, wrapper: char, x: Miniscript) -> Result<Miniscript, MiniscriptError> {
+        let x = Box::new(x);
+        let node = match wrapper {
+            'a' => Terminal::Alt(x),
+            's' => Terminal::Swap(x),
+            'c' => Terminal::Check(x),
+            'd' => Terminal::DupIf(x),
+            'v' => Terminal::Verify(x),
+            'j' => Terminal::NonZero(x),
+            'n' => Terminal::ZeroNotEqual(x),
+            't' => Terminal::AndV(x, Box::new(self.node(Terminal::True)?)),
+            'l' => Terminal::OrI(Box::new(self.node(Terminal::False)?), x),
+            'u' => Terminal::OrI(x, Box::new(self.node(Terminal::False)?)),
+            _ => return Err(MiniscriptError::UnknownWrapper(wrapper)),
+        };
+        self.node(node)
+    }
+
+    fn fragment(&mut self, name: &str) -> Result<Miniscript, MiniscriptError> {
+        let node = match name {
+            "pk_k" => Terminal::PkK(self.key()?),
+            "pk_h" => Terminal::PkH(self.key()?),
+            "pk" | "pkh" => {
+                let key = self.key()?;
+                let key = self.node(if name == "pk" { Terminal::PkK(key) } else { Terminal::PkH(key) })?;
+                Terminal::Check(Box::new(key))
+            }
+            "expr_raw_pkh" => Terminal::RawPkH(self.hash()?),
+            "older" => Terminal::Older(self.number()? as u32),
+            "after" => Terminal::After(self.number()? as u32),
+            "sha256" => Terminal::Sha256(self.hash()?),
+            "hash256" => Terminal::Hash256(self.hash()?),
+            "ripemd160" => Terminal::Ripemd160(self.hash()?),
+            "hash160" => Terminal::Hash160(self.hash()?),
+            "and_v" | "and_b" | "or_b" | "or_c" | "or_d" | "or_i" => {
+                let x = Box::new(self.expression()?);
+                self.expect(b',')?;
+                let y = Box::new(self.expression()?);
+                match name {
+                    "and_v" => Terminal::AndV(x, y),
+                    "and_b" => Terminal::AndB(x, y),
+                    "or_b" => Terminal::OrB(x, y),
+                    "or_c" => Terminal::OrC(x, y),
+                    "or_d" => Terminal::OrD(x, y),
+                    _ => Terminal::OrI(x, y),
+                }
+            }
+            "andor" | "and_n" => {
+                let x = Box::new(self.expression()?);
+                self.expect(b',')?;
+                let y = Box::new(self.expression()?);
+                let z = match name {
+                    "andor" => {
+                        self.expect(b',')?;
+                        self.expression()?
+                    }
+                    // and_n(X,Y) = andor(X,Y,0)
+                    _ => self.node(Terminal::False)?,
+                };
+                Terminal::AndOr(x, y, Box::new(z))
+            }
+            "thresh" => {
+                let k = self.number()?;
+                let mut subs = vec![];
+                while self.peek() == Some(b',') {
+                    self.position += 1;
+                    subs.push(self.expression()?);
+                }
+                Terminal::Thresh(k, subs)
+            }
+            "multi" | "multi_a" => {
+                let k = self.number()?;
+                let mut keys = vec![];
+                while self.peek() == Some(b',') {
+                    self.position += 1;
+                    keys.push(self.key()?);
+                }
+                if name == "multi" {
+                    Terminal::Multi(k, keys)
+                } else {
+                    Terminal::MultiA(k, keys)
+                }
+            }
+            _ => return Err(MiniscriptError::UnknownFragment(name.to_string())),
+        };
+        self.node(node)
+    }
+
+    fn key(&mut self) -> Result<Vec<u8>, MiniscriptError> {
+        let token = self.token();
+        let key = hex::decode(token).map_err(|_| MiniscriptError::InvalidKey(token.to_string()))?;
+        validate_key(&key, self.context)?;
+        Ok(key)
+    }
+
+    // A hash of the length the fragment takes, 20 or 32 bytes
+    fn hash<const N: usize>(&mut self) -> Result<[u8; N], MiniscriptError> {
+        let token = self.token();
+        hex::decode(token)
+            .ok()
+            .and_then(|hash| hash.try_into().ok())
+            .ok_or_else(|| MiniscriptError::InvalidHash(token.to_string()))
+    }
+
+    fn number(&mut self) -> Result<usize, MiniscriptError> {
+        let start = self.position;
+        let token = self.token();
+        // Leading zeros and signs aren't allowed
+        if token.is_empty() || !token.bytes().all(|b| b.is_ascii_digit()) || (token.len() > 1 && token.starts_with('0')) {
+            return Err(MiniscriptError::Syntax("number".to_string(), start));
+        }
+        token
+            .parse::<u32>()
+            .map(|n| n as usize)
+            .map_err(|_| MiniscriptError::Syntax("number".to_string(), start))
+    }
+}
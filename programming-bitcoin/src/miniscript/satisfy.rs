@@ -0,0 +1,355 @@
+use std::collections::HashMap;
+use ripemd::Ripemd160;
+use sha2::{Digest, Sha256};
+
+use crate::miniscript::error::MiniscriptError;
+use crate::miniscript::miniscript::{Miniscript, Terminal};
+use crate::utils::hash160::hash160;
+use crate::utils::hash256::hash256;
+
+// Sequence bits of BIP68 relative locktimes
+const SEQUENCE_DISABLE_FLAG: u32 = 1 << 31;
+const SEQUENCE_TYPE_FLAG: u32 = 1 << 22;
+const SEQUENCE_MASK: u32 = 0xffff;
+const LOCKTIME_THRESHOLD: u32 = 500_000_000;
+
+/// The signatures, hash preimages and timelocks available to satisfy a
+/// Miniscript
+#[derive(Debug, Clone, Default)]
+pub struct Satisfier {
+    // Signatures by the key they are for, serialized as in the script
+    signatures: HashMap<Vec<u8>, Vec<u8>>,
+    preimages: Vec<Vec<u8>>,
+    sequence: Option<u32>,
+    lock_time: Option<u32>,
+}
+
+impl Satisfier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a signature by a key, serialized as it is in the script. ECDSA
+    /// signatures are DER with the sighash byte appended, Schnorr
+    /// signatures 64 or 65 bytes.
+    pub fn add_signature(&mut self, key: Vec<u8>, signature: Vec<u8>) {
+        self.signatures.insert(key, signature);
+    }
+
+    /// Adds a preimage for the hash fragments, which only take preimages of
+    /// 32 bytes
+    pub fn add_preimage(&mut self, preimage: Vec<u8>) {
+        self.preimages.push(preimage);
+    }
+
+    /// Sets the sequence of the input being spent, which older() checks
+    pub fn set_sequence(&mut self, sequence: u32) {
+        self.sequence = Some(sequence);
+    }
+
+    /// Sets the lock time of the spending transaction, which after() checks
+    pub fn set_lock_time(&mut self, lock_time: u32) {
+        self.lock_time = Some(lock_time);
+    }
+
+    fn signature(&self, key: &[u8]) -> Option<&Vec<u8>> {
+        self.signatures.get(key)
+    }
+
+    // The key behind a decoded pk_h, if there is a signature by it
+    fn key_with_hash(&self, hash: &[u8; 20]) -> Option<&Vec<u8>> {
+        self.signatures.keys().find(|key| hash160(key) == hash)
+    }
+
+    fn preimage(&self, hash: fn(&[u8]) -> Vec<u8>, digest: &[u8]) -> Option<&Vec<u8>> {
+        self.preimages.iter().find(|preimage| preimage.len() == 32 && hash(preimage) == digest)
+    }
+
+    // The input's sequence must enable relative locktimes, count in the same
+    // units and be at least n (BIP112)
+    fn check_older(&self, n: u32) -> bool {
+        self.sequence.is_some_and(|sequence| {
+            sequence & SEQUENCE_DISABLE_FLAG == 0
+                && sequence & SEQUENCE_TYPE_FLAG == n & SEQUENCE_TYPE_FLAG
+                && sequence & SEQUENCE_MASK >= n & SEQUENCE_MASK
+        })
+    }
+
+    // The lock time must be a height or a time as n is, and be at least n
+    // (BIP65)
+    fn check_after(&self, n: u32) -> bool {
+        self.lock_time.is_some_and(|lock_time| {
+            (lock_time < LOCKTIME_THRESHOLD) == (n < LOCKTIME_THRESHOLD) && lock_time >= n
+        })
+    }
+}
+
+impl Miniscript {
+    /// Returns the smallest witness that satisfies the expression and that
+    /// a third party can't change into another valid witness. Elements are
+    /// bottom of the stack first; a P2WSH spend puts the witness script
+    /// (encode()) after them.
+    pub fn satisfy(&self, satisfier: &Satisfier) -> Result<Vec<Vec<u8>>, MiniscriptError> {
+        let sat = satisfactions(self, satisfier).sat;
+        if !sat.available {
+            return Err(MiniscriptError::Unsatisfiable);
+        }
+        if sat.malleable {
+            return Err(MiniscriptError::MalleableSatisfaction);
+        }
+        Ok(sat.elements)
+    }
+}
+
+// A witness stack that satisfies or dissatisfies an expression, with what a
+// third party could do with it
+#[derive(Debug, Clone)]
+struct Stack {
+    available: bool,
+    // It includes a signature, which a third party can't make
+    has_sig: bool,
+    // A third party could change it into another valid stack
+    malleable: bool,
+    // Serialized size, with each element's length prefix
+    size: usize,
+    elements: Vec<Vec<u8>>,
+}
+
+impl Stack {
+    fn invalid() -> Self {
+        Self { available: false, has_sig: false, malleable: false, size: 0, elements: vec![] }
+    }
+
+    fn empty() -> Self {
+        Self { available: true, ..Self::invalid() }
+    }
+
+    fn element(element: Vec<u8>) -> Self {
+        let size = element.len() + if element.len() < 253 { 1 } else { 3 };
+        Self { size, elements: vec![element], ..Self::empty() }
+    }
+
+    fn zero() -> Self {
+        Self::element(vec![])
+    }
+
+    fn one() -> Self {
+        Self::element(vec![1])
+    }
+
+    fn signature(signature: Option<&Vec<u8>>) -> Self {
+        match signature {
+            Some(signature) => Self { has_sig: true, ..Self::element(signature.clone()) },
+            None => Self::invalid(),
+        }
+    }
+
+    fn malleable(self) -> Self {
+        Self { malleable: true, ..self }
+    }
+
+    // This stack with other on top of it
+    fn then(self, other: Self) -> Self {
+        if !self.available || !other.available {
+            return Self::invalid();
+        }
+        let mut elements = self.elements;
+        elements.extend(other.elements);
+        Self {
+            available: true,
+            has_sig: self.has_sig || other.has_sig,
+            malleable: self.malleable || other.malleable,
+            size: self.size + other.size,
+            elements,
+        }
+    }
+
+    // The better of two stacks that satisfy (or dissatisfy) the same thing.
+    // A stack without a signature is taken over one with, since a third
+    // party could always replace the one with by the one without. Between
+    // two without, a third party can pick either, so both are malleable.
+    fn or(self, other: Self) -> Self {
+        let (mut a, mut b) = match (self.available, other.available) {
+            (false, _) => return other,
+            (_, false) => return self,
+            _ => (self, other),
+        };
+        match (a.has_sig, b.has_sig) {
+            (false, true) => return a,
+            (true, false) => return b,
+            (false, false) => {
+                a.malleable = true;
+                b.malleable = true;
+            }
+            (true, true) => {
+                if a.malleable != b.malleable {
+                    return if a.malleable { b } else { a };
+                }
+            }
+        }
+        if a.size <= b.size {
+            a
+        } else {
+            b
+        }
+    }
+}
+
+// The best satisfaction and dissatisfaction of an expression
+struct Satisfactions {
+    sat: Stack,
+    dsat: Stack,
+}
+
+// Follows the satisfactions and dissatisfactions of each fragment in the
+// Miniscript specification. Alternatives that aren't the canonical
+// dissatisfaction are marked malleable.
+fn satisfactions(miniscript: &Miniscript, satisfier: &Satisfier) -> Satisfactions {
+    let subs = miniscript.node().subs().into_iter().map(|sub| satisfactions(sub, satisfier)).collect::<Vec<_>>();
+    let sub = |i: usize| (subs[i].sat.clone(), subs[i].dsat.clone());
+    let check = |ok: bool| if ok { Stack::empty() } else { Stack::invalid() };
+    let hash = |hash: fn(&[u8]) -> Vec<u8>, digest: &[u8]| Satisfactions {
+        sat: satisfier.preimage(hash, digest).map_or(Stack::invalid(), |preimage| Stack::element(preimage.clone())),
+        // Any other 32 bytes dissatisfy, so a third party can change it
+        dsat: Stack::element(vec![0; 32]).malleable(),
+    };
+
+    let (sat, dsat) = match miniscript.node() {
+        Terminal::False => (Stack::invalid(), Stack::empty()),
+        Terminal::True => (Stack::empty(), Stack::invalid()),
+        Terminal::PkK(key) => (Stack::signature(satisfier.signature(key)), Stack::zero()),
+        Terminal::PkH(key) => {
+            let key_element = Stack::element(key.clone());
+            (Stack::signature(satisfier.signature(key)).then(key_element.clone()), Stack::zero().then(key_element))
+        }
+        Terminal::RawPkH(hash) => match satisfier.key_with_hash(hash) {
+            Some(key) => {
+                let key_element = Stack::element(key.clone());
+                (Stack::signature(satisfier.signature(key)).then(key_element.clone()), Stack::zero().then(key_element))
+            }
+            None => (Stack::invalid(), Stack::invalid()),
+        },
+        Terminal::Older(n) => (check(satisfier.check_older(*n)), Stack::invalid()),
+        Terminal::After(n) => (check(satisfier.check_after(*n)), Stack::invalid()),
+        Terminal::Sha256(digest) => return hash(|preimage| Sha256::digest(preimage).to_vec(), digest),
+        Terminal::Hash256(digest) => return hash(hash256, digest),
+        Terminal::Ripemd160(digest) => return hash(|preimage| Ripemd160::digest(preimage).to_vec(), digest),
+        Terminal::Hash160(digest) => return hash(hash160, digest),
+        Terminal::Alt(_) | Terminal::Swap(_) | Terminal::Check(_) | Terminal::ZeroNotEqual(_) => sub(0),
+        Terminal::DupIf(_) => (subs[0].sat.clone().then(Stack::one()), Stack::zero()),
+        Terminal::Verify(_) => (subs[0].sat.clone(), Stack::invalid()),
+        Terminal::NonZero(_) => {
+            let (x_sat, x_dsat) = sub(0);
+            // A dissatisfaction of X that doesn't need a signature is
+            // another way to dissatisfy
+            let dsat = if x_dsat.available && !x_dsat.has_sig { Stack::zero().malleable() } else { Stack::zero() };
+            (x_sat, dsat)
+        }
+        Terminal::AndV(..) => {
+            let ((x_sat, _), (y_sat, y_dsat)) = (sub(0), sub(1));
+            (y_sat.then(x_sat.clone()), y_dsat.then(x_sat).malleable())
+        }
+        Terminal::AndB(..) => {
+            let ((x_sat, x_dsat), (y_sat, y_dsat)) = (sub(0), sub(1));
+            let dsat = y_dsat
+                .clone()
+                .then(x_dsat.clone())
+                .or(y_sat.clone().then(x_dsat).malleable())
+                .or(y_dsat.then(x_sat.clone()).malleable());
+            (y_sat.then(x_sat), dsat)
+        }
+        Terminal::AndOr(..) => {
+            let ((x_sat, x_dsat), (y_sat, y_dsat), (z_sat, z_dsat)) = (sub(0), sub(1), sub(2));
+            let sat = y_sat.then(x_sat.clone()).or(z_sat.then(x_dsat.clone()));
+            let dsat = y_dsat.then(x_sat).malleable().or(z_dsat.then(x_dsat));
+            (sat, dsat)
+        }
+        Terminal::OrB(..) => {
+            let ((x_sat, x_dsat), (z_sat, z_dsat)) = (sub(0), sub(1));
+            let sat = z_dsat
+                .clone()
+                .then(x_sat.clone())
+                .or(z_sat.clone().then(x_dsat.clone()))
+                .or(z_sat.then(x_sat).malleable());
+            (sat, z_dsat.then(x_dsat))
+        }
+        Terminal::OrC(..) => {
+            let ((x_sat, x_dsat), (z_sat, _)) = (sub(0), sub(1));
+            (x_sat.or(z_sat.then(x_dsat)), Stack::invalid())
+        }
+        Terminal::OrD(..) => {
+            let ((x_sat, x_dsat), (z_sat, z_dsat)) = (sub(0), sub(1));
+            (x_sat.or(z_sat.then(x_dsat.clone())), z_dsat.then(x_dsat))
+        }
+        Terminal::OrI(..) => {
+            let ((x_sat, x_dsat), (z_sat, z_dsat)) = (sub(0), sub(1));
+            let sat = x_sat.then(Stack::one()).or(z_sat.then(Stack::zero()));
+            let dsat = x_dsat.then(Stack::one()).or(z_dsat.then(Stack::zero()));
+            (sat, dsat)
+        }
+        Terminal::Thresh(k, _) => {
+            // stacks[j] satisfies j of the subexpressions seen so far. The
+            // last subexpression is at the bottom of the stack, so they are
+            // added from the last.
+            let mut stacks = vec![Stack::empty()];
+            for sub in subs.iter().rev() {
+                let mut next = vec![stacks[0].clone().then(sub.dsat.clone())];
+                for j in 1..stacks.len() {
+                    next.push(stacks[j].clone().then(sub.dsat.clone()).or(stacks[j - 1].clone().then(sub.sat.clone())));
+                }
+                next.push(stacks[stacks.len() - 1].clone().then(sub.sat.clone()));
+                stacks = next;
+            }
+            threshold_result(stacks, *k)
+        }
+        Terminal::Multi(k, keys) => {
+            // The signatures go in the order of their keys, after the
+            // element OP_CHECKMULTISIG pops by mistake
+            let mut stacks = vec![Stack::zero()];
+            for key in keys {
+                let signature = Stack::signature(satisfier.signature(key));
+                let mut next = vec![stacks[0].clone()];
+                for j in 1..stacks.len() {
+                    next.push(stacks[j].clone().or(stacks[j - 1].clone().then(signature.clone())));
+                }
+                next.push(stacks[stacks.len() - 1].clone().then(signature));
+                stacks = next;
+            }
+            let dsat = (0..*k).fold(Stack::zero(), |dsat, _| dsat.then(Stack::zero()));
+            (stacks.swap_remove(*k), dsat)
+        }
+        Terminal::MultiA(k, keys) => {
+            // Each key takes a signature or an empty element, with the first
+            // key's on top
+            let mut stacks = vec![Stack::empty()];
+            for key in keys.iter().rev() {
+                let signature = Stack::signature(satisfier.signature(key));
+                let mut next = vec![stacks[0].clone().then(Stack::zero())];
+                for j in 1..stacks.len() {
+                    next.push(stacks[j].clone().then(Stack::zero()).or(stacks[j - 1].clone().then(signature.clone())));
+                }
+                next.push(stacks[stacks.len() - 1].clone().then(signature));
+                stacks = next;
+            }
+            threshold_result(stacks, *k)
+        }
+    };
+    Satisfactions { sat, dsat }
+}
+
+// Satisfying exactly k is the satisfaction; satisfying none is the canonical
+// dissatisfaction, and any other count also dissatisfies
+fn threshold_result(stacks: Vec<Stack>, k: usize) -> (Stack, Stack) {
+    let mut sat = Stack::invalid();
+    let mut dsat = Stack::invalid();
+    for (j, stack) in stacks.into_iter().enumerate() {
+        if j == k {
+            sat = stack;
+        } else if j == 0 {
+            dsat = dsat.or(stack);
+        } else {
+            dsat = dsat.or(stack.malleable());
+        }
+    }
+    (sat, dsat)
+}
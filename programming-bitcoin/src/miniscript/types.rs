@@ -0,0 +1,401 @@
+use std::fmt;
+use crate::miniscript::error::MiniscriptError;
+use crate::miniscript::miniscript::{Context, Miniscript, Terminal};
+
+// older() values with this bit set count in units of 512 seconds (BIP68)
+const SEQUENCE_TYPE_FLAG: u32 = 1 << 22;
+// after() values from this one on are timestamps, below it block heights
+const LOCKTIME_THRESHOLD: u32 = 500_000_000;
+
+/// What an expression leaves on the stack
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BaseType {
+    // Takes its inputs from the top of the stack and pushes nonzero if
+    // satisfied, zero if dissatisfied
+    B,
+    // Takes its inputs from the top of the stack and either continues or
+    // fails the script
+    V,
+    // Like B, but pushes a key that still needs a signature check
+    K,
+    // Like B, but takes its inputs from under the top element
+    W,
+}
+
+/// The type of a Miniscript expression: its base type and the properties
+/// that decide which fragments it can be used in, and whether its
+/// satisfactions can be changed by a third party
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Type {
+    pub base: BaseType,
+    // z: consumes no stack elements
+    pub zero_arg: bool,
+    // o: consumes exactly one stack element
+    pub one_arg: bool,
+    // n: the top input is never zero when satisfying
+    pub nonzero: bool,
+    // d: has a dissatisfaction that needs no signature
+    pub dissatisfiable: bool,
+    // u: pushes exactly 1 when satisfied
+    pub unit: bool,
+    // e: has exactly one dissatisfaction without a signature
+    pub expressive: bool,
+    // f: every dissatisfaction needs a signature
+    pub forced: bool,
+    // s: every satisfaction needs a signature
+    pub safe: bool,
+    // m: a satisfaction that a third party can't change always exists
+    pub non_malleable: bool,
+}
+
+impl Type {
+    fn new(base: BaseType) -> Self {
+        Self {
+            base,
+            zero_arg: false,
+            one_arg: false,
+            nonzero: false,
+            dissatisfiable: false,
+            unit: false,
+            expressive: false,
+            forced: false,
+            safe: false,
+            non_malleable: false,
+        }
+    }
+
+    /// Type checks a fragment against the types of its subexpressions,
+    /// following the rules of the Miniscript specification
+    pub(crate) fn of(node: &Terminal, context: Context) -> Result<Self, MiniscriptError> {
+        use BaseType::*;
+        let check = |ok: bool| if ok { Ok(()) } else { Err(MiniscriptError::TypeCheck(node.name().to_string())) };
+        // Bases Y and Z may take in and_v, andor and or_i
+        let bkv = |base: BaseType| base == B || base == K || base == V;
+
+        let ty = match node {
+            Terminal::False => Type {
+                zero_arg: true,
+                dissatisfiable: true,
+                unit: true,
+                expressive: true,
+                safe: true,
+                non_malleable: true,
+                ..Type::new(B)
+            },
+            Terminal::True => Type { zero_arg: true, unit: true, forced: true, non_malleable: true, ..Type::new(B) },
+            Terminal::PkK(_) => Type {
+                one_arg: true,
+                nonzero: true,
+                dissatisfiable: true,
+                unit: true,
+                expressive: true,
+                safe: true,
+                non_malleable: true,
+                ..Type::new(K)
+            },
+            Terminal::PkH(_) | Terminal::RawPkH(_) => Type {
+                nonzero: true,
+                dissatisfiable: true,
+                unit: true,
+                expressive: true,
+                safe: true,
+                non_malleable: true,
+                ..Type::new(K)
+            },
+            Terminal::Older(_) | Terminal::After(_) => Type { zero_arg: true, forced: true, non_malleable: true, ..Type::new(B) },
+            Terminal::Sha256(_) | Terminal::Hash256(_) | Terminal::Ripemd160(_) | Terminal::Hash160(_) => Type {
+                one_arg: true,
+                nonzero: true,
+                dissatisfiable: true,
+                unit: true,
+                non_malleable: true,
+                ..Type::new(B)
+            },
+            Terminal::Alt(x) | Terminal::Swap(x) => {
+                let x = x.ty();
+                check(x.base == B && (matches!(node, Terminal::Alt(_)) || x.one_arg))?;
+                Type {
+                    dissatisfiable: x.dissatisfiable,
+                    unit: x.unit,
+                    expressive: x.expressive,
+                    forced: x.forced,
+                    safe: x.safe,
+                    non_malleable: x.non_malleable,
+                    ..Type::new(W)
+                }
+            }
+            Terminal::Check(x) => {
+                let x = x.ty();
+                check(x.base == K)?;
+                Type { base: B, unit: true, ..*x }
+            }
+            Terminal::DupIf(x) => {
+                let x = x.ty();
+                check(x.base == V && x.zero_arg)?;
+                Type {
+                    one_arg: true,
+                    nonzero: true,
+                    dissatisfiable: true,
+                    // OP_IF only requires an exact 0 or 1 in tapscript
+                    // (MINIMALIF), so elsewhere a satisfaction could
+                    // put anything nonzero on the stack
+                    unit: context == Context::Tap,
+                    expressive: true,
+                    safe: x.safe,
+                    non_malleable: x.non_malleable,
+                    ..Type::new(B)
+                }
+            }
+            Terminal::Verify(x) => {
+                let x = x.ty();
+                check(x.base == B)?;
+                Type {
+                    zero_arg: x.zero_arg,
+                    one_arg: x.one_arg,
+                    nonzero: x.nonzero,
+                    forced: true,
+                    safe: x.safe,
+                    non_malleable: x.non_malleable,
+                    ..Type::new(V)
+                }
+            }
+            Terminal::NonZero(x) => {
+                let x = x.ty();
+                check(x.base == B && x.nonzero)?;
+                Type {
+                    one_arg: x.one_arg,
+                    nonzero: true,
+                    dissatisfiable: true,
+                    unit: x.unit,
+                    expressive: x.forced,
+                    safe: x.safe,
+                    non_malleable: x.non_malleable,
+                    ..Type::new(B)
+                }
+            }
+            Terminal::ZeroNotEqual(x) => {
+                let x = x.ty();
+                check(x.base == B)?;
+                Type { unit: true, ..*x }
+            }
+            Terminal::AndV(x, y) => {
+                let (x, y) = (x.ty(), y.ty());
+                check(x.base == V && bkv(y.base))?;
+                Type {
+                    zero_arg: x.zero_arg && y.zero_arg,
+                    one_arg: (x.zero_arg && y.one_arg) || (x.one_arg && y.zero_arg),
+                    nonzero: x.nonzero || (x.zero_arg && y.nonzero),
+                    unit: y.unit,
+                    forced: x.safe || y.forced,
+                    safe: x.safe || y.safe,
+                    non_malleable: x.non_malleable && y.non_malleable,
+                    ..Type::new(y.base)
+                }
+            }
+            Terminal::AndB(x, y) => {
+                let (x, y) = (x.ty(), y.ty());
+                check(x.base == B && y.base == W)?;
+                Type {
+                    zero_arg: x.zero_arg && y.zero_arg,
+                    one_arg: (x.zero_arg && y.one_arg) || (x.one_arg && y.zero_arg),
+                    nonzero: x.nonzero || (x.zero_arg && y.nonzero),
+                    dissatisfiable: x.dissatisfiable && y.dissatisfiable,
+                    unit: true,
+                    expressive: x.expressive && y.expressive && x.safe && y.safe,
+                    forced: (x.forced && (y.forced || x.safe)) || (y.forced && y.safe),
+                    safe: x.safe || y.safe,
+                    non_malleable: x.non_malleable && y.non_malleable,
+                    ..Type::new(B)
+                }
+            }
+            Terminal::AndOr(x, y, z) => {
+                let (x, y, z) = (x.ty(), y.ty(), z.ty());
+                check(x.base == B && x.dissatisfiable && x.unit && y.base == z.base && bkv(y.base))?;
+                Type {
+                    zero_arg: x.zero_arg && y.zero_arg && z.zero_arg,
+                    one_arg: (x.zero_arg && y.one_arg && z.one_arg) || (x.one_arg && y.zero_arg && z.zero_arg),
+                    dissatisfiable: z.dissatisfiable,
+                    unit: y.unit && z.unit,
+                    expressive: x.expressive && z.expressive && (x.safe || y.forced),
+                    forced: z.forced && (x.safe || y.forced),
+                    safe: z.safe && (x.safe || y.safe),
+                    non_malleable: x.non_malleable
+                        && y.non_malleable
+                        && z.non_malleable
+                        && x.expressive
+                        && (x.safe || y.safe || z.safe),
+                    ..Type::new(y.base)
+                }
+            }
+            Terminal::OrB(x, z) => {
+                let (x, z) = (x.ty(), z.ty());
+                check(x.base == B && x.dissatisfiable && z.base == W && z.dissatisfiable)?;
+                Type {
+                    zero_arg: x.zero_arg && z.zero_arg,
+                    one_arg: (x.zero_arg && z.one_arg) || (x.one_arg && z.zero_arg),
+                    dissatisfiable: true,
+                    unit: true,
+                    expressive: x.expressive && z.expressive,
+                    safe: x.safe && z.safe,
+                    non_malleable: x.non_malleable
+                        && z.non_malleable
+                        && x.expressive
+                        && z.expressive
+                        && (x.safe || z.safe),
+                    ..Type::new(B)
+                }
+            }
+            Terminal::OrC(x, z) | Terminal::OrD(x, z) => {
+                let (x, z) = (x.ty(), z.ty());
+                let or_d = matches!(node, Terminal::OrD(..));
+                check(x.base == B && x.dissatisfiable && x.unit && z.base == if or_d { B } else { V })?;
+                Type {
+                    zero_arg: x.zero_arg && z.zero_arg,
+                    one_arg: x.one_arg && z.zero_arg,
+                    dissatisfiable: or_d && z.dissatisfiable,
+                    unit: or_d && z.unit,
+                    expressive: or_d && x.expressive && z.expressive,
+                    // or_c is V, which is never dissatisfied
+                    forced: !or_d || z.forced,
+                    safe: x.safe && z.safe,
+                    non_malleable: x.non_malleable && z.non_malleable && x.expressive && (x.safe || z.safe),
+                    ..Type::new(z.base)
+                }
+            }
+            Terminal::OrI(x, z) => {
+                let (x, z) = (x.ty(), z.ty());
+                check(x.base == z.base && bkv(x.base))?;
+                Type {
+                    one_arg: x.zero_arg && z.zero_arg,
+                    dissatisfiable: x.dissatisfiable || z.dissatisfiable,
+                    unit: x.unit && z.unit,
+                    expressive: (x.expressive && z.forced) || (z.expressive && x.forced),
+                    forced: x.forced && z.forced,
+                    safe: x.safe && z.safe,
+                    non_malleable: x.non_malleable && z.non_malleable && (x.safe || z.safe),
+                    ..Type::new(x.base)
+                }
+            }
+            Terminal::Thresh(k, subs) => {
+                let types = subs.iter().map(Miniscript::ty).collect::<Vec<_>>();
+                check(types.iter().enumerate().all(|(i, ty)| {
+                    ty.base == if i == 0 { B } else { W } && ty.dissatisfiable && ty.unit
+                }))?;
+                let count = |property: fn(&Type) -> bool| types.iter().filter(|ty| property(ty)).count();
+                let n = types.len();
+                Type {
+                    zero_arg: count(|ty| ty.zero_arg) == n,
+                    one_arg: count(|ty| ty.one_arg) == 1 && count(|ty| ty.zero_arg) == n - 1,
+                    dissatisfiable: true,
+                    unit: true,
+                    expressive: count(|ty| ty.expressive) == n && count(|ty| ty.safe) == n,
+                    // Any k subexpressions include one that needs a signature
+                    safe: n - count(|ty| ty.safe) < *k,
+                    non_malleable: count(|ty| ty.non_malleable) == n
+                        && count(|ty| ty.expressive) == n
+                        && count(|ty| ty.safe) >= n - k,
+                    ..Type::new(B)
+                }
+            }
+            Terminal::Multi(..) | Terminal::MultiA(..) => Type {
+                nonzero: matches!(node, Terminal::Multi(..)),
+                dissatisfiable: true,
+                unit: true,
+                expressive: true,
+                safe: true,
+                non_malleable: true,
+                ..Type::new(B)
+            },
+        };
+        Ok(ty)
+    }
+}
+
+// The base type followed by the letters of the properties it has, as in
+// Bondesmu for pk()
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.base)?;
+        let properties = [
+            (self.zero_arg, 'z'),
+            (self.one_arg, 'o'),
+            (self.nonzero, 'n'),
+            (self.dissatisfiable, 'd'),
+            (self.unit, 'u'),
+            (self.expressive, 'e'),
+            (self.forced, 'f'),
+            (self.safe, 's'),
+            (self.non_malleable, 'm'),
+        ];
+        properties.iter().filter(|(has, _)| *has).try_for_each(|(_, letter)| write!(f, "{}", letter))
+    }
+}
+
+/// The kinds of timelocks an expression uses. A transaction has a single
+/// sequence and lock time, so one satisfaction can't need both a height and
+/// a time lock of the same kind.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TimelockInfo {
+    pub csv_with_height: bool,
+    pub csv_with_time: bool,
+    pub cltv_with_height: bool,
+    pub cltv_with_time: bool,
+    // Some satisfaction needs both a height and a time lock of one kind,
+    // and can never be spent
+    pub contains_combination: bool,
+}
+
+impl TimelockInfo {
+    pub(crate) fn of(node: &Terminal) -> Self {
+        let subs = node.subs();
+        let timelocks = |i: usize| *subs[i].timelocks();
+        match node {
+            Terminal::Older(n) => Self {
+                csv_with_height: n & SEQUENCE_TYPE_FLAG == 0,
+                csv_with_time: n & SEQUENCE_TYPE_FLAG != 0,
+                ..Self::default()
+            },
+            Terminal::After(n) => Self {
+                cltv_with_height: *n < LOCKTIME_THRESHOLD,
+                cltv_with_time: *n >= LOCKTIME_THRESHOLD,
+                ..Self::default()
+            },
+            Terminal::AndV(..) | Terminal::AndB(..) => Self::and(timelocks(0), timelocks(1)),
+            Terminal::AndOr(..) => Self::or(Self::and(timelocks(0), timelocks(1)), timelocks(2)),
+            Terminal::OrB(..) | Terminal::OrC(..) | Terminal::OrD(..) | Terminal::OrI(..) => {
+                Self::or(timelocks(0), timelocks(1))
+            }
+            // With k above 1, any two subexpressions can be satisfied
+            // together
+            Terminal::Thresh(k, _) => subs.iter().map(|sub| *sub.timelocks()).fold(Self::default(), |acc, timelocks| {
+                if *k > 1 {
+                    Self::and(acc, timelocks)
+                } else {
+                    Self::or(acc, timelocks)
+                }
+            }),
+            _ => subs.first().map_or(Self::default(), |sub| *sub.timelocks()),
+        }
+    }
+
+    // Both are satisfied together
+    fn and(a: Self, b: Self) -> Self {
+        let mut result = Self::or(a, b);
+        result.contains_combination |= (a.csv_with_height && b.csv_with_time)
+            || (a.csv_with_time && b.csv_with_height)
+            || (a.cltv_with_height && b.cltv_with_time)
+            || (a.cltv_with_time && b.cltv_with_height);
+        result
+    }
+
+    // Only one is satisfied
+    fn or(a: Self, b: Self) -> Self {
+        Self {
+            csv_with_height: a.csv_with_height || b.csv_with_height,
+            csv_with_time: a.csv_with_time || b.csv_with_time,
+            cltv_with_height: a.cltv_with_height || b.cltv_with_height,
+            cltv_with_time: a.cltv_with_time || b.cltv_with_time,
+            contains_combination: a.contains_combination || b.contains_combination,
+        }
+    }
+}
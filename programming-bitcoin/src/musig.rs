@@ -0,0 +1,4 @@
+pub mod error;
+pub mod key_agg;
+pub mod nonce;
+pub mod session;
@@ -0,0 +1,44 @@
+use std::fmt;
+
+/// Why a MuSig2 operation failed. Where the BIP327 algorithm can blame a
+/// participant, the variant carries the index of the signer whose
+/// contribution (public key, nonce or partial signature) was invalid.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MusigError {
+    InvalidPublicKey(usize),
+    InvalidPubNonce(usize),
+    InvalidPartialSignature(usize),
+    // The aggregate nonce does not decode to two points (or infinity)
+    InvalidAggNonce,
+    // A tweak is not below n, or tweaking gives the point at infinity
+    InvalidTweak,
+    // The secret nonce is zero, not below n, or for another public key
+    InvalidSecNonce,
+    // The secret key is zero or not below n
+    InvalidSecretKey,
+    // The aggregate key is the point at infinity
+    InfiniteAggregateKey,
+    // The signer's public key is not one of the aggregated keys
+    PublicKeyNotFound,
+    // Our own partial signature does not verify
+    PartialSignatureVerificationFailed,
+}
+
+impl fmt::Display for MusigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MusigError::InvalidPublicKey(signer) => write!(f, "invalid public key from signer {}", signer),
+            MusigError::InvalidPubNonce(signer) => write!(f, "invalid public nonce from signer {}", signer),
+            MusigError::InvalidPartialSignature(signer) => write!(f, "invalid partial signature from signer {}", signer),
+            MusigError::InvalidAggNonce => write!(f, "invalid aggregate nonce"),
+            MusigError::InvalidTweak => write!(f, "invalid tweak"),
+            MusigError::InvalidSecNonce => write!(f, "invalid secret nonce"),
+            MusigError::InvalidSecretKey => write!(f, "invalid secret key"),
+            MusigError::InfiniteAggregateKey => write!(f, "aggregate public key is the point at infinity"),
+            MusigError::PublicKeyNotFound => write!(f, "public key is not one of the aggregated keys"),
+            MusigError::PartialSignatureVerificationFailed => write!(f, "partial signature does not verify"),
+        }
+    }
+}
+
+impl std::error::Error for MusigError {}
@@ -0,0 +1,133 @@
+use primitive_types::U256;
+use crate::ecc::generator_table::GeneratorTable;
+use crate::ecc::jacobian_point::JacobianPoint;
+use crate::ecc::point::Point;
+use crate::ecc::s256point::S256Point;
+use crate::ecc::scalar::Scalar;
+use crate::ecc::xonly_public_key::XOnlyPublicKey;
+use crate::musig::error::MusigError;
+use crate::taproot::tweak::tap_tweak_hash;
+use crate::utils::tagged_hash::tagged_hash;
+
+/// Sorts public keys lexicographically, so that signers who don't agree on
+/// an order still end up with the same aggregate key
+pub fn key_sort(pubkeys: &[[u8; 33]]) -> Vec<[u8; 33]> {
+    let mut sorted = pubkeys.to_vec();
+    sorted.sort();
+    sorted
+}
+
+/// The result of BIP327 KeyAgg: the aggregate key Q of a list of compressed
+/// public keys, along with the accumulated sign (gacc) and tweak (tacc) of
+/// any tweaks applied to it since.
+#[derive(Debug, Clone)]
+pub struct KeyAggContext {
+    pubkeys: Vec<[u8; 33]>,
+    // The first key that differs from the first one. Its coefficient is 1,
+    // which saves a multiplication for one of the signers.
+    second_key: Option<[u8; 33]>,
+    list_hash: Vec<u8>,
+    q: (U256, U256),
+    gacc: Scalar,
+    tacc: Scalar,
+}
+
+impl KeyAggContext {
+    /// Aggregates the keys into Q = a_1 * P_1 + ... + a_u * P_u, where each
+    /// coefficient a_i commits to the whole list of keys
+    pub fn new(pubkeys: &[[u8; 33]]) -> Result<Self, MusigError> {
+        let list_hash = tagged_hash("KeyAgg list", &pubkeys.concat());
+        let second_key = pubkeys.iter().find(|pubkey| **pubkey != pubkeys[0]).copied();
+        let mut context = Self {
+            pubkeys: pubkeys.to_vec(),
+            second_key,
+            list_hash,
+            q: (U256::zero(), U256::zero()),
+            gacc: Scalar::one(),
+            tacc: Scalar::zero(),
+        };
+
+        let mut q = JacobianPoint::infinity();
+        for (index, pubkey) in pubkeys.iter().enumerate() {
+            let point = point_from_bytes(pubkey).ok_or(MusigError::InvalidPublicKey(index))?;
+            q = q.add(&point.multiply(context.key_agg_coeff(pubkey).to_u256()));
+        }
+        context.q = q.to_affine().ok_or(MusigError::InfiniteAggregateKey)?;
+        Ok(context)
+    }
+
+    /// Tweaks the aggregate key by t * G. An x-only tweak (as used by
+    /// taproot) is applied to the even-y version of the key; a plain tweak
+    /// (as used by BIP32 derivation) to the key itself.
+    pub fn apply_tweak(&mut self, tweak: &[u8; 32], is_xonly: bool) -> Result<(), MusigError> {
+        let t = Scalar::from_bytes(tweak).ok_or(MusigError::InvalidTweak)?;
+        let g = if is_xonly && self.q.1.bit(0) { -Scalar::one() } else { Scalar::one() };
+
+        // Q' = g * Q + t * G
+        let q = JacobianPoint::from_affine(self.q.0, self.q.1).multiply(g.to_u256());
+        let q = q.add(&GeneratorTable::multiply(t.to_u256()));
+        self.q = q.to_affine().ok_or(MusigError::InvalidTweak)?;
+        self.gacc = g * self.gacc;
+        self.tacc = t + g * self.tacc;
+        Ok(())
+    }
+
+    /// Applies the BIP341 tweak, making the aggregate key the output key of
+    /// a taproot output with this script tree (or no script path at all)
+    pub fn apply_taproot_tweak(&mut self, merkle_root: Option<&[u8]>) -> Result<(), MusigError> {
+        let tweak = tap_tweak_hash(&self.aggregate_public_key(), merkle_root);
+        self.apply_tweak(&tweak.try_into().unwrap(), true)
+    }
+
+    pub fn pubkeys(&self) -> &[[u8; 33]] {
+        &self.pubkeys
+    }
+
+    /// The aggregate key, including its y coordinate
+    pub fn aggregate_point(&self) -> Point {
+        S256Point::new_s256_point_unchecked(self.q.0, self.q.1)
+    }
+
+    /// The aggregate key as used in BIP340 signatures
+    pub fn aggregate_public_key(&self) -> XOnlyPublicKey {
+        XOnlyPublicKey::from_point(&self.aggregate_point())
+    }
+
+    pub(crate) fn q(&self) -> (U256, U256) {
+        self.q
+    }
+
+    pub(crate) fn gacc(&self) -> Scalar {
+        self.gacc
+    }
+
+    pub(crate) fn tacc(&self) -> Scalar {
+        self.tacc
+    }
+
+    // a_i = int(hash_KeyAgg coefficient(L || pk_i)) mod n, or 1 for the
+    // second key
+    pub(crate) fn key_agg_coeff(&self, pubkey: &[u8; 33]) -> Scalar {
+        if Some(*pubkey) == self.second_key {
+            return Scalar::one();
+        }
+        let data = [self.list_hash.as_slice(), pubkey].concat();
+        Scalar::reduce_bytes(&tagged_hash("KeyAgg coefficient", &data))
+    }
+}
+
+// Parses a 33 byte compressed point
+pub(crate) fn point_from_bytes(bytes: &[u8]) -> Option<JacobianPoint> {
+    if bytes.len() != 33 {
+        return None;
+    }
+    Point::from_sec(bytes).ok().map(|point| JacobianPoint::from_point(&point))
+}
+
+// Serializes a point as 33 compressed bytes
+pub(crate) fn point_to_bytes(x: U256, y: U256) -> [u8; 33] {
+    let mut bytes = [0u8; 33];
+    bytes[0] = if y.bit(0) { 0x03 } else { 0x02 };
+    bytes[1..].copy_from_slice(&x.to_big_endian());
+    bytes
+}
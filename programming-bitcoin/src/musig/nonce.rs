@@ -0,0 +1,172 @@
+use std::fmt;
+use crate::ecc::generator_table::GeneratorTable;
+use crate::ecc::jacobian_point::JacobianPoint;
+use crate::ecc::private_key::PrivateKey;
+use crate::ecc::scalar::Scalar;
+use crate::ecc::xonly_public_key::XOnlyPublicKey;
+use crate::musig::error::MusigError;
+use crate::musig::key_agg::{point_from_bytes, point_to_bytes};
+use crate::utils::tagged_hash::tagged_hash;
+use zeroize::Zeroize;
+
+/// A signer's secret nonce: k1 || k2 || the signer's public key. It must be
+/// used for exactly one signature, so it can't be cloned and Session::sign
+/// takes it by value. Signing twice with the same nonce reveals the secret
+/// key.
+pub struct SecNonce([u8; 97]);
+
+impl SecNonce {
+    pub fn from_bytes(bytes: [u8; 97]) -> Self {
+        Self(bytes)
+    }
+
+    /// The serialized nonce. Only for storing it between the two rounds of
+    /// signing; never send it to anyone.
+    pub fn to_bytes(&self) -> [u8; 97] {
+        self.0
+    }
+
+    pub(crate) fn k1(&self) -> Option<Scalar> {
+        Scalar::from_bytes(self.0[..32].try_into().unwrap()).filter(|k| !k.is_zero())
+    }
+
+    pub(crate) fn k2(&self) -> Option<Scalar> {
+        Scalar::from_bytes(self.0[32..64].try_into().unwrap()).filter(|k| !k.is_zero())
+    }
+
+    pub(crate) fn pubkey(&self) -> &[u8] {
+        &self.0[64..]
+    }
+}
+
+// Only the public key part is shown
+impl fmt::Debug for SecNonce {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SecNonce(<redacted>, {})", hex::encode(self.pubkey()))
+    }
+}
+
+impl Drop for SecNonce {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+/// A signer's public nonce, the two points R1 and R2 as 33 bytes each. It is
+/// sent to the other signers in the first round.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PubNonce([u8; 66]);
+
+impl PubNonce {
+    pub fn from_bytes(bytes: [u8; 66]) -> Self {
+        Self(bytes)
+    }
+
+    pub fn to_bytes(&self) -> [u8; 66] {
+        self.0
+    }
+
+    // Decodes R1 and R2
+    pub(crate) fn points(&self) -> Option<(JacobianPoint, JacobianPoint)> {
+        Some((point_from_bytes(&self.0[..33])?, point_from_bytes(&self.0[33..])?))
+    }
+}
+
+/// The sum of all the public nonces. Either half may be the point at
+/// infinity, which is encoded as 33 zero bytes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AggNonce([u8; 66]);
+
+impl AggNonce {
+    pub fn from_bytes(bytes: [u8; 66]) -> Self {
+        Self(bytes)
+    }
+
+    pub fn to_bytes(&self) -> [u8; 66] {
+        self.0
+    }
+
+    pub(crate) fn points(&self) -> Option<(JacobianPoint, JacobianPoint)> {
+        Some((point_from_bytes_ext(&self.0[..33])?, point_from_bytes_ext(&self.0[33..])?))
+    }
+}
+
+/// BIP327 NonceGen. rand must be 32 freshly generated random bytes; the
+/// optional arguments only add defence in depth against bad randomness.
+/// aggregate_key is the x-only aggregate key the nonce will sign for.
+pub fn nonce_gen(
+    rand: [u8; 32],
+    secret_key: Option<&PrivateKey>,
+    pubkey: &[u8; 33],
+    aggregate_key: Option<&XOnlyPublicKey>,
+    msg: Option<&[u8]>,
+    extra_in: Option<&[u8]>,
+) -> (SecNonce, PubNonce) {
+    let rand = match secret_key {
+        Some(secret_key) => {
+            let aux = tagged_hash("MuSig/aux", &rand);
+            let secret = secret_key.secret().to_big_endian();
+            secret.iter().zip(aux.iter()).map(|(a, b)| a ^ b).collect()
+        }
+        None => rand.to_vec(),
+    };
+    let aggregate_key = aggregate_key.map(|key| key.serialize()).unwrap_or_default();
+    let msg_prefixed = match msg {
+        Some(msg) => [&[0x01], &(msg.len() as u64).to_be_bytes()[..], msg].concat(),
+        None => vec![0x00],
+    };
+    let extra_in = extra_in.unwrap_or_default();
+
+    let mut data = rand;
+    data.push(pubkey.len() as u8);
+    data.extend_from_slice(pubkey);
+    data.push(aggregate_key.len() as u8);
+    data.extend_from_slice(&aggregate_key);
+    data.extend_from_slice(&msg_prefixed);
+    data.extend_from_slice(&(extra_in.len() as u32).to_be_bytes());
+    data.extend_from_slice(extra_in);
+
+    let mut secnonce = [0u8; 97];
+    let mut pubnonce = [0u8; 66];
+    for i in 0..2u8 {
+        let k = Scalar::reduce_bytes(&tagged_hash("MuSig/nonce", &[data.as_slice(), &[i]].concat()));
+        // Only fails with negligible probability
+        assert!(!k.is_zero(), "MuSig2 nonce is zero");
+        let (x, y) = GeneratorTable::multiply_ct(k.to_u256()).to_affine().unwrap();
+        secnonce[32 * i as usize..32 * (i as usize + 1)].copy_from_slice(&k.to_bytes());
+        pubnonce[33 * i as usize..33 * (i as usize + 1)].copy_from_slice(&point_to_bytes(x, y));
+    }
+    data.zeroize();
+    secnonce[64..].copy_from_slice(pubkey);
+    (SecNonce(secnonce), PubNonce(pubnonce))
+}
+
+/// BIP327 NonceAgg: sums the R1s and the R2s of every signer's nonce
+pub fn nonce_agg(pubnonces: &[PubNonce]) -> Result<AggNonce, MusigError> {
+    let mut r1 = JacobianPoint::infinity();
+    let mut r2 = JacobianPoint::infinity();
+    for (index, pubnonce) in pubnonces.iter().enumerate() {
+        let (r1_i, r2_i) = pubnonce.points().ok_or(MusigError::InvalidPubNonce(index))?;
+        r1 = r1.add(&r1_i);
+        r2 = r2.add(&r2_i);
+    }
+    let mut aggnonce = [0u8; 66];
+    aggnonce[..33].copy_from_slice(&point_to_bytes_ext(&r1));
+    aggnonce[33..].copy_from_slice(&point_to_bytes_ext(&r2));
+    Ok(AggNonce(aggnonce))
+}
+
+// Like point_from_bytes, but 33 zero bytes decode to the point at infinity
+fn point_from_bytes_ext(bytes: &[u8]) -> Option<JacobianPoint> {
+    if bytes.iter().all(|b| *b == 0) {
+        return Some(JacobianPoint::infinity());
+    }
+    point_from_bytes(bytes)
+}
+
+fn point_to_bytes_ext(point: &JacobianPoint) -> [u8; 33] {
+    match point.to_affine() {
+        Some((x, y)) => point_to_bytes(x, y),
+        None => [0u8; 33],
+    }
+}
@@ -0,0 +1,147 @@
+use primitive_types::U256;
+use crate::ecc::generator_table::GeneratorTable;
+use crate::ecc::jacobian_point::JacobianPoint;
+use crate::ecc::private_key::PrivateKey;
+use crate::ecc::scalar::Scalar;
+use crate::ecc::schnorr_signature::SchnorrSignature;
+use crate::ecc::secp256k1_params::S256Params;
+use crate::ecc::xonly_public_key::challenge;
+use crate::musig::error::MusigError;
+use crate::musig::key_agg::{point_from_bytes, point_to_bytes, KeyAggContext};
+use crate::musig::nonce::{AggNonce, PubNonce, SecNonce};
+use crate::utils::tagged_hash::tagged_hash;
+
+/// The state shared by all signers once the nonces have been exchanged: the
+/// (tweaked) aggregate key, the aggregate nonce and the message. From it each
+/// signer makes a partial signature, and anyone can check and combine them.
+#[derive(Debug, Clone)]
+pub struct Session {
+    key_agg: KeyAggContext,
+    msg: Vec<u8>,
+    // The nonce coefficient
+    b: Scalar,
+    // The final nonce point R = R1 + b * R2
+    r: (U256, U256),
+    // The BIP340 challenge
+    e: Scalar,
+}
+
+impl Session {
+    pub fn new(key_agg: &KeyAggContext, aggnonce: &AggNonce, msg: &[u8]) -> Result<Self, MusigError> {
+        let (qx, _) = key_agg.q();
+        let (r1, r2) = aggnonce.points().ok_or(MusigError::InvalidAggNonce)?;
+
+        // b = int(hash_MuSig/noncecoef(aggnonce || xbytes(Q) || m)) mod n
+        let data = [aggnonce.to_bytes().as_slice(), &qx.to_big_endian(), msg].concat();
+        let b = Scalar::reduce_bytes(&tagged_hash("MuSig/noncecoef", &data));
+
+        // If R is infinity (which an attacker can cause, but not profit from)
+        // G is used instead so that signing can still go ahead
+        let r = r1
+            .add(&r2.multiply(b.to_u256()))
+            .to_affine()
+            .unwrap_or((S256Params::gx(), S256Params::gy()));
+        let e = challenge(r.0, qx, msg);
+        Ok(Self { key_agg: key_agg.clone(), msg: msg.to_vec(), b, r, e })
+    }
+
+    pub fn msg(&self) -> &[u8] {
+        &self.msg
+    }
+
+    /// Makes this signer's partial signature. The secret nonce is consumed,
+    /// so it can't accidentally be used a second time.
+    pub fn sign(&self, secnonce: SecNonce, secret_key: &PrivateKey) -> Result<[u8; 32], MusigError> {
+        let (k1_prime, k2_prime) = match (secnonce.k1(), secnonce.k2()) {
+            (Some(k1), Some(k2)) => (k1, k2),
+            _ => return Err(MusigError::InvalidSecNonce),
+        };
+        // Negate the nonces if R has an odd y, as BIP340 needs an even R
+        let (k1, k2) = if self.r.1.bit(0) { (-k1_prime, -k2_prime) } else { (k1_prime, k2_prime) };
+
+        let d = Scalar::from_u256(secret_key.secret())
+            .filter(|d| !d.is_zero())
+            .ok_or(MusigError::InvalidSecretKey)?;
+        let (px, py) = secret_key.public_point().coordinates().unwrap();
+        let pubkey = point_to_bytes(px, py);
+        if secnonce.pubkey() != pubkey {
+            return Err(MusigError::InvalidSecNonce);
+        }
+        let a = self.key_agg_coeff(&pubkey)?;
+
+        // d = g * gacc * d', where g negates the key if Q has an odd y
+        let d = self.g() * self.key_agg.gacc() * d;
+        let s = k1 + self.b * k2 + self.e * a * d;
+        let psig = s.to_bytes();
+
+        // Check the partial signature before giving it out
+        let r1 = GeneratorTable::multiply_ct(k1_prime.to_u256());
+        let r2 = GeneratorTable::multiply_ct(k2_prime.to_u256());
+        let pubnonce = PubNonce::from_bytes([point_bytes(&r1), point_bytes(&r2)].concat().try_into().unwrap());
+        if !self.partial_sig_verify(&psig, &pubnonce, &pubkey) {
+            return Err(MusigError::PartialSignatureVerificationFailed);
+        }
+        Ok(psig)
+    }
+
+    /// Checks a signer's partial signature against their public nonce and
+    /// public key: s * G = R* + e * a * g' * P
+    pub fn partial_sig_verify(&self, psig: &[u8; 32], pubnonce: &PubNonce, pubkey: &[u8; 33]) -> bool {
+        let s = match Scalar::from_bytes(psig) {
+            Some(s) => s,
+            None => return false,
+        };
+        let (r1, r2) = match pubnonce.points() {
+            Some(points) => points,
+            None => return false,
+        };
+        let point = match point_from_bytes(pubkey) {
+            Some(point) => point,
+            None => return false,
+        };
+        let a = match self.key_agg_coeff(pubkey) {
+            Ok(a) => a,
+            Err(_) => return false,
+        };
+
+        let r_star = r1.add(&r2.multiply(self.b.to_u256()));
+        let r_star = if self.r.1.bit(0) { r_star.negate() } else { r_star };
+        let g = self.g() * self.key_agg.gacc();
+        let expected = r_star.add(&point.multiply((self.e * a * g).to_u256()));
+        GeneratorTable::multiply(s.to_u256()).add(&expected.negate()).is_infinity()
+    }
+
+    /// Sums the partial signatures into a BIP340 signature for the aggregate
+    /// key. This doesn't check the partial signatures; an invalid one gives
+    /// an invalid signature.
+    pub fn partial_sig_agg(&self, psigs: &[[u8; 32]]) -> Result<SchnorrSignature, MusigError> {
+        let mut s = Scalar::zero();
+        for (index, psig) in psigs.iter().enumerate() {
+            s = s + Scalar::from_bytes(psig).ok_or(MusigError::InvalidPartialSignature(index))?;
+        }
+        // Account for the tweaks: s + e * g * tacc
+        let s = s + self.e * self.g() * self.key_agg.tacc();
+        Ok(SchnorrSignature::new(self.r.0, s.to_u256()))
+    }
+
+    // -1 if the aggregate key has an odd y, so its x-only form stands for -Q
+    fn g(&self) -> Scalar {
+        if self.key_agg.q().1.bit(0) {
+            -Scalar::one()
+        } else {
+            Scalar::one()
+        }
+    }
+
+    fn key_agg_coeff(&self, pubkey: &[u8; 33]) -> Result<Scalar, MusigError> {
+        if !self.key_agg.pubkeys().contains(pubkey) {
+            return Err(MusigError::PublicKeyNotFound);
+        }
+        Ok(self.key_agg.key_agg_coeff(pubkey))
+    }
+}
+
+fn point_bytes(point: &JacobianPoint) -> [u8; 33] {
+    let (x, y) = point.to_affine().unwrap();
+    point_to_bytes(x, y)
+}
@@ -1,7 +1,8 @@
 use std::collections::HashMap;
 
+use crate::ecc::batch_verifier::BatchVerifier;
 use crate::ecc::point::Point;
-use crate::ecc::signature::Signature;
+use crate::ecc::signature::{DerMode, Signature};
 use crate::utils::hash256::hash256;
 use crate::utils::hash160::hash160;
 
@@ -184,8 +185,30 @@ pub fn op_hash256(stack: &mut Vec<Vec<u8>>) -> bool {
     true
 }
 
+// Parses a signature as pushed by a script: DER followed by the sighash type
+// byte. In strict mode the encoding must be BIP66 DER, s must be low and the
+// sighash type must be one of ALL, NONE or SINGLE (optionally ANYONECANPAY).
+fn parse_script_signature(raw: &[u8], mode: DerMode) -> Option<Signature> {
+    let (sighash_type, der) = raw.split_last()?;
+    match mode {
+        DerMode::Strict => {
+            if !(1..=3).contains(&(sighash_type & !0x80)) {
+                return None;
+            }
+            let signature = Signature::parse_der(der, DerMode::Strict).ok()?;
+            signature.check_low_s().ok()?;
+            Some(signature)
+        }
+        DerMode::Lax => Signature::parse_der(der, DerMode::Lax).ok(),
+    }
+}
+
 // 172 - OP_CHECKSIG
-fn op_checksig(stack: &mut Vec<Vec<u8>>, z: Vec<u8>) -> bool {
+// With a batch verifier, the check is added to the batch and assumed to pass.
+// The script is then only valid if the whole batch verifies. Script::run only
+// passes the batch to the script's last opcode, as later opcodes would act on
+// a result that hasn't been checked yet.
+fn op_checksig(stack: &mut Vec<Vec<u8>>, z: Vec<u8>, mode: DerMode, batch: Option<&mut BatchVerifier>) -> bool {
     if stack.len() < 2 {
         return false;
     }
@@ -194,10 +217,27 @@ fn op_checksig(stack: &mut Vec<Vec<u8>>, z: Vec<u8>) -> bool {
     let pub_key = stack.pop().unwrap();
     let signature_bytes = stack.pop().unwrap();
     
-    // 1. Convert pub_key bytes to S256Point
-    let pubkey_point = Point::parse_to_s256_point(pub_key);
-    // 2. Convert signature bytes to Signature
-    let signature = Signature::parse(signature_bytes);
+    // 1. Convert pub_key bytes to S256Point. An invalid key fails the check.
+    let pubkey_point = match Point::from_sec(&pub_key) {
+        Ok(point) => point,
+        Err(_) => {
+            stack.push(encode_num(0));
+            return false;
+        }
+    };
+    // 2. Convert signature bytes to Signature, failing on a bad encoding
+    let signature = match parse_script_signature(&signature_bytes, mode) {
+        Some(signature) => signature,
+        None => {
+            stack.push(encode_num(0));
+            return false;
+        }
+    };
+    if let Some(batch) = batch {
+        batch.add_ecdsa(pubkey_point, z, signature);
+        stack.push(encode_num(1));
+        return true;
+    }
     // 3. Verify signature using point.verify(z, signature)
     let result = pubkey_point.verify(z, signature);
     
@@ -211,11 +251,15 @@ fn op_checksig(stack: &mut Vec<Vec<u8>>, z: Vec<u8>) -> bool {
 }
 
 // 174 - OP_CHECKMULTISIG
-fn op_checkmultisig(stack: &mut Vec<Vec<u8>>, z: Vec<u8>) -> bool {
+// Which signature goes with which key depends on the result of each check, so
+// these are always verified straight away rather than batched
+fn op_checkmultisig(stack: &mut Vec<Vec<u8>>, z: Vec<u8>, mode: DerMode, _batch: Option<&mut BatchVerifier>) -> bool {
     if stack.is_empty() {
         return false;
     }
-    let n: usize = decode_num(&stack.pop().unwrap()).try_into().unwrap();
+    let Ok(n) = usize::try_from(decode_num(&stack.pop().unwrap())) else {
+        return false;
+    };
     if stack.len() < n + 1 {
         return false;
     }
@@ -223,28 +267,28 @@ fn op_checkmultisig(stack: &mut Vec<Vec<u8>>, z: Vec<u8>) -> bool {
     for _ in 0..n {
         sec_pubkeys.push(stack.pop().unwrap());
     }
-    let m: usize = decode_num(&stack.pop().unwrap()).try_into().unwrap();
+    let Ok(m) = usize::try_from(decode_num(&stack.pop().unwrap())) else {
+        return false;
+    };
     if stack.len() < m + 1 {
         return false;
     }
-    let mut der_signatures: Vec<Vec<u8>> = vec![];
+    let mut signatures: Vec<Signature> = vec![];
     for _ in 0..m {
-        // remove the final byte (sig hash) of the stack item first
-        let signature = stack.pop().unwrap();
-        let signature_bytes = &signature[..signature.len()-1];
-        der_signatures.push(signature_bytes.to_vec());
+        // the final byte of the stack item is the sig hash
+        match parse_script_signature(&stack.pop().unwrap(), mode) {
+            Some(signature) => signatures.push(signature),
+            None => return false,
+        }
     }
     stack.pop();
 
     // Multisignature verification
+    // An invalid key can't match any signature, so it is skipped
     let pubkey_points = sec_pubkeys
         .into_iter()
-        .map(Point::parse_to_s256_point);
-    
-    let signatures: Vec<Signature> = der_signatures
-        .into_iter()
-        .map(Signature::parse)
-        .collect();
+        .filter_map(|sec| Point::from_sec(&sec).ok());
+
 
     // Verify each signature
     let mut sig_index: usize = 0;
@@ -270,6 +314,7 @@ fn op_checkmultisig(stack: &mut Vec<Vec<u8>>, z: Vec<u8>) -> bool {
 }
 
 // type StackOpFunc = fn(&mut Vec<Vec<u8>>, &mut Vec<Vec<u8>>) -> bool;
+type StackSigOpFunc = fn(&mut Vec<Vec<u8>>, Vec<u8>, DerMode, Option<&mut BatchVerifier>) -> bool;
 
 #[derive(Clone)]
 pub enum OpFunction {
@@ -278,7 +323,7 @@ pub enum OpFunction {
     // StackAltStackOp(StackOpFunc),
     // StackHashOp(fn(&mut Vec<Vec<u8>>) -> bool), // has the same signature as StackOp anyway
     // StackLocktimeSequenceOp(fn(&mut Vec<Vec<u8>>, u32, u32) -> bool),
-    StackSigOp(fn(&mut Vec<Vec<u8>>, Vec<u8>) -> bool),
+    StackSigOp(StackSigOpFunc),
 }
 
 // keys are in decimal
@@ -1,18 +1,39 @@
 use std::fmt;
 use std::io::{Cursor, Error, Read};
 
+use crate::ecc::batch_verifier::BatchVerifier;
+use crate::ecc::signature::DerMode;
 use crate::script::op::{self, create_op_code_names, encode_num, OpFunction};
-use crate::utils::varint::{encode_varint, read_varint};
+use crate::utils::varint::{encode_varint, read_bytes, read_varint};
 
 use sha2::{Digest, Sha256};
 
+/// A script command: an opcode, or an element the script pushes. Keeping
+/// the two apart means a one byte push, like the 32 in OP_SIZE 32
+/// OP_EQUALVERIFY, is never read as the opcode with the same value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Op(u8),
+    Data(Vec<u8>),
+}
+
+impl Command {
+    /// The pushed element, or None for an opcode
+    pub fn data(&self) -> Option<&[u8]> {
+        match self {
+            Command::Op(_) => None,
+            Command::Data(data) => Some(data),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct Script {
-    pub commands: Vec<Vec<u8>>,
+    pub commands: Vec<Command>,
 }
 
 impl Script {
-    pub fn new(commands: Vec<Vec<u8>>) -> Self {
+    pub fn new(commands: Vec<Command>) -> Self {
         Self { commands }
     }
 
@@ -34,28 +55,22 @@ impl Script {
             if (1..=75).contains(&current_byte) {
                 // the next n bytes are an element
                 let n = current_byte;
-                let mut cmd = vec![0u8; n as usize];
-                reader.read_exact(&mut cmd)?;
-                commands.push(cmd);
+                commands.push(Command::Data(read_bytes(reader, n as u64)?));
                 count += n as u64;
             } else if current_byte == 76 {
                 // op_pushdata1, so the next byte tells us how many bytes to read
                 let data_length = read_varint(reader)?;
-                let mut cmd = vec![0u8; data_length as usize];
-                reader.read_exact(&mut cmd)?;
-                commands.push(cmd);
+                commands.push(Command::Data(read_bytes(reader, data_length)?));
                 count += data_length + 1;
             } else if current_byte == 77 {
                 // op_pushdata2, so the next two bytes tells us how many bytes to read
                 let data_length = read_varint(reader)?;
-                let mut cmd = vec![0u8; data_length as usize];
-                reader.read_exact(&mut cmd)?;
-                commands.push(cmd);
+                commands.push(Command::Data(read_bytes(reader, data_length)?));
                 count += data_length + 2;
             } else {
                 // it is an op_code we add to the stack
                 let op_code = current_byte;
-                commands.push(vec![op_code]);
+                commands.push(Command::Op(op_code));
             }
         }
 
@@ -88,20 +103,22 @@ impl Script {
                 let position_after_varint = reader.position();
                 let varint_length = position_after_varint - position_before_varint;
 
+                if height_length > 4 {
+                    return Err(Error::new(std::io::ErrorKind::InvalidData, "BIP-34 height is longer than 4 bytes"));
+                }
                 let mut padded_bytes = [0u8; 4];
-                let mut height_bytes = vec![0u8; height_length as usize];
-                reader.read_exact(&mut height_bytes)?;
+                let height_bytes = read_bytes(reader, height_length)?;
                 let bytes_clone = height_bytes.clone();
                 padded_bytes[..height_length as usize].copy_from_slice(&bytes_clone);
                 let height = u32::from_le_bytes(padded_bytes);
-                commands.push(height_bytes);
+                commands.push(Command::Data(height_bytes));
 
                 // Read the remaining arbitrary data
-                let remaining_length = length - height_length - varint_length;
+                let remaining_length = length
+                    .checked_sub(height_length + varint_length)
+                    .ok_or_else(|| Error::new(std::io::ErrorKind::InvalidData, "BIP-34 height is longer than the script_sig"))?;
                 if remaining_length > 0 {
-                    let mut arbitrary_data = vec![0u8; remaining_length as usize];
-                    reader.read_exact(&mut arbitrary_data)?;
-                    commands.push(arbitrary_data);
+                    commands.push(Command::Data(read_bytes(reader, remaining_length)?));
                 }
 
                 let script = Self { commands };
@@ -110,31 +127,32 @@ impl Script {
         }
     }
 
-    fn raw_serialize(&self) -> Vec<u8> {
+    /// Serializes the script without its length prefix, as it is hashed
+    /// into P2SH and P2WSH outputs
+    pub fn raw_serialize(&self) -> Vec<u8> {
         let mut result = vec![];
         for cmd in &self.commands {
-            if cmd.len() == 1 {
-                // must be an opcode
-                let op_code = cmd[0];
-                result.push(op_code);
-            } else {
-                let length = cmd.len();
-                if length < 76 {
-                    // encode length as a single byte
-                    result.push(length as u8);
-                } else if length <= 0xff {
-                    // op_pushdata1, then encode length as a byte
-                    result.push(76);
-                    result.push(length as u8);
-                } else if length <= 520 {
-                    // op_pushdata2, then encode length as two bytes
-                    result.push(77);
-                    result.extend_from_slice(&length.to_le_bytes()[..2]);
-                } else {
-                    // if it's longer than 520 bytes it's invalid - error
-                    panic!("too long a cmd");
+            match cmd {
+                Command::Op(op_code) => result.push(*op_code),
+                Command::Data(cmd) => {
+                    let length = cmd.len();
+                    if length < 76 {
+                        // encode length as a single byte
+                        result.push(length as u8);
+                    } else if length <= 0xff {
+                        // op_pushdata1, then encode length as a byte
+                        result.push(76);
+                        result.push(length as u8);
+                    } else if length <= 520 {
+                        // op_pushdata2, then encode length as two bytes
+                        result.push(77);
+                        result.extend_from_slice(&length.to_le_bytes()[..2]);
+                    } else {
+                        // if it's longer than 520 bytes it's invalid - error
+                        panic!("too long a cmd");
+                    }
+                    result.extend_from_slice(cmd);
                 }
-                result.extend_from_slice(cmd);
             }
         }
         result
@@ -179,7 +197,7 @@ impl Script {
     // Serialize a BIP-34 script by appending the arbitrary data without a length varint
     fn raw_serialize_bip_34(&self) -> Vec<u8> {
         let mut result = vec![];
-        let height_cmd = &self.commands[0];
+        let height_cmd = self.commands[0].data().expect("BIP-34 height is a push");
         let length = height_cmd.len();
         if length < 76 {
             // encode length as a single byte
@@ -198,8 +216,10 @@ impl Script {
         }
         result.extend_from_slice(height_cmd);
 
-        let arb_data = &self.commands[1];
-        result.extend_from_slice(arb_data);
+        match &self.commands[1] {
+            Command::Op(op_code) => result.push(*op_code),
+            Command::Data(arb_data) => result.extend_from_slice(arb_data),
+        }
 
         result
     }
@@ -211,93 +231,133 @@ impl Script {
     }
 
     pub fn evaluate(self, z: Vec<u8>, witness: Option<Vec<Vec<u8>>>) -> bool {
+        self.evaluate_with_mode(z, witness, DerMode::Lax)
+    }
+
+    /// Evaluates the script, parsing signatures for OP_CHECKSIG and
+    /// OP_CHECKMULTISIG with the given DER mode. With DerMode::Strict,
+    /// non-canonical signatures fail the check (BIP66 and low S).
+    pub fn evaluate_with_mode(self, z: Vec<u8>, witness: Option<Vec<Vec<u8>>>, mode: DerMode) -> bool {
+        self.run(z, witness, mode, None)
+    }
+
+    /// Evaluates the script, but adds a final OP_CHECKSIG check to the batch
+    /// instead of verifying it. Checks whose result later opcodes consume
+    /// are verified straight away. A true result only means the script is
+    /// valid once the batch has also been verified.
+    pub fn evaluate_deferred(self, z: Vec<u8>, witness: Option<Vec<Vec<u8>>>, mode: DerMode, batch: &mut BatchVerifier) -> bool {
+        self.run(z, witness, mode, Some(batch))
+    }
+
+    fn run(self, z: Vec<u8>, witness: Option<Vec<Vec<u8>>>, mode: DerMode, mut batch: Option<&mut BatchVerifier>) -> bool {
         let mut commands = self.commands.clone();
         let mut stack = vec![];
         // let altstack = vec![];
         while !commands.is_empty() {
             let cmd = commands.remove(0);
-            let is_op_code = cmd.len() == 1; // if the command is of length 1, evaluate it as an op_code
-            if is_op_code {
-                let op_code = cmd[0];
-                let names = op::create_op_code_names();
-                let op_name = *names.get(&op_code).unwrap();
-                dbg!(&op_name);
-                let operations = op::create_op_code_functions();
-                let op_function = operations.get(&op_code).unwrap().clone();
-                let operation_result: bool = match op_function {
-                    OpFunction::StackOp(func) => func(&mut stack),
-                    OpFunction::StackSigOp(func) => func(&mut stack, z.clone()),
-                };
-
-                if !operation_result {
-                    dbg!(format!("bad op: {op_name}"));
-                    return false;
-                }
-            } else {
-                // Handle data element by pushing to stack
-                stack.push(cmd.clone());
-
-                // Check for P2SH
-                if commands.len() == 3 &&
-                commands[0][0] == 0xa9 && // OP_HASH160
-                commands[1].len() > 1 && // redeem script is a data element
-                commands[1].len() == 20 && // redeem script is 20 bytes long
-                commands[2][0] == 0x87
-                {
-                    // OP_EQUAL
-                    commands.pop();
-                    let h160 = commands.pop().unwrap();
-                    commands.pop();
-                    let op_hash160_result = op::op_hash160(&mut stack);
-                    if !op_hash160_result {
+            match cmd {
+                Command::Op(op_code) => {
+                    let names = op::create_op_code_names();
+                    let operations = op::create_op_code_functions();
+                    // Opcodes without an implementation fail the script
+                    let (Some(op_name), Some(op_function)) = (names.get(&op_code).copied(), operations.get(&op_code).cloned()) else {
                         return false;
-                    }
-                    stack.push(h160);
-                    let op_equal_result = op::op_equal(&mut stack);
-                    if !op_equal_result {
-                        return false;
-                    }
-                    let op_verify_result = op::op_verify(&mut stack);
-                    if !op_verify_result {
-                        dbg!("bad p2sh h160");
+                    };
+                    dbg!(&op_name);
+                    let operation_result: bool = match op_function {
+                        OpFunction::StackOp(func) => func(&mut stack),
+                        // A deferred check is assumed to pass, so only the
+                        // last opcode, whose result is the script's, can be
+                        // batched
+                        OpFunction::StackSigOp(func) => {
+                            let batch = if commands.is_empty() { batch.as_deref_mut() } else { None };
+                            func(&mut stack, z.clone(), mode, batch)
+                        }
+                    };
+
+                    if !operation_result {
+                        dbg!(format!("bad op: {op_name}"));
                         return false;
                     }
-                    let mut redeem_script = encode_varint(cmd.len() as u64);
-                    redeem_script.extend(cmd);
-                    let mut stream = Cursor::new(redeem_script);
-                    commands.extend(Script::parse(&mut stream).unwrap().get_commands());
                 }
+                Command::Data(cmd) => {
+                    // Handle data element by pushing to stack
+                    stack.push(cmd.clone());
+
+                    // Check for P2SH
+                    if commands.len() == 3 &&
+                    commands[0] == Command::Op(0xa9) && // OP_HASH160
+                    commands[1].data().is_some_and(|h160| h160.len() == 20) && // 20 byte redeem script hash
+                    commands[2] == Command::Op(0x87)
+                    {
+                        // OP_EQUAL
+                        commands.pop();
+                        let Some(Command::Data(h160)) = commands.pop() else {
+                            return false;
+                        };
+                        commands.pop();
+                        let op_hash160_result = op::op_hash160(&mut stack);
+                        if !op_hash160_result {
+                            return false;
+                        }
+                        stack.push(h160);
+                        let op_equal_result = op::op_equal(&mut stack);
+                        if !op_equal_result {
+                            return false;
+                        }
+                        let op_verify_result = op::op_verify(&mut stack);
+                        if !op_verify_result {
+                            dbg!("bad p2sh h160");
+                            return false;
+                        }
+                        let mut redeem_script = encode_varint(cmd.len() as u64);
+                        redeem_script.extend(cmd);
+                        let mut stream = Cursor::new(redeem_script);
+                        match Script::parse(&mut stream) {
+                            Ok(redeem_script) => commands.extend(redeem_script.get_commands()),
+                            Err(_) => return false,
+                        }
+                    }
 
-                // Could change these conditions to use the specific methods we're using below
-                // Check for native segwit (P2WPKH)
-                if stack.len() == 2 && stack[0] == vec![] && stack[1].len() == 20 {
-                    let h160 = stack.pop().unwrap();
-                    stack.pop();
-                    commands.extend(witness.clone().unwrap());
-                    commands.extend(Script::p2pkh_script(h160).get_commands());
-                }
+                    // Could change these conditions to use the specific methods we're using below
+                    // Check for native segwit (P2WPKH)
+                    if stack.len() == 2 && stack[0] == vec![] && stack[1].len() == 20 {
+                        let Some(witness) = witness.clone() else {
+                            return false;
+                        };
+                        let h160 = stack.pop().unwrap();
+                        stack.pop();
+                        commands.extend(witness.into_iter().map(Command::Data));
+                        commands.extend(Script::p2pkh_script(h160).get_commands());
+                    }
 
-                // Check for P2WSH
-                if stack.len() == 2 && stack[0] == vec![] && stack[1].len() == 32 {
-                    let s256 = stack.pop().unwrap();
-                    stack.pop();
-                    let witness = witness.clone().unwrap();
-                    commands.extend_from_slice(&witness[..witness.len() - 1]);
-                    let witness_script = &witness[witness.len() - 1];
-                    let witness_hash = Sha256::digest(witness_script).to_vec();
-                    if s256 != witness_hash {
-                        println!(
-                            "Bad SHA256.\n{}\n{}",
-                            hex::encode(s256),
-                            hex::encode(witness_hash)
-                        );
-                        return false;
+                    // Check for P2WSH
+                    if stack.len() == 2 && stack[0] == vec![] && stack[1].len() == 32 {
+                        let witness = match witness.clone() {
+                            Some(witness) if !witness.is_empty() => witness,
+                            _ => return false,
+                        };
+                        let s256 = stack.pop().unwrap();
+                        stack.pop();
+                        commands.extend(witness[..witness.len() - 1].iter().cloned().map(Command::Data));
+                        let witness_script = &witness[witness.len() - 1];
+                        let witness_hash = Sha256::digest(witness_script).to_vec();
+                        if s256 != witness_hash {
+                            println!(
+                                "Bad SHA256.\n{}\n{}",
+                                hex::encode(s256),
+                                hex::encode(witness_hash)
+                            );
+                            return false;
+                        }
+                        let mut stream = encode_varint(witness_script.len() as u64);
+                        stream.extend_from_slice(witness_script);
+                        let mut stream = Cursor::new(stream);
+                        match Script::parse(&mut stream) {
+                            Ok(witness_script) => commands.extend(witness_script.commands),
+                            Err(_) => return false,
+                        }
                     }
-                    let mut stream = encode_varint(witness_script.len() as u64);
-                    stream.extend_from_slice(witness_script);
-                    let mut stream = Cursor::new(stream);
-                    let witness_script_commands = Script::parse(&mut stream).unwrap().commands;
-                    commands.extend_from_slice(&witness_script_commands);
                 }
             }
         }
@@ -314,98 +374,93 @@ impl Script {
     pub fn p2pkh_script(h160: Vec<u8>) -> Self {
         let raw_hash = h160;
         // OP_DUP, OP_HASH160, hash160 data element, OP_EQUALVERIFY, OP_CHECKSIG
-        let commands: Vec<Vec<u8>> = vec![vec![0x76], vec![0xa9], raw_hash, vec![0x88], vec![0xac]];
+        let commands = vec![Command::Op(0x76), Command::Op(0xa9), Command::Data(raw_hash), Command::Op(0x88), Command::Op(0xac)];
         // script_pubkey
         Script::new(commands)
     }
 
+    /// Takes a hash160 of a redeem script and returns the p2sh script_pubkey
+    pub fn p2sh_script(h160: Vec<u8>) -> Self {
+        // OP_HASH160, hash160 data element, OP_EQUAL
+        Self::new(vec![Command::Op(0xa9), Command::Data(h160), Command::Op(0x87)])
+    }
+
     /// Takes a hash160 and returns the p2wpkh script_pubkey
     pub fn p2wpkh_script(h160: Vec<u8>) -> Self {
-        Self::new(vec![vec![0x00], h160])
+        Self::new(vec![Command::Op(0x00), Command::Data(h160)])
     }
 
     /// Takes a hash256 and returns the p2wsh script_pubkey
     pub fn p2wsh_script(h256: Vec<u8>) -> Self {
-        Self::new(vec![vec![0x00], h256])
+        Self::new(vec![Command::Op(0x00), Command::Data(h256)])
     }
 
-    pub fn get_commands(self) -> Vec<Vec<u8>> {
+    /// Takes a 32 byte x-only output key and returns the p2tr script_pubkey
+    pub fn p2tr_script(output_key: Vec<u8>) -> Self {
+        // OP_1, output key
+        Self::new(vec![Command::Op(0x51), Command::Data(output_key)])
+    }
+
+    pub fn get_commands(self) -> Vec<Command> {
         self.commands
     }
 
+    /// Returns the redeem script of a P2SH script_sig, its last element
     pub fn get_redeem_script(&self) -> Self {
+        let command = self.commands.last().unwrap().data().unwrap();
         let mut redeem_script = vec![];
-        redeem_script.extend_from_slice(&encode_varint(self.commands[3].len() as u64));
-        redeem_script.extend_from_slice(&self.commands[3]);
+        redeem_script.extend_from_slice(&encode_varint(command.len() as u64));
+        redeem_script.extend_from_slice(command);
         let mut stream = Cursor::new(redeem_script);
         Self::parse(&mut stream).unwrap()
     }
 
     pub fn is_p2pk_script_pubkey(&self) -> bool {
         // pubkey followed by OP_CHECKSIG
-        let first_element_data = self.commands[0].len() > 1;
-        let second_element_checksig = self.commands[1] == vec![0xac];
-
-        first_element_data && second_element_checksig
+        matches!(self.commands.as_slice(), [Command::Data(sec), Command::Op(0xac)] if sec.len() > 1)
     }
 
     pub fn is_p2wpkh_script_pubkey(&self) -> bool {
         // OP_0 and a 20 byte hash
-        let length_2 = self.commands.len() == 2;
-        let first_byte_zero = self.commands[0][0] == 0x00;
-        let second_element_data = self.commands[1].len() > 1;
-        let data_20_long = self.commands[1].len() == 20;
-
-        length_2 && first_byte_zero && second_element_data && data_20_long
+        matches!(self.commands.as_slice(), [Command::Op(0x00), Command::Data(h160)] if h160.len() == 20)
     }
 
     pub fn is_p2wsh_script_pubkey(&self) -> bool {
         // OP_0 and a 32 byte (SHA256) hash
-        let length_2 = self.commands.len() == 2;
-        let first_byte_zero = self.commands[0][0] == 0x00;
-        let second_element_data = self.commands[1].len() > 1;
-        let data_32_long = self.commands[1].len() == 32;
-
-        length_2 && first_byte_zero && second_element_data && data_32_long
+        matches!(self.commands.as_slice(), [Command::Op(0x00), Command::Data(h256)] if h256.len() == 32)
     }
 
     pub fn is_p2sh_script_pubkey(&self) -> bool {
         // OP_HASH160, 20 byte hash, OP_EQUAL
-        self.commands[0][0] == 0xa9 && // OP_HASH160
-        self.commands[1].len() > 1 && // hash is a data element
-        self.commands[1].len() == 20 && // hash is 20 bytes long
-        self.commands[2][0] == 0x87 // OP_EQUAL
+        matches!(self.commands.as_slice(), [Command::Op(0xa9), Command::Data(h160), Command::Op(0x87)] if h160.len() == 20)
     }
 
     pub fn is_p2sh_script_sig(&self) -> bool {
         // what do we know about the lengths of these elements?
         // OP_0, signature, pubkey, redeem script
-        self.commands[0][0] == 0x00 && // OP_0
-        self.commands[1].len() > 1 && // signature script is a data element
-        self.commands[2].len() > 1 && // pubkey is a data element
-        self.commands[3].len() > 1 // redeem script is a data element
+        matches!(
+            self.commands.as_slice(),
+            [Command::Op(0x00), Command::Data(sig), Command::Data(sec), Command::Data(redeem_script), ..]
+                if sig.len() > 1 && sec.len() > 1 && redeem_script.len() > 1
+        )
     }
 
     pub fn is_p2tr_script_pubkey(&self) -> bool {
         // OP_1, data
-        self.commands[0][0] == 0x51 && // OP_1
-        self.commands[1].len() > 1 // data element
+        matches!(self.commands.as_slice(), [Command::Op(0x51), Command::Data(key)] if key.len() > 1)
     }
 
     pub fn is_p2pkh_script_pubkey(&self) -> bool {
         // OP_DUP, OP_HASH160, data, OP_EQUALVERIFY, OP_CHECKSIG
-        // let commands: Vec<Vec<u8>> = vec![vec![0x76], vec![0xa9], raw_hash, vec![0x88], vec![0xac]];
-        self.commands[0][0] == 0x76 && // OP_DUP
-        self.commands[1][0] == 0xa9 && // OP_HASH160
-        self.commands[2].len() > 1 && // data
-        self.commands[3][0] == 0x88 && // OP_EQUALVERIFY
-        self.commands[4][0] == 0xac // OP_CHECKSIG
+        matches!(
+            self.commands.as_slice(),
+            [Command::Op(0x76), Command::Op(0xa9), Command::Data(h160), Command::Op(0x88), Command::Op(0xac)] if h160.len() > 1
+        )
     }
 
     pub fn is_redeem_script(&self) -> bool {
-        // Possibly a redeem script
-        self.commands.len() == 1 && // contains one element
-        self.commands[0].len() > 1 // element is a data element
+        // Possibly a redeem script: a single data element
+        matches!(self.commands.as_slice(), [Command::Data(redeem_script)] if redeem_script.len() > 1)
     }
 
     pub fn script_type(&self) -> String {
@@ -430,15 +485,16 @@ impl fmt::Display for Script {
         writeln!(f, "  Script type: {}", self.script_type())?;
         writeln!(f, "  Length: {} byte(s)", self.raw_serialize().len())?;
         writeln!(f, "  Data:")?;
-        self.commands.iter().try_fold((), |_, cmd| {
-            if cmd.len() == 1 {
+        self.commands.iter().try_fold((), |_, cmd| match cmd {
+            Command::Op(op_code) => {
                 let op_name = op_code_names
-                    .get(&cmd[0])
-                    .map_or(format!("NO OP CODE FOUND ({})", cmd[0]), |name| {
+                    .get(op_code)
+                    .map_or(format!("NO OP CODE FOUND ({})", op_code), |name| {
                         name.to_string()
                     });
                 writeln!(f, "\t     {} ", op_name)
-            } else {
+            }
+            Command::Data(cmd) => {
                 let mut hex_string = String::with_capacity(cmd.len() * 2);
                 let mut ascii_string = String::with_capacity(cmd.len());
                 
@@ -469,15 +525,16 @@ impl fmt::Debug for Script {
         writeln!(f, "    Length: {} byte(s)", self.raw_serialize().len())?;
         writeln!(f, "    Data:")?;
         
-        self.commands.iter().try_fold((), |_, cmd| {
-            if cmd.len() == 1 {
+        self.commands.iter().try_fold((), |_, cmd| match cmd {
+            Command::Op(op_code) => {
                 let op_name = op_code_names
-                    .get(&cmd[0])
-                    .map_or(format!("NO OP CODE FOUND ({})", cmd[0]), |name| {
+                    .get(op_code)
+                    .map_or(format!("NO OP CODE FOUND ({})", op_code), |name| {
                         name.to_string()
                     });
                 writeln!(f, "        {} ", op_name)
-            } else {
+            }
+            Command::Data(cmd) => {
                 let mut hex_string = String::with_capacity(cmd.len() * 2);
                 let mut ascii_string = String::with_capacity(cmd.len());
                 
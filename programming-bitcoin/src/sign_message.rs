@@ -0,0 +1,2 @@
+pub mod bip322;
+pub mod legacy;
@@ -0,0 +1,186 @@
+use std::io::{Cursor, Read};
+use crate::address::address::address_to_script_pubkey;
+use crate::ecc::private_key::PrivateKey;
+use crate::script::script::{Command, Script};
+use crate::transactions::tx::Tx;
+use crate::transactions::tx_input::TxInput;
+use crate::transactions::tx_output::TxOutput;
+use crate::utils::base64::{decode_base64, encode_base64};
+use crate::utils::hash160::hash160;
+use crate::utils::sig_hash_type::SigHashType;
+use crate::utils::tagged_hash::tagged_hash;
+use crate::utils::varint::{encode_varint, read_varint};
+
+// BIP322 proves control of an address by spending a virtual output locked to
+// that address' script_pubkey (the message challenge). The output belongs to
+// the to_spend tx, which commits to the message, and is spent by the to_sign
+// tx. Neither is ever broadcast. Verification is ordinary script evaluation
+// of to_sign through Tx::verify_input, so only the script types it handles
+// (P2PKH, P2SH-P2WPKH, P2WPKH and P2WSH) are supported.
+
+pub fn message_hash(message: &[u8]) -> Vec<u8> {
+    tagged_hash("BIP0322-signed-message", message)
+}
+
+/// Builds the virtual to_spend tx: a single input spending the null outpoint
+/// with OP_0 <message_hash>, and a single 0 value output paying to the
+/// message challenge
+pub fn to_spend(message_challenge: &Script, message: &[u8]) -> Tx {
+    let script_sig = Script::new(vec![Command::Op(0x00), Command::Data(message_hash(message))]);
+    let input = TxInput::new([0u8; 32], 0xffffffffu32.to_le_bytes(), script_sig, [0u8; 4], None, None);
+    let output = TxOutput::new(0, message_challenge.clone());
+    Tx::new(0, vec![input], vec![output], 0, false, false)
+}
+
+/// Builds the virtual to_sign tx spending output 0 of to_spend, with a single
+/// 0 value OP_RETURN output. It is a segwit tx when a witness is given. The
+/// input carries the output it spends, as to_spend can't be fetched.
+pub fn to_sign(to_spend: &Tx, script_sig: Script, witness: Option<Vec<Vec<u8>>>) -> Tx {
+    let prev_tx_id: [u8; 32] = hex::decode(to_spend.id()).unwrap().try_into().unwrap();
+    let segwit = witness.is_some();
+    let input = TxInput::new(prev_tx_id, [0u8; 4], script_sig, [0u8; 4], witness, None)
+        .with_prev_output(to_spend.tx_outs[0].clone());
+    let output = TxOutput::new(0, Script::new(vec![Command::Op(0x6a)]));
+    Tx::new(0, vec![input], vec![output], 0, false, segwit)
+}
+
+/// Signs a message with the simple format: the Base64 encoded witness of
+/// to_sign. Only addresses that are spent entirely by witness (P2WPKH here)
+/// can use it.
+pub fn sign_simple(private_key: &PrivateKey, address: &str, message: &[u8]) -> Result<String, String> {
+    let (script_pubkey, _) = address_to_script_pubkey(address)?;
+    if !script_pubkey.is_p2wpkh_script_pubkey() {
+        return Err("The simple format needs a P2WPKH address".to_string());
+    }
+    let tx = sign_to_sign(private_key, &script_pubkey, message)?;
+    Ok(encode_base64(&encode_witness(tx.tx_ins[0].witness.as_ref().unwrap())))
+}
+
+/// Signs a message with the full format: the Base64 encoded to_sign tx.
+/// Works for P2PKH, P2SH-P2WPKH and P2WPKH addresses.
+pub fn sign_full(private_key: &PrivateKey, address: &str, message: &[u8]) -> Result<String, String> {
+    let (script_pubkey, _) = address_to_script_pubkey(address)?;
+    let tx = sign_to_sign(private_key, &script_pubkey, message)?;
+    Ok(encode_base64(&tx.serialize()))
+}
+
+pub fn verify_simple(address: &str, message: &[u8], signature: &str) -> Result<bool, String> {
+    let (script_pubkey, _) = address_to_script_pubkey(address)?;
+    let witness = decode_witness(&decode_base64(signature)?)?;
+    let to_spend = to_spend(&script_pubkey, message);
+    let tx = to_sign(&to_spend, Script::new(vec![]), Some(witness));
+    Ok(verify_to_sign(&tx))
+}
+
+pub fn verify_full(address: &str, message: &[u8], signature: &str) -> Result<bool, String> {
+    verify_full_with_lookup(address, message, signature, &TxInput::fetch_prev_output)
+}
+
+/// Like verify_full, but finds the outputs spent by proof of funds inputs
+/// with the given lookup, which is passed the input and the address' network
+pub fn verify_full_with_lookup(
+    address: &str,
+    message: &[u8],
+    signature: &str,
+    lookup: &impl Fn(&TxInput, bool) -> Result<TxOutput, String>,
+) -> Result<bool, String> {
+    let (script_pubkey, testnet) = address_to_script_pubkey(address)?;
+    let raw = decode_base64(signature)?;
+    let length = raw.len() as u64;
+    let mut cursor = Cursor::new(raw);
+    let mut tx = Tx::try_parse(&mut cursor, testnet).map_err(|e| e.to_string())?;
+    if cursor.position() != length {
+        return Err("Trailing data after to_sign".to_string());
+    }
+    let to_spend = to_spend(&script_pubkey, message);
+
+    // to_sign must spend to_spend's output as its first input and have a
+    // single empty OP_RETURN output. Any other inputs are real UTXOs (a
+    // proof of funds), and are verified like any other input.
+    let first_input = match tx.tx_ins.first() {
+        Some(input) => input,
+        None => return Ok(false),
+    };
+    let spends_to_spend = first_input.get_prev_tx_id_be() == to_spend.id()
+        && first_input.prev_index == [0u8; 4];
+    let outputs = tx.get_tx_outs();
+    let op_return_output = outputs.len() == 1
+        && outputs[0].get_amount() == 0
+        && outputs[0].get_script_pubkey().serialize() == Script::new(vec![Command::Op(0x6a)]).serialize();
+    if !(tx.version == 0 || tx.version == 2) || !spends_to_spend || !op_return_output {
+        return Ok(false);
+    }
+    tx.tx_ins[0].prev_output = Some(to_spend.tx_outs[0].clone());
+    for input in tx.tx_ins.iter_mut().skip(1) {
+        match lookup(input, testnet) {
+            Ok(prev_output) => input.prev_output = Some(prev_output),
+            Err(_) => return Ok(false),
+        }
+    }
+    Ok(verify_to_sign(&tx))
+}
+
+// Verifies every input of to_sign, whose inputs all carry the outputs they
+// spend
+fn verify_to_sign(to_sign: &Tx) -> bool {
+    (0..to_sign.tx_ins.len()).all(|index| to_sign.verify_input(SigHashType::SigHashAll, index))
+}
+
+// Builds and signs to_sign for a key that can spend the message challenge
+fn sign_to_sign(private_key: &PrivateKey, script_pubkey: &Script, message: &[u8]) -> Result<Tx, String> {
+    let sec = private_key.public_point().clone().sec(true);
+    let h160 = hash160(&sec);
+    let to_spend = to_spend(script_pubkey, message);
+
+    let sign = |z: Vec<u8>| [private_key.sign(z).der(), vec![SigHashType::SigHashAll as u8]].concat();
+    let p2wpkh = Script::p2wpkh_script(h160.clone());
+    let mut tx;
+    if script_pubkey.is_p2wpkh_script_pubkey() && script_pubkey.commands[1].data() == Some(&h160[..]) {
+        tx = to_sign(&to_spend, Script::new(vec![]), Some(vec![]));
+        let z = tx.sig_hash_bip143(0, None, None);
+        tx.tx_ins[0].witness = Some(vec![sign(z), sec]);
+    } else if script_pubkey.is_p2sh_script_pubkey() && script_pubkey.commands[1].data() == Some(&hash160(&p2wpkh.serialize()[1..])[..]) {
+        // The script_sig pushes the P2WPKH redeem script
+        let script_sig = Script::new(vec![Command::Data(p2wpkh.serialize()[1..].to_vec())]);
+        tx = to_sign(&to_spend, script_sig, Some(vec![]));
+        let z = tx.sig_hash_bip143(0, Some(p2wpkh), None);
+        tx.tx_ins[0].witness = Some(vec![sign(z), sec]);
+    } else if script_pubkey.is_p2pkh_script_pubkey() && script_pubkey.commands[2].data() == Some(&h160[..]) {
+        tx = to_sign(&to_spend, Script::new(vec![]), None);
+        let z = tx.sig_hash(&SigHashType::SigHashAll, 0, false);
+        tx.tx_ins[0].script_sig = Script::new(vec![Command::Data(sign(z)), Command::Data(sec)]);
+    } else {
+        return Err("The private key cannot sign for this address".to_string());
+    }
+    Ok(tx)
+}
+
+/// Serializes a witness stack: the number of items, then each item with its
+/// length, all as varints
+pub fn encode_witness(witness: &[Vec<u8>]) -> Vec<u8> {
+    let mut result = encode_varint(witness.len() as u64);
+    for item in witness {
+        result.extend(encode_varint(item.len() as u64));
+        result.extend_from_slice(item);
+    }
+    result
+}
+
+pub fn decode_witness(raw: &[u8]) -> Result<Vec<Vec<u8>>, String> {
+    let mut cursor = Cursor::new(raw.to_vec());
+    let count = read_varint(&mut cursor).map_err(|e| e.to_string())?;
+    let mut witness = vec![];
+    for _ in 0..count {
+        let length = read_varint(&mut cursor).map_err(|e| e.to_string())? as usize;
+        if length > raw.len() {
+            return Err("Witness item is longer than the witness".to_string());
+        }
+        let mut item = vec![0u8; length];
+        cursor.read_exact(&mut item).map_err(|e| e.to_string())?;
+        witness.push(item);
+    }
+    if cursor.position() as usize != raw.len() {
+        return Err("Trailing data after witness".to_string());
+    }
+    Ok(witness)
+}
@@ -0,0 +1,101 @@
+use primitive_types::U256;
+use crate::address::address::address_to_script_pubkey;
+use crate::ecc::private_key::PrivateKey;
+use crate::ecc::recoverable_signature::RecoverableSignature;
+use crate::ecc::signature::Signature;
+use crate::script::script::Script;
+use crate::utils::base64::{decode_base64, encode_base64};
+use crate::utils::hash160::hash160;
+use crate::utils::hash256::hash256;
+use crate::utils::varint::encode_varint;
+
+const MESSAGE_PREFIX: &str = "Bitcoin Signed Message:\n";
+
+/// The address type a legacy signature claims to be for. BIP137 encodes it
+/// in the header byte, on top of the recovery id.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MessageAddressType {
+    P2pkhUncompressed,
+    P2pkh,
+    P2shP2wpkh,
+    P2wpkh,
+}
+
+impl MessageAddressType {
+    // The header byte is this base plus the recovery id
+    fn header_base(self) -> u8 {
+        match self {
+            MessageAddressType::P2pkhUncompressed => 27,
+            MessageAddressType::P2pkh => 31,
+            MessageAddressType::P2shP2wpkh => 35,
+            MessageAddressType::P2wpkh => 39,
+        }
+    }
+
+    fn from_header(header: u8) -> Result<Self, String> {
+        match header {
+            27..=30 => Ok(MessageAddressType::P2pkhUncompressed),
+            31..=34 => Ok(MessageAddressType::P2pkh),
+            35..=38 => Ok(MessageAddressType::P2shP2wpkh),
+            39..=42 => Ok(MessageAddressType::P2wpkh),
+            _ => Err(format!("Invalid signed message header: {}", header)),
+        }
+    }
+
+    pub fn compressed(self) -> bool {
+        self != MessageAddressType::P2pkhUncompressed
+    }
+}
+
+/// The hash that gets signed: hash256 of the prefix and the message, each
+/// preceded by its length as a varint
+pub fn signed_message_hash(message: &str) -> Vec<u8> {
+    let mut data = encode_varint(MESSAGE_PREFIX.len() as u64);
+    data.extend_from_slice(MESSAGE_PREFIX.as_bytes());
+    data.extend(encode_varint(message.len() as u64));
+    data.extend_from_slice(message.as_bytes());
+    hash256(&data)
+}
+
+/// Signs a message, returning the 65 byte BIP137 signature as Base64
+pub fn sign_message(private_key: &PrivateKey, message: &str, address_type: MessageAddressType) -> String {
+    let signature = private_key.sign_recoverable(signed_message_hash(message));
+    let mut raw = signature.serialize_compact(false);
+    raw[0] = address_type.header_base() + signature.recovery_id();
+    encode_base64(&raw)
+}
+
+/// Verifies a Base64 signature against a P2PKH, P2SH-P2WPKH or P2WPKH address.
+/// The public key is recovered from the signature, and the signature is
+/// valid if that key (compressed or not, as the header says) hashes to the
+/// address. Like most wallets, this accepts a P2PKH header for segwit
+/// addresses, since older software only knew the P2PKH headers.
+pub fn verify_message(address: &str, signature: &str, message: &str) -> Result<bool, String> {
+    let raw = decode_base64(signature)?;
+    if raw.len() != 65 {
+        return Err(format!("Invalid signature length: {}", raw.len()));
+    }
+    let address_type = MessageAddressType::from_header(raw[0])?;
+    let recovery_id = (raw[0] - 27) & 3;
+    let r = U256::from_big_endian(&raw[1..33]);
+    let s = U256::from_big_endian(&raw[33..65]);
+    let signature = RecoverableSignature::new(Signature::new(r, s), recovery_id)?;
+
+    let point = match signature.recover(signed_message_hash(message)) {
+        Ok(point) => point,
+        Err(_) => return Ok(false),
+    };
+    let h160 = hash160(&point.sec(address_type.compressed()));
+    let (script_pubkey, _) = address_to_script_pubkey(address)?;
+
+    let p2wpkh = Script::p2wpkh_script(h160.clone());
+    let p2sh_p2wpkh = Script::p2sh_script(hash160(&p2wpkh.serialize()[1..]));
+    let candidates = match address_type {
+        MessageAddressType::P2pkhUncompressed => vec![Script::p2pkh_script(h160)],
+        MessageAddressType::P2pkh => vec![Script::p2pkh_script(h160), p2sh_p2wpkh, p2wpkh],
+        MessageAddressType::P2shP2wpkh => vec![p2sh_p2wpkh],
+        MessageAddressType::P2wpkh => vec![p2wpkh],
+    };
+    let script_pubkey = script_pubkey.serialize();
+    Ok(candidates.iter().any(|candidate| candidate.serialize() == script_pubkey))
+}
@@ -0,0 +1,5 @@
+pub mod control_block;
+pub mod error;
+pub mod spend_info;
+pub mod tap_tree;
+pub mod tweak;
@@ -0,0 +1,87 @@
+use crate::ecc::xonly_public_key::XOnlyPublicKey;
+use crate::taproot::tap_tree::{tap_branch_hash, TapLeaf};
+use crate::taproot::tweak::tweak_public_key;
+
+// The control block may hold at most 128 merkle path hashes
+const MAX_PATH_LENGTH: usize = 128;
+
+/// The last witness element of a script path spend. It proves that the
+/// leaf being executed is committed to by the output key.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ControlBlock {
+    leaf_version: u8,
+    output_key_parity: bool,
+    internal_key: XOnlyPublicKey,
+    merkle_path: Vec<Vec<u8>>,
+}
+
+impl ControlBlock {
+    pub fn new(leaf_version: u8, output_key_parity: bool, internal_key: XOnlyPublicKey, merkle_path: Vec<Vec<u8>>) -> Self {
+        Self {
+            leaf_version,
+            output_key_parity,
+            internal_key,
+            merkle_path,
+        }
+    }
+
+    pub fn leaf_version(&self) -> u8 {
+        self.leaf_version
+    }
+
+    pub fn output_key_parity(&self) -> bool {
+        self.output_key_parity
+    }
+
+    pub fn internal_key(&self) -> XOnlyPublicKey {
+        self.internal_key
+    }
+
+    pub fn merkle_path(&self) -> &Vec<Vec<u8>> {
+        &self.merkle_path
+    }
+
+    // (leaf_version | parity) || internal key || path hashes
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = vec![self.leaf_version | self.output_key_parity as u8];
+        result.extend(self.internal_key.serialize());
+        for hash in &self.merkle_path {
+            result.extend_from_slice(hash);
+        }
+        result
+    }
+
+    pub fn parse(raw: &[u8]) -> Result<Self, String> {
+        if raw.len() < 33 || !(raw.len() - 33).is_multiple_of(32) {
+            return Err(format!("Invalid control block length: {}", raw.len()));
+        }
+        let path_length = (raw.len() - 33) / 32;
+        if path_length > MAX_PATH_LENGTH {
+            return Err(format!("Control block merkle path is too long: {}", path_length));
+        }
+        let internal_key = XOnlyPublicKey::parse(&raw[1..33])?;
+        let merkle_path = raw[33..].chunks(32).map(|hash| hash.to_vec()).collect();
+        Ok(Self {
+            leaf_version: raw[0] & 0xfe,
+            output_key_parity: raw[0] & 1 == 1,
+            internal_key,
+            merkle_path,
+        })
+    }
+
+    /// Checks that the leaf, hashed up the merkle path and tweaked into the
+    /// internal key, gives this output key with the recorded parity
+    pub fn verify(&self, output_key: &XOnlyPublicKey, leaf: &TapLeaf) -> bool {
+        if leaf.leaf_version() != self.leaf_version {
+            return false;
+        }
+        let mut hash = leaf.leaf_hash();
+        for sibling in &self.merkle_path {
+            hash = tap_branch_hash(&hash, sibling);
+        }
+        match tweak_public_key(&self.internal_key, Some(&hash)) {
+            Ok((key, parity)) => key == *output_key && parity == self.output_key_parity,
+            Err(_) => false,
+        }
+    }
+}
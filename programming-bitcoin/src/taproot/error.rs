@@ -0,0 +1,19 @@
+use std::fmt;
+
+/// Why a taproot script tree could not be built
+#[derive(Debug, Clone, PartialEq)]
+pub enum TaprootError {
+    // Leaf versions must be even, as the control block keeps the output key
+    // parity in the lowest bit
+    InvalidLeafVersion(u8),
+}
+
+impl fmt::Display for TaprootError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TaprootError::InvalidLeafVersion(version) => write!(f, "invalid leaf version: {:#04x}", version),
+        }
+    }
+}
+
+impl std::error::Error for TaprootError {}
@@ -0,0 +1,78 @@
+use crate::ecc::xonly_public_key::XOnlyPublicKey;
+use crate::script::script::Script;
+use crate::taproot::control_block::ControlBlock;
+use crate::taproot::tap_tree::{TapLeaf, TapTree};
+use crate::taproot::tweak::tweak_public_key;
+use crate::utils::bech32::encode_segwit_address;
+
+/// Everything needed to pay to and spend from a taproot output: the
+/// internal key, the optional script tree and the tweaked output key.
+#[derive(Clone)]
+pub struct TaprootSpendInfo {
+    internal_key: XOnlyPublicKey,
+    tree: Option<TapTree>,
+    merkle_root: Option<Vec<u8>>,
+    output_key: XOnlyPublicKey,
+    output_key_parity: bool,
+}
+
+impl TaprootSpendInfo {
+    pub fn new(internal_key: XOnlyPublicKey, tree: Option<TapTree>) -> Result<Self, String> {
+        let merkle_root = tree.as_ref().map(|tree| tree.merkle_root());
+        let (output_key, output_key_parity) = tweak_public_key(&internal_key, merkle_root.as_deref())?;
+        Ok(Self {
+            internal_key,
+            tree,
+            merkle_root,
+            output_key,
+            output_key_parity,
+        })
+    }
+
+    // An output that can only be spent with the (tweaked) internal key
+    pub fn key_path_only(internal_key: XOnlyPublicKey) -> Result<Self, String> {
+        Self::new(internal_key, None)
+    }
+
+    pub fn internal_key(&self) -> XOnlyPublicKey {
+        self.internal_key
+    }
+
+    pub fn tree(&self) -> Option<&TapTree> {
+        self.tree.as_ref()
+    }
+
+    pub fn merkle_root(&self) -> Option<Vec<u8>> {
+        self.merkle_root.clone()
+    }
+
+    pub fn output_key(&self) -> XOnlyPublicKey {
+        self.output_key
+    }
+
+    pub fn output_key_parity(&self) -> bool {
+        self.output_key_parity
+    }
+
+    pub fn script_pubkey(&self) -> Script {
+        Script::p2tr_script(self.output_key.serialize())
+    }
+
+    // bech32m address with witness version 1
+    pub fn address(&self, testnet: bool) -> String {
+        let hrp = if testnet { "tb" } else { "bc" };
+        encode_segwit_address(hrp, 1, &self.output_key.serialize())
+    }
+
+    /// Returns the control block for spending this leaf by script path, or
+    /// None if the leaf is not in the tree
+    pub fn control_block(&self, leaf: &TapLeaf) -> Option<ControlBlock> {
+        let merkle_path = self.tree.as_ref()?.merkle_path(leaf)?;
+        Some(ControlBlock::new(
+            leaf.leaf_version(),
+            self.output_key_parity,
+            self.internal_key,
+            merkle_path,
+        ))
+    }
+}
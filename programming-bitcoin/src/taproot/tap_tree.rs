@@ -0,0 +1,106 @@
+use crate::script::script::Script;
+use crate::taproot::error::TaprootError;
+use crate::utils::tagged_hash::tagged_hash;
+
+/// The leaf version for tapscript, the only one with consensus meaning so far
+pub const TAPSCRIPT_LEAF_VERSION: u8 = 0xc0;
+
+/// A single script in a taproot script tree
+#[derive(Clone)]
+pub struct TapLeaf {
+    script: Script,
+    leaf_version: u8,
+}
+
+impl TapLeaf {
+    pub fn new(script: Script) -> Self {
+        Self { script, leaf_version: TAPSCRIPT_LEAF_VERSION }
+    }
+
+    // The lowest bit of the version is used for the output key parity in the
+    // control block, so it must be clear
+    pub fn new_with_version(script: Script, leaf_version: u8) -> Result<Self, TaprootError> {
+        if leaf_version & 1 != 0 {
+            return Err(TaprootError::InvalidLeafVersion(leaf_version));
+        }
+        Ok(Self { script, leaf_version })
+    }
+
+    pub fn script(&self) -> &Script {
+        &self.script
+    }
+
+    pub fn leaf_version(&self) -> u8 {
+        self.leaf_version
+    }
+
+    // hash_TapLeaf(leaf_version || compact_size(script) || script)
+    pub fn leaf_hash(&self) -> Vec<u8> {
+        let mut data = vec![self.leaf_version];
+        data.extend(self.script.serialize());
+        tagged_hash("TapLeaf", &data)
+    }
+}
+
+/// Returns hash_TapBranch of two child hashes. The children are sorted first,
+/// so a branch commits to its pair of children but not to their order.
+pub fn tap_branch_hash(a: &[u8], b: &[u8]) -> Vec<u8> {
+    let (left, right) = if a <= b { (a, b) } else { (b, a) };
+    let mut data = left.to_vec();
+    data.extend_from_slice(right);
+    tagged_hash("TapBranch", &data)
+}
+
+/// A binary tree of scripts committed to by a taproot output
+#[derive(Clone)]
+pub enum TapTree {
+    Leaf(TapLeaf),
+    Branch(Box<TapTree>, Box<TapTree>),
+}
+
+impl TapTree {
+    pub fn leaf(script: Script) -> Self {
+        TapTree::Leaf(TapLeaf::new(script))
+    }
+
+    pub fn branch(left: TapTree, right: TapTree) -> Self {
+        TapTree::Branch(Box::new(left), Box::new(right))
+    }
+
+    /// The hash of the root of the tree, which the output key is tweaked with
+    pub fn merkle_root(&self) -> Vec<u8> {
+        match self {
+            TapTree::Leaf(leaf) => leaf.leaf_hash(),
+            TapTree::Branch(left, right) => tap_branch_hash(&left.merkle_root(), &right.merkle_root()),
+        }
+    }
+
+    /// Returns every leaf, left to right, together with its merkle path: the
+    /// sibling hashes from the leaf up to (but not including) the root
+    pub fn leaves_with_paths(&self) -> Vec<(TapLeaf, Vec<Vec<u8>>)> {
+        match self {
+            TapTree::Leaf(leaf) => vec![(leaf.clone(), vec![])],
+            TapTree::Branch(left, right) => {
+                let mut leaves = vec![];
+                for (child, sibling) in [(left, right), (right, left)] {
+                    let sibling_hash = sibling.merkle_root();
+                    for (leaf, mut path) in child.leaves_with_paths() {
+                        path.push(sibling_hash.clone());
+                        leaves.push((leaf, path));
+                    }
+                }
+                leaves
+            }
+        }
+    }
+
+    /// Returns the merkle path of the first leaf matching this script and
+    /// leaf version, or None if the tree does not contain it
+    pub fn merkle_path(&self, leaf: &TapLeaf) -> Option<Vec<Vec<u8>>> {
+        let leaf_hash = leaf.leaf_hash();
+        self.leaves_with_paths()
+            .into_iter()
+            .find(|(candidate, _)| candidate.leaf_hash() == leaf_hash)
+            .map(|(_, path)| path)
+    }
+}
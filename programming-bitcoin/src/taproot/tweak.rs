@@ -0,0 +1,50 @@
+use crate::ecc::generator_table::GeneratorTable;
+use crate::ecc::private_key::PrivateKey;
+use crate::ecc::scalar::Scalar;
+use crate::ecc::xonly_public_key::XOnlyPublicKey;
+use crate::utils::tagged_hash::tagged_hash;
+
+/// Returns the tweak t = hash_TapTweak(internal_key || merkle_root). A key
+/// path only output has no merkle root and hashes just the internal key.
+pub fn tap_tweak_hash(internal_key: &XOnlyPublicKey, merkle_root: Option<&[u8]>) -> Vec<u8> {
+    let mut data = internal_key.serialize();
+    if let Some(root) = merkle_root {
+        data.extend_from_slice(root);
+    }
+    tagged_hash("TapTweak", &data)
+}
+
+/// Computes the output key Q = P + t*G, where P is the internal key lifted to
+/// an even y. Returns Q as an x-only key along with whether its y is odd,
+/// which a control block has to commit to.
+pub fn tweak_public_key(internal_key: &XOnlyPublicKey, merkle_root: Option<&[u8]>) -> Result<(XOnlyPublicKey, bool), String> {
+    let t = tweak_scalar(internal_key, merkle_root)?;
+    let (px, py) = XOnlyPublicKey::lift_x(internal_key.x()).unwrap();
+    match GeneratorTable::multiply(t.to_u256()).add_affine(px, py).to_affine() {
+        Some((qx, qy)) => Ok((XOnlyPublicKey::parse(&qx.to_big_endian())?, qy.bit(0))),
+        None => Err("Tweaked public key is the point at infinity".to_string()),
+    }
+}
+
+/// Tweaks a private key so that it signs for the output key returned by
+/// tweak_public_key. The secret is negated first if its public key has an
+/// odd y, since the internal key is always taken with an even y.
+pub fn tweak_private_key(private_key: &PrivateKey, merkle_root: Option<&[u8]>) -> Result<PrivateKey, String> {
+    let internal_key = private_key.xonly_public_key();
+    let t = tweak_scalar(&internal_key, merkle_root)?;
+    let (_, py) = private_key.public_point().coordinates().unwrap();
+    let secret = Scalar::reduce(private_key.secret());
+    let d = if py.bit(0) { -secret } else { secret };
+
+    let tweaked = d + t;
+    if tweaked.is_zero() {
+        return Err("Tweaked private key is zero".to_string());
+    }
+    Ok(PrivateKey::new(tweaked.to_u256()))
+}
+
+// The tweak as a scalar, which BIP341 requires to be below n
+fn tweak_scalar(internal_key: &XOnlyPublicKey, merkle_root: Option<&[u8]>) -> Result<Scalar, String> {
+    let hash: [u8; 32] = tap_tweak_hash(internal_key, merkle_root).try_into().unwrap();
+    Scalar::from_bytes(&hash).ok_or("Taproot tweak is not below the curve order".to_string())
+}
@@ -2,4 +2,5 @@ pub mod tx;
 pub mod tx_output;
 pub mod tx_input;
 pub mod tx_fetcher;
-pub mod input_signing_data;
\ No newline at end of file
+pub mod input_signing_data;
+pub mod nonce_audit;
\ No newline at end of file
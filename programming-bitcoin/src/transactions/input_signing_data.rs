@@ -6,7 +6,9 @@ pub struct InputSigningData {
     pub index: usize,
     pub private_key_str: String,
     pub sig_hash_type: SigHashType,
-    pub input: TxInput
+    pub input: TxInput,
+    // Grind for a low R signature, as Bitcoin Core does
+    pub low_r: bool
 }
 
 impl InputSigningData {
@@ -15,7 +17,13 @@ impl InputSigningData {
             index,
             private_key_str,
             sig_hash_type,
-            input
+            input,
+            low_r: false
         }
     }
+
+    pub fn with_low_r(mut self) -> Self {
+        self.low_r = true;
+        self
+    }
 }
@@ -0,0 +1,242 @@
+use std::collections::BTreeMap;
+use primitive_types::U256;
+
+use crate::ecc::nonce_reuse::recover_private_key;
+use crate::ecc::point::Point;
+use crate::ecc::private_key::PrivateKey;
+use crate::ecc::signature::{DerMode, Signature};
+use crate::script::script::{Command, Script};
+use crate::transactions::tx::Tx;
+use crate::transactions::tx_input::TxInput;
+use crate::transactions::tx_output::TxOutput;
+use crate::utils::sig_hash_type::SigHashType;
+
+// Scans signed transactions for ECDSA nonce reuse. Inputs that spend a single
+// key (P2PK, P2PKH, P2WPKH and P2SH-P2WPKH) are reduced to their signature,
+// public key and sighash; two signatures by one key with the same r and
+// different sighashes give the key away. The outputs being spent are fetched
+// unless the inputs carry them, and inputs whose output can't be found are
+// skipped. So are inputs whose signature doesn't verify against the sighash
+// computed here, as a wrong sighash would make nonce reuse look harmless.
+
+/// An input of a transaction
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct InputRef {
+    pub txid: String,
+    pub index: usize,
+}
+
+/// The signature of a single-key input, with the public key and the sighash
+/// it signs
+#[derive(Debug, Clone)]
+pub struct InputSignature {
+    pub input: InputRef,
+    pub z: Vec<u8>,
+    pub signature: Signature,
+    pub public_key: Point,
+}
+
+impl InputSignature {
+    /// Extracts the signature of the input at this index. Returns None for
+    /// coinbase inputs, inputs whose prevout can't be found, inputs that
+    /// aren't single-key spends, signatures with a sighash type this crate
+    /// can't compute, and signatures that don't verify.
+    pub fn from_input(tx: &Tx, index: usize) -> Option<Self> {
+        Self::from_input_with_lookup(tx, index, &TxInput::fetch_prev_output)
+    }
+
+    /// Like from_input, but finds the output the input spends with the given
+    /// lookup, which is passed the input and the tx's network
+    pub fn from_input_with_lookup(
+        tx: &Tx,
+        index: usize,
+        lookup: &impl Fn(&TxInput, bool) -> Result<TxOutput, String>,
+    ) -> Option<Self> {
+        if tx.is_coinbase() {
+            return None;
+        }
+        // The sighashes below look the prevout up again, so it is given to
+        // the input once it has been found
+        let prev_output = lookup(tx.tx_ins.get(index)?, tx.testnet).ok()?;
+        let mut tx = tx.clone();
+        tx.tx_ins[index].prev_output = Some(prev_output.clone());
+        let tx = &tx;
+        let input = &tx.tx_ins[index];
+        let script_pubkey = prev_output.get_script_pubkey();
+        let commands = &script_pubkey.commands;
+        let script_sig = &input.script_sig.commands;
+        let witness = input.witness.clone().unwrap_or_default();
+
+        // Tx::sig_hash only computes SIGHASH_ALL, and serializes segwit txs
+        // with their witness, so it can't give the sighash of a legacy input
+        // in a segwit tx
+        let legacy = !tx.segwit;
+        let (sig, sec, z) = if legacy && commands.len() == 5 && script_pubkey.is_p2pkh_script_pubkey() {
+            let [Command::Data(sig), Command::Data(sec)] = script_sig.as_slice() else { return None };
+            if sig.last() != Some(&(SigHashType::SigHashAll as u8)) {
+                return None;
+            }
+            (sig, sec.as_slice(), tx.sig_hash(&SigHashType::SigHashAll, index, false))
+        } else if legacy && commands.len() == 2 && script_pubkey.is_p2pk_script_pubkey() {
+            let [Command::Data(sig)] = script_sig.as_slice() else { return None };
+            if sig.last() != Some(&(SigHashType::SigHashAll as u8)) {
+                return None;
+            }
+            (sig, commands[0].data()?, tx.sig_hash(&SigHashType::SigHashAll, index, false))
+        } else if commands.len() == 2 && script_pubkey.is_p2wpkh_script_pubkey() {
+            let [sig, sec] = witness.as_slice() else { return None };
+            // sig_hash_bip143 only computes SIGHASH_ALL
+            if sig.last() != Some(&(SigHashType::SigHashAll as u8)) {
+                return None;
+            }
+            (sig, sec.as_slice(), tx.sig_hash_bip143(index, None, None))
+        } else if commands.len() == 3 && script_pubkey.is_p2sh_script_pubkey() {
+            // The script_sig is just the redeem script OP_0 <20-byte hash>
+            let [Command::Data(raw_redeem_script)] = script_sig.as_slice() else { return None };
+            if raw_redeem_script.len() != 22 || raw_redeem_script[..2] != [0x00, 0x14] {
+                return None;
+            }
+            let redeem_script = Script::new(vec![Command::Op(0x00), Command::Data(raw_redeem_script[2..].to_vec())]);
+            let [sig, sec] = witness.as_slice() else { return None };
+            if sig.last() != Some(&(SigHashType::SigHashAll as u8)) {
+                return None;
+            }
+            (sig, sec.as_slice(), tx.sig_hash_bip143(index, Some(redeem_script), None))
+        } else {
+            return None;
+        };
+
+        let signature = Signature::parse_der(&sig[..sig.len() - 1], DerMode::Lax).ok()?;
+        let public_key = Point::from_sec(sec).ok()?;
+        if !public_key.clone().verify(z.clone(), signature.clone()) {
+            return None;
+        }
+        Some(Self {
+            input: InputRef { txid: tx.id(), index },
+            z,
+            signature,
+            public_key,
+        })
+    }
+}
+
+/// One key signing more than once with the same nonce
+#[derive(Debug)]
+pub struct NonceReuseFinding {
+    pub r: U256,
+    // Compressed SEC of the key that reused the nonce
+    pub public_key: Vec<u8>,
+    pub inputs: Vec<InputRef>,
+    // None if every signature was over the same sighash, which doesn't leak
+    // the key (yet)
+    pub private_key: Option<PrivateKey>,
+}
+
+/// The result of auditing a set of transactions
+#[derive(Debug, Default)]
+pub struct NonceAuditReport {
+    pub nonce_reuse: Vec<NonceReuseFinding>,
+    // r values used by more than one key. That alone doesn't reveal the keys,
+    // but it means the signer's nonce generation is broken.
+    pub shared_nonces: Vec<(U256, Vec<InputRef>)>,
+    // Signatures with a high s, which a correct signer never produces
+    pub high_s: Vec<InputRef>,
+    // Inputs that could not be audited
+    pub skipped: Vec<InputRef>,
+}
+
+impl NonceAuditReport {
+    /// True if nothing was flagged. Skipped inputs don't count.
+    pub fn is_clean(&self) -> bool {
+        self.nonce_reuse.is_empty() && self.shared_nonces.is_empty() && self.high_s.is_empty()
+    }
+
+    /// Returns every flagged input, sorted and without duplicates
+    pub fn affected_inputs(&self) -> Vec<InputRef> {
+        let mut inputs: Vec<InputRef> = self
+            .nonce_reuse
+            .iter()
+            .flat_map(|finding| finding.inputs.iter().cloned())
+            .chain(self.shared_nonces.iter().flat_map(|(_, inputs)| inputs.iter().cloned()))
+            .chain(self.high_s.iter().cloned())
+            .collect();
+        inputs.sort();
+        inputs.dedup();
+        inputs
+    }
+}
+
+/// Audits every input of the given transactions
+pub fn audit_transactions(txs: &[Tx]) -> NonceAuditReport {
+    audit_transactions_with_lookup(txs, &TxInput::fetch_prev_output)
+}
+
+/// Like audit_transactions, but finds the outputs being spent with the given
+/// lookup
+pub fn audit_transactions_with_lookup(
+    txs: &[Tx],
+    lookup: &impl Fn(&TxInput, bool) -> Result<TxOutput, String>,
+) -> NonceAuditReport {
+    let mut signatures = vec![];
+    let mut skipped = vec![];
+    for tx in txs {
+        for index in 0..tx.tx_ins.len() {
+            match InputSignature::from_input_with_lookup(tx, index, lookup) {
+                Some(signature) => signatures.push(signature),
+                None => skipped.push(InputRef { txid: tx.id(), index }),
+            }
+        }
+    }
+    let mut report = audit_signatures(signatures);
+    skipped.sort();
+    skipped.dedup();
+    report.skipped = skipped;
+    report
+}
+
+/// Audits signatures that were already extracted from their inputs
+pub fn audit_signatures(mut signatures: Vec<InputSignature>) -> NonceAuditReport {
+    // The same transaction may have been passed in twice
+    signatures.sort_by(|a, b| a.input.cmp(&b.input));
+    signatures.dedup_by(|a, b| a.input == b.input);
+
+    let mut report = NonceAuditReport::default();
+    let mut by_r: BTreeMap<U256, BTreeMap<Vec<u8>, Vec<&InputSignature>>> = BTreeMap::new();
+    for signature in &signatures {
+        if !signature.signature.is_low_s() {
+            report.high_s.push(signature.input.clone());
+        }
+        by_r.entry(signature.signature.r())
+            .or_default()
+            .entry(signature.public_key.clone().sec(true))
+            .or_default()
+            .push(signature);
+    }
+
+    for (r, by_key) in by_r {
+        if by_key.len() > 1 {
+            let inputs = by_key.values().flatten().map(|signature| signature.input.clone()).collect();
+            report.shared_nonces.push((r, inputs));
+        }
+        for (public_key, signatures) in by_key {
+            if signatures.len() < 2 {
+                continue;
+            }
+            report.nonce_reuse.push(NonceReuseFinding {
+                r,
+                public_key,
+                inputs: signatures.iter().map(|signature| signature.input.clone()).collect(),
+                private_key: recover_from_any_pair(&signatures),
+            });
+        }
+    }
+    report
+}
+
+// Tries pairs until two signatures over different sighashes turn up
+fn recover_from_any_pair(signatures: &[&InputSignature]) -> Option<PrivateKey> {
+    let first = signatures[0];
+    signatures[1..].iter().find_map(|other| {
+        recover_private_key(&first.z, &first.signature, &other.z, &other.signature, &first.public_key).ok()
+    })
+}
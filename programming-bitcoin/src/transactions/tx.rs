@@ -1,17 +1,22 @@
-use std::io::{Cursor, Read};
+use std::io::{Cursor, Error, ErrorKind, Read};
 use std::fmt;
 use primitive_types::U256;
 
 use crate::ecc::private_key::PrivateKey;
-use crate::script::script::Script;
+use crate::ecc::batch_verifier::BatchVerifier;
+use crate::ecc::signature::DerMode;
+use crate::script::script::{Command, Script};
 use crate::utils::hash256::hash256;
-use crate::utils::varint::{ read_varint, encode_varint };
+use crate::utils::varint::{ read_bytes, read_varint, encode_varint };
 use crate::transactions::tx_input::TxInput;
 use crate::transactions::tx_output::TxOutput;
 use crate::utils::sig_hash_type::SigHashType;
 
 use super::input_signing_data::InputSigningData;
 
+// The combined script to evaluate for an input, with its z and witness
+type InputScript = (Script, Vec<u8>, Option<Vec<Vec<u8>>>);
+
 #[derive(Clone)]
 pub struct Tx {
     pub version: u32,
@@ -121,12 +126,8 @@ impl Tx {
 
                 let witness = input.witness.unwrap();
                 for item in witness {
-                    if item.len() == 1 {
-                        result.extend_from_slice(&item);
-                    } else {
-                        result.extend_from_slice(&encode_varint(item.len() as u64));
-                        result.extend_from_slice(&item);
-                    }
+                    result.extend_from_slice(&encode_varint(item.len() as u64));
+                    result.extend_from_slice(&item);
                 }
             });
 
@@ -136,11 +137,18 @@ impl Tx {
         result
     }
 
+    /// Parses a tx, panicking on malformed bytes. Use try_parse for
+    /// transactions that come from untrusted input.
     pub fn parse(stream: &mut Cursor<Vec<u8>>, testnet: bool) -> Self {
+        Self::try_parse(stream, testnet).unwrap()
+    }
+
+    /// Parses a tx, failing on malformed or truncated bytes
+    pub fn try_parse(stream: &mut Cursor<Vec<u8>>, testnet: bool) -> Result<Self, Error> {
         let current_pos = stream.position();
         stream.set_position(current_pos + 4);
         let mut marker_byte = [0u8; 1];
-        stream.read_exact(&mut marker_byte).unwrap();
+        stream.read_exact(&mut marker_byte)?;
         let marker = marker_byte[0];
         stream.set_position(current_pos);
         // consider using traits to use different methods here
@@ -152,34 +160,32 @@ impl Tx {
     }
 
     /// Parses legacy (pre-segwit) transactions
-    fn parse_legacy(stream: &mut Cursor<Vec<u8>>, testnet: bool) -> Self {
+    fn parse_legacy(stream: &mut Cursor<Vec<u8>>, testnet: bool) -> Result<Self, Error> {
         let mut buffer = [0u8; 4];
-        stream.read_exact(&mut buffer).unwrap();
+        stream.read_exact(&mut buffer)?;
         let version = u32::from_le_bytes(buffer);
 
         // Parse inputs
-        // Add proper error handling
-        let input_count = read_varint(stream).unwrap();
+        let input_count = read_varint(stream)?;
         
         let tx_ins: Vec<TxInput> = (0..input_count)
             .map(|_| {
-                TxInput::parse(stream).unwrap()
+                TxInput::parse(stream)
             })
-            .collect();
+            .collect::<Result<_, _>>()?;
 
         // Parse outputs
-        // Add proper error handling
-        let output_count = read_varint(stream).unwrap();
+        let output_count = read_varint(stream)?;
         
         let tx_outs: Vec<TxOutput> = (0..output_count)
             .map(|_| {
                 TxOutput::parse(stream)
             })
-            .collect();
+            .collect::<Result<_, _>>()?;
 
         // Parse the locktime
         let mut buffer = [0u8; 4];
-        stream.read_exact(&mut buffer).unwrap();
+        stream.read_exact(&mut buffer)?;
         let locktime = u32::from_le_bytes(buffer);
 
         // Parse testnet flag (1 byte) - can we parse this if it's not actually included?
@@ -189,84 +195,73 @@ impl Tx {
         // let testnet = true;
         let segwit = false;
 
-        Self {
+        Ok(Self {
             version,
             tx_ins,
             tx_outs,
             locktime,
             testnet,
             segwit
-        }
+        })
     }
 
-    fn parse_segwit(stream: &mut Cursor<Vec<u8>>, testnet: bool) -> Self {
+    fn parse_segwit(stream: &mut Cursor<Vec<u8>>, testnet: bool) -> Result<Self, Error> {
         // first, read the version
         let mut buffer = [0u8; 4];
-        stream.read_exact(&mut buffer).unwrap();
+        stream.read_exact(&mut buffer)?;
         let version = u32::from_le_bytes(buffer);
 
         // read the next two bytes
         let mut marker_bytes = [0u8; 2];
-        stream.read_exact(&mut marker_bytes).unwrap();
+        stream.read_exact(&mut marker_bytes)?;
         if marker_bytes != [0x00, 0x01] {
-            panic!("Not a segwit transaction - marker bytes: {:?}", marker_bytes);
+            return Err(Error::new(ErrorKind::InvalidData, format!("Not a segwit transaction - marker bytes: {:?}", marker_bytes)));
         }
 
         // Parse inputs
-        // Add proper error handling
-        let input_count = read_varint(stream).unwrap();
+        let input_count = read_varint(stream)?;
         
         let mut tx_ins: Vec<TxInput> = (0..input_count)
             .map(|_| {
-                TxInput::parse(stream).unwrap()
+                TxInput::parse(stream)
             })
-            .collect();
+            .collect::<Result<_, _>>()?;
 
         // Parse outputs
-        // Add proper error handling
-        let output_count = read_varint(stream).unwrap();
+        let output_count = read_varint(stream)?;
         
         let tx_outs: Vec<TxOutput> = (0..output_count)
             .map(|_| {
                 TxOutput::parse(stream)
             })
-            .collect();
+            .collect::<Result<_, _>>()?;
 
         // Parse the witness data for each input
-        tx_ins = tx_ins
-            .into_iter()
-            .map(|input| {
-                let witness_count = read_varint(stream).unwrap();
-                let mut items = vec![];
-                for _ in 0..witness_count {
-                    let length = read_varint(stream).unwrap() as usize;
-                    if length == 0 {
-                        items.push(vec![0]);
-                    } else {
-                        let mut witness_item = vec![0u8; length];
-                        stream.read_exact(&mut witness_item).unwrap();
-                        items.push(witness_item);
-                    }
-                }
-                input.set_witness(Some(items.clone()))
-            })
-            .collect();
+        for input in tx_ins.iter_mut() {
+            let witness_count = read_varint(stream)?;
+            let mut items = vec![];
+            for _ in 0..witness_count {
+                let length = read_varint(stream)?;
+                items.push(read_bytes(stream, length)?);
+            }
+            input.witness = Some(items);
+        }
 
         // Parse the locktime
         let mut buffer = [0u8; 4];
-        stream.read_exact(&mut buffer).unwrap();
+        stream.read_exact(&mut buffer)?;
         let locktime = u32::from_le_bytes(buffer);
 
         let segwit = true;
 
-        Self {
+        Ok(Self {
             version,
             tx_ins,
             tx_outs,
             locktime,
             testnet,
             segwit
-        }
+        })
     }
 
     pub fn fee(&self) -> u64 {
@@ -329,29 +324,71 @@ impl Tx {
     }
 
     pub fn verify_input(&self, sig_hash_type: SigHashType, index: usize) -> bool {
-        let input: &TxInput = &self.tx_ins[index];
-        let script_pubkey = input.script_pubkey(self.testnet);
+        self.verify_input_with_mode(sig_hash_type, index, DerMode::Lax)
+    }
+
+    /// Verifies the input at this index, parsing its signatures with the
+    /// given DER mode
+    pub fn verify_input_with_mode(&self, sig_hash_type: SigHashType, index: usize, mode: DerMode) -> bool {
+        let Some((script, z, witness)) = self.input_script(sig_hash_type, index) else {
+            return false;
+        };
+        script.evaluate_with_mode(z, witness, mode)
+    }
+
+    /// Evaluates the input's script, adding its OP_CHECKSIG checks to the
+    /// batch instead of verifying them. The input is only valid if this
+    /// returns true and the batch verifies.
+    pub fn verify_input_deferred(&self, sig_hash_type: SigHashType, index: usize, mode: DerMode, batch: &mut BatchVerifier) -> bool {
+        let Some((script, z, witness)) = self.input_script(sig_hash_type, index) else {
+            return false;
+        };
+        script.evaluate_deferred(z, witness, mode, batch)
+    }
+
+    /// Verifies every input, checking all the signatures together at the end
+    /// with a BatchVerifier. The error says which input failed.
+    pub fn verify_batched(&self, mode: DerMode) -> Result<(), String> {
+        let mut batch = BatchVerifier::new();
+        // The input each batch entry came from
+        let mut entry_inputs = vec![];
+        for index in 0..self.tx_ins.len() {
+            if !self.verify_input_deferred(SigHashType::SigHashAll, index, mode, &mut batch) {
+                return Err(format!("Script evaluation failed for input {}", index));
+            }
+            entry_inputs.resize(batch.len(), index);
+        }
+        batch
+            .verify()
+            .map_err(|entry| format!("Invalid signature for input {}", entry_inputs[entry]))
+    }
+
+    // Returns the combined script to evaluate for the input at this index,
+    // along with z and the witness. None when the prevout can't be found or
+    // the input is malformed for the script it spends.
+    fn input_script(&self, sig_hash_type: SigHashType, index: usize) -> Option<InputScript> {
+        let input: &TxInput = self.tx_ins.get(index)?;
+        let script_pubkey = input.fetch_prev_output(self.testnet).ok()?.get_script_pubkey();
         // z calculated differently for a segwit tx
         let z: Vec<u8>;
         let witness;
         // could refactor to a match statement
         if script_pubkey.is_p2sh_script_pubkey() {
-            let command = input.script_sig.commands[input.script_sig.commands.len() - 1].clone();
-            let mut raw_redeem = vec![];
-            raw_redeem.extend_from_slice(&[command.len() as u8]);
-            raw_redeem.extend_from_slice(&command);
+            let command = input.script_sig.commands.last()?.data()?;
+            let mut raw_redeem = encode_varint(command.len() as u64);
+            raw_redeem.extend_from_slice(command);
             let mut stream: Cursor<Vec<u8>> =  Cursor::new(raw_redeem);
-            let redeem_script = Script::parse(&mut stream).unwrap();
+            let redeem_script = Script::parse(&mut stream).ok()?;
             if redeem_script.is_p2wpkh_script_pubkey() {
                 z = self.sig_hash_bip143(index, Some(redeem_script), None);
                 witness = input.clone().witness;
             } else if redeem_script.is_p2wsh_script_pubkey() {
-                let input_witness = input.witness.clone().unwrap();
-                let command = &input_witness[input_witness.len() - 1];
+                let input_witness = input.witness.clone()?;
+                let command = input_witness.last()?;
                 let mut raw_witness = encode_varint(command.len() as u64);
                 raw_witness.extend_from_slice(command);
                 let mut stream: Cursor<Vec<u8>> =  Cursor::new(raw_witness);
-                let witness_script = Script::parse(&mut stream).unwrap();
+                let witness_script = Script::parse(&mut stream).ok()?;
                 z = self.sig_hash_bip143(index, None, Some(witness_script));
                 witness = Some(input_witness);
             } else {
@@ -362,12 +399,12 @@ impl Tx {
             z = self.sig_hash_bip143(index, None, None);
             witness = input.clone().witness;
         } else if script_pubkey.is_p2wsh_script_pubkey() {
-            let input_witness = input.witness.clone().unwrap();
-            let command = &input_witness[input_witness.len() - 1];
+            let input_witness = input.witness.clone()?;
+            let command = input_witness.last()?;
             let mut raw_witness = encode_varint(command.len() as u64);
             raw_witness.extend_from_slice(command);
             let mut stream: Cursor<Vec<u8>> =  Cursor::new(raw_witness);
-            let witness_script = Script::parse(&mut stream).unwrap();
+            let witness_script = Script::parse(&mut stream).ok()?;
             z = self.sig_hash_bip143(index, None, Some(witness_script));
             witness = Some(input_witness);
         } else { // legacy tx
@@ -379,23 +416,23 @@ impl Tx {
 
         let script_sig = input.script_sig.clone();
         let combined_script = script_sig.concat(script_pubkey);
-        combined_script.evaluate(z, witness)
+        Some((combined_script, z, witness))
     }
 
     /// Verify the transaction
     pub fn verify(&self) -> bool {
-        // fee() will always be positive as it returns u64
+        self.verify_with_mode(DerMode::Lax)
+    }
 
-        for (index, _) in self.tx_ins.iter().enumerate() {
-            if !self.verify_input(SigHashType::SigHashAll, index) {
-                return false
-            }
-        }
-        true
+    /// Verify the transaction, parsing signatures with the given DER mode.
+    /// The signatures are checked together with a BatchVerifier.
+    pub fn verify_with_mode(&self, mode: DerMode) -> bool {
+        // fee() will always be positive as it returns u64
+        self.verify_batched(mode).is_ok()
     }
 
     /// Returns a byte vector of the signature hash to be signed for the input at this index
-    fn sig_hash_bip143(&self, input_index: usize, redeem_script: Option<Script>, witness_script: Option<Script>) -> Vec<u8> {
+    pub fn sig_hash_bip143(&self, input_index: usize, redeem_script: Option<Script>, witness_script: Option<Script>) -> Vec<u8> {
         // per BIP143 spec
         let tx_in = self.tx_ins[input_index].clone();
 
@@ -411,9 +448,9 @@ impl Tx {
         if witness_script.is_some() {
             script_code = witness_script.unwrap().serialize();
         } else if redeem_script.is_some() {
-            script_code = Script::p2pkh_script(redeem_script.unwrap().commands[1].clone()).serialize()
+            script_code = Script::p2pkh_script(redeem_script.unwrap().commands[1].data().unwrap().to_vec()).serialize()
         } else {
-            script_code = Script::p2pkh_script(tx_in.script_pubkey(self.testnet).commands[1].clone()).serialize();
+            script_code = Script::p2pkh_script(tx_in.script_pubkey(self.testnet).commands[1].data().unwrap().to_vec()).serialize();
         }
         result.extend_from_slice(&script_code);
 
@@ -472,17 +509,28 @@ impl Tx {
     }
 
     pub fn sign_input(&self, index: usize, private_key_str: &str, sig_hash_type: SigHashType, unsigned_input: TxInput) -> TxInput {
+        self.sign_input_with_grinding(index, private_key_str, sig_hash_type, unsigned_input, false)
+    }
+
+    // Like sign_input, but with a low R signature (see PrivateKey::sign_low_r),
+    // which is a byte smaller half of the time
+    pub fn sign_input_low_r(&self, index: usize, private_key_str: &str, sig_hash_type: SigHashType, unsigned_input: TxInput) -> TxInput {
+        self.sign_input_with_grinding(index, private_key_str, sig_hash_type, unsigned_input, true)
+    }
+
+    fn sign_input_with_grinding(&self, index: usize, private_key_str: &str, sig_hash_type: SigHashType, unsigned_input: TxInput, low_r: bool) -> TxInput {
         // signing the tx - getting z
         let z = self.sig_hash(&sig_hash_type, index, false); // assumes not p2sh
         // Private key associated with the public key of the output we are spending from
         let private_key = PrivateKey::new(U256::from_str_radix(private_key_str, 16).unwrap());
-        let der = private_key.sign(z).der();
+        let signature = if low_r { private_key.sign_low_r(z) } else { private_key.sign(z) };
+        let der = signature.der();
 
         // Signature concatenated with the sig hash type as 1 byte
         let sig = [der, vec![sig_hash_type as u8]].concat();
         let sec = private_key.point().sec(true); // assuming compressed is true
         // script sig for p2pkh is the signature, the sig hash and the pub key
-        let script_sig = Script::new(vec![sig, sec]);
+        let script_sig = Script::new(vec![Command::Data(sig), Command::Data(sec)]);
         let mut prev_tx_id = [0u8; 32];
         prev_tx_id.copy_from_slice(&hex::decode(unsigned_input.get_prev_tx_id_be()).unwrap());
         let prev_index = unsigned_input.prev_index;
@@ -490,14 +538,16 @@ impl Tx {
         let witness = unsigned_input.witness;
         let height = unsigned_input.height;
         // return a new signed input
-        TxInput::new(prev_tx_id, prev_index, script_sig, sequence, witness, height)
+        let mut signed_input = TxInput::new(prev_tx_id, prev_index, script_sig, sequence, witness, height);
+        signed_input.prev_output = unsigned_input.prev_output;
+        signed_input
     }
 
     pub fn sign_multiple_inputs(&self, input_signing_data: Vec<InputSigningData>) -> Vec<TxInput> {
         input_signing_data
             .into_iter()
             .map(|data| {
-                self.sign_input(data.index, &data.private_key_str, data.sig_hash_type, data.input)
+                self.sign_input_with_grinding(data.index, &data.private_key_str, data.sig_hash_type, data.input, data.low_r)
             })
             .collect()
     }
@@ -516,7 +566,7 @@ impl Tx {
 
     pub fn coinbase_height(&self) -> u32 {
         let script_sig = &self.tx_ins[0].script_sig;
-        let block_height: &Vec<u8> = &script_sig.commands[0];
+        let block_height = script_sig.commands[0].data().unwrap();
         u32::from_le_bytes(block_height[..4].try_into().unwrap())
     }
 
@@ -34,7 +34,7 @@ impl TxFetcher {
 
             let raw = hex::decode(response_text.trim())?;
             let mut cursor = Cursor::new(raw);
-            let tx = Tx::parse(&mut cursor, testnet);
+            let tx = Tx::try_parse(&mut cursor, testnet)?;
             
             if tx.id() != tx_id {
                 return Err(format!("not the same id: tx.id(): {} vs tx_id: {}", tx.id(), tx_id).into());
@@ -58,7 +58,7 @@ impl TxFetcher {
 
         let raw = hex::decode(response_text.trim())?;
         let mut cursor = Cursor::new(raw);
-        let tx = Tx::parse(&mut cursor, testnet);
+        let tx = Tx::try_parse(&mut cursor, testnet)?;
         
         if tx.id() != tx_id {
             return Err(format!("not the same id: tx.id(): {} vs tx_id: {}", tx.id(), tx_id).into());
@@ -1,8 +1,9 @@
 use std::io::{Cursor, Error, Read};
 
-use crate::{script::script::Script, transactions::tx_fetcher::TxFetcher};
+use crate::{script::script::{Command, Script}, transactions::tx_fetcher::TxFetcher};
 
 use super::tx::Tx;
+use super::tx_output::TxOutput;
 
 #[derive(Clone)]
 pub struct TxInput {
@@ -12,6 +13,9 @@ pub struct TxInput {
     pub sequence: [u8; 4],
     pub witness: Option<Vec<Vec<u8>>>,
     pub height: Option<u32>,
+    // The output being spent, when it is known up front. It is not
+    // serialized, and is looked up by prev_tx_id when None.
+    pub prev_output: Option<TxOutput>,
 }
 
 impl TxInput {
@@ -26,6 +30,7 @@ impl TxInput {
             sequence,
             witness,
             height,
+            prev_output: None,
         }
     }
 
@@ -51,6 +56,7 @@ impl TxInput {
             sequence,
             witness,
             height,
+            prev_output: None,
         })
     }
 
@@ -103,8 +109,24 @@ impl TxInput {
         TxFetcher::fetch(&fetcher, &tx_id_hex, testnet, fresh).unwrap()
     }
 
+    /// Returns the output this input spends, fetching its tx when it isn't
+    /// known. Fails, rather than panicking, when the tx can't be fetched or
+    /// has no output at prev_index.
+    pub fn fetch_prev_output(&self, testnet: bool) -> Result<TxOutput, String> {
+        if let Some(prev_output) = &self.prev_output {
+            return Ok(prev_output.clone());
+        }
+        let fetcher = TxFetcher::build();
+        let tx = fetcher.fetch(&self.get_prev_tx_id_be(), testnet, true).map_err(|e| e.to_string())?;
+        let index = u32::from_le_bytes(self.prev_index) as usize;
+        tx.tx_outs.get(index).cloned().ok_or_else(|| format!("{} has no output {}", tx.id(), index))
+    }
+
     /// Get the output value by looking up the tx hash. Returns the amount in satoshi.
     pub fn value(&self, testnet: bool) -> u64 {
+        if let Some(prev_output) = &self.prev_output {
+            return prev_output.get_amount();
+        }
         let tx = &self.fetch_tx(testnet, true);
         let index = u32::from_le_bytes(self.prev_index) as usize;
         tx.get_tx_outs()[index].get_amount()
@@ -112,7 +134,7 @@ impl TxInput {
 
     /// Returns a TxInput whose script_sig field is empty (0), witness is none
     pub fn empty_script_sig(&self) -> Self {
-        let empty_commands= vec![vec![0]];
+        let empty_commands= vec![Command::Op(0)];
         let empty_script_sig = Script::new(empty_commands);
         Self {
             prev_tx_id: self.prev_tx_id,
@@ -121,11 +143,15 @@ impl TxInput {
             sequence: self.sequence,
             witness: self.witness.clone(),
             height: self.height,
+            prev_output: self.prev_output.clone(),
         }
     } 
 
     /// Get the script_pubkey by looking up the tx hash
     pub fn script_pubkey(&self, testnet: bool) -> Script {
+        if let Some(prev_output) = &self.prev_output {
+            return prev_output.get_script_pubkey();
+        }
         let tx = &self.fetch_tx(testnet, true);
         let index = u32::from_le_bytes(self.prev_index) as usize;
         tx.get_tx_outs()[index].get_script_pubkey()
@@ -144,6 +170,7 @@ impl TxInput {
             sequence: self.sequence,
             witness: self.witness.clone(),
             height: self.height,
+            prev_output: self.prev_output.clone(),
         }
     }
 
@@ -165,6 +192,14 @@ impl TxInput {
         }
     }
 
+    /// Sets the output this input spends, so that its value and
+    /// script_pubkey don't have to be fetched. Used for outputs that are not
+    /// on chain, such as those of BIP322's virtual transactions.
+    pub fn with_prev_output(mut self, prev_output: TxOutput) -> Self {
+        self.prev_output = Some(prev_output);
+        self
+    }
+
     pub fn witness_length(&self) -> u8 {
         self.witness.as_ref().map_or(0, |w| w.len() as u8)
     }
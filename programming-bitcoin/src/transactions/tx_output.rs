@@ -1,5 +1,5 @@
 use std::fmt;
-use std::io::{Cursor, Read};
+use std::io::{Cursor, Error, Read};
 use crate::script::script::Script;
 
 #[derive(Clone, Debug)]
@@ -16,16 +16,16 @@ impl TxOutput {
         }
     }
 
-    pub fn parse(cursor: &mut Cursor<Vec<u8>>) -> Self {
+    pub fn parse(cursor: &mut Cursor<Vec<u8>>) -> Result<Self, Error> {
         let mut amount_buffer= [0u8; 8];
-        cursor.read_exact(&mut amount_buffer).unwrap();
+        cursor.read_exact(&mut amount_buffer)?;
         let amount: u64 = u64::from_le_bytes(amount_buffer); 
-        let script_pubkey = Script::parse(cursor).unwrap();
+        let script_pubkey = Script::parse(cursor)?;
 
-        Self {
+        Ok(Self {
             amount,
             script_pubkey
-        }
+        })
     }
 
     /// Serializes the transaction output into a byte vector
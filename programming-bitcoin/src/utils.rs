@@ -1,6 +1,10 @@
 pub mod base58;
+pub mod base64;
+pub mod bech32;
 pub mod hash160;
 pub mod hash256;
 pub mod rng;
 pub mod varint;
 pub mod sig_hash_type;
+pub mod tagged_hash;
+pub mod hmac_sha512;
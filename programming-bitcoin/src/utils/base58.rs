@@ -1,5 +1,3 @@
-use primitive_types::U256;
-
 use crate::utils::hash256::hash256;
 
 const BASE58_ALPHABET: &str = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
@@ -16,13 +14,24 @@ pub fn encode_base58(bytes: &[u8]) -> String {
             break;
         }
     }
-    let mut num = U256::from_big_endian(bytes);
-    // This is the loop that figures out what Base58 digit to use.
-    while num > U256::zero() {
-        let div_rem = num.div_mod(U256::from(58u32));
-        num = div_rem.0;  // quotient
-        let remainder = div_rem.1;  // remainder
-        result.push(BASE58_ALPHABET.chars().nth(remainder.as_u32() as usize).unwrap());
+    // Convert to base 58 digits, least significant first. This works on the
+    // bytes directly, so payloads longer than 32 bytes (such as WIF keys)
+    // are fine.
+    let mut digits: Vec<u8> = vec![];
+    for byte in &bytes[leading_zeros..] {
+        let mut carry = *byte as u32;
+        for digit in digits.iter_mut() {
+            carry += (*digit as u32) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+    for digit in digits {
+        result.push(BASE58_ALPHABET.chars().nth(digit as usize).unwrap());
     }
     
     for _ in 0..leading_zeros {
@@ -40,6 +49,17 @@ pub fn encode_base58_checksum(bytes: &[u8]) -> String {
 }
 
 pub fn decode_base58(address: &str) -> Result<Vec<u8>, String> {
+    // Return the hash (payload without the version byte)
+    let payload = decode_base58_checksum(address)?;
+    if payload.is_empty() {
+        return Err("Invalid address length".to_string());
+    }
+    Ok(payload[1..].to_vec())
+}
+
+// Decodes a Base58Check string and validates its checksum. Returns the whole
+// payload, including any version byte.
+pub fn decode_base58_checksum(address: &str) -> Result<Vec<u8>, String> {
     // Base58 chars
     let base58_chars: Vec<char> = BASE58_ALPHABET.chars().collect();
     
@@ -87,6 +107,5 @@ pub fn decode_base58(address: &str) -> Result<Vec<u8>, String> {
         return Err("Checksum verification failed".to_string());
     }
 
-    // Return the hash (payload without the version byte)
-    Ok(payload[1..].to_vec())
+    Ok(payload.to_vec())
 }
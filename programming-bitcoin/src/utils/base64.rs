@@ -0,0 +1,46 @@
+// Standard Base64 (RFC 4648) with padding, as used for signed messages
+
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub fn encode_base64(bytes: &[u8]) -> String {
+    let mut result = String::new();
+    for chunk in bytes.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = ((b[0] as u32) << 16) | ((b[1] as u32) << 8) | (b[2] as u32);
+        // Every 3 bytes become 4 characters, padded with '=' at the end
+        for i in 0..4 {
+            if i <= chunk.len() {
+                result.push(BASE64_ALPHABET[((n >> (18 - 6 * i)) & 63) as usize] as char);
+            } else {
+                result.push('=');
+            }
+        }
+    }
+    result
+}
+
+pub fn decode_base64(encoded: &str) -> Result<Vec<u8>, String> {
+    if !encoded.len().is_multiple_of(4) {
+        return Err(format!("Invalid Base64 length: {}", encoded.len()));
+    }
+    let mut result = vec![];
+    let chunks: Vec<&[u8]> = encoded.as_bytes().chunks(4).collect();
+    for (index, chunk) in chunks.iter().enumerate() {
+        let padding = chunk.iter().rev().take_while(|c| **c == b'=').count();
+        if padding > 2 || (padding > 0 && index != chunks.len() - 1) {
+            return Err("Invalid Base64 padding".to_string());
+        }
+        let mut n: u32 = 0;
+        for c in &chunk[..4 - padding] {
+            let value = BASE64_ALPHABET
+                .iter()
+                .position(|a| a == c)
+                .ok_or_else(|| format!("Invalid Base64 character: {}", *c as char))?;
+            n = (n << 6) | value as u32;
+        }
+        n <<= 6 * padding;
+        let bytes = [(n >> 16) as u8, (n >> 8) as u8, n as u8];
+        result.extend_from_slice(&bytes[..3 - padding]);
+    }
+    Ok(result)
+}
@@ -0,0 +1,155 @@
+// Bech32 (BIP173) and Bech32m (BIP350) encoding of segwit addresses.
+// Version 0 witness programs use Bech32, every later version uses Bech32m.
+
+const CHARSET: &str = "qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+const BECH32_CONST: u32 = 1;
+const BECH32M_CONST: u32 = 0x2bc830a3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Variant {
+    Bech32,
+    Bech32m,
+}
+
+impl Variant {
+    fn constant(self) -> u32 {
+        match self {
+            Variant::Bech32 => BECH32_CONST,
+            Variant::Bech32m => BECH32M_CONST,
+        }
+    }
+}
+
+fn polymod(values: &[u8]) -> u32 {
+    const GENERATOR: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+    let mut chk: u32 = 1;
+    for value in values {
+        let top = chk >> 25;
+        chk = ((chk & 0x1ffffff) << 5) ^ (*value as u32);
+        for (i, generator) in GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                chk ^= generator;
+            }
+        }
+    }
+    chk
+}
+
+fn hrp_expand(hrp: &str) -> Vec<u8> {
+    let mut result: Vec<u8> = hrp.bytes().map(|b| b >> 5).collect();
+    result.push(0);
+    result.extend(hrp.bytes().map(|b| b & 31));
+    result
+}
+
+fn create_checksum(hrp: &str, data: &[u8], variant: Variant) -> Vec<u8> {
+    let mut values = hrp_expand(hrp);
+    values.extend_from_slice(data);
+    values.extend_from_slice(&[0u8; 6]);
+    let polymod = polymod(&values) ^ variant.constant();
+    (0..6).map(|i| ((polymod >> (5 * (5 - i))) & 31) as u8).collect()
+}
+
+// Regroups a stream of from_bits-wide values into to_bits-wide values
+fn convert_bits(data: &[u8], from_bits: u32, to_bits: u32, pad: bool) -> Result<Vec<u8>, String> {
+    let mut acc: u32 = 0;
+    let mut bits: u32 = 0;
+    let max_value = (1u32 << to_bits) - 1;
+    let mut result = vec![];
+    for value in data {
+        let value = *value as u32;
+        if value >> from_bits != 0 {
+            return Err(format!("Invalid {}-bit value: {}", from_bits, value));
+        }
+        acc = (acc << from_bits) | value;
+        bits += from_bits;
+        while bits >= to_bits {
+            bits -= to_bits;
+            result.push(((acc >> bits) & max_value) as u8);
+        }
+    }
+    if pad {
+        if bits > 0 {
+            result.push(((acc << (to_bits - bits)) & max_value) as u8);
+        }
+    } else if bits >= from_bits || ((acc << (to_bits - bits)) & max_value) != 0 {
+        return Err("Invalid padding".to_string());
+    }
+    Ok(result)
+}
+
+/// Encodes 5-bit data with the given human readable part
+pub fn encode(hrp: &str, data: &[u8], variant: Variant) -> String {
+    let checksum = create_checksum(hrp, data, variant);
+    let charset: Vec<char> = CHARSET.chars().collect();
+    let mut result = format!("{}1", hrp);
+    for value in data.iter().chain(checksum.iter()) {
+        result.push(charset[*value as usize]);
+    }
+    result
+}
+
+/// Decodes a bech32 or bech32m string into its human readable part and 5-bit
+/// data (without the checksum), along with the variant the checksum matched
+pub fn decode(bech: &str) -> Result<(String, Vec<u8>, Variant), String> {
+    if bech.to_lowercase() != bech && bech.to_uppercase() != bech {
+        return Err("Mixed case string".to_string());
+    }
+    let bech = bech.to_lowercase();
+    let separator = bech.rfind('1').ok_or("Missing separator")?;
+    if separator == 0 || separator + 7 > bech.len() || bech.len() > 90 {
+        return Err("Invalid bech32 length".to_string());
+    }
+    let hrp = &bech[..separator];
+    if hrp.bytes().any(|b| !(33..=126).contains(&b)) {
+        return Err("Invalid human readable part".to_string());
+    }
+    let mut data = vec![];
+    for c in bech[separator + 1..].chars() {
+        let value = CHARSET.find(c).ok_or_else(|| format!("Invalid bech32 character: {}", c))?;
+        data.push(value as u8);
+    }
+    let mut values = hrp_expand(hrp);
+    values.extend_from_slice(&data);
+    let variant = match polymod(&values) {
+        BECH32_CONST => Variant::Bech32,
+        BECH32M_CONST => Variant::Bech32m,
+        _ => return Err("Checksum verification failed".to_string()),
+    };
+    data.truncate(data.len() - 6);
+    Ok((hrp.to_string(), data, variant))
+}
+
+/// Encodes a witness program as a segwit address, e.g. hrp "bc" for mainnet
+/// and "tb" for testnet
+pub fn encode_segwit_address(hrp: &str, witness_version: u8, program: &[u8]) -> String {
+    let variant = if witness_version == 0 { Variant::Bech32 } else { Variant::Bech32m };
+    let mut data = vec![witness_version];
+    data.extend(convert_bits(program, 8, 5, true).unwrap());
+    encode(hrp, &data, variant)
+}
+
+/// Decodes a segwit address, returning the witness version and program
+pub fn decode_segwit_address(hrp: &str, address: &str) -> Result<(u8, Vec<u8>), String> {
+    let (decoded_hrp, data, variant) = decode(address)?;
+    if decoded_hrp != hrp {
+        return Err(format!("Unexpected human readable part: {}", decoded_hrp));
+    }
+    if data.is_empty() || data[0] > 16 {
+        return Err("Invalid witness version".to_string());
+    }
+    let witness_version = data[0];
+    let program = convert_bits(&data[1..], 5, 8, false)?;
+    if program.len() < 2 || program.len() > 40 {
+        return Err(format!("Invalid witness program length: {}", program.len()));
+    }
+    if witness_version == 0 && program.len() != 20 && program.len() != 32 {
+        return Err(format!("Invalid version 0 witness program length: {}", program.len()));
+    }
+    let expected = if witness_version == 0 { Variant::Bech32 } else { Variant::Bech32m };
+    if variant != expected {
+        return Err("Wrong checksum variant for witness version".to_string());
+    }
+    Ok((witness_version, program))
+}
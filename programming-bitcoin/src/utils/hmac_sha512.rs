@@ -0,0 +1,9 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha512;
+
+// HMAC-SHA512, as used by BIP32 key derivation and BIP39 seeds
+pub fn hmac_sha512(key: &[u8], data: &[u8]) -> [u8; 64] {
+    let mut hmac = Hmac::<Sha512>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    hmac.update(data);
+    hmac.finalize().into_bytes().into()
+}
@@ -1,6 +1,6 @@
 /// Varint is shorthand for variable integer, which is a way to encode an
 /// integer into bytes that range from 0 to 2^64 – 1.
-use std::io::{Cursor, Read, Error};
+use std::io::{Cursor, Read, Error, ErrorKind};
 
 /// Reads a varint from a cursor
 pub fn read_varint(cursor: &mut Cursor<Vec<u8>>) -> Result<u64, Error> {
@@ -31,6 +31,18 @@ pub fn read_varint(cursor: &mut Cursor<Vec<u8>>) -> Result<u64, Error> {
     }
 }
 
+/// Reads a length-prefixed field's bytes from a cursor. A length longer than
+/// what is left fails before anything is allocated.
+pub fn read_bytes(cursor: &mut Cursor<Vec<u8>>, length: u64) -> Result<Vec<u8>, Error> {
+    let remaining = (cursor.get_ref().len() as u64).saturating_sub(cursor.position());
+    if length > remaining {
+        return Err(Error::new(ErrorKind::UnexpectedEof, "length is longer than the remaining bytes"));
+    }
+    let mut bytes = vec![0u8; length as usize];
+    cursor.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
 pub fn encode_varint(i: u64) -> Vec<u8> {
     if i < 0xfd {
         vec![i as u8]
@@ -0,0 +1,296 @@
+use programming_bitcoin::address::address::{address_to_script_pubkey, script_pubkey_to_address};
+use programming_bitcoin::descriptor::checksum::descriptor_checksum;
+use programming_bitcoin::descriptor::descriptor::Descriptor;
+use programming_bitcoin::descriptor::error::DescriptorError;
+use programming_bitcoin::descriptor::key::{DescriptorKey, KeySource, Wildcard};
+use programming_bitcoin::ecc::xonly_public_key::XOnlyPublicKey;
+use programming_bitcoin::hd::account::{Account, Chain, Purpose};
+use programming_bitcoin::hd::derivation_path::DerivationPath;
+use programming_bitcoin::hd::extended_key::{ExtendedPrivateKey, HARDENED};
+use programming_bitcoin::hd::mnemonic::Mnemonic;
+use programming_bitcoin::script::script::{Command, Script};
+use programming_bitcoin::taproot::spend_info::TaprootSpendInfo;
+use programming_bitcoin::taproot::tap_tree::TapTree;
+use programming_bitcoin::utils::hash160::hash160;
+
+fn script_hex(descriptor: &str, index: u32) -> String {
+    hex::encode(Descriptor::parse(descriptor).unwrap().script_pubkey(index).unwrap().raw_serialize())
+}
+
+#[test]
+fn test_descriptor_checksum() {
+    // From BIP380
+    assert_eq!(descriptor_checksum("raw(deadbeef)").unwrap(), "89f8spxm");
+    assert!(Descriptor::parse("raw(deadbeef)#89f8spxm").is_ok());
+    for invalid in ["raw(deadbeef)#", "raw(deadbeef)#89f8spxmx", "raw(deadbeef)#89f8spx", "raw(deedbeef)#89f8spxm", "raw(deadbeef)#9f8spxm", "raw(deadbeef)##9f8spxm"] {
+        assert_eq!(Descriptor::parse(invalid).err(), Some(DescriptorError::InvalidChecksum), "{}", invalid);
+    }
+    assert_eq!(descriptor_checksum("raw(deadbeef)\n").err(), Some(DescriptorError::InvalidCharacter('\n')));
+
+    // The example from Bitcoin Core's docs, which is already canonical
+    let core = "wpkh([d34db33f/84h/0h/0h]xpub6DJ2dNUysrn5Vt36jH2KLBT2i1auw1tTSSomg8PhqNiUtx8QX2SvC9nrHu81fT41fvDUnhMjEzQgXnQjKEu3oaqMSzhSrHMxyyoEAmUHQbY/0/*)#cjjspncu";
+    assert_eq!(Descriptor::parse(core).unwrap().to_string(), core);
+}
+
+#[test]
+fn test_descriptor_scripts() {
+    // Vectors from BIP381, BIP382, BIP383 and BIP386. The sh(wpkh()) script
+    // was checked against Python's hashlib instead.
+    let vectors = [
+        ("pk(L4rK1yDtCWekvXuE6oXD9jCYfFNV2cWRpVuPLBcCU2z8TrisoyY1)", "2103a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bdac"),
+        ("pk(03a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd)", "2103a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bdac"),
+        ("pkh([deadbeef/1/2'/3/4']L4rK1yDtCWekvXuE6oXD9jCYfFNV2cWRpVuPLBcCU2z8TrisoyY1)", "76a9149a1c78a507689f6f54b847ad1cef1e614ee23f1e88ac"),
+        ("pkh(02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5)", "76a91406afd46bcdfd22ef94ac122aa11f241244a37ecc88ac"),
+        ("wpkh(L4rK1yDtCWekvXuE6oXD9jCYfFNV2cWRpVuPLBcCU2z8TrisoyY1)", "00149a1c78a507689f6f54b847ad1cef1e614ee23f1e"),
+        ("sh(wpkh(L4rK1yDtCWekvXuE6oXD9jCYfFNV2cWRpVuPLBcCU2z8TrisoyY1))", "a91484ab21b1b2fd065d4504ff693d832434b6108d7b87"),
+        (
+            "multi(1,L4rK1yDtCWekvXuE6oXD9jCYfFNV2cWRpVuPLBcCU2z8TrisoyY1,5KYZdUEo39z3FPrtuX2QbbwGnNP5zTd7yyr2SC1j299sBCnWjss)",
+            "512103a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd4104a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd5b8dec5235a0fa8722476c7709c02559e3aa73aa03918ba2d492eea75abea23552ae",
+        ),
+        ("tr(a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd)", "512077aab6e066f8a7419c5ab714c12c67d25007ed55a43cadcacb4d7a970a093f11"),
+        ("tr(L4rK1yDtCWekvXuE6oXD9jCYfFNV2cWRpVuPLBcCU2z8TrisoyY1)", "512077aab6e066f8a7419c5ab714c12c67d25007ed55a43cadcacb4d7a970a093f11"),
+        ("raw(deadbeef)", "deadbeef"),
+        ("addr(bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu)", "0014c0cebcd6c3d3ca8c75dc5ec62ebe55330ef910e2"),
+    ];
+    for (descriptor, script_pubkey) in vectors {
+        assert_eq!(script_hex(descriptor, 0), script_pubkey, "{}", descriptor);
+        // Without wildcards the index doesn't matter
+        assert_eq!(script_hex(descriptor, 7), script_pubkey, "{}", descriptor);
+        assert!(!Descriptor::parse(descriptor).unwrap().has_wildcard());
+    }
+
+    // sh and wsh hash the script the inner descriptor gives
+    let multi = "multi(2,03a0434d9e47f3c86235477c7b1ae6ae5d3442d49b1943c2b752a68e2a47e247c7,03774ae7f858a9411e5ef4246b70c65aac5649980be5c17891bbec17895da008cb)";
+    let inner = Descriptor::parse(multi).unwrap().script_pubkey(0).unwrap();
+    let sh = Descriptor::parse(&format!("sh({})", multi)).unwrap();
+    assert_eq!(sh.redeem_script(0).unwrap().unwrap().raw_serialize(), inner.raw_serialize());
+    assert_eq!(sh.script_pubkey(0).unwrap().raw_serialize(), Script::p2sh_script(hash160(&inner.raw_serialize())).raw_serialize());
+    let sh_wsh = Descriptor::parse(&format!("sh(wsh({}))", multi)).unwrap();
+    let wsh = Descriptor::parse(&format!("wsh({})", multi)).unwrap();
+    assert_eq!(sh_wsh.witness_script(0).unwrap().unwrap().raw_serialize(), inner.raw_serialize());
+    assert_eq!(sh_wsh.redeem_script(0).unwrap().unwrap().raw_serialize(), wsh.script_pubkey(0).unwrap().raw_serialize());
+    assert!(wsh.redeem_script(0).unwrap().is_none());
+    assert_eq!(hex::encode(&wsh.script_pubkey(0).unwrap().raw_serialize()[..2]), "0020");
+
+    // sortedmulti orders the keys by their SEC serialization
+    let sorted = Descriptor::parse(&multi.replace("multi(", "sortedmulti(")).unwrap().script_pubkey(0).unwrap();
+    let reversed = "multi(2,03774ae7f858a9411e5ef4246b70c65aac5649980be5c17891bbec17895da008cb,03a0434d9e47f3c86235477c7b1ae6ae5d3442d49b1943c2b752a68e2a47e247c7)";
+    assert_eq!(sorted.raw_serialize(), Descriptor::parse(reversed).unwrap().script_pubkey(0).unwrap().raw_serialize());
+    assert_ne!(sorted.raw_serialize(), inner.raw_serialize());
+
+    // Counts over 16 are pushed as script numbers
+    let key = "03a0434d9e47f3c86235477c7b1ae6ae5d3442d49b1943c2b752a68e2a47e247c7";
+    let wsh_20 = Descriptor::parse(&format!("wsh(multi(17{}))", format!(",{}", key).repeat(20))).unwrap();
+    let witness_script = hex::encode(wsh_20.witness_script(0).unwrap().unwrap().raw_serialize());
+    assert_eq!(witness_script, format!("0111{}0114ae", format!("21{}", key).repeat(20)));
+}
+
+#[test]
+fn test_descriptor_tap_tree() {
+    let internal = "c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5";
+    let a = "fff97bd5755eeea420453a14355235d382f6472f8568a18b2f057a1460297556";
+    let b = "e493dbf1c10d80f3581e4904930b1404cc6c13900ee0758474fa94abe8c4cd13";
+    let descriptor = Descriptor::parse(&format!("tr({},{{pk({}),{{pk({}),pk({})}}}})", internal, a, b, a)).unwrap();
+
+    let leaf = |key: &str| TapTree::leaf(Script::new(vec![Command::Data(hex::decode(key).unwrap()), Command::Op(0xac)]));
+    let tree = TapTree::branch(leaf(a), TapTree::branch(leaf(b), leaf(a)));
+    let expected = TaprootSpendInfo::new(XOnlyPublicKey::parse(&hex::decode(internal).unwrap()).unwrap(), Some(tree)).unwrap();
+    let spend_info = descriptor.spend_info(0).unwrap().unwrap();
+    assert_eq!(spend_info.output_key(), expected.output_key());
+    assert_eq!(descriptor.script_pubkey(0).unwrap().raw_serialize(), expected.script_pubkey().raw_serialize());
+    assert_eq!(descriptor.address(0, false).unwrap(), expected.address(false));
+    assert_eq!(descriptor.to_string_without_checksum(), format!("tr({},{{pk({}),{{pk({}),pk({})}}}})", internal, a, b, a));
+
+    // A compressed key in a leaf is used x-only
+    let compressed = Descriptor::parse(&format!("tr({},pk(03{}))", internal, a)).unwrap();
+    let xonly = Descriptor::parse(&format!("tr({},pk({}))", internal, a)).unwrap();
+    assert_eq!(compressed.script_pubkey(0).unwrap().raw_serialize(), xonly.script_pubkey(0).unwrap().raw_serialize());
+
+    // pkh() leaves hash the x-only key
+    let pkh = Descriptor::parse(&format!("tr({},{{pk({}),pkh(03{})}})", internal, a, b)).unwrap();
+    let pkh_leaf = TapTree::leaf(Script::p2pkh_script(hash160(&hex::decode(b).unwrap())));
+    let expected = TaprootSpendInfo::new(XOnlyPublicKey::parse(&hex::decode(internal).unwrap()).unwrap(), Some(TapTree::branch(leaf(a), pkh_leaf))).unwrap();
+    assert_eq!(pkh.script_pubkey(0).unwrap().raw_serialize(), expected.script_pubkey().raw_serialize());
+    assert_eq!(pkh.to_string_without_checksum(), format!("tr({},{{pk({}),pkh(03{})}})", internal, a, b));
+}
+
+#[test]
+fn test_descriptor_accounts() {
+    // Descriptors for the accounts of the "abandon ... about" mnemonic give
+    // the addresses the account module derives
+    let mnemonic = Mnemonic::parse("abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about").unwrap();
+    let seed = mnemonic.to_seed("");
+    for (purpose, testnet, template) in [
+        (Purpose::Bip44, false, "pkh({})"),
+        (Purpose::Bip49, true, "sh(wpkh({}))"),
+        (Purpose::Bip84, false, "wpkh({})"),
+        (Purpose::Bip86, false, "tr({})"),
+    ] {
+        let account = Account::from_seed(&seed, purpose, 0, testnet).unwrap();
+        let master = ExtendedPrivateKey::from_seed(&seed, testnet).unwrap();
+        assert_eq!(hex::encode(master.fingerprint()), "73c5da0a");
+        let origin = format!("[73c5da0a{}]", &account.path().to_string()[1..]);
+        for chain in [Chain::Receive, Chain::Change] {
+            let key = format!("{}{}/{}/*", origin, account.extended_public_key(), chain.number());
+            let descriptor = Descriptor::parse(&template.replace("{}", &key)).unwrap();
+            assert!(descriptor.has_wildcard());
+            for index in 0..3 {
+                assert_eq!(descriptor.address(index, testnet).unwrap(), account.address(chain, index).unwrap());
+            }
+
+            // The same from the master xprv, with the hardened steps in the key
+            let key = format!("{}{}/{}/*", master.to_base58(), &account.path().to_string()[1..], chain.number());
+            let descriptor = Descriptor::parse(&template.replace("{}", &key)).unwrap();
+            assert_eq!(descriptor.address(2, testnet).unwrap(), account.address(chain, 2).unwrap());
+        }
+    }
+
+    // Hardened wildcards need the private key
+    let master = ExtendedPrivateKey::from_seed(&seed, false).unwrap();
+    let descriptor = Descriptor::parse(&format!("wpkh({}/84h/0h/0h/0/*h)", master.to_base58())).unwrap();
+    let key = master.derive_path(&DerivationPath::parse("m/84'/0'/0'/0/5'").unwrap()).unwrap();
+    assert_eq!(descriptor.address(5, false).unwrap(), Purpose::Bip84.address(&key.public_key(), false));
+    let scripts = descriptor.script_pubkeys(3..6).unwrap();
+    assert_eq!(scripts.len(), 3);
+    assert_eq!(scripts[2].raw_serialize(), descriptor.script_pubkey(5).unwrap().raw_serialize());
+    assert_eq!(descriptor.script_pubkey(HARDENED).err(), Some(DescriptorError::InvalidIndex(HARDENED)));
+}
+
+#[test]
+fn test_descriptor_keys() {
+    let key = DescriptorKey::parse("[d34db33f/84'/0h/0']xpub6DJ2dNUysrn5Vt36jH2KLBT2i1auw1tTSSomg8PhqNiUtx8QX2SvC9nrHu81fT41fvDUnhMjEzQgXnQjKEu3oaqMSzhSrHMxyyoEAmUHQbY/1/*").unwrap();
+    let origin = key.origin().unwrap();
+    assert_eq!(origin.fingerprint, [0xd3, 0x4d, 0xb3, 0x3f]);
+    assert_eq!(origin.path.to_string(), "m/84'/0'/0'");
+    let KeySource::Extended { path, wildcard, .. } = key.source() else { panic!("not an extended key") };
+    assert_eq!(path.indexes(), [1]);
+    assert_eq!(*wildcard, Wildcard::Unhardened);
+    assert!(key.has_wildcard());
+    // Hardened steps are written with h
+    assert!(key.to_string().starts_with("[d34db33f/84h/0h/0h]xpub"));
+
+    // Uncompressed hex keys stay uncompressed
+    let uncompressed = "04a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd5b8dec5235a0fa8722476c7709c02559e3aa73aa03918ba2d492eea75abea235";
+    let key = DescriptorKey::parse(uncompressed).unwrap();
+    assert!(key.is_uncompressed());
+    assert_eq!(key.to_string(), uncompressed);
+    assert_eq!(hex::encode(key.sec(0).unwrap()), uncompressed);
+    assert!(DescriptorKey::parse("[deadbeef]03a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd").unwrap().origin().unwrap().path.indexes().is_empty());
+
+    for invalid in [
+        "",
+        "[deadbee]03a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd",
+        "[deadbeefa]03a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd",
+        "[deadbeef/x]03a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd",
+        "deadbeef]03a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd",
+        "[deadbeef03a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd",
+        // Hybrid keys and points off the curve
+        "06a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd5b8dec5235a0fa8722476c7709c02559e3aa73aa03918ba2d492eea75abea235",
+        "03a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bdaa",
+        "xpub6DJ2dNUysrn5Vt36jH2KLBT2i1auw1tTSSomg8PhqNiUtx8QX2SvC9nrHu81fT41fvDUnhMjEzQgXnQjKEu3oaqMSzhSrHMxyyoEAmUHQbY/*/0",
+        "xpub6DJ2dNUysrn5Vt36jH2KLBT2i1auw1tTSSomg8PhqNiUtx8QX2SvC9nrHu81fT41fvDUnhMjEzQgXnQjKEu3oaqMSzhSrHMxyyoEAmUHQbY//0",
+        "xpub6DJ2dNUysrn5Vt36jH2KLBT2i1auw1tTSSomg8PhqNiUtx8QX2SvC9nrHu81fT41fvDUnhMjEzQgXnQjKEu3oaqMSzhSrHMxyyoEAmUHQbZ/0",
+    ] {
+        assert_eq!(DescriptorKey::parse(invalid).err(), Some(DescriptorError::InvalidKey(invalid.to_string())), "{}", invalid);
+    }
+    for hardened in [
+        "xpub6DJ2dNUysrn5Vt36jH2KLBT2i1auw1tTSSomg8PhqNiUtx8QX2SvC9nrHu81fT41fvDUnhMjEzQgXnQjKEu3oaqMSzhSrHMxyyoEAmUHQbY/1h/*",
+        "xpub6DJ2dNUysrn5Vt36jH2KLBT2i1auw1tTSSomg8PhqNiUtx8QX2SvC9nrHu81fT41fvDUnhMjEzQgXnQjKEu3oaqMSzhSrHMxyyoEAmUHQbY/*'",
+    ] {
+        assert_eq!(DescriptorKey::parse(hardened).err(), Some(DescriptorError::HardenedFromPublic(hardened.to_string())));
+    }
+}
+
+#[test]
+fn test_descriptor_round_trip() {
+    let xpub = "xpub6DJ2dNUysrn5Vt36jH2KLBT2i1auw1tTSSomg8PhqNiUtx8QX2SvC9nrHu81fT41fvDUnhMjEzQgXnQjKEu3oaqMSzhSrHMxyyoEAmUHQbY";
+    for (descriptor, canonical) in [
+        (format!("wpkh([d34db33f/84'/0'/0']{}/0/*)", xpub), format!("wpkh([d34db33f/84h/0h/0h]{}/0/*)", xpub)),
+        (format!("sh(wsh(sortedmulti(1,{}/0/*,[deadbeef]02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5)))", xpub), String::new()),
+        ("pkh(02C6047F9441ED7D6D3045406E95C07CD85C778E4B8CEF3CA7ABAC09B95C709EE5)".to_string(), "pkh(02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5)".to_string()),
+        ("raw(DEADBEEF)".to_string(), "raw(deadbeef)".to_string()),
+        ("pk(L4rK1yDtCWekvXuE6oXD9jCYfFNV2cWRpVuPLBcCU2z8TrisoyY1)".to_string(), String::new()),
+        ("addr(2Mww8dCYPUpKHofjgcXcBCEGmniw9CoaiD2)".to_string(), String::new()),
+    ] {
+        let canonical = if canonical.is_empty() { descriptor.clone() } else { canonical };
+        let parsed = Descriptor::parse(&descriptor).unwrap();
+        assert_eq!(parsed.to_string_without_checksum(), canonical);
+        let with_checksum = parsed.to_string();
+        assert_eq!(with_checksum, format!("{}#{}", canonical, descriptor_checksum(&canonical).unwrap()));
+        // The checksum is over the string as written
+        let checksum = descriptor_checksum(&descriptor).unwrap();
+        assert!(Descriptor::parse(&format!("{}#{}", descriptor, checksum)).is_ok());
+        assert_eq!(Descriptor::parse(&with_checksum).unwrap().to_string(), with_checksum);
+    }
+}
+
+#[test]
+fn test_descriptor_errors() {
+    let key = "03a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd";
+    let uncompressed = "04a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd5b8dec5235a0fa8722476c7709c02559e3aa73aa03918ba2d492eea75abea235";
+    let xonly = "a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd";
+    let parse = |descriptor: String| Descriptor::parse(&descriptor).err();
+
+    assert_eq!(parse(format!("foo({})", key)), Some(DescriptorError::UnknownFunction("foo".to_string())));
+    assert_eq!(parse(format!("pk({}", key)), Some(DescriptorError::Syntax("')'".to_string(), 69)));
+    assert_eq!(parse(format!("pk({}))", key)), Some(DescriptorError::Syntax("end of descriptor".to_string(), 70)));
+    assert_eq!(parse(key.to_string()), Some(DescriptorError::Syntax("script expression".to_string(), 0)));
+    assert_eq!(parse(format!("sh(sh(pk({})))", key)), Some(DescriptorError::InvalidNesting("sh".to_string())));
+    assert_eq!(parse(format!("wsh(wpkh({}))", key)), Some(DescriptorError::InvalidNesting("wpkh".to_string())));
+    assert_eq!(parse(format!("wsh(wsh(pk({})))", key)), Some(DescriptorError::InvalidNesting("wsh".to_string())));
+    assert_eq!(parse(format!("sh(tr({}))", key)), Some(DescriptorError::InvalidNesting("tr".to_string())));
+    assert_eq!(parse(format!("tr({},multi(1,{}))", key, key)), Some(DescriptorError::InvalidNesting("multi".to_string())));
+    assert_eq!(parse("sh(raw(deadbeef))".to_string()), Some(DescriptorError::InvalidNesting("raw".to_string())));
+
+    assert_eq!(parse(format!("wpkh({})", uncompressed)), Some(DescriptorError::UncompressedKey(uncompressed.to_string())));
+    assert_eq!(parse(format!("wsh(pk({}))", uncompressed)), Some(DescriptorError::UncompressedKey(uncompressed.to_string())));
+    assert_eq!(parse(format!("tr({})", uncompressed)), Some(DescriptorError::UncompressedKey(uncompressed.to_string())));
+    assert!(parse(format!("sh(pk({}))", uncompressed)).is_none());
+    assert_eq!(parse(format!("pk({})", xonly)), Some(DescriptorError::XOnlyKey(xonly.to_string())));
+    assert_eq!(parse(format!("wpkh({})", xonly)), Some(DescriptorError::XOnlyKey(xonly.to_string())));
+
+    assert_eq!(parse(format!("multi(0,{})", key)), Some(DescriptorError::InvalidThreshold(0)));
+    assert_eq!(parse(format!("multi(2,{})", key)), Some(DescriptorError::InvalidThreshold(2)));
+    assert_eq!(parse(format!("multi(x,{})", key)), Some(DescriptorError::Syntax("threshold".to_string(), 6)));
+    assert_eq!(parse(format!("multi(1{})", format!(",{}", key).repeat(17))), Some(DescriptorError::TooManyKeys(17)));
+    assert!(parse(format!("wsh(multi(16{}))", format!(",{}", key).repeat(16))).is_none());
+    // BIP383 allows 20 keys inside wsh()
+    assert!(parse(format!("wsh(multi(20{}))", format!(",{}", key).repeat(20))).is_none());
+    assert!(parse(format!("sh(wsh(multi(1{})))", format!(",{}", key).repeat(20))).is_none());
+    assert_eq!(parse(format!("wsh(multi(1{}))", format!(",{}", key).repeat(21))), Some(DescriptorError::TooManyKeys(21)));
+    assert_eq!(parse(format!("sh(multi(1{}))", format!(",{}", key).repeat(17))), Some(DescriptorError::TooManyKeys(17)));
+
+    // A P2SH redeem script has to fit in a 520 byte push, so sh() takes 15
+    // compressed keys and 7 uncompressed ones
+    assert!(parse(format!("sh(multi(1{}))", format!(",{}", key).repeat(15))).is_none());
+    assert_eq!(parse(format!("sh(multi(1{}))", format!(",{}", key).repeat(16))), Some(DescriptorError::ScriptTooLarge(547)));
+    assert!(parse(format!("sh(multi(1{}))", format!(",{}", uncompressed).repeat(7))).is_none());
+    assert_eq!(parse(format!("sh(multi(1{}))", format!(",{}", uncompressed).repeat(8))), Some(DescriptorError::ScriptTooLarge(531)));
+
+    assert!(matches!(parse("addr(bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyv)".to_string()), Some(DescriptorError::InvalidAddress(_))));
+    assert_eq!(parse("raw(deadbee)".to_string()), Some(DescriptorError::InvalidScript));
+    assert_eq!(parse("raw(4c)".to_string()), Some(DescriptorError::InvalidScript));
+
+    // Scripts without an address form
+    let pk = Descriptor::parse(&format!("pk({})", key)).unwrap();
+    assert_eq!(pk.address(0, false).err(), Some(DescriptorError::NoAddress));
+    assert!(pk.spend_info(0).unwrap().is_none());
+}
+
+#[test]
+fn test_script_pubkey_to_address() {
+    for (address, testnet) in [
+        ("1LqBGSKuX5yYUonjxT5qGfpUsXKYYWeabA", false),
+        ("mkmZxiEcEd8ZqjQWVZuC6so5dFMKEFpN2j", true),
+        ("2Mww8dCYPUpKHofjgcXcBCEGmniw9CoaiD2", true),
+        ("bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu", false),
+        ("bc1qwqdg6squsna38e46795at95yu9atm8azzmyvckulcc7kytlcckxswvvzej", false),
+        ("bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr", false),
+    ] {
+        let (script_pubkey, address_testnet) = address_to_script_pubkey(address).unwrap();
+        assert_eq!(address_testnet, testnet);
+        assert_eq!(script_pubkey_to_address(&script_pubkey, testnet).unwrap(), address);
+    }
+    let p2pk = Script::new(vec![Command::Data(hex::decode("02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5").unwrap()), Command::Op(0xac)]);
+    assert!(script_pubkey_to_address(&p2pk, false).is_none());
+}
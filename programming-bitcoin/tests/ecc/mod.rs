@@ -1,71 +1,126 @@
 use ecc::{field_element::*, mod_exp::mod_exp, private_key::PrivateKey, secp256k1_params::S256Params};
 use primitive_types::U256;
-use ecc::point::Point;
-use programming_bitcoin::utils::rng;
+use ecc::point::{Point, PointError};
+use ecc::s256point::S256Point;
+use ecc::signature::{DerMode, Signature, SignatureError};
+use ecc::schnorr_signature::SchnorrSignature;
+use ecc::xonly_public_key::XOnlyPublicKey;
+use ecc::scalar::Scalar;
+use ecc::batch_verifier::BatchVerifier;
+use ecc::adaptor::AdaptorError;
+use ecc::nonce_reuse::{candidate_keys, recover_private_key, NonceReuseError};
+use ecc::generator_table::GeneratorTable;
+use ecc::private_key::WifMetadata;
+use ecc::jacobian_point::JacobianPoint;
+use ecc::recoverable_signature::{recover_public_key, RecoverableSignature};
+use num_bigint::BigUint;
+use programming_bitcoin::utils::{hash256::hash256, rng};
 use programming_bitcoin::ecc;
+use ecc::s256field_element::{S256Field, P};
+
+type F223 = FieldElement<Fp<223>>;
 
 #[test]
 fn a_equals_itself() {
-    let element_a = FieldElement::new(U256::from(3), U256::from(13));
+    let element_a = FieldElement::<Fp<13>>::new(U256::from(3));
     assert!(&element_a == &element_a);
 }
 
 #[test]
 fn a_not_equals_b() {
-    let element_a = FieldElement::new(U256::from(3), U256::from(13));
-    let element_b = FieldElement::new(U256::from(12), U256::from(13));
+    let element_a = FieldElement::<Fp<13>>::new(U256::from(3));
+    let element_b = FieldElement::<Fp<13>>::new(U256::from(12));
     assert!(!(&element_a == &element_b));
 }
 
 #[test]
 fn a_add_b_eq_c() {
-    let element_a = FieldElement::new(U256::from(7), U256::from(13));
-    let element_b = FieldElement::new(U256::from(12), U256::from(13));
-    let element_c = FieldElement::new(U256::from(6), U256::from(13));
+    let element_a = FieldElement::<Fp<13>>::new(U256::from(7));
+    let element_b = FieldElement::<Fp<13>>::new(U256::from(12));
+    let element_c = FieldElement::<Fp<13>>::new(U256::from(6));
     assert!(&(&element_a + &element_b) == &element_c);
 }
 
 #[test]
 fn a_sub_b_eq_c() {
-    let element_a = FieldElement::new(U256::from(12), U256::from(13));
-    let element_b = FieldElement::new(U256::from(7), U256::from(13));
-    let element_c = FieldElement::new(U256::from(5), U256::from(13));
+    let element_a = FieldElement::<Fp<13>>::new(U256::from(12));
+    let element_b = FieldElement::<Fp<13>>::new(U256::from(7));
+    let element_c = FieldElement::<Fp<13>>::new(U256::from(5));
     assert_eq!(&(&element_a - &element_b), &element_c);
 }
 
 #[test]
 fn a_mul_b_eq_c() {
-    let element_a = FieldElement::new(U256::from(3), U256::from(13));
-    let element_b = FieldElement::new(U256::from(12), U256::from(13));
-    let element_c = FieldElement::new(U256::from(10), U256::from(13));
+    let element_a = FieldElement::<Fp<13>>::new(U256::from(3));
+    let element_b = FieldElement::<Fp<13>>::new(U256::from(12));
+    let element_c = FieldElement::<Fp<13>>::new(U256::from(10));
     assert_eq!(&(&element_a * &element_b), &element_c);
 }
 
 #[test]
 fn a_div_b_eq_c() {
-    let element_a = FieldElement::new(U256::from(7), U256::from(19));
-    let element_b = FieldElement::new(U256::from(5), U256::from(19));
-    let element_c = FieldElement::new(U256::from(9), U256::from(19));
+    let element_a = FieldElement::<Fp<19>>::new(U256::from(7));
+    let element_b = FieldElement::<Fp<19>>::new(U256::from(5));
+    let element_c = FieldElement::<Fp<19>>::new(U256::from(9));
     assert_eq!(&(&element_a / &element_b), &element_c);
 }
 
 #[test]
 fn a_pow_3_eq_b() {
-    let element_a = FieldElement::new(U256::from(3), U256::from(13));
-    let element_b = FieldElement::new(U256::from(1), U256::from(13));
+    let element_a = FieldElement::<Fp<13>>::new(U256::from(3));
+    let element_b = FieldElement::<Fp<13>>::new(U256::from(1));
     assert_eq!(&element_a.pow(U256::from(3)), &element_b);
 }
 
+#[test]
+fn test_small_field_sqrt() {
+    let y = F223::new(U256::from(105));
+    let root = y.pow(U256::from(2)).sqrt();
+    assert!(&root == &y || &root == &(&F223::new(U256::zero()) - &y));
+    assert_eq!(y.prime(), U256::from(223));
+}
+
+// The secp256k1 prime with none of S256Field's specialised arithmetic
+#[derive(Debug, Clone)]
+struct GenericS256Field;
+
+impl FieldParams for GenericS256Field {
+    const PRIME: U256 = P;
+}
+
+#[test]
+fn test_s256_field_matches_generic_reduction() {
+    let edge = [U256::zero(), U256::one(), P - 1, P - 2, S256Params::gx(), S256Params::gy()];
+    let random: Vec<U256> = (0..8).map(|_| rng::get_random_u256() % P).collect();
+    let values: Vec<U256> = edge.iter().copied().chain(random).collect();
+    for &a in &values {
+        for &b in &values {
+            let (fast_a, fast_b) = (FieldElement::<S256Field>::new(a), FieldElement::<S256Field>::new(b));
+            let (slow_a, slow_b) = (FieldElement::<GenericS256Field>::new(a), FieldElement::<GenericS256Field>::new(b));
+            assert_eq!((&fast_a + &fast_b).num(), (&slow_a + &slow_b).num());
+            assert_eq!((&fast_a - &fast_b).num(), (&slow_a - &slow_b).num());
+            assert_eq!((&fast_a * &fast_b).num(), (&slow_a * &slow_b).num());
+            assert_eq!((&fast_a * 977u32).num(), (&slow_a * 977u32).num());
+        }
+        let fast = FieldElement::<S256Field>::new(a);
+        let slow = FieldElement::<GenericS256Field>::new(a);
+        assert_eq!(fast.sqrt().num(), slow.sqrt().num());
+        if !a.is_zero() {
+            assert_eq!(fast.mod_inverse().num(), slow.mod_inverse().num());
+            assert_eq!((&fast / &fast).num(), U256::one());
+        }
+    }
+}
+
 #[test]
 fn test_on_curve() {
     let zero = U256::zero();
-    let prime = U256::from(223);
-    let a = FieldElement::new(zero, prime);
-    let b = FieldElement::new(U256::from(7), prime);
+    let a = F223::new(zero);
+    let b = F223::new(U256::from(7));
 
     // for a valid point, assert is ok. for an invalid point, assert they panic
-    let x = FieldElement::new(U256::from(192), prime);
-    let y = FieldElement::new(U256::from(105), prime);
+    let x = F223::new(U256::from(192));
+    let y = F223::new(U256::from(105));
     let valid_point = Point::new(Some(x), Some(y), a, b);
     dbg!(valid_point);
 }
@@ -127,5 +182,805 @@ fn test_point_from_sec() {
     // uncompressed sec, as seen from the prepended 0x04
     let raw_sec = hex::decode("04887387e452b8eacc4acfde10d9aaf7f6d9a0f975aabb10d006e4da568744d06c61de6d95231cd89026e286df3b6ae4a894a3378e393e93a0f45b666329a0ae34").unwrap();
     Point::parse_to_s256_point(raw_sec);
-    // TODO add test cases for uncompressed - 0x02 and 0x03
+
+    // compressed sec with both parities of y
+    for secret in [1u32, 2, 3, 12345] {
+        let point = PrivateKey::new(U256::from(secret)).point();
+        let compressed = Point::from_sec(&point.clone().sec(true)).unwrap();
+        let uncompressed = Point::from_sec(&point.clone().sec(false)).unwrap();
+        assert!(&compressed == &point);
+        assert!(&uncompressed == &point);
+    }
+}
+
+#[test]
+fn test_point_from_sec_errors() {
+    let sec = PrivateKey::new(U256::from(12345)).point().sec(false);
+
+    assert_eq!(Point::from_sec(&[]).unwrap_err(), PointError::InvalidSecLength(0));
+    assert_eq!(Point::from_sec(&[0x05; 33]).unwrap_err(), PointError::InvalidSecPrefix(0x05));
+    assert_eq!(Point::from_sec(&sec[..33]).unwrap_err(), PointError::InvalidSecLength(33));
+    assert_eq!(Point::from_sec(&[vec![0x02], sec[1..].to_vec()].concat()).unwrap_err(), PointError::InvalidSecLength(65));
+
+    // y changed so the point is off the curve
+    let mut off_curve = sec.clone();
+    off_curve[64] ^= 1;
+    assert_eq!(Point::from_sec(&off_curve).unwrap_err(), PointError::NotOnCurve);
+
+    // x = 5 has no y on secp256k1, and x = p is out of range
+    let mut no_y = vec![0x02];
+    no_y.extend_from_slice(&U256::from(5).to_big_endian());
+    assert_eq!(Point::from_sec(&no_y).unwrap_err(), PointError::InvalidXCoordinate);
+    let mut too_large = vec![0x03];
+    too_large.extend_from_slice(&S256Params::p().to_big_endian());
+    assert_eq!(Point::from_sec(&too_large).unwrap_err(), PointError::CoordinateOutOfRange);
+}
+
+#[test]
+fn test_point_try_new() {
+    let a = F223::new(U256::zero());
+    let b = F223::new(U256::from(7));
+    let on_curve = (F223::new(U256::from(192)), F223::new(U256::from(105)));
+    let off_curve = (F223::new(U256::from(200)), F223::new(U256::from(119)));
+
+    assert!(Point::try_new(Some(on_curve.0.clone()), Some(on_curve.1), a.clone(), b.clone()).is_ok());
+    assert_eq!(Point::try_new(Some(off_curve.0), Some(off_curve.1), a.clone(), b.clone()).unwrap_err(), PointError::NotOnCurve);
+    assert_eq!(Point::try_new(Some(on_curve.0), None, a.clone(), b.clone()).unwrap_err(), PointError::MissingCoordinate);
+    assert!(Point::try_new(None, None, a, b).unwrap().is_infinity());
+
+    assert_eq!(S256Point::try_new_s256_point(Some(S256Params::p()), Some(U256::one())).unwrap_err(), PointError::CoordinateOutOfRange);
+    assert_eq!(S256Point::try_new_s256_point(Some(S256Params::gx()), Some(S256Params::gx())).unwrap_err(), PointError::NotOnCurve);
+    assert!(S256Point::try_new_s256_point(Some(S256Params::gx()), Some(S256Params::gy())).is_ok());
+}
+
+#[test]
+fn test_jacobian_multiply_matches_affine() {
+    let g = S256Params::g();
+    let coefficients = [
+        U256::one(),
+        U256::from(2),
+        U256::from(5000),
+        U256::from(2018).pow(U256::from(5)),
+        U256::from_str_radix("0xdeadbeef12345", 16).unwrap(),
+        S256Params::n() - U256::one(),
+        rng::get_random_u256(),
+    ];
+    for coefficient in coefficients {
+        let expected = S256Point::multiply_affine(&g, coefficient);
+        let output = S256Point::multiply(&g, coefficient);
+        assert!(&output == &expected);
+        assert!(&(&g * coefficient) == &expected);
+    }
+}
+
+#[test]
+fn test_multiply_by_order_is_infinity() {
+    let g = S256Params::g();
+    assert!(S256Point::multiply(&g, S256Params::n()).is_infinity());
+    assert!(S256Point::multiply(&g, U256::zero()).is_infinity());
+}
+
+#[test]
+fn test_small_curve_multiply() {
+    // (47, 71) has order 21 on y^2 = x^3 + 7 over F_223
+    let a = F223::new(U256::zero());
+    let b = F223::new(U256::from(7));
+    let x = F223::new(U256::from(47));
+    let y = F223::new(U256::from(71));
+    let point = Point::new(Some(x), Some(y), a, b);
+    assert!((&point * U256::from(21)).is_infinity());
+    assert_eq!((&point * U256::from(2)).coordinates(), Some((U256::from(36), U256::from(111))));
+}
+
+#[test]
+fn test_verify_book_signature() {
+    let px = U256::from_str_radix("0x887387e452b8eacc4acfde10d9aaf7f6d9a0f975aabb10d006e4da568744d06c", 16).unwrap();
+    let py = U256::from_str_radix("0x61de6d95231cd89026e286df3b6ae4a894a3378e393e93a0f45b666329a0ae34", 16).unwrap();
+    let point = S256Point::new_s256_point(Some(px), Some(py));
+    let z = hex::decode("ec208baa0fc1c19f708a9ca96fdeff3ac3f230bb4a7ba4aede4942ad003c0f60").unwrap();
+    let r = U256::from_str_radix("0xac8d1c87e51d0d441be8b3dd5b05c8795b48875dffe00b7ffcfac23010d3a395", 16).unwrap();
+    let s = U256::from_str_radix("0x68342ceff8935ededd102dd876ffd6ba72d6a427a3edb13d26eb0781cb423c4", 16).unwrap();
+    assert!(point.clone().verify(z.clone(), Signature::new(r, s)));
+    assert!(!point.verify(z, Signature::new(r, s + U256::one())));
+}
+
+#[test]
+fn test_sign_and_verify() {
+    let private_key = PrivateKey::new(U256::from(12345));
+    let z = hash256(b"Programming Bitcoin!");
+    let signature = private_key.sign(z.clone());
+    assert!(private_key.point().verify(z, signature));
+}
+
+#[test]
+fn test_generator_table_matches_multiply() {
+    let g = S256Params::g();
+    let mut coefficients = vec![
+        U256::zero(),
+        U256::one(),
+        U256::from(15),
+        U256::from(16),
+        U256::one() << 255,
+        U256::MAX,
+        S256Params::n(),
+        S256Params::n() - U256::one(),
+        S256Params::n() + U256::one(),
+    ];
+    for _ in 0..8 {
+        coefficients.push(rng::get_random_u256());
+    }
+    for coefficient in coefficients {
+        let expected = S256Point::multiply(&g, coefficient);
+        let output = S256Point::multiply_generator(coefficient);
+        assert!(&output == &expected);
+    }
+}
+
+#[test]
+fn test_generator_table_matches_affine() {
+    let coefficient = U256::from_str_radix("0x12345def", 16).unwrap();
+    let expected = S256Point::multiply_affine(&S256Params::g(), coefficient);
+    assert!(&S256Point::multiply_generator(coefficient) == &expected);
+    assert!(&PrivateKey::new(coefficient).point() == &expected);
+}
+
+#[test]
+fn test_bip340_vectors() {
+    let vectors = include_str!("bip340_test_vectors.csv");
+    for line in vectors.lines().skip(1) {
+        let fields: Vec<&str> = line.splitn(8, ',').collect();
+        let index = fields[0];
+        let secret_key = fields[1];
+        let public_key = hex::decode(fields[2]).unwrap();
+        let message = hex::decode(fields[4]).unwrap();
+        let raw_signature = hex::decode(fields[5]).unwrap();
+        let expected = fields[6] == "TRUE";
+
+        if !secret_key.is_empty() {
+            let private_key = PrivateKey::new(U256::from_big_endian(&hex::decode(secret_key).unwrap()));
+            let aux_rand: [u8; 32] = hex::decode(fields[3]).unwrap().try_into().unwrap();
+            assert_eq!(private_key.xonly_public_key().serialize(), public_key, "vector {index}: public key");
+            let signature = private_key.sign_schnorr(&message, aux_rand);
+            assert_eq!(signature.serialize(), raw_signature, "vector {index}: signature");
+        }
+
+        let signature = SchnorrSignature::parse(&raw_signature).unwrap();
+        let result = match XOnlyPublicKey::parse(&public_key) {
+            Ok(key) => key.verify_schnorr(&message, &signature),
+            Err(_) => false,
+        };
+        assert_eq!(result, expected, "vector {index}: verification");
+    }
+}
+
+#[test]
+fn test_sign_and_verify_schnorr() {
+    let private_key = PrivateKey::new(rng::get_random_u256());
+    let msg = hash256(b"Programming Bitcoin!");
+    let aux_rand: [u8; 32] = hash256(b"aux").try_into().unwrap();
+    let signature = private_key.sign_schnorr(&msg, aux_rand);
+    let public_key = private_key.point();
+    assert!(public_key.verify_schnorr(&msg, &signature));
+    assert!(!public_key.verify_schnorr(b"another message", &signature));
+}
+
+fn to_biguint(value: U256) -> BigUint {
+    BigUint::from_bytes_be(&value.to_big_endian())
+}
+
+fn from_biguint(value: BigUint) -> U256 {
+    U256::from_big_endian(&value.to_bytes_be())
+}
+
+#[test]
+fn test_scalar_arithmetic_matches_biguint() {
+    let n = to_biguint(S256Params::n());
+    let mut values = vec![
+        U256::zero(),
+        U256::one(),
+        S256Params::n() - U256::one(),
+        S256Params::n() / 2,
+        U256::MAX,
+    ];
+    for _ in 0..16 {
+        values.push(rng::get_random_u256());
+    }
+    for a in &values {
+        for b in &values {
+            let (x, y) = (Scalar::reduce(*a), Scalar::reduce(*b));
+            let (big_x, big_y) = (to_biguint(*a) % &n, to_biguint(*b) % &n);
+            assert_eq!((x + y).to_u256(), from_biguint((&big_x + &big_y) % &n));
+            assert_eq!((x - y).to_u256(), from_biguint((&big_x + &n - &big_y) % &n));
+            assert_eq!((x * y).to_u256(), from_biguint((&big_x * &big_y) % &n));
+        }
+        let x = Scalar::reduce(*a);
+        assert!((x + -x).is_zero());
+        if let Some(x_inv) = x.invert() {
+            assert_eq!(x * x_inv, Scalar::one());
+        } else {
+            assert!(x.is_zero());
+        }
+    }
+}
+
+#[test]
+fn test_scalar_range_checks() {
+    let n = S256Params::n();
+    assert!(Scalar::from_u256(n).is_none());
+    assert!(Scalar::from_u256(U256::MAX).is_none());
+    assert_eq!(Scalar::from_u256(n - U256::one()).unwrap().to_u256(), n - U256::one());
+    assert!(Scalar::reduce(n).is_zero());
+    assert_eq!(Scalar::reduce(U256::MAX).to_u256(), U256::MAX - n);
+    assert!(Scalar::from_bytes(&n.to_big_endian()).is_none());
+
+    let bytes = rng::get_random_u256().to_big_endian();
+    let scalar = Scalar::reduce_bytes(&bytes);
+    if let Some(checked) = Scalar::from_bytes(&bytes) {
+        assert_eq!(checked, scalar);
+        assert_eq!(checked.to_bytes(), bytes);
+    }
+
+    assert!(!Scalar::reduce(n / 2).is_high());
+    assert!(Scalar::reduce(n / 2 + U256::one()).is_high());
+    assert!(Scalar::zero().invert().is_none());
+}
+
+#[test]
+fn test_recover_public_key() {
+    for _ in 0..8 {
+        let private_key = PrivateKey::new(rng::get_random_u256());
+        let z = hash256(&rng::get_random_u256().to_big_endian());
+        let signature = private_key.sign_recoverable(z.clone());
+        let recovered = signature.recover(z.clone()).unwrap();
+        let point = private_key.point();
+        assert!(&recovered == &point);
+        assert!(point.verify(z.clone(), signature.signature().clone()));
+
+        // The other parity gives a different (valid) key
+        let flipped = recover_public_key(z, signature.signature(), signature.recovery_id() ^ 1).unwrap();
+        assert!(!(&flipped == &recovered));
+    }
+}
+
+#[test]
+fn test_recover_rejects_invalid_input() {
+    let private_key = PrivateKey::new(U256::from(12345));
+    let z = hash256(b"Programming Bitcoin!");
+    let signature = private_key.sign(z.clone());
+    assert!(recover_public_key(z.clone(), &signature, 4).is_err());
+    assert!(recover_public_key(z.clone(), &Signature::new(U256::zero(), signature.s()), 0).is_err());
+    assert!(recover_public_key(z.clone(), &Signature::new(signature.r(), S256Params::n()), 0).is_err());
+    // r + n would be above p for almost every r
+    assert!(recover_public_key(z, &Signature::new(S256Params::n() - U256::one(), signature.s()), 2).is_err());
+}
+
+#[test]
+fn test_compact_signature_round_trip() {
+    let private_key = PrivateKey::new(U256::from(12345));
+    let z = hash256(b"Programming Bitcoin!");
+    let signature = private_key.sign_recoverable(z.clone());
+    for compressed in [true, false] {
+        let raw = signature.serialize_compact(compressed);
+        assert_eq!(raw.len(), 65);
+        assert_eq!(raw[0], 27 + signature.recovery_id() + if compressed { 4 } else { 0 });
+        let (parsed, parsed_compressed) = RecoverableSignature::parse_compact(&raw).unwrap();
+        assert_eq!(parsed_compressed, compressed);
+        assert_eq!(parsed.recovery_id(), signature.recovery_id());
+        assert_eq!(parsed.signature().r(), signature.signature().r());
+        assert_eq!(parsed.signature().s(), signature.signature().s());
+        assert!(&parsed.recover(z.clone()).unwrap() == &PrivateKey::new(U256::from(12345)).point());
+    }
+    let mut raw = signature.serialize_compact(true);
+    raw[0] = 35;
+    assert!(RecoverableSignature::parse_compact(&raw).is_err());
+    assert!(RecoverableSignature::parse_compact(&raw[..64]).is_err());
+}
+
+#[test]
+fn test_der_round_trip() {
+    // Covers r and s both with and without the leading zero byte
+    for i in 0..16u32 {
+        let private_key = PrivateKey::new(rng::get_random_u256());
+        let signature = private_key.sign(hash256(&i.to_le_bytes()));
+        let parsed = Signature::parse(signature.der());
+        assert_eq!(parsed.r(), signature.r());
+        assert_eq!(parsed.s(), signature.s());
+    }
+}
+
+#[test]
+fn test_parse_der_strict_errors() {
+    let signature = PrivateKey::new(U256::from(12345)).sign(hash256(b"strict der"));
+    let der = signature.der();
+    assert!(Signature::parse_der(&der, DerMode::Strict).is_ok());
+
+    let with = |index: usize, byte: u8| {
+        let mut raw = der.clone();
+        raw[index] = byte;
+        Signature::parse_der(&raw, DerMode::Strict).unwrap_err()
+    };
+    let r_length = der[3] as usize;
+    let s_tag = 4 + r_length;
+
+    assert_eq!(Signature::parse_der(&der[..7], DerMode::Strict).unwrap_err(), SignatureError::InvalidLength(7));
+    assert_eq!(Signature::parse_der(&[der.clone(), vec![0; 32]].concat(), DerMode::Strict).unwrap_err(), SignatureError::InvalidLength(der.len() + 32));
+    assert_eq!(with(0, 0x31), SignatureError::InvalidSequenceTag);
+    assert_eq!(with(1, der[1] - 1), SignatureError::InvalidSequenceLength);
+    assert_eq!(with(2, 0x03), SignatureError::InvalidIntegerTag);
+    assert_eq!(with(s_tag, 0x03), SignatureError::InvalidIntegerTag);
+    assert_eq!(with(3, 0x7f), SignatureError::InvalidIntegerLength);
+    assert_eq!(with(4, 0x80), SignatureError::NegativeInteger);
+    assert_eq!(with(s_tag + 2, 0x80), SignatureError::NegativeInteger);
+
+    // A zero byte in front of a positive integer is padding
+    let padded = [0x30, 0x07, 0x02, 0x02, 0x00, 0x01, 0x02, 0x01, 0x01];
+    assert_eq!(Signature::parse_der(&padded, DerMode::Strict).unwrap_err(), SignatureError::ExcessivePadding);
+    assert!(Signature::parse_der(&padded, DerMode::Lax).is_ok());
+
+    // An empty integer
+    let empty = [0x30, 0x06, 0x02, 0x00, 0x02, 0x02, 0x01, 0x01];
+    assert_eq!(Signature::parse_der(&empty, DerMode::Strict).unwrap_err(), SignatureError::InvalidIntegerLength);
+}
+
+#[test]
+fn test_parse_der_lax() {
+    let signature = PrivateKey::new(U256::from(12345)).sign(hash256(b"lax der"));
+    let der = signature.der();
+
+    // Long form lengths and trailing data are tolerated
+    let mut long_form = vec![0x30, 0x82, 0x00, der[1]];
+    long_form.extend_from_slice(&der[2..]);
+    long_form.extend_from_slice(&[0xde, 0xad]);
+    assert!(Signature::parse_der(&long_form, DerMode::Strict).is_err());
+    let parsed = Signature::parse_der(&long_form, DerMode::Lax).unwrap();
+    assert_eq!(parsed.r(), signature.r());
+    assert_eq!(parsed.s(), signature.s());
+
+    // Integers must still fit in 256 bits and stay within the encoding
+    let too_large = [vec![0x30, 0x27, 0x02, 0x21, 0x01], vec![0xff; 32], vec![0x02, 0x02, 0x01, 0x01]].concat();
+    assert_eq!(Signature::parse_der(&too_large, DerMode::Lax).unwrap_err(), SignatureError::IntegerTooLarge);
+    assert_eq!(Signature::parse_der(&der[..der.len() - 1], DerMode::Lax).unwrap_err(), SignatureError::InvalidIntegerLength);
+    assert_eq!(Signature::parse_der(&[0x30], DerMode::Lax).unwrap_err(), SignatureError::InvalidLength(1));
+    assert_eq!(Signature::parse_der(&[], DerMode::Lax).unwrap_err(), SignatureError::InvalidSequenceTag);
+}
+
+#[test]
+fn test_low_s() {
+    let signature = PrivateKey::new(U256::from(12345)).sign(hash256(b"low s"));
+    assert!(signature.is_low_s());
+    assert!(signature.check_low_s().is_ok());
+
+    let high = Signature::new(signature.r(), (-Scalar::from_u256(signature.s()).unwrap()).to_u256());
+    assert!(!high.is_low_s());
+    assert_eq!(high.check_low_s().unwrap_err(), SignatureError::HighS);
+    // Both verify, but only the normalized one is low
+    let point = PrivateKey::new(U256::from(12345)).point();
+    assert!(point.verify(hash256(b"low s"), high.clone()));
+    let normalized = high.normalize_s();
+    assert!(normalized.is_low_s());
+    assert_eq!(normalized.s(), signature.s());
+    assert_eq!(signature.normalize_s().s(), signature.s());
+}
+
+#[test]
+fn test_batch_verifier() {
+    let mut batch = BatchVerifier::new();
+    assert!(batch.verify().is_ok());
+    for i in 0..12u32 {
+        let private_key = PrivateKey::new(U256::from(1000 + i));
+        let msg = hash256(&i.to_le_bytes());
+        if i % 3 == 0 {
+            let signature = private_key.sign(msg.clone());
+            assert_eq!(batch.add_ecdsa(private_key.point(), msg, signature), i as usize);
+        } else {
+            let signature = private_key.sign_schnorr(&msg, [0u8; 32]);
+            batch.add_schnorr(private_key.xonly_public_key(), msg, signature);
+        }
+    }
+    assert_eq!(batch.len(), 12);
+    for threads in [1, 2, 5, 32] {
+        assert_eq!(batch.verify_with_threads(threads), Ok(()));
+    }
+
+    // The lowest invalid entry is reported, whichever kind it is
+    let wrong_msg = hash256(b"wrong");
+    let mut bad_schnorr = batch.clone();
+    let key = PrivateKey::new(U256::from(1));
+    let key_point = || PrivateKey::new(U256::from(1)).point();
+    bad_schnorr.add_schnorr(key.xonly_public_key(), wrong_msg.clone(), key.sign_schnorr(&hash256(b"right"), [0u8; 32]));
+    bad_schnorr.add_ecdsa(key_point(), wrong_msg.clone(), key.sign(hash256(b"right")));
+    for threads in [1, 2, 5, 32] {
+        assert_eq!(bad_schnorr.verify_with_threads(threads), Err(12));
+    }
+    let mut bad_ecdsa = batch.clone();
+    bad_ecdsa.add_ecdsa(key_point(), wrong_msg.clone(), key.sign(hash256(b"right")));
+    bad_ecdsa.add_schnorr(key.xonly_public_key(), wrong_msg.clone(), key.sign_schnorr(&hash256(b"right"), [0u8; 32]));
+    for threads in [1, 2, 5, 32] {
+        assert_eq!(bad_ecdsa.verify_with_threads(threads), Err(12));
+    }
+}
+
+#[test]
+fn test_batch_verifier_bip340_vectors() {
+    // Every valid vector verifies as one batch, and each invalid one is
+    // found when added among them
+    let vectors = include_str!("bip340_test_vectors.csv");
+    let mut valid = BatchVerifier::new();
+    let mut invalid = vec![];
+    for line in vectors.lines().skip(1) {
+        let fields: Vec<&str> = line.splitn(8, ',').collect();
+        let public_key = match XOnlyPublicKey::parse(&hex::decode(fields[2]).unwrap()) {
+            Ok(public_key) => public_key,
+            Err(_) => continue,
+        };
+        let msg = hex::decode(fields[4]).unwrap();
+        let raw = hex::decode(fields[5]).unwrap();
+        let signature = SchnorrSignature::new(U256::from_big_endian(&raw[..32]), U256::from_big_endian(&raw[32..]));
+        if fields[6] == "TRUE" {
+            valid.add_schnorr(public_key, msg, signature);
+        } else {
+            invalid.push((public_key, msg, signature));
+        }
+    }
+    assert!(valid.verify().is_ok());
+    assert!(!invalid.is_empty());
+    for (public_key, msg, signature) in invalid {
+        let mut batch = valid.clone();
+        let index = batch.add_schnorr(public_key, msg, signature);
+        assert_eq!(batch.verify_with_threads(1), Err(index));
+    }
+}
+
+#[test]
+fn test_ecdsa_adaptor_signature() {
+    let signer_secret = rng::get_random_u256();
+    let signer = PrivateKey::new(signer_secret);
+    let adaptor_secret = rng::get_random_u256();
+    let encryption_key = PrivateKey::new(adaptor_secret).point();
+    let z = hash256(b"adaptor signatures");
+    let pre_signature = signer.encrypt_sign(z.clone(), &encryption_key, [7u8; 32]).unwrap();
+
+    let public_key = PrivateKey::new(signer_secret).point();
+    assert!(pre_signature.verify(&public_key, z.clone(), &encryption_key));
+    assert!(!pre_signature.verify(&public_key, hash256(b"another message"), &encryption_key));
+    let other_key = PrivateKey::new(rng::get_random_u256()).point();
+    assert!(!pre_signature.verify(&public_key, z.clone(), &other_key));
+    assert!(!pre_signature.verify(&other_key, z.clone(), &encryption_key));
+
+    let decrypted = pre_signature.decrypt(&PrivateKey::new(adaptor_secret)).unwrap();
+    assert!(decrypted.is_low_s());
+    assert_eq!(decrypted.r(), pre_signature.r());
+    assert!(public_key.clone().verify(z.clone(), decrypted.clone()));
+    let wrong = pre_signature.decrypt(&PrivateKey::new(adaptor_secret + 1)).unwrap();
+    assert!(!public_key.clone().verify(z.clone(), wrong.clone()));
+
+    // Seeing the final signature reveals the adaptor secret
+    let recovered = pre_signature.recover(&decrypted, &encryption_key).unwrap();
+    assert_eq!(recovered.point().coordinates(), encryption_key.coordinates());
+    assert_eq!(pre_signature.recover(&wrong, &encryption_key).err(), Some(AdaptorError::SecretNotFound));
+    assert_eq!(pre_signature.recover(&signer.sign(z), &encryption_key).err(), Some(AdaptorError::SecretNotFound));
+}
+
+#[test]
+fn test_schnorr_adaptor_signature() {
+    // Enough runs to hit both parities of R = R' + T
+    for _ in 0..8 {
+        let signer = PrivateKey::new(rng::get_random_u256());
+        let adaptor_secret = rng::get_random_u256();
+        let encryption_key = PrivateKey::new(adaptor_secret).point();
+        let msg = b"scriptless scripts";
+        let pre_signature = signer.encrypt_sign_schnorr(msg, &encryption_key, [0u8; 32]).unwrap();
+
+        let public_key = signer.xonly_public_key();
+        assert!(pre_signature.verify(&public_key, msg, &encryption_key));
+        assert!(!pre_signature.verify(&public_key, b"another message", &encryption_key));
+        let other_key = PrivateKey::new(rng::get_random_u256()).point();
+        assert!(!pre_signature.verify(&public_key, msg, &other_key));
+
+        let signature = pre_signature.decrypt(&PrivateKey::new(adaptor_secret)).unwrap();
+        assert!(public_key.verify_schnorr(msg, &signature));
+        let wrong = pre_signature.decrypt(&PrivateKey::new(adaptor_secret + 1)).unwrap();
+        assert!(!public_key.verify_schnorr(msg, &wrong));
+
+        let recovered = pre_signature.recover(&signature, &encryption_key).unwrap();
+        assert_eq!(recovered.point().coordinates(), encryption_key.coordinates());
+        assert_eq!(pre_signature.recover(&wrong, &encryption_key).err(), Some(AdaptorError::SecretNotFound));
+    }
+}
+
+#[test]
+fn test_adaptor_atomic_swap() {
+    // Alice and Bob each pre-sign their side of the swap under Alice's
+    // adaptor point. Alice claims Bob's coins by decrypting his
+    // pre-signature, which hands Bob the secret to decrypt hers.
+    let alice = PrivateKey::new(rng::get_random_u256());
+    let bob = PrivateKey::new(rng::get_random_u256());
+    let secret = rng::get_random_u256();
+    let adaptor_point = PrivateKey::new(secret).point();
+    let alice_tx = hash256(b"alice pays bob");
+    let bob_tx = b"bob pays alice";
+
+    let alice_pre = alice.encrypt_sign(alice_tx.clone(), &adaptor_point, [1u8; 32]).unwrap();
+    let bob_pre = bob.encrypt_sign_schnorr(bob_tx, &adaptor_point, [2u8; 32]).unwrap();
+    assert!(bob_pre.verify(&bob.xonly_public_key(), bob_tx, &adaptor_point));
+
+    let bob_signature = bob_pre.decrypt(&PrivateKey::new(secret)).unwrap();
+    assert!(bob.xonly_public_key().verify_schnorr(bob_tx, &bob_signature));
+
+    let learned = bob_pre.recover(&bob_signature, &adaptor_point).unwrap();
+    let alice_signature = alice_pre.decrypt(&learned).unwrap();
+    assert!(alice.point().verify(alice_tx, alice_signature));
+}
+
+#[test]
+fn test_ecdh() {
+    let alice_secret = U256::from_str_radix("1111111111111111111111111111111111111111111111111111111111111111", 16).unwrap();
+    let bob_secret = U256::from_str_radix("2222222222222222222222222222222222222222222222222222222222222222", 16).unwrap();
+    let alice = PrivateKey::new(alice_secret);
+    let bob = PrivateKey::new(bob_secret);
+    let alice_point = PrivateKey::new(alice_secret).point();
+    let bob_point = PrivateKey::new(bob_secret).point();
+
+    // sha256(0x02 || x) of the shared point, as libsecp256k1's secp256k1_ecdh
+    let expected = "b36b6d195982c5be874d6d542dc268234379e1ae4ff1709402135b7de5cf0766";
+    assert_eq!(hex::encode(alice.ecdh(&bob_point).unwrap()), expected);
+    assert_eq!(hex::encode(bob.ecdh(&alice_point).unwrap()), expected);
+
+    let carol = PrivateKey::new(rng::get_random_u256());
+    assert_ne!(hex::encode(carol.ecdh(&bob_point).unwrap()), expected);
+    assert!(alice.ecdh(&S256Point::new_s256_infinity()).is_err());
+    assert!(PrivateKey::new(U256::zero()).ecdh(&bob_point).is_err());
+}
+
+#[test]
+fn test_multiply_ct() {
+    let point = JacobianPoint::from_point(&PrivateKey::new(rng::get_random_u256()).point());
+    let n_minus_one = S256Params::n() - 1;
+    for k in [U256::zero(), U256::one(), U256::from(15), U256::from(16), n_minus_one, rng::get_random_u256()] {
+        assert_eq!(GeneratorTable::multiply_ct(k).to_affine(), GeneratorTable::multiply(k).to_affine());
+        assert_eq!(point.multiply_ct(k).to_affine(), point.multiply(k).to_affine());
+    }
+    assert!(JacobianPoint::infinity().multiply_ct(U256::from(7)).is_infinity());
+}
+
+#[test]
+fn test_private_key_debug_redacted() {
+    let secret = U256::from_str_radix("c0ffee00c0ffee00c0ffee00c0ffee00c0ffee00c0ffee00c0ffee00c0ffee00", 16).unwrap();
+    let private_key = PrivateKey::new(secret);
+    let public_key = hex::encode(PrivateKey::new(secret).point().sec(true));
+    for printed in [format!("{:?}", private_key), format!("{:#?}", private_key), format!("{}", private_key)] {
+        assert!(!printed.contains("c0ffee"));
+        assert!(!printed.contains(&secret.to_string()));
+        assert!(printed.contains(&public_key));
+    }
+}
+
+#[test]
+fn test_private_key_zeroized_on_drop() {
+    use std::mem::MaybeUninit;
+
+    let secret = U256::from_str_radix("5ec4e75ec4e75ec4e75ec4e75ec4e75ec4e75ec4e75ec4e75ec4e75ec4e75ec4", 16).unwrap();
+    let needle: Vec<u8> = secret.0.iter().flat_map(|limb| limb.to_ne_bytes()).collect();
+    let contains_secret = |slot: &MaybeUninit<PrivateKey>| {
+        // Safety: the slot stays allocated after the key in it is dropped
+        let memory = unsafe {
+            std::slice::from_raw_parts(slot.as_ptr() as *const u8, std::mem::size_of::<PrivateKey>())
+        };
+        memory.windows(needle.len()).any(|window| window == needle.as_slice())
+    };
+
+    let mut slot = MaybeUninit::<PrivateKey>::uninit();
+    slot.write(PrivateKey::new(secret));
+    // Make sure the scan would find the secret if it were still there
+    assert!(contains_secret(&slot));
+    unsafe { slot.assume_init_drop() };
+    assert!(!contains_secret(&slot));
+}
+
+#[test]
+fn test_wif() {
+    // From chapter 4 of Programming Bitcoin
+    let cases = [
+        (U256::from(5003), true, true, "cMahea7zqjxrtgAbB7LSGbcQUr1uX1ojuat9jZodMN8rFTv2sfUK"),
+        (U256::from(2021).pow(U256::from(5)), false, true, "91avARGdfge8E4tZfYLoxeJ5sGBdNJQH4kvjpWAxgzczjbCwxic"),
+        (U256::from(0x54321deadbeefu64), true, false, "KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgiuQJv1h8Ytr2S53a"),
+    ];
+    for (secret, compressed, testnet, wif) in cases {
+        assert_eq!(PrivateKey::new(secret).wif(compressed, testnet), wif);
+        let (private_key, metadata) = PrivateKey::from_wif(wif).unwrap();
+        assert_eq!(private_key.to_bytes(), secret.to_big_endian());
+        assert_eq!(metadata, WifMetadata { compressed, testnet });
+    }
+
+    let secret = rng::get_random_u256() % S256Params::n();
+    for (compressed, testnet) in [(false, false), (false, true), (true, false), (true, true)] {
+        let wif = PrivateKey::new(secret).wif(compressed, testnet);
+        let (private_key, metadata) = PrivateKey::from_wif(&wif).unwrap();
+        assert_eq!(private_key.wif(metadata.compressed, metadata.testnet), wif);
+    }
+}
+
+#[test]
+fn test_wif_errors() {
+    let wif = "KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgiuQJv1h8Ytr2S53a";
+    let mut corrupted = wif.to_string();
+    corrupted.replace_range(10..11, "x");
+    assert!(PrivateKey::from_wif(&corrupted).is_err());
+    assert!(PrivateKey::from_wif("0OIl").is_err());
+
+    let secret = [0x11u8; 32];
+    let encode = |payload: &[u8]| programming_bitcoin::utils::base58::encode_base58_checksum(payload);
+    let with = |prefix: u8, suffix: &[u8]| [&[prefix][..], &secret, suffix].concat();
+    assert!(PrivateKey::from_wif(&encode(&with(0x80, &[0x01]))).is_ok());
+    assert_eq!(
+        PrivateKey::from_wif(&encode(&with(0x00, &[0x01]))).err(),
+        Some("Invalid WIF network prefix: 0x00".to_string())
+    );
+    assert_eq!(
+        PrivateKey::from_wif(&encode(&with(0x80, &[0x02]))).err(),
+        Some("Invalid WIF compression flag: 0x02".to_string())
+    );
+    assert_eq!(
+        PrivateKey::from_wif(&encode(&with(0xef, &[0x01, 0x01]))).err(),
+        Some("Invalid WIF length: 35".to_string())
+    );
+    // A secret of n is out of range
+    let n = S256Params::n().to_big_endian();
+    assert!(PrivateKey::from_wif(&encode(&[&[0x80][..], &n].concat())).is_err());
+}
+
+#[test]
+fn test_private_key_bytes_and_hex() {
+    let secret = rng::get_random_u256() % S256Params::n();
+    let private_key = PrivateKey::new(secret);
+    let bytes = private_key.to_bytes();
+    assert_eq!(PrivateKey::from_bytes(&bytes).unwrap().to_bytes(), bytes);
+    let hex = private_key.to_hex();
+    assert_eq!(hex.len(), 64);
+    assert_eq!(PrivateKey::from_hex(&hex).unwrap().to_bytes(), bytes);
+    assert_eq!(PrivateKey::from_hex(&hex.to_uppercase()).unwrap().to_bytes(), bytes);
+
+    assert!(PrivateKey::from_bytes(&bytes[..31]).is_err());
+    assert!(PrivateKey::from_bytes(&[0u8; 32]).is_err());
+    assert!(PrivateKey::from_bytes(&S256Params::n().to_big_endian()).is_err());
+    assert!(PrivateKey::from_hex("not hex").is_err());
+    assert_eq!(
+        PrivateKey::from_hex("0000000000000000000000000000000000000000000000000000000000000001").unwrap().to_hex(),
+        "0000000000000000000000000000000000000000000000000000000000000001"
+    );
+}
+
+// Hands out fixed 32 byte blocks, one per call, to check which candidates
+// generate rejects. Shorter requests take the start of the block.
+struct ScriptedRng(Vec<[u8; 32]>);
+
+impl rand::RngCore for ScriptedRng {
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0u8; 4];
+        self.fill_bytes(&mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0u8; 8];
+        self.fill_bytes(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        dest.copy_from_slice(&self.0.remove(0)[..dest.len()]);
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl rand::CryptoRng for ScriptedRng {}
+
+#[test]
+fn test_generate_private_key() {
+    let a = PrivateKey::generate();
+    let b = PrivateKey::generate();
+    assert_ne!(a.to_bytes(), b.to_bytes());
+    for key in [&a, &b] {
+        let secret = U256::from_big_endian(&key.to_bytes());
+        assert!(!secret.is_zero() && secret < S256Params::n());
+    }
+
+    // Zero, n and 2^256 - 1 are all rejected
+    let valid = [0x42u8; 32];
+    let mut rng = ScriptedRng(vec![[0u8; 32], S256Params::n().to_big_endian(), [0xffu8; 32], valid]);
+    assert_eq!(PrivateKey::generate_with_rng(&mut rng).to_bytes(), valid);
+    assert!(rng.0.is_empty());
+}
+
+#[test]
+fn test_sign_with_extra_entropy() {
+    // From rust-secp256k1's test_noncedata, signed by libsecp256k1 with
+    // [42; 32] as nonce data
+    let private_key = PrivateKey::from_hex("57f0148f94d13095cfda539d0da0d1541304b678d8b36e243980aab4e1b7cead").unwrap();
+    let z = hex::decode("887d04bb1cf1b1554f1b268dfe62d13064ca67ae45348d50d1392ce2d13418ac").unwrap();
+    let signature = private_key.sign_with_extra_entropy(z.clone(), &[42u8; 32]);
+    let expected = hex::decode("24861b3edd4e7da43319c635091405feced6efa4ec99c3c3c35f6c3ba0ed8816116772e84994084db85a6c20589f6a85af569d42275c2a5dd900da5776b99d5d").unwrap();
+    assert_eq!(signature.r(), U256::from_big_endian(&expected[..32]));
+    assert_eq!(signature.s(), U256::from_big_endian(&expected[32..]));
+
+    // No extra entropy is plain RFC 6979
+    let z_int = U256::from_big_endian(&z);
+    assert_eq!(private_key.deterministic_k_with_extra_entropy(z_int, &[]), private_key.deterministic_k(z_int));
+
+    // Fresh randomness gives a different, but still valid, signature
+    let mut entropy = [0u8; 32];
+    rand::RngCore::fill_bytes(&mut rand::rngs::OsRng, &mut entropy);
+    let randomized = private_key.sign_with_extra_entropy(z.clone(), &entropy);
+    assert_ne!(randomized.r(), private_key.sign(z.clone()).r());
+    let public_key = PrivateKey::from_hex("57f0148f94d13095cfda539d0da0d1541304b678d8b36e243980aab4e1b7cead").unwrap().point();
+    assert!(public_key.clone().verify(z.clone(), randomized));
+    assert!(public_key.verify(z, signature));
+}
+
+#[test]
+fn test_sign_low_r() {
+    // From rust-secp256k1's test_low_r: the plain RFC 6979 signature has a
+    // high r, so one retry with counter 1 as extra entropy is needed
+    let private_key = PrivateKey::from_hex("57f0148f94d13095cfda539d0da0d1541304b678d8b36e243980aab4e1b7cead").unwrap();
+    let z = hex::decode("887d04bb1cf1b1554f1b268dfe62d13064ca67ae45348d50d1392ce2d13418ac").unwrap();
+    assert!(private_key.sign(z.clone()).r().bit(255));
+    let signature = private_key.sign_low_r(z.clone());
+    let expected = hex::decode("047dd4d049db02b430d24c41c7925b2725bcd5a85393513bdec04b4dc363632b1054d0180094122b380f4cfa391e6296244da773173e78fc745c1b9c79f7b713").unwrap();
+    assert_eq!(signature.r(), U256::from_big_endian(&expected[..32]));
+    assert_eq!(signature.s(), U256::from_big_endian(&expected[32..]));
+
+    for i in 0..16u8 {
+        let z = hash256(&[i]);
+        let signature = private_key.sign_low_r(z.clone());
+        assert!(!signature.r().bit(255));
+        assert!(signature.der().len() <= 70);
+        // Grinding keeps a signature that is already low R
+        let plain = private_key.sign(z.clone());
+        if !plain.r().bit(255) {
+            assert_eq!(plain.r(), signature.r());
+        }
+        let public_key = PrivateKey::from_hex("57f0148f94d13095cfda539d0da0d1541304b678d8b36e243980aab4e1b7cead").unwrap().point();
+        assert!(public_key.verify(z, signature));
+    }
+}
+
+// Signs z with a fixed nonce k, the way a broken signer would
+pub fn sign_with_nonce(secret: Scalar, k: Scalar, z: &[u8]) -> Signature {
+    let (x, _) = PrivateKey::new(k.to_u256()).point().coordinates().unwrap();
+    let r = Scalar::reduce(x);
+    let s = (Scalar::reduce_bytes(z) + r * secret) * k.invert().unwrap();
+    Signature::new(r.to_u256(), s.to_u256())
+}
+
+#[test]
+fn test_recover_key_from_nonce_reuse() {
+    let secret = Scalar::reduce(U256::from_str_radix("0x1e99423a4ed27608a15a2616a2b0e9e52ced330ac530edcc32c8ffc6a526aedd", 16).unwrap());
+    let public_key = PrivateKey::new(secret.to_u256()).point();
+    let k = Scalar::reduce_bytes(&hash256(b"the same nonce twice"));
+    let (z1, z2) = (hash256(b"first message"), hash256(b"second message"));
+    let sig1 = sign_with_nonce(secret, k, &z1);
+    let sig2 = sign_with_nonce(secret, k, &z2);
+    assert!(public_key.clone().verify(z1.clone(), sig1.clone()));
+    assert!(public_key.clone().verify(z2.clone(), sig2.clone()));
+
+    // One of the two candidates is the key, whichever sign each s has
+    let candidates = candidate_keys(&z1, &sig1, &z2, &sig2).unwrap();
+    assert_eq!(candidates.len(), 2);
+    assert!(candidates.iter().any(|candidate| candidate.to_bytes() == secret.to_bytes()));
+    for (sig1, sig2) in [(sig1.clone(), sig2.clone()), (sig1.normalize_s(), sig2.normalize_s()), (sig1.clone(), sig2.normalize_s())] {
+        let recovered = recover_private_key(&z1, &sig1, &z2, &sig2, &public_key).unwrap();
+        assert_eq!(recovered.to_bytes(), secret.to_bytes());
+    }
+
+    let other_nonce = sign_with_nonce(secret, k + Scalar::one(), &z2);
+    assert_eq!(recover_private_key(&z1, &sig1, &z2, &other_nonce, &public_key).err(), Some(NonceReuseError::DifferentNonce));
+    assert_eq!(recover_private_key(&z1, &sig1, &z1, &sig1, &public_key).err(), Some(NonceReuseError::SameMessage));
+    let other_key = PrivateKey::new(U256::from(2)).point();
+    assert_eq!(recover_private_key(&z1, &sig1, &z2, &sig2, &other_key).err(), Some(NonceReuseError::KeyMismatch));
+    let zero_s = Signature::new(sig2.r(), U256::zero());
+    assert_eq!(candidate_keys(&z1, &sig1, &z2, &zero_s).err(), Some(NonceReuseError::InvalidSignature));
 }
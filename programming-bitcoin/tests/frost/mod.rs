@@ -0,0 +1,180 @@
+use primitive_types::U256;
+use programming_bitcoin::ecc::frost::*;
+use programming_bitcoin::ecc::private_key::PrivateKey;
+use programming_bitcoin::ecc::scalar::Scalar;
+use programming_bitcoin::ecc::xonly_public_key::XOnlyPublicKey;
+
+// Runs both rounds of signing with the given signers and returns the
+// signing package and their signature shares
+fn sign_with(key_packages: &[&KeyPackage], message: &[u8]) -> (SigningPackage, Vec<SignatureShare>) {
+    let (nonces, commitments): (Vec<SigningNonces>, Vec<SigningCommitments>) =
+        key_packages.iter().map(|key_package| commit(key_package)).unzip();
+    let signing_package = SigningPackage::new(commitments, message).unwrap();
+    let shares = nonces
+        .into_iter()
+        .zip(key_packages)
+        .map(|(nonces, key_package)| sign(&signing_package, nonces, key_package).unwrap())
+        .collect();
+    (signing_package, shares)
+}
+
+fn dealer_key_packages(threshold: usize, participants: u16) -> (Vec<KeyPackage>, PublicKeyPackage) {
+    let (shares, public_key_package) = trusted_dealer_keygen(threshold, participants).unwrap();
+    let key_packages = shares.into_iter().map(|share| share.into_key_package().unwrap()).collect();
+    (key_packages, public_key_package)
+}
+
+// Runs distributed key generation between n simulated participants
+fn dkg(threshold: usize, participants: u16) -> (Vec<KeyPackage>, Vec<PublicKeyPackage>) {
+    let (secrets, round1): (Vec<DkgRound1Secret>, Vec<DkgRound1Package>) =
+        (1..=participants).map(|id| dkg_part1(id, threshold, participants).unwrap()).unzip();
+    let round2: Vec<DkgRound2Package> = secrets.iter().flat_map(|secret| dkg_part2(secret, &round1).unwrap()).collect();
+    secrets.iter().map(|secret| dkg_part3(secret, &round1, &round2).unwrap()).unzip()
+}
+
+#[test]
+fn test_frost_trusted_dealer_2_of_3() {
+    let (key_packages, public_key_package) = dealer_key_packages(2, 3);
+    let group_key = public_key_package.group_public_key();
+    for key_package in &key_packages {
+        assert_eq!(key_package.group_public_key(), group_key);
+        assert_eq!(key_package.threshold(), 2);
+        assert_eq!(public_key_package.verifying_share(key_package.identifier()).unwrap().coordinates(), key_package.verifying_share().coordinates());
+    }
+
+    // Every pair of signers can sign
+    let message = b"FROST 2-of-3";
+    for (a, b) in [(0, 1), (0, 2), (1, 2)] {
+        let (signing_package, shares) = sign_with(&[&key_packages[a], &key_packages[b]], message);
+        for share in &shares {
+            assert!(verify_signature_share(&signing_package, share, &public_key_package));
+        }
+        let signature = aggregate(&signing_package, &shares, &public_key_package).unwrap();
+        assert!(group_key.verify_schnorr(message, &signature));
+        assert!(!group_key.verify_schnorr(b"another message", &signature));
+    }
+}
+
+#[test]
+fn test_frost_dealer_shares_recover_secret() {
+    let secret = Scalar::reduce(U256::from(0xdeadbeefu64));
+    let (shares, public_key_package) = trusted_dealer_keygen_with_secret(secret, 3, 5).unwrap();
+    let expected = XOnlyPublicKey::from_point(&PrivateKey::new(secret.to_u256()).point());
+    assert_eq!(public_key_package.group_public_key(), expected);
+
+    for share in &shares {
+        assert!(share.verify().is_ok());
+    }
+    // The Lagrange coefficients of any three signers add up to 1 at x = 0
+    let signers = [1, 3, 5];
+    let sum = signers.iter().fold(Scalar::zero(), |sum, id| sum + lagrange_coefficient(*id, &signers));
+    assert_eq!(sum, Scalar::one());
+}
+
+#[test]
+fn test_frost_dkg_3_of_5() {
+    let (key_packages, public_key_packages) = dkg(3, 5);
+    // Everyone agrees on the group key and on each other's verifying shares
+    for public_key_package in &public_key_packages[1..] {
+        assert_eq!(public_key_package, &public_key_packages[0]);
+    }
+    let public_key_package = &public_key_packages[0];
+    for key_package in &key_packages {
+        assert_eq!(public_key_package.verifying_share(key_package.identifier()).unwrap().coordinates(), key_package.verifying_share().coordinates());
+    }
+
+    let group_key = public_key_package.group_public_key();
+    let message = b"FROST 3-of-5 with distributed key generation";
+    for signers in [[0, 1, 2], [0, 2, 4], [1, 3, 4], [2, 3, 4]] {
+        let chosen: Vec<&KeyPackage> = signers.iter().map(|i| &key_packages[*i]).collect();
+        let (signing_package, shares) = sign_with(&chosen, message);
+        let signature = aggregate(&signing_package, &shares, public_key_package).unwrap();
+        assert!(group_key.verify_schnorr(message, &signature));
+    }
+
+    // More signers than the threshold also works
+    let chosen: Vec<&KeyPackage> = key_packages.iter().collect();
+    let (signing_package, shares) = sign_with(&chosen, message);
+    let signature = aggregate(&signing_package, &shares, public_key_package).unwrap();
+    assert!(group_key.verify_schnorr(message, &signature));
+}
+
+#[test]
+fn test_frost_not_enough_signers() {
+    let (key_packages, public_key_package) = dealer_key_packages(3, 5);
+    let (nonces, commitments): (Vec<SigningNonces>, Vec<SigningCommitments>) =
+        key_packages[..2].iter().map(commit).unzip();
+    let small_package = SigningPackage::new(commitments, b"message").unwrap();
+    let nonces = nonces.into_iter().next().unwrap();
+    assert_eq!(sign(&small_package, nonces, &key_packages[0]).unwrap_err(), FrostError::NotEnoughSigners);
+
+    // Nor does the coordinator aggregate, rather than blame an honest signer
+    let (signing_package, shares) = sign_with(&[&key_packages[0], &key_packages[1], &key_packages[2]], b"message");
+    assert_eq!(aggregate(&signing_package, &[], &public_key_package).unwrap_err(), FrostError::NotEnoughSigners);
+    assert_eq!(aggregate(&small_package, &shares, &public_key_package).unwrap_err(), FrostError::NotEnoughSigners);
+    assert_eq!(public_key_package.threshold(), 3);
+
+    // A signer who isn't in the package can't sign
+    let (nonces, _) = commit(&key_packages[4]);
+    let (_, commitments): (Vec<SigningNonces>, Vec<SigningCommitments>) = key_packages[..3].iter().map(commit).unzip();
+    let signing_package = SigningPackage::new(commitments, b"message").unwrap();
+    assert_eq!(sign(&signing_package, nonces, &key_packages[4]).unwrap_err(), FrostError::SignerNotInPackage(5));
+}
+
+#[test]
+fn test_frost_invalid_signature_share() {
+    let (key_packages, public_key_package) = dealer_key_packages(2, 3);
+    let message = b"blame the cheater";
+    let (signing_package, mut shares) = sign_with(&[&key_packages[0], &key_packages[2]], message);
+
+    // Replace signer 3's share with one made for a different message
+    let (nonces, commitments) = commit(&key_packages[2]);
+    let (_, other_commitments) = commit(&key_packages[0]);
+    let other_package = SigningPackage::new(vec![other_commitments, commitments], b"something else").unwrap();
+    shares[1] = sign(&other_package, nonces, &key_packages[2]).unwrap();
+
+    assert!(verify_signature_share(&signing_package, &shares[0], &public_key_package));
+    assert!(!verify_signature_share(&signing_package, &shares[1], &public_key_package));
+    assert_eq!(
+        aggregate(&signing_package, &shares, &public_key_package).unwrap_err(),
+        FrostError::InvalidSignatureShare(3)
+    );
+    assert_eq!(
+        aggregate(&signing_package, &shares[..1], &public_key_package).unwrap_err(),
+        FrostError::MissingParticipant(3)
+    );
+}
+
+#[test]
+fn test_frost_keygen_errors() {
+    assert_eq!(trusted_dealer_keygen(0, 3).unwrap_err(), FrostError::InvalidThreshold);
+    assert_eq!(trusted_dealer_keygen(4, 3).unwrap_err(), FrostError::InvalidThreshold);
+    assert_eq!(trusted_dealer_keygen_with_secret(Scalar::zero(), 2, 3).unwrap_err(), FrostError::InvalidSecret);
+    assert_eq!(dkg_part1(0, 2, 3).unwrap_err(), FrostError::InvalidIdentifier(0));
+    assert_eq!(dkg_part1(4, 2, 3).unwrap_err(), FrostError::InvalidIdentifier(4));
+
+    let (_, commitments): (Vec<SigningNonces>, Vec<SigningCommitments>) =
+        dealer_key_packages(2, 3).0[..1].iter().map(commit).unzip();
+    let duplicated = vec![commitments[0].clone(), commitments[0].clone()];
+    assert_eq!(SigningPackage::new(duplicated, b"").unwrap_err(), FrostError::DuplicateIdentifier(1));
+}
+
+#[test]
+fn test_frost_dkg_detects_cheating() {
+    let (secrets, round1): (Vec<DkgRound1Secret>, Vec<DkgRound1Package>) =
+        (1..=3).map(|id| dkg_part1(id, 2, 3).unwrap()).unzip();
+    assert_eq!(dkg_part2(&secrets[0], &round1[..2]).unwrap_err(), FrostError::MissingParticipant(3));
+
+    // Participant 2 commits to a polynomial of the wrong degree
+    let mut wrong_degree = round1.clone();
+    wrong_degree[1] = dkg_part1(2, 3, 3).unwrap().1;
+    assert_eq!(dkg_part2(&secrets[0], &wrong_degree).unwrap_err(), FrostError::InvalidCommitment(2));
+
+    // Participant 2 sends shares of a different polynomial than they committed to
+    let round2: Vec<DkgRound2Package> = secrets.iter().flat_map(|secret| dkg_part2(secret, &round1).unwrap()).collect();
+    let (other_secret, _) = dkg_part1(2, 2, 3).unwrap();
+    let other_shares = dkg_part2(&other_secret, &round1).unwrap();
+    let mut cheating: Vec<DkgRound2Package> = round2.into_iter().filter(|package| package.sender() != 2).collect();
+    cheating.extend(other_shares);
+    assert_eq!(dkg_part3(&secrets[0], &round1, &cheating).unwrap_err(), FrostError::InvalidSecretShare(2));
+}
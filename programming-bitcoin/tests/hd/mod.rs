@@ -0,0 +1,532 @@
+use primitive_types::U256;
+use programming_bitcoin::ecc::secp256k1_params::S256Params;
+use programming_bitcoin::address::address::address_to_script_pubkey;
+use programming_bitcoin::hd::account::{Account, Chain, Purpose};
+use programming_bitcoin::hd::derivation_path::DerivationPath;
+use programming_bitcoin::hd::error::{Bip32Error, Bip39Error};
+use programming_bitcoin::hd::extended_key::{ExtendedPrivateKey, ExtendedPublicKey, HARDENED};
+use programming_bitcoin::hd::mnemonic::Mnemonic;
+use programming_bitcoin::utils::base58::{decode_base58_checksum, encode_base58, encode_base58_checksum};
+use programming_bitcoin::utils::hash160::hash160;
+
+// Test vectors 1 to 4 from BIP32: the seed, then each derivation path with
+// its xprv and xpub
+type Vector = (&'static str, &'static [(&'static str, &'static str, &'static str)]);
+
+const VECTORS: [Vector; 4] = [
+    (
+        // Test vector 1
+        "000102030405060708090a0b0c0d0e0f",
+        &[
+            ("m", "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi", "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8"),
+            ("m/0'", "xprv9uHRZZhk6KAJC1avXpDAp4MDc3sQKNxDiPvvkX8Br5ngLNv1TxvUxt4cV1rGL5hj6KCesnDYUhd7oWgT11eZG7XnxHrnYeSvkzY7d2bhkJ7", "xpub68Gmy5EdvgibQVfPdqkBBCHxA5htiqg55crXYuXoQRKfDBFA1WEjWgP6LHhwBZeNK1VTsfTFUHCdrfp1bgwQ9xv5ski8PX9rL2dZXvgGDnw"),
+            ("m/0'/1", "xprv9wTYmMFdV23N2TdNG573QoEsfRrWKQgWeibmLntzniatZvR9BmLnvSxqu53Kw1UmYPxLgboyZQaXwTCg8MSY3H2EU4pWcQDnRnrVA1xe8fs", "xpub6ASuArnXKPbfEwhqN6e3mwBcDTgzisQN1wXN9BJcM47sSikHjJf3UFHKkNAWbWMiGj7Wf5uMash7SyYq527Hqck2AxYysAA7xmALppuCkwQ"),
+            ("m/0'/1/2'", "xprv9z4pot5VBttmtdRTWfWQmoH1taj2axGVzFqSb8C9xaxKymcFzXBDptWmT7FwuEzG3ryjH4ktypQSAewRiNMjANTtpgP4mLTj34bhnZX7UiM", "xpub6D4BDPcP2GT577Vvch3R8wDkScZWzQzMMUm3PWbmWvVJrZwQY4VUNgqFJPMM3No2dFDFGTsxxpG5uJh7n7epu4trkrX7x7DogT5Uv6fcLW5"),
+            ("m/0'/1/2'/2", "xprvA2JDeKCSNNZky6uBCviVfJSKyQ1mDYahRjijr5idH2WwLsEd4Hsb2Tyh8RfQMuPh7f7RtyzTtdrbdqqsunu5Mm3wDvUAKRHSC34sJ7in334", "xpub6FHa3pjLCk84BayeJxFW2SP4XRrFd1JYnxeLeU8EqN3vDfZmbqBqaGJAyiLjTAwm6ZLRQUMv1ZACTj37sR62cfN7fe5JnJ7dh8zL4fiyLHV"),
+            ("m/0'/1/2'/2/1000000000", "xprvA41z7zogVVwxVSgdKUHDy1SKmdb533PjDz7J6N6mV6uS3ze1ai8FHa8kmHScGpWmj4WggLyQjgPie1rFSruoUihUZREPSL39UNdE3BBDu76", "xpub6H1LXWLaKsWFhvm6RVpEL9P4KfRZSW7abD2ttkWP3SSQvnyA8FSVqNTEcYFgJS2UaFcxupHiYkro49S8yGasTvXEYBVPamhGW6cFJodrTHy"),
+        ],
+    ),
+    (
+        // Test vector 2
+        "fffcf9f6f3f0edeae7e4e1dedbd8d5d2cfccc9c6c3c0bdbab7b4b1aeaba8a5a29f9c999693908d8a8784817e7b7875726f6c696663605d5a5754514e4b484542",
+        &[
+            ("m", "xprv9s21ZrQH143K31xYSDQpPDxsXRTUcvj2iNHm5NUtrGiGG5e2DtALGdso3pGz6ssrdK4PFmM8NSpSBHNqPqm55Qn3LqFtT2emdEXVYsCzC2U", "xpub661MyMwAqRbcFW31YEwpkMuc5THy2PSt5bDMsktWQcFF8syAmRUapSCGu8ED9W6oDMSgv6Zz8idoc4a6mr8BDzTJY47LJhkJ8UB7WEGuduB"),
+            ("m/0", "xprv9vHkqa6EV4sPZHYqZznhT2NPtPCjKuDKGY38FBWLvgaDx45zo9WQRUT3dKYnjwih2yJD9mkrocEZXo1ex8G81dwSM1fwqWpWkeS3v86pgKt", "xpub69H7F5d8KSRgmmdJg2KhpAK8SR3DjMwAdkxj3ZuxV27CprR9LgpeyGmXUbC6wb7ERfvrnKZjXoUmmDznezpbZb7ap6r1D3tgFxHmwMkQTPH"),
+            ("m/0/2147483647'", "xprv9wSp6B7kry3Vj9m1zSnLvN3xH8RdsPP1Mh7fAaR7aRLcQMKTR2vidYEeEg2mUCTAwCd6vnxVrcjfy2kRgVsFawNzmjuHc2YmYRmagcEPdU9", "xpub6ASAVgeehLbnwdqV6UKMHVzgqAG8Gr6riv3Fxxpj8ksbH9ebxaEyBLZ85ySDhKiLDBrQSARLq1uNRts8RuJiHjaDMBU4Zn9h8LZNnBC5y4a"),
+            ("m/0/2147483647'/1", "xprv9zFnWC6h2cLgpmSA46vutJzBcfJ8yaJGg8cX1e5StJh45BBciYTRXSd25UEPVuesF9yog62tGAQtHjXajPPdbRCHuWS6T8XA2ECKADdw4Ef", "xpub6DF8uhdarytz3FWdA8TvFSvvAh8dP3283MY7p2V4SeE2wyWmG5mg5EwVvmdMVCQcoNJxGoWaU9DCWh89LojfZ537wTfunKau47EL2dhHKon"),
+            ("m/0/2147483647'/1/2147483646'", "xprvA1RpRA33e1JQ7ifknakTFpgNXPmW2YvmhqLQYMmrj4xJXXWYpDPS3xz7iAxn8L39njGVyuoseXzU6rcxFLJ8HFsTjSyQbLYnMpCqE2VbFWc", "xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL"),
+            ("m/0/2147483647'/1/2147483646'/2", "xprvA2nrNbFZABcdryreWet9Ea4LvTJcGsqrMzxHx98MMrotbir7yrKCEXw7nadnHM8Dq38EGfSh6dqA9QWTyefMLEcBYJUuekgW4BYPJcr9E7j", "xpub6FnCn6nSzZAw5Tw7cgR9bi15UV96gLZhjDstkXXxvCLsUXBGXPdSnLFbdpq8p9HmGsApME5hQTZ3emM2rnY5agb9rXpVGyy3bdW6EEgAtqt"),
+        ],
+    ),
+    (
+        // Test vector 3
+        "4b381541583be4423346c643850da4b320e46a87ae3d2a4e6da11eba819cd4acba45d239319ac14f863b8d5ab5a0d0c64d2e8a1e7d1457df2e5a3c51c73235be",
+        &[
+            ("m", "xprv9s21ZrQH143K25QhxbucbDDuQ4naNntJRi4KUfWT7xo4EKsHt2QJDu7KXp1A3u7Bi1j8ph3EGsZ9Xvz9dGuVrtHHs7pXeTzjuxBrCmmhgC6", "xpub661MyMwAqRbcEZVB4dScxMAdx6d4nFc9nvyvH3v4gJL378CSRZiYmhRoP7mBy6gSPSCYk6SzXPTf3ND1cZAceL7SfJ1Z3GC8vBgp2epUt13"),
+            ("m/0'", "xprv9uPDJpEQgRQfDcW7BkF7eTya6RPxXeJCqCJGHuCJ4GiRVLzkTXBAJMu2qaMWPrS7AANYqdq6vcBcBUdJCVVFceUvJFjaPdGZ2y9WACViL4L", "xpub68NZiKmJWnxxS6aaHmn81bvJeTESw724CRDs6HbuccFQN9Ku14VQrADWgqbhhTHBaohPX4CjNLf9fq9MYo6oDaPPLPxSb7gwQN3ih19Zm4Y"),
+        ],
+    ),
+    (
+        // Test vector 4
+        "3ddd5602285899a946114506157c7997e5444528f3003f6134712147db19b678",
+        &[
+            ("m", "xprv9s21ZrQH143K48vGoLGRPxgo2JNkJ3J3fqkirQC2zVdk5Dgd5w14S7fRDyHH4dWNHUgkvsvNDCkvAwcSHNAQwhwgNMgZhLtQC63zxwhQmRv", "xpub661MyMwAqRbcGczjuMoRm6dXaLDEhW1u34gKenbeYqAix21mdUKJyuyu5F1rzYGVxyL6tmgBUAEPrEz92mBXjByMRiJdba9wpnN37RLLAXa"),
+            ("m/0'", "xprv9vB7xEWwNp9kh1wQRfCCQMnZUEG21LpbR9NPCNN1dwhiZkjjeGRnaALmPXCX7SgjFTiCTT6bXes17boXtjq3xLpcDjzEuGLQBM5ohqkao9G", "xpub69AUMk3qDBi3uW1sXgjCmVjJ2G6WQoYSnNHyzkmdCHEhSZ4tBok37xfFEqHd2AddP56Tqp4o56AePAgCjYdvpW2PU2jbUPFKsav5ut6Ch1m"),
+            ("m/0'/1'", "xprv9xJocDuwtYCMNAo3Zw76WENQeAS6WGXQ55RCy7tDJ8oALr4FWkuVoHJeHVAcAqiZLE7Je3vZJHxspZdFHfnBEjHqU5hG1Jaj32dVoS6XLT1", "xpub6BJA1jSqiukeaesWfxe6sNK9CCGaujFFSJLomWHprUL9DePQ4JDkM5d88n49sMGJxrhpjazuXYWdMf17C9T5XnxkopaeS7jGk1GyyVziaMt"),
+        ],
+    ),
+];
+
+#[test]
+fn test_bip32_vectors() {
+    for (seed, chains) in VECTORS {
+        let master = ExtendedPrivateKey::from_seed(&hex::decode(seed).unwrap(), false).unwrap();
+        for (path, xprv, xpub) in chains {
+            let mut key = ExtendedPrivateKey::from_base58(&master.to_base58()).unwrap();
+            let mut public_key = master.extended_public_key();
+            let derivation_path = DerivationPath::parse(path).unwrap();
+            for &index in derivation_path.indexes() {
+                key = key.derive_child(index).unwrap();
+                // Public derivation gives the same key for non-hardened children
+                public_key = match public_key.derive_child(index) {
+                    Ok(child) => child,
+                    Err(error) => {
+                        assert_eq!(error, Bip32Error::HardenedFromPublic(index));
+                        key.extended_public_key()
+                    }
+                };
+            }
+            assert_eq!(key.to_base58(), *xprv, "xprv for {}", path);
+            assert_eq!(key.extended_public_key().to_base58(), *xpub, "xpub for {}", path);
+            assert_eq!(public_key.to_string(), *xpub, "public derivation for {}", path);
+            assert_eq!(master.derive_path(&derivation_path).unwrap().to_base58(), *xprv, "derive_path for {}", path);
+
+            // Both round trip through Base58
+            assert_eq!(ExtendedPrivateKey::from_base58(xprv).unwrap().to_base58(), *xprv);
+            assert_eq!(ExtendedPublicKey::from_base58(xpub).unwrap(), public_key);
+        }
+    }
+}
+
+#[test]
+fn test_bip32_key_metadata() {
+    let seed = hex::decode(VECTORS[0].0).unwrap();
+    let master = ExtendedPrivateKey::from_seed(&seed, false).unwrap();
+    assert_eq!(master.depth(), 0);
+    assert_eq!(master.parent_fingerprint(), [0; 4]);
+    // The identifier and fingerprint of vector 1's master key from BIP32
+    assert_eq!(hex::encode(master.identifier()), "3442193e1bb70916e914552172cd4e2dbc9df811");
+    assert_eq!(hex::encode(master.fingerprint()), "3442193e");
+    assert_eq!(master.fingerprint(), master.extended_public_key().fingerprint());
+
+    let child = master.derive_child(HARDENED).unwrap().derive_child(1).unwrap();
+    assert_eq!(child.depth(), 2);
+    assert_eq!(child.child_number(), 1);
+    assert_eq!(child.parent_fingerprint(), master.derive_child(HARDENED).unwrap().fingerprint());
+    assert_eq!(child.identifier(), hash160(&child.public_key().sec(true)));
+    assert_eq!(child.extended_public_key().public_key().coordinates(), child.public_key().coordinates());
+
+    // Testnet keys serialize as tprv and tpub
+    let testnet = ExtendedPrivateKey::from_seed(&seed, true).unwrap().derive_child(HARDENED).unwrap();
+    let tprv = testnet.to_base58();
+    let tpub = testnet.extended_public_key().to_base58();
+    assert!(tprv.starts_with("tprv"));
+    assert!(tpub.starts_with("tpub"));
+    assert!(ExtendedPrivateKey::from_base58(&tprv).unwrap().testnet());
+    assert!(ExtendedPublicKey::from_base58(&tpub).unwrap().testnet());
+    assert_eq!(testnet.private_key().to_bytes(), master.derive_child(HARDENED).unwrap().private_key().to_bytes());
+
+    // Secrets stay out of Debug output
+    let debug = format!("{:?}", master);
+    assert!(!debug.contains(&hex::encode(master.chain_code())));
+    assert!(!debug.contains(&master.private_key().to_hex()));
+}
+
+#[test]
+fn test_bip32_derivation_errors() {
+    assert_eq!(ExtendedPrivateKey::from_seed(&[0; 15], false).err(), Some(Bip32Error::InvalidSeedLength(15)));
+    assert_eq!(ExtendedPrivateKey::from_seed(&[0; 65], false).err(), Some(Bip32Error::InvalidSeedLength(65)));
+
+    let master = ExtendedPrivateKey::from_seed(&hex::decode(VECTORS[0].0).unwrap(), false).unwrap();
+    let xpub = master.extended_public_key();
+    assert_eq!(xpub.derive_child(HARDENED + 5).unwrap_err(), Bip32Error::HardenedFromPublic(HARDENED + 5));
+
+    // A key at depth 255 has no children
+    let mut payload = master.serialize();
+    payload[4] = 255;
+    payload[5..9].copy_from_slice(&[1, 2, 3, 4]);
+    let deepest = ExtendedPrivateKey::from_base58(&encode_base58_checksum(&payload)).unwrap();
+    assert_eq!(deepest.derive_child(0).err(), Some(Bip32Error::MaxDepthExceeded));
+    assert_eq!(deepest.extended_public_key().derive_child(0).unwrap_err(), Bip32Error::MaxDepthExceeded);
+}
+
+// Re-encodes a serialized key after changing it
+fn tampered(serialized: &[u8], change: impl FnOnce(&mut Vec<u8>)) -> String {
+    let mut payload = serialized.to_vec();
+    change(&mut payload);
+    encode_base58_checksum(&payload)
+}
+
+#[test]
+fn test_bip32_invalid_keys() {
+    let master = ExtendedPrivateKey::from_seed(&hex::decode(VECTORS[0].0).unwrap(), false).unwrap();
+    let xprv = master.serialize();
+    let xpub = master.extended_public_key().serialize();
+    let parse_xprv = |s: &str| ExtendedPrivateKey::from_base58(s).err();
+    let parse_xpub = |s: &str| ExtendedPublicKey::from_base58(s).err();
+
+    // Wrong lengths and versions
+    assert_eq!(parse_xprv(&tampered(&xprv, |payload| { payload.pop(); })), Some(Bip32Error::InvalidLength(77)));
+    assert_eq!(parse_xpub(&tampered(&xpub, |payload| payload.push(0))), Some(Bip32Error::InvalidLength(79)));
+    assert_eq!(parse_xprv(&tampered(&xprv, |payload| payload[..4].copy_from_slice(&[0; 4]))), Some(Bip32Error::InvalidVersion([0; 4])));
+    let xpub_string = master.extended_public_key().to_base58();
+    let xprv_string = master.to_base58();
+    assert_eq!(parse_xprv(&xpub_string), Some(Bip32Error::InvalidVersion(xpub[..4].try_into().unwrap())));
+    assert_eq!(parse_xpub(&xprv_string), Some(Bip32Error::InvalidVersion(xprv[..4].try_into().unwrap())));
+    // Private key data under a public version and the other way round
+    let public_version = tampered(&xprv, |payload| payload[..4].copy_from_slice(&xpub[..4]));
+    assert_eq!(parse_xpub(&public_version), Some(Bip32Error::InvalidPublicKey));
+    let private_version = tampered(&xpub, |payload| payload[..4].copy_from_slice(&xprv[..4]));
+    assert_eq!(parse_xprv(&private_version), Some(Bip32Error::InvalidPrivateKey));
+
+    // Master keys with a parent
+    assert_eq!(parse_xprv(&tampered(&xprv, |payload| payload[5] = 1)), Some(Bip32Error::InvalidParentFingerprint));
+    assert_eq!(parse_xpub(&tampered(&xpub, |payload| payload[12] = 1)), Some(Bip32Error::InvalidChildNumber));
+
+    // Private keys outside [1, n), including the all-zero and all-0xff keys
+    // from rust-bitcoin's tests
+    let zero = "xprv9s21ZrQH143K24Mfq5zL5MhWK9hUhhGbd45hLXo2Pq2oqzMMo63oStZzF93Y5wvzdUayhgkkFoicQZcP3y52uPPxFnfoLZB21Teqt1VvEHx";
+    let all_ff = "xprv9s21ZrQH143K24Mfq5zL5MhWK9hUhhGbd45hLXo2Pq2oqzMMo63oStZzFAzHGBP2UuGCqWLTAPLcMtD9y5gkZ6Eq3Rjuahrv17fENZ3QzxW";
+    assert_eq!(parse_xprv(zero), Some(Bip32Error::InvalidPrivateKey));
+    assert_eq!(parse_xprv(all_ff), Some(Bip32Error::InvalidPrivateKey));
+    let n = S256Params::n().to_big_endian();
+    assert_eq!(parse_xprv(&tampered(&xprv, |payload| payload[46..].copy_from_slice(&n))), Some(Bip32Error::InvalidPrivateKey));
+    let n_minus_one = (S256Params::n() - U256::one()).to_big_endian();
+    assert!(parse_xprv(&tampered(&xprv, |payload| payload[46..].copy_from_slice(&n_minus_one))).is_none());
+    assert_eq!(parse_xprv(&tampered(&xprv, |payload| payload[45] = 1)), Some(Bip32Error::InvalidPrivateKey));
+
+    // Public keys that are not compressed points on the curve
+    assert_eq!(parse_xpub(&tampered(&xpub, |payload| payload[45] = 0x04)), Some(Bip32Error::InvalidPublicKey));
+    assert_eq!(parse_xpub(&tampered(&xpub, |payload| payload[46..].copy_from_slice(&[0xff; 32]))), Some(Bip32Error::InvalidPublicKey));
+
+    // A bad checksum
+    assert_eq!(decode_base58_checksum(&xprv_string).unwrap(), xprv);
+    let bad_checksum = encode_base58(&[&xprv[..], &[0; 4]].concat());
+    assert!(matches!(parse_xprv(&bad_checksum), Some(Bip32Error::Base58(_))));
+}
+
+
+// The Trezor reference vectors for the English wordlist, all with the
+// passphrase "TREZOR": entropy, phrase, seed and, where published, the
+// master xprv
+type MnemonicVector = (&'static str, &'static str, &'static str, Option<&'static str>);
+
+const MNEMONIC_VECTORS: [MnemonicVector; 24] = [
+    (
+        "00000000000000000000000000000000",
+        "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+        "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04",
+        None,
+    ),
+    (
+        "7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f",
+        "legal winner thank year wave sausage worth useful legal winner thank yellow",
+        "2e8905819b8723fe2c1d161860e5ee1830318dbf49a83bd451cfb8440c28bd6fa457fe1296106559a3c80937a1c1069be3a3a5bd381ee6260e8d9739fce1f607",
+        None,
+    ),
+    (
+        "80808080808080808080808080808080",
+        "letter advice cage absurd amount doctor acoustic avoid letter advice cage above",
+        "d71de856f81a8acc65e6fc851a38d4d7ec216fd0796d0a6827a3ad6ed5511a30fa280f12eb2e47ed2ac03b5c462a0358d18d69fe4f985ec81778c1b370b652a8",
+        None,
+    ),
+    (
+        "ffffffffffffffffffffffffffffffff",
+        "zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo wrong",
+        "ac27495480225222079d7be181583751e86f571027b0497b5b5d11218e0a8a13332572917f0f8e5a589620c6f15b11c61dee327651a14c34e18231052e48c069",
+        None,
+    ),
+    (
+        "000000000000000000000000000000000000000000000000",
+        "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon agent",
+        "035895f2f481b1b0f01fcf8c289c794660b289981a78f8106447707fdd9666ca06da5a9a565181599b79f53b844d8a71dd9f439c52a3d7b3e8a79c906ac845fa",
+        None,
+    ),
+    (
+        "7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f",
+        "legal winner thank year wave sausage worth useful legal winner thank year wave sausage worth useful legal will",
+        "f2b94508732bcbacbcc020faefecfc89feafa6649a5491b8c952cede496c214a0c7b3c392d168748f2d4a612bada0753b52a1c7ac53c1e93abd5c6320b9e95dd",
+        None,
+    ),
+    (
+        "808080808080808080808080808080808080808080808080",
+        "letter advice cage absurd amount doctor acoustic avoid letter advice cage absurd amount doctor acoustic avoid letter always",
+        "107d7c02a5aa6f38c58083ff74f04c607c2d2c0ecc55501dadd72d025b751bc27fe913ffb796f841c49b1d33b610cf0e91d3aa239027f5e99fe4ce9e5088cd65",
+        None,
+    ),
+    (
+        "ffffffffffffffffffffffffffffffffffffffffffffffff",
+        "zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo when",
+        "0cd6e5d827bb62eb8fc1e262254223817fd068a74b5b449cc2f667c3f1f985a76379b43348d952e2265b4cd129090758b3e3c2c49103b5051aac2eaeb890a528",
+        None,
+    ),
+    (
+        "0000000000000000000000000000000000000000000000000000000000000000",
+        "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art",
+        "bda85446c68413707090a52022edd26a1c9462295029f2e60cd7c4f2bbd3097170af7a4d73245cafa9c3cca8d561a7c3de6f5d4a10be8ed2a5e608d68f92fcc8",
+        Some("xprv9s21ZrQH143K32qBagUJAMU2LsHg3ka7jqMcV98Y7gVeVyNStwYS3U7yVVoDZ4btbRNf4h6ibWpY22iRmXq35qgLs79f312g2kj5539ebPM"),
+    ),
+    (
+        "7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f",
+        "legal winner thank year wave sausage worth useful legal winner thank year wave sausage worth useful legal winner thank year wave sausage worth title",
+        "bc09fca1804f7e69da93c2f2028eb238c227f2e9dda30cd63699232578480a4021b146ad717fbb7e451ce9eb835f43620bf5c514db0f8add49f5d121449d3e87",
+        Some("xprv9s21ZrQH143K3Y1sd2XVu9wtqxJRvybCfAetjUrMMco6r3v9qZTBeXiBZkS8JxWbcGJZyio8TrZtm6pkbzG8SYt1sxwNLh3Wx7to5pgiVFU"),
+    ),
+    (
+        "8080808080808080808080808080808080808080808080808080808080808080",
+        "letter advice cage absurd amount doctor acoustic avoid letter advice cage absurd amount doctor acoustic avoid letter advice cage absurd amount doctor acoustic bless",
+        "c0c519bd0e91a2ed54357d9d1ebef6f5af218a153624cf4f2da911a0ed8f7a09e2ef61af0aca007096df430022f7a2b6fb91661a9589097069720d015e4e982f",
+        Some("xprv9s21ZrQH143K3CSnQNYC3MqAAqHwxeTLhDbhF43A4ss4ciWNmCY9zQGvAKUSqVUf2vPHBTSE1rB2pg4avopqSiLVzXEU8KziNnVPauTqLRo"),
+    ),
+    (
+        "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+        "zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo vote",
+        "dd48c104698c30cfe2b6142103248622fb7bb0ff692eebb00089b32d22484e1613912f0a5b694407be899ffd31ed3992c456cdf60f5d4564b8ba3f05a69890ad",
+        Some("xprv9s21ZrQH143K2WFF16X85T2QCpndrGwx6GueB72Zf3AHwHJaknRXNF37ZmDrtHrrLSHvbuRejXcnYxoZKvRquTPyp2JiNG3XcjQyzSEgqCB"),
+    ),
+    (
+        "9e885d952ad362caeb4efe34a8e91bd2",
+        "ozone drill grab fiber curtain grace pudding thank cruise elder eight picnic",
+        "274ddc525802f7c828d8ef7ddbcdc5304e87ac3535913611fbbfa986d0c9e5476c91689f9c8a54fd55bd38606aa6a8595ad213d4c9c9f9aca3fb217069a41028",
+        None,
+    ),
+    (
+        "6610b25967cdcca9d59875f5cb50b0ea75433311869e930b",
+        "gravity machine north sort system female filter attitude volume fold club stay feature office ecology stable narrow fog",
+        "628c3827a8823298ee685db84f55caa34b5cc195a778e52d45f59bcf75aba68e4d7590e101dc414bc1bbd5737666fbbef35d1f1903953b66624f910feef245ac",
+        None,
+    ),
+    (
+        "68a79eaca2324873eacc50cb9c6eca8cc68ea5d936f98787c60c7ebc74e6ce7c",
+        "hamster diagram private dutch cause delay private meat slide toddler razor book happy fancy gospel tennis maple dilemma loan word shrug inflict delay length",
+        "64c87cde7e12ecf6704ab95bb1408bef047c22db4cc7491c4271d170a1b213d20b385bc1588d9c7b38f1b39d415665b8a9030c9ec653d75e65f847d8fc1fc440",
+        Some("xprv9s21ZrQH143K2XTAhys3pMNcGn261Fi5Ta2Pw8PwaVPhg3D8DWkzWQwjTJfskj8ofb81i9NP2cUNKxwjueJHHMQAnxtivTA75uUFqPFeWzk"),
+    ),
+    (
+        "c0ba5a8e914111210f2bd131f3d5e08d",
+        "scheme spot photo card baby mountain device kick cradle pact join borrow",
+        "ea725895aaae8d4c1cf682c1bfd2d358d52ed9f0f0591131b559e2724bb234fca05aa9c02c57407e04ee9dc3b454aa63fbff483a8b11de949624b9f1831a9612",
+        None,
+    ),
+    (
+        "6d9be1ee6ebd27a258115aad99b7317b9c8d28b6d76431c3",
+        "horn tenant knee talent sponsor spell gate clip pulse soap slush warm silver nephew swap uncle crack brave",
+        "fd579828af3da1d32544ce4db5c73d53fc8acc4ddb1e3b251a31179cdb71e853c56d2fcb11aed39898ce6c34b10b5382772db8796e52837b54468aeb312cfc3d",
+        None,
+    ),
+    (
+        "9f6a2878b2520799a44ef18bc7df394e7061a224d2c33cd015b157d746869863",
+        "panda eyebrow bullet gorilla call smoke muffin taste mesh discover soft ostrich alcohol speed nation flash devote level hobby quick inner drive ghost inside",
+        "72be8e052fc4919d2adf28d5306b5474b0069df35b02303de8c1729c9538dbb6fc2d731d5f832193cd9fb6aeecbc469594a70e3dd50811b5067f3b88b28c3e8d",
+        Some("xprv9s21ZrQH143K2WNnKmssvZYM96VAr47iHUQUTUyUXH3sAGNjhJANddnhw3i3y3pBbRAVk5M5qUGFr4rHbEWwXgX4qrvrceifCYQJbbFDems"),
+    ),
+    (
+        "23db8160a31d3e0dca3688ed941adbf3",
+        "cat swing flag economy stadium alone churn speed unique patch report train",
+        "deb5f45449e615feff5640f2e49f933ff51895de3b4381832b3139941c57b59205a42480c52175b6efcffaa58a2503887c1e8b363a707256bdd2b587b46541f5",
+        None,
+    ),
+    (
+        "8197a4a47f0425faeaa69deebc05ca29c0a5b5cc76ceacc0",
+        "light rule cinnamon wrap drastic word pride squirrel upgrade then income fatal apart sustain crack supply proud access",
+        "4cbdff1ca2db800fd61cae72a57475fdc6bab03e441fd63f96dabd1f183ef5b782925f00105f318309a7e9c3ea6967c7801e46c8a58082674c860a37b93eda02",
+        None,
+    ),
+    (
+        "066dca1a2bb7e8a1db2832148ce9933eea0f3ac9548d793112d9a95c9407efad",
+        "all hour make first leader extend hole alien behind guard gospel lava path output census museum junior mass reopen famous sing advance salt reform",
+        "26e975ec644423f4a4c4f4215ef09b4bd7ef924e85d1d17c4cf3f136c2863cf6df0a475045652c57eb5fb41513ca2a2d67722b77e954b4b3fc11f7590449191d",
+        Some("xprv9s21ZrQH143K3rEfqSM4QZRVmiMuSWY9wugscmaCjYja3SbUD3KPEB1a7QXJoajyR2T1SiXU7rFVRXMV9XdYVSZe7JoUXdP4SRHTxsT1nzm"),
+    ),
+    (
+        "f30f8c1da665478f49b001d94c5fc452",
+        "vessel ladder alter error federal sibling chat ability sun glass valve picture",
+        "2aaa9242daafcee6aa9d7269f17d4efe271e1b9a529178d7dc139cd18747090bf9d60295d0ce74309a78852a9caadf0af48aae1c6253839624076224374bc63f",
+        None,
+    ),
+    (
+        "c10ec20dc3cd9f652c7fac2f1230f7a3c828389a14392f05",
+        "scissors invite lock maple supreme raw rapid void congress muscle digital elegant little brisk hair mango congress clump",
+        "7b4a10be9d98e6cba265566db7f136718e1398c71cb581e1b2f464cac1ceedf4f3e274dc270003c670ad8d02c4558b2f8e39edea2775c9e232c7cb798b069e88",
+        None,
+    ),
+    (
+        "f585c11aec520db57dd353c69554b21a89b20fb0650966fa0a9d6f74fd989d8f",
+        "void come effort suffer camp survey warrior heavy shoot primary clutch crush open amazing screen patrol group space point ten exist slush involve unfold",
+        "01f5bced59dec48e362f2c45b5de68b9fd6c92c6634f44d6d40aab69056506f0e35524a518034ddc1192e1dacd32c1ed3eaa3c3b131c88ed8e7e54c49a5d0998",
+        Some("xprv9s21ZrQH143K39rnQJknpH1WEPFJrzmAqqasiDcVrNuk926oizzJDDQkdiTvNPr2FYDYzWgiMiC63YmfPAa2oPyNB23r2g7d1yiK6WpqaQS"),
+    ),
+];
+
+#[test]
+fn test_bip39_vectors() {
+    for (entropy, phrase, seed, xprv) in MNEMONIC_VECTORS {
+        let mnemonic = Mnemonic::from_entropy(&hex::decode(entropy).unwrap()).unwrap();
+        assert_eq!(mnemonic.phrase(), phrase);
+        assert_eq!(mnemonic.word_count(), phrase.split(' ').count());
+        assert_eq!(hex::encode(mnemonic.to_seed("TREZOR")), seed);
+
+        let parsed = Mnemonic::parse(phrase).unwrap();
+        assert_eq!(hex::encode(parsed.entropy()), entropy);
+        if let Some(xprv) = xprv {
+            assert_eq!(parsed.to_extended_private_key("TREZOR", false).unwrap().to_base58(), xprv);
+        }
+    }
+}
+
+#[test]
+fn test_bip39_parse_errors() {
+    // From rust-bip39: the correct phrase is
+    // "letter advice cage absurd amount doctor acoustic avoid letter advice cage above"
+    let parse = |phrase: &str| Mnemonic::parse(phrase).err();
+    assert_eq!(parse("getter advice cage absurd amount doctor acoustic avoid letter advice cage above"), Some(Bip39Error::UnknownWord(0)));
+    assert_eq!(parse("letter advice cagex absurd amount doctor acoustic avoid letter advice cage above"), Some(Bip39Error::UnknownWord(2)));
+    assert_eq!(parse("advice cage absurd amount doctor acoustic avoid letter advice cage above"), Some(Bip39Error::InvalidWordCount(11)));
+    assert_eq!(parse("primary advice cage absurd amount doctor acoustic avoid letter advice cage above"), Some(Bip39Error::InvalidChecksum));
+    assert_eq!(parse(""), Some(Bip39Error::InvalidWordCount(0)));
+    // Any whitespace separates words
+    assert!(parse("  letter advice cage absurd amount doctor\tacoustic avoid letter advice cage\nabove ").is_none());
+
+    assert_eq!(Mnemonic::from_entropy(&[0; 15]).err(), Some(Bip39Error::InvalidEntropyLength(15)));
+    assert_eq!(Mnemonic::from_entropy(&[0; 17]).err(), Some(Bip39Error::InvalidEntropyLength(17)));
+    assert_eq!(Mnemonic::from_entropy(&[0; 36]).err(), Some(Bip39Error::InvalidEntropyLength(36)));
+    assert_eq!(Mnemonic::generate(13).err(), Some(Bip39Error::InvalidWordCount(13)));
+    assert_eq!(Mnemonic::generate(27).err(), Some(Bip39Error::InvalidWordCount(27)));
+}
+
+#[test]
+fn test_bip39_generate() {
+    for word_count in [12, 15, 18, 21, 24] {
+        let mnemonic = Mnemonic::generate(word_count).unwrap();
+        assert_eq!(mnemonic.words().len(), word_count);
+        assert_eq!(mnemonic.entropy().len(), word_count / 3 * 4);
+        let parsed = Mnemonic::parse(&mnemonic.phrase()).unwrap();
+        assert_eq!(parsed.entropy(), mnemonic.entropy());
+        // The words stay out of Debug output
+        let debug = format!("{:?}", mnemonic);
+        assert!(!debug.contains(&mnemonic.phrase()));
+        assert_eq!(debug, format!("Mnemonic {{ word_count: {}, words: \"<redacted>\" }}", word_count));
+    }
+    // A passphrase changes the seed, and the master key comes from the seed
+    // for the passphrase given
+    let mnemonic = Mnemonic::parse(MNEMONIC_VECTORS[0].1).unwrap();
+    assert_ne!(mnemonic.to_seed(""), mnemonic.to_seed("TREZOR"));
+    assert_eq!(
+        mnemonic.to_extended_private_key("", true).unwrap().private_key().to_bytes(),
+        ExtendedPrivateKey::from_seed(&mnemonic.to_seed(""), true).unwrap().private_key().to_bytes()
+    );
+}
+
+#[test]
+fn test_derivation_path() {
+    let path = DerivationPath::parse("m/84'/1'/0'/0/5").unwrap();
+    assert_eq!(path.indexes(), [HARDENED + 84, HARDENED + 1, HARDENED, 0, 5]);
+    assert_eq!(path.depth(), 5);
+    assert_eq!(path.to_string(), "m/84'/1'/0'/0/5");
+    // h marks hardened indexes too, and Display writes them with '
+    assert_eq!(DerivationPath::parse("m/84h/1h/0h/0/5").unwrap(), path);
+    assert_eq!(DerivationPath::parse("m/84'/1'/0'").unwrap().child(0).child(5), path);
+    assert_eq!(DerivationPath::parse("m").unwrap(), DerivationPath::master());
+    assert_eq!(DerivationPath::master().to_string(), "m");
+    assert_eq!(DerivationPath::parse("m/2147483647'").unwrap().indexes(), [u32::MAX]);
+
+    for invalid in ["", "M/0", "0/1", "m/", "m//1", "m/0/", "m/+1", "m/-1", "m/1''", "m/ 1", "m/0x1", "m/2147483648", "m/2147483648'", "m/1/m"] {
+        assert_eq!(DerivationPath::parse(invalid).err(), Some(Bip32Error::InvalidPath(invalid.to_string())), "{:?}", invalid);
+    }
+    let too_deep = format!("m{}", "/0".repeat(256));
+    assert_eq!(DerivationPath::parse(&too_deep).err(), Some(Bip32Error::MaxDepthExceeded));
+
+    // Public keys can only follow unhardened paths
+    let master = ExtendedPrivateKey::from_seed(&hex::decode(VECTORS[0].0).unwrap(), false).unwrap();
+    let unhardened = DerivationPath::new(vec![1, 2, 3]);
+    assert_eq!(
+        master.extended_public_key().derive_path(&unhardened).unwrap(),
+        master.derive_path(&unhardened).unwrap().extended_public_key()
+    );
+    assert_eq!(master.extended_public_key().derive_path(&path).unwrap_err(), Bip32Error::HardenedFromPublic(HARDENED + 84));
+    assert_eq!(master.derive_path(&DerivationPath::master()).unwrap().to_base58(), master.to_base58());
+}
+
+const ABANDON_ABOUT: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+// The accounts of the "abandon ... about" mnemonic from the BIP49, BIP84 and
+// BIP86 test vectors: purpose, testnet, account xprv and xpub with SLIP-132
+// versions, the first two receive addresses and the first change address
+const ACCOUNT_VECTORS: [(Purpose, bool, &str, &str, [&str; 3]); 4] = [
+    (
+        Purpose::Bip44,
+        false,
+        "xprv9xpXFhFpqdQK3TmytPBqXtGSwS3DLjojFhTGht8gwAAii8py5X6pxeBnQ6ehJiyJ6nDjWGJfZ95WxByFXVkDxHXrqu53WCRGypk2ttuqncb",
+        "xpub6BosfCnifzxcFwrSzQiqu2DBVTshkCXacvNsWGYJVVhhawA7d4R5WSWGFNbi8Aw6ZRc1brxMyWMzG3DSSSSoekkudhUd9yLb6qx39T9nMdj",
+        ["1LqBGSKuX5yYUonjxT5qGfpUsXKYYWeabA", "1Ak8PffB2meyfYnbXZR9EGfLfFZVpzJvQP", "1J3J6EvPrv8q6AC3VCjWV45Uf3nssNMRtH"],
+    ),
+    (
+        Purpose::Bip49,
+        true,
+        "uprv91G7gZkzehuMVxDJTYE6tLivdF8e4rvzSu1LFfKw3b2Qx1Aj8vpoFnHdfUZ3hmi9jsvPifmZ24RTN2KhwB8BfMLTVqaBReibyaFFcTP1s9n",
+        "upub5EFU65HtV5TeiSHmZZm7FUffBGy8UKeqp7vw43jYbvZPpoVsgU93oac7Wk3u6moKegAEWtGNF8DehrnHtv21XXEMYRUocHqguyjknFHYfgY",
+        ["2Mww8dCYPUpKHofjgcXcBCEGmniw9CoaiD2", "2N55m54k8vr95ggehfUcNkdbUuQvaqG2GxK", "2MvdUi5o3f2tnEFh9yGvta6FzptTZtkPJC8"],
+    ),
+    (
+        Purpose::Bip84,
+        false,
+        "zprvAdG4iTXWBoARxkkzNpNh8r6Qag3irQB8PzEMkAFeTRXxHpbF9z4QgEvBRmfvqWvGp42t42nvgGpNgYSJA9iefm1yYNZKEm7z6qUWCroSQnE",
+        "zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs",
+        ["bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu", "bc1qnjg0jd8228aq7egyzacy8cys3knf9xvrerkf9g", "bc1q8c6fshw2dlwun7ekn9qwf37cu2rn755upcp6el"],
+    ),
+    (
+        Purpose::Bip86,
+        false,
+        "xprv9xgqHN7yz9MwCkxsBPN5qetuNdQSUttZNKw1dcYTV4mkaAFiBVGQziHs3NRSWMkCzvgjEe3n9xV8oYywvM8at9yRqyaZVz6TYYhX98VjsUk",
+        "xpub6BgBgsespWvERF3LHQu6CnqdvfEvtMcQjYrcRzx53QJjSxarj2afYWcLteoGVky7D3UKDP9QyrLprQ3VCECoY49yfdDEHGCtMMj92pReUsQ",
+        ["bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr", "bc1p4qhjn9zdvkux4e44uhx8tc55attvtyu358kutcqkudyccelu0was9fqzwh", "bc1p3qkhfews2uk44qtvauqyr2ttdsw7svhkl9nkm9s9c3x4ax5h60wqwruhk7"],
+    ),
+];
+
+#[test]
+fn test_account_vectors() {
+    let seed = Mnemonic::parse(ABANDON_ABOUT).unwrap().to_seed("");
+    for (purpose, testnet, xprv, xpub, [receive_0, receive_1, change_0]) in ACCOUNT_VECTORS {
+        let account = Account::from_seed(&seed, purpose, 0, testnet).unwrap();
+        assert_eq!(account.to_slip132_private(), xprv, "{:?}", purpose);
+        assert_eq!(account.to_slip132_public(), xpub, "{:?}", purpose);
+        assert_eq!(account.receive_address(0).unwrap(), receive_0, "{:?}", purpose);
+        assert_eq!(account.receive_address(1).unwrap(), receive_1, "{:?}", purpose);
+        assert_eq!(account.change_address(0).unwrap(), change_0, "{:?}", purpose);
+
+        // The address decodes to the account's script_pubkey for it
+        let (script_pubkey, address_testnet) = address_to_script_pubkey(receive_0).unwrap();
+        assert_eq!(address_testnet, testnet);
+        assert_eq!(script_pubkey.serialize(), account.script_pubkey(Chain::Receive, 0).unwrap().serialize());
+
+        // The private key matches the public key, and the SLIP-132 versions
+        // only change the prefix of the serialization
+        let private_key = account.private_key(Chain::Change, 0).unwrap();
+        assert_eq!(private_key.point().coordinates(), account.public_key(Chain::Change, 0).unwrap().coordinates());
+        assert_eq!(
+            decode_base58_checksum(xpub).unwrap()[4..],
+            account.extended_public_key().serialize()[4..]
+        );
+    }
+}
+
+#[test]
+fn test_account_paths() {
+    let seed = Mnemonic::parse(ABANDON_ABOUT).unwrap().to_seed("");
+    let account = Account::from_seed(&seed, Purpose::Bip84, 3, true).unwrap();
+    assert_eq!(account.path().to_string(), "m/84'/1'/3'");
+    assert_eq!(account.address_path(Chain::Change, 7).to_string(), "m/84'/1'/3'/1/7");
+    assert_eq!(account.extended_private_key().depth(), 3);
+    assert_eq!(account.extended_private_key().child_number(), HARDENED + 3);
+    assert!(account.to_slip132_public().starts_with("vpub"));
+    assert!(account.to_slip132_private().starts_with("vprv"));
+    assert!(account.receive_address(0).unwrap().starts_with("tb1q"));
+
+    // Addresses come from the key at the address path
+    let master = ExtendedPrivateKey::from_seed(&seed, true).unwrap();
+    let key = master.derive_path(&account.address_path(Chain::Change, 7)).unwrap();
+    assert_eq!(key.public_key().coordinates(), account.public_key(Chain::Change, 7).unwrap().coordinates());
+    assert_eq!(account.address(Chain::Change, 7).unwrap(), Purpose::Bip84.address(&key.public_key(), true));
+
+    // Mainnet BIP49 uses ypub, BIP44 and BIP86 keep xpub
+    let mainnet = |purpose| Account::from_seed(&seed, purpose, 0, false).unwrap().to_slip132_public();
+    assert!(mainnet(Purpose::Bip49).starts_with("ypub"));
+    assert!(mainnet(Purpose::Bip44).starts_with("xpub"));
+    assert_eq!(mainnet(Purpose::Bip86), Account::from_seed(&seed, Purpose::Bip86, 0, false).unwrap().extended_public_key().to_base58());
+
+    // Account indexes are hardened, so they must be below 2^31
+    assert_eq!(
+        Account::from_seed(&seed, Purpose::Bip44, HARDENED, false).err(),
+        Some(Bip32Error::InvalidPath("m/44'/0'/2147483648'".to_string()))
+    );
+}
@@ -0,0 +1,313 @@
+use primitive_types::U256;
+use sha2::{Digest, Sha256};
+
+use programming_bitcoin::ecc::private_key::PrivateKey;
+use programming_bitcoin::miniscript::error::MiniscriptError;
+use programming_bitcoin::miniscript::miniscript::{Context, Miniscript, Terminal};
+use programming_bitcoin::miniscript::satisfy::Satisfier;
+use programming_bitcoin::script::script::{Command, Script};
+use programming_bitcoin::utils::hash160::hash160;
+
+// The compressed SEC key of the private key n
+fn key(n: u64) -> String {
+    hex::encode(PrivateKey::new(U256::from(n)).point().sec(true))
+}
+
+fn xonly_key(n: u64) -> String {
+    hex::encode(PrivateKey::new(U256::from(n)).xonly_public_key().serialize())
+}
+
+// Stand-in signatures, of different sizes so the smallest witness is clear
+fn signature(n: u8) -> Vec<u8> {
+    vec![n; 70 + n as usize]
+}
+
+// Replaces K1, K2... and X1, X2... with keys, and H with the SHA256 of the
+// preimage 32 bytes of 0x01
+fn expand(expression: &str) -> String {
+    let mut expanded = expression.replace('H', &hex::encode(Sha256::digest(PREIMAGE)));
+    for n in 1..=3 {
+        expanded = expanded.replace(&format!("K{}", n), &key(n)).replace(&format!("X{}", n), &xonly_key(n));
+    }
+    expanded
+}
+
+const PREIMAGE: [u8; 32] = [1; 32];
+
+fn segwit(expression: &str) -> Miniscript {
+    Miniscript::parse(&expand(expression), Context::Segwit).unwrap()
+}
+
+fn parse_error(expression: &str, context: Context) -> MiniscriptError {
+    Miniscript::parse(&expand(expression), context).unwrap_err()
+}
+
+fn satisfier(signers: &[u64]) -> Satisfier {
+    let mut satisfier = Satisfier::new();
+    for n in signers {
+        satisfier.add_signature(hex::decode(key(*n)).unwrap(), signature(*n as u8));
+        satisfier.add_signature(hex::decode(xonly_key(*n)).unwrap(), signature(*n as u8));
+    }
+    satisfier
+}
+
+#[test]
+fn test_miniscript_scripts() {
+    let cases = [
+        ("pk(K1)", "21K1ac"),
+        ("pkh(K1)", "76a914P188ac"),
+        ("and_v(v:pk(K1),older(144))", "21K1ad029000b2"),
+        ("or_d(pk(K1),and_v(v:pk(K2),older(1000)))", "21K1ac736421K2ad02e803b268"),
+        // 32 and 20 are pushed as one byte
+        ("sha256(H)", "82012088a820H87"),
+        ("andor(pk(K1),older(20),pk(K2))", "21K1ac6421K2ac670114b268"),
+        ("thresh(2,pk(K1),s:pk(K2),s:pk(K3))", "21K1ac7c21K2ac937c21K3ac935287"),
+        ("multi(2,K1,K2,K3)", "5221K121K221K353ae"),
+        ("t:or_c(pk(K1),v:pkh(K2))", "21K1ac6476a914P288ad6851"),
+        ("or_i(pk(K1),and_v(v:pk(K2),after(500000)))", "6321K1ac6721K2ad0320a107b168"),
+        ("and_b(pk(K1),a:older(16))", "21K1ac6b60b26c9a"),
+        ("j:pk(K1)", "82926321K1ac68"),
+    ];
+    for (expression, expected) in cases {
+        let mut expected = expected.to_string();
+        for n in 1..=3 {
+            expected = expected
+                .replace(&format!("K{}", n), &key(n))
+                .replace(&format!("P{}", n), &hex::encode(hash160(&hex::decode(key(n)).unwrap())));
+        }
+        let expected = expected.replace('H', &hex::encode(Sha256::digest(PREIMAGE)));
+        assert_eq!(hex::encode(segwit(expression).encode()), expected, "{}", expression);
+    }
+
+    // In tapscript keys are x-only and multi_a replaces multi
+    let multi_a = Miniscript::parse(&expand("multi_a(2,X1,X2)"), Context::Tap).unwrap();
+    assert_eq!(hex::encode(multi_a.encode()), format!("20{}ac20{}ba529c", xonly_key(1), xonly_key(2)));
+
+    let miniscript = segwit("pk(K1)");
+    assert_eq!(
+        miniscript.script_pubkey().unwrap().raw_serialize(),
+        Script::p2wsh_script(Sha256::digest(miniscript.encode()).to_vec()).raw_serialize()
+    );
+    assert_eq!(miniscript.script_size(), 35);
+}
+
+#[test]
+fn test_miniscript_round_trip() {
+    let expressions = [
+        "pk(K1)",
+        "or_b(pk(K1),s:pk(K2))",
+        "and_b(pk(K1),a:older(10))",
+        "or_i(pk(K1),and_v(v:pk(K2),after(500000)))",
+        "j:pk(K1)",
+        "n:pk(K1)",
+        "dv:older(10)",
+        "andor(pk(K1),pk(K2),pk(K3))",
+        "and_n(pk(K1),sha256(H))",
+        "l:pk(K1)",
+        "u:pk(K1)",
+        "t:or_c(pk(K1),v:pk(K2))",
+        "thresh(1,pk(K1),a:pk(K2))",
+        "thresh(2,pk(K1),s:pk(K2),sln:older(1000))",
+        "and_v(v:pk(K1),older(100))",
+        "multi(2,K1,K2,K3)",
+        "or_d(pk(K1),and_v(v:pk(K2),older(1000)))",
+        "and_v(v:sha256(H),pk(K1))",
+        "and_v(v:hash160(ab0d8af1f57b0553f58d8cb8c9ecd3bed48c6ab2),pk(K1))",
+        "and_v(v:ripemd160(ab0d8af1f57b0553f58d8cb8c9ecd3bed48c6ab2),pk(K1))",
+        "and_v(v:hash256(H),pk(K1))",
+        "and_v(v:multi(1,K1,K2),pk(K3))",
+        "and_v(v:thresh(1,pk(K1),a:pk(K2)),pk(K3))",
+    ];
+    for expression in expressions {
+        let expanded = expand(expression);
+        let miniscript = Miniscript::parse(&expanded, Context::Segwit).unwrap();
+        assert_eq!(miniscript.to_string(), expanded);
+        let decoded = Miniscript::from_script(&miniscript.to_script(), Context::Segwit).unwrap();
+        assert_eq!(decoded, miniscript, "{}", expression);
+    }
+
+    // c:pk_k is written pk
+    assert_eq!(segwit("c:pk_k(K1)").to_string(), expand("pk(K1)"));
+
+    // Decoding can't recover the keys of pk_h
+    let decoded = Miniscript::from_script(&segwit("pkh(K1)").to_script(), Context::Segwit).unwrap();
+    let hash = hex::encode(hash160(&hex::decode(key(1)).unwrap()));
+    assert_eq!(decoded.to_string(), format!("c:expr_raw_pkh({})", hash));
+    assert_eq!(decoded.encode(), segwit("pkh(K1)").encode());
+    // which is a P2PKH script_pubkey
+    let p2pkh = Script::p2pkh_script(hex::decode(&hash).unwrap());
+    assert_eq!(Miniscript::from_script(&p2pkh, Context::Segwit).unwrap(), decoded);
+
+    // and_v chains decode nested to the left, which is the same script
+    let chain = segwit("and_v(v:pk(K1),and_v(v:pk(K2),pk(K3)))");
+    let decoded = Miniscript::from_script(&chain.to_script(), Context::Segwit).unwrap();
+    assert_eq!(decoded.to_string(), expand("and_v(and_v(v:pk(K1),v:pk(K2)),pk(K3))"));
+    assert_eq!(decoded.encode(), chain.encode());
+
+    let multi_a = Miniscript::parse(&expand("multi_a(2,X1,X2,X3)"), Context::Tap).unwrap();
+    assert_eq!(Miniscript::from_script(&multi_a.to_script(), Context::Tap).unwrap(), multi_a);
+}
+
+#[test]
+fn test_miniscript_types() {
+    let cases = [
+        ("pk(K1)", "Bonduesm"),
+        ("pkh(K1)", "Bnduesm"),
+        ("older(10)", "Bzfm"),
+        ("sha256(H)", "Bondum"),
+        ("and_v(v:pk(K1),older(144))", "Bonfsm"),
+        ("or_d(pk(K1),and_v(v:pk(K2),older(1000)))", "Bfsm"),
+        ("thresh(2,pk(K1),s:pk(K2),s:pk(K3))", "Bduesm"),
+        ("multi(2,K1,K2,K3)", "Bnduesm"),
+        ("or_i(older(10),older(20))", "Bof"),
+        ("dv:older(10)", "Bondem"),
+    ];
+    for (expression, ty) in cases {
+        assert_eq!(segwit(expression).ty().to_string(), ty, "{}", expression);
+    }
+    let verify = Miniscript::from_node(Terminal::Verify(Box::new(segwit("pk(K1)"))), Context::Segwit).unwrap();
+    assert_eq!(verify.ty().to_string(), "Vonfsm");
+    // d: is only u in tapscript, where OP_IF takes exactly 0 or 1
+    let tap = Miniscript::parse("dv:older(10)", Context::Tap).unwrap();
+    assert_eq!(tap.ty().to_string(), "Bonduem");
+
+    assert!(segwit("or_d(pk(K1),and_v(v:pk(K2),older(1000)))").is_sane());
+    // Anyone can spend with the preimage, once it is public
+    let hash_lock = segwit("or_d(pk(K1),sha256(H))");
+    assert!(!hash_lock.requires_signature());
+    assert!(!hash_lock.is_sane());
+    // A third party can swap one timelock branch for the other
+    assert!(!segwit("or_i(older(10),older(20))").is_non_malleable());
+    // Heights and times can't both be met by one sequence
+    let mixed = segwit("and_v(v:pk(K1),and_v(v:older(10),older(4194305)))");
+    assert!(mixed.has_mixed_timelocks());
+    assert!(!mixed.is_sane());
+    assert!(!segwit("or_i(and_v(v:pk(K1),older(10)),and_v(v:pk(K2),older(4194305)))").has_mixed_timelocks());
+    assert!(segwit("thresh(2,pk(K1),s:pk(K2),sln:after(500000),sln:after(1700000000))").has_mixed_timelocks());
+    assert!(!segwit("thresh(1,pk(K1),s:pk(K2),sln:after(500000),sln:after(1700000000))").has_mixed_timelocks());
+    // One signature by K1 would satisfy both
+    let repeated = segwit("and_v(v:pk(K1),pk(K1))");
+    assert!(repeated.has_repeated_keys());
+    assert!(!repeated.is_sane());
+}
+
+#[test]
+fn test_miniscript_errors() {
+    let context = Context::Segwit;
+    assert_eq!(parse_error("and_v(pk(K1),pk(K2))", context), MiniscriptError::TypeCheck("and_v".to_string()));
+    assert_eq!(parse_error("s:older(10)", context), MiniscriptError::TypeCheck("s:".to_string()));
+    assert_eq!(parse_error("v:pk(K1)", context), MiniscriptError::NotTopLevel);
+    assert_eq!(parse_error("older(0)", context), MiniscriptError::InvalidTimelock(0));
+    assert_eq!(parse_error("after(2147483648)", context), MiniscriptError::InvalidTimelock(1 << 31));
+    assert!(Miniscript::parse("older(78)", context).is_ok());
+    assert!(Miniscript::parse("older(128)", context).is_ok());
+    // 100 is pushed as one byte, which is data and not OP_NOTIF
+    let older = Miniscript::parse("older(100)", context).unwrap();
+    assert_eq!(hex::encode(older.encode()), "0164b2");
+    assert_eq!(Miniscript::from_script(&older.to_script(), context).unwrap(), older);
+    assert_eq!(parse_error("multi(3,K1,K2)", context), MiniscriptError::InvalidThreshold(3));
+    assert_eq!(parse_error("thresh(0,pk(K1))", context), MiniscriptError::InvalidThreshold(0));
+    assert_eq!(parse_error("multi(1,X1)", Context::Tap), MiniscriptError::InvalidContext("multi".to_string()));
+    assert_eq!(parse_error("multi_a(1,K1)", context), MiniscriptError::InvalidContext("multi_a".to_string()));
+    assert_eq!(parse_error("pk(X1)", context), MiniscriptError::InvalidKey(xonly_key(1)));
+    assert_eq!(parse_error("pk(K1)", Context::Tap), MiniscriptError::InvalidKey(key(1)));
+    assert_eq!(parse_error("sha256(abcd)", context), MiniscriptError::InvalidHash("abcd".to_string()));
+    assert_eq!(parse_error("foo(K1)", context), MiniscriptError::UnknownFragment("foo".to_string()));
+    assert_eq!(parse_error("x:pk(K1)", context), MiniscriptError::UnknownWrapper('x'));
+    assert_eq!(parse_error("older(010)", context), MiniscriptError::Syntax("number".to_string(), 6));
+    assert_eq!(parse_error("pk(K1", context), MiniscriptError::Syntax("')'".to_string(), 69));
+    assert_eq!(parse_error("pk(K1))", context), MiniscriptError::Syntax("end of expression".to_string(), 70));
+
+    // Scripts that no Miniscript compiles to
+    let k1 = hex::decode(key(1)).unwrap();
+    let invalid = [
+        // OP_RETURN
+        Script::new(vec![Command::Op(0x6a)]),
+        // 5 should be pushed with OP_5
+        Script::new(vec![Command::Data(vec![0x05]), Command::Op(0xb2)]),
+        // OP_CHECKSIG OP_VERIFY instead of OP_CHECKSIGVERIFY
+        Script::new(vec![Command::Data(k1.clone()), Command::Op(0xac), Command::Op(0x69), Command::Op(0x51)]),
+        // a bare key has type K
+        Script::new(vec![Command::Data(k1.clone())]),
+        Script::new(vec![Command::Op(0x63), Command::Data(k1), Command::Op(0xac), Command::Op(0x68)]),
+    ];
+    for script in invalid {
+        assert!(Miniscript::from_script(&script, Context::Segwit).is_err(), "{:?}", script.commands);
+    }
+    assert_eq!(
+        Miniscript::from_script(&Script::new(vec![Command::Data(vec![0x05]), Command::Op(0xb2)]), Context::Segwit).unwrap_err(),
+        MiniscriptError::InvalidScript(0)
+    );
+
+    // Building fragments from nodes type checks them too
+    let pk = Box::new(segwit("pk(K1)"));
+    assert_eq!(
+        Miniscript::from_node(Terminal::AndV(pk.clone(), pk), Context::Segwit).unwrap_err(),
+        MiniscriptError::TypeCheck("and_v".to_string())
+    );
+}
+
+#[test]
+fn test_miniscript_satisfy() {
+    let witness = |expression: &str, satisfier: &Satisfier| segwit(expression).satisfy(satisfier);
+
+    assert_eq!(witness("pk(K1)", &satisfier(&[1])), Ok(vec![signature(1)]));
+    assert_eq!(witness("pk(K1)", &satisfier(&[2])), Err(MiniscriptError::Unsatisfiable));
+    assert_eq!(witness("pkh(K1)", &satisfier(&[1])), Ok(vec![signature(1), hex::decode(key(1)).unwrap()]));
+
+    // The key, or the recovery key after 1000 blocks
+    let recovery = "or_d(pk(K1),and_v(v:pk(K2),older(1000)))";
+    assert_eq!(witness(recovery, &satisfier(&[1])), Ok(vec![signature(1)]));
+    assert_eq!(witness(recovery, &satisfier(&[2])), Err(MiniscriptError::Unsatisfiable));
+    let mut after_1000 = satisfier(&[2]);
+    after_1000.set_sequence(1000);
+    assert_eq!(witness(recovery, &after_1000), Ok(vec![signature(2), vec![]]));
+    after_1000.set_sequence(999);
+    assert_eq!(witness(recovery, &after_1000), Err(MiniscriptError::Unsatisfiable));
+    // Sequences in units of time don't satisfy a height
+    after_1000.set_sequence(1000 | 1 << 22);
+    assert_eq!(witness(recovery, &after_1000), Err(MiniscriptError::Unsatisfiable));
+    // With both keys, the smaller witness
+    let mut both = satisfier(&[1, 2]);
+    both.set_sequence(1000);
+    assert_eq!(witness(recovery, &both), Ok(vec![signature(1)]));
+
+    let mut after = satisfier(&[1]);
+    after.set_lock_time(800_000);
+    assert_eq!(witness("and_v(v:pk(K1),after(800000))", &after), Ok(vec![signature(1)]));
+    after.set_lock_time(1_700_000_000);
+    assert_eq!(witness("and_v(v:pk(K1),after(800000))", &after), Err(MiniscriptError::Unsatisfiable));
+
+    // The dummy element, then the signatures in the order of the keys
+    assert_eq!(witness("multi(2,K1,K2,K3)", &satisfier(&[3, 1])), Ok(vec![vec![], signature(1), signature(3)]));
+    assert_eq!(witness("multi(2,K1,K2,K3)", &satisfier(&[1, 2, 3])), Ok(vec![vec![], signature(1), signature(2)]));
+    assert_eq!(witness("multi(2,K1,K2,K3)", &satisfier(&[2])), Err(MiniscriptError::Unsatisfiable));
+
+    // The first subexpression's witness is on top
+    let thresh = "thresh(2,pk(K1),s:pk(K2),s:pk(K3))";
+    assert_eq!(witness(thresh, &satisfier(&[1, 3])), Ok(vec![signature(3), vec![], signature(1)]));
+    assert_eq!(witness(thresh, &satisfier(&[1, 2, 3])), Ok(vec![vec![], signature(2), signature(1)]));
+
+    assert_eq!(witness("or_i(pk(K1),pk(K2))", &satisfier(&[1])), Ok(vec![signature(1), vec![1]]));
+    assert_eq!(witness("or_i(pk(K1),pk(K2))", &satisfier(&[2])), Ok(vec![signature(2), vec![]]));
+
+    let mut preimage = satisfier(&[1]);
+    preimage.add_preimage(PREIMAGE.to_vec());
+    assert_eq!(witness("and_v(v:pk(K1),sha256(H))", &preimage), Ok(vec![PREIMAGE.to_vec(), signature(1)]));
+    assert_eq!(witness("and_v(v:pk(K1),sha256(H))", &satisfier(&[1])), Err(MiniscriptError::Unsatisfiable));
+
+    // Without a signature, a third party could take either branch
+    let mut sequence = Satisfier::new();
+    sequence.set_sequence(20);
+    assert_eq!(witness("or_i(older(10),older(20))", &sequence), Err(MiniscriptError::MalleableSatisfaction));
+    assert_eq!(witness("or_i(older(10),older(30))", &sequence), Ok(vec![vec![1]]));
+
+    // A decoded pk_h finds its key from the signatures
+    let decoded = Miniscript::from_script(&segwit("pkh(K1)").to_script(), Context::Segwit).unwrap();
+    assert_eq!(decoded.satisfy(&satisfier(&[1])), Ok(vec![signature(1), hex::decode(key(1)).unwrap()]));
+
+    // multi_a takes an element for every key, with the first key's on top
+    let multi_a = Miniscript::parse(&expand("multi_a(1,X1,X2)"), Context::Tap).unwrap();
+    assert_eq!(multi_a.satisfy(&satisfier(&[2])), Ok(vec![signature(2), vec![]]));
+    assert_eq!(multi_a.satisfy(&satisfier(&[1])), Ok(vec![vec![], signature(1)]));
+}
@@ -0,0 +1,269 @@
+use primitive_types::U256;
+use programming_bitcoin::ecc::private_key::PrivateKey;
+use programming_bitcoin::ecc::xonly_public_key::XOnlyPublicKey;
+use programming_bitcoin::musig::error::MusigError;
+use programming_bitcoin::musig::key_agg::{key_sort, KeyAggContext};
+use programming_bitcoin::musig::nonce::{nonce_agg, nonce_gen, AggNonce, PubNonce, SecNonce};
+use programming_bitcoin::musig::session::Session;
+use programming_bitcoin::taproot::tweak::tweak_public_key;
+use programming_bitcoin::utils::hash256::hash256;
+use serde_json::Value;
+
+// The BIP327 reference vectors, as shipped with libsecp256k1's musig module
+// (src/modules/musig/vectors.h) and converted back to JSON. libsecp256k1
+// omits the pubnonces of the valid signing cases; their nonce_indices are
+// taken from the BIP and checked against the aggregate nonce below.
+const BIP327_VECTORS: &str = include_str!("bip327_test_vectors.json");
+
+fn vectors() -> Value {
+    serde_json::from_str(BIP327_VECTORS).unwrap()
+}
+
+fn bytes<const N: usize>(value: &Value) -> [u8; N] {
+    hex::decode(value.as_str().unwrap()).unwrap().try_into().unwrap()
+}
+
+fn indices(value: &Value) -> Vec<usize> {
+    value.as_array().unwrap().iter().map(|i| i.as_u64().unwrap() as usize).collect()
+}
+
+fn pick<const N: usize>(all: &Value, value: &Value) -> Vec<[u8; N]> {
+    indices(value).iter().map(|i| bytes(&all[*i])).collect()
+}
+
+fn private_key(value: &Value) -> PrivateKey {
+    PrivateKey::new(U256::from_big_endian(&bytes::<32>(value)))
+}
+
+// Builds the key aggregation context with the case's tweaks applied
+fn tweaked_context(pubkeys: &[[u8; 33]], tweaks: &[[u8; 32]], is_xonly: &Value) -> Result<KeyAggContext, MusigError> {
+    let mut context = KeyAggContext::new(pubkeys)?;
+    for (tweak, is_xonly) in tweaks.iter().zip(is_xonly.as_array().unwrap()) {
+        context.apply_tweak(tweak, is_xonly.as_bool().unwrap())?;
+    }
+    Ok(context)
+}
+
+#[test]
+fn test_key_sort() {
+    let v = &vectors()["key_agg"];
+    let pubkeys: Vec<[u8; 33]> = (0..3).map(|i| bytes(&v["pubkeys"][i])).collect();
+    let sorted = key_sort(&pubkeys);
+    assert!(sorted.windows(2).all(|pair| pair[0] <= pair[1]));
+    assert_eq!(sorted, key_sort(&[pubkeys[2], pubkeys[0], pubkeys[1]]));
+}
+
+#[test]
+fn test_key_agg_vectors() {
+    let v = &vectors()["key_agg"];
+    for case in v["valid_test_cases"].as_array().unwrap() {
+        let pubkeys = pick(&v["pubkeys"], &case["key_indices"]);
+        let context = KeyAggContext::new(&pubkeys).unwrap();
+        assert_eq!(context.aggregate_public_key().serialize(), bytes::<32>(&case["expected"]).to_vec());
+    }
+    for case in v["error_test_cases"].as_array().unwrap() {
+        let key_indices = indices(&case["key_indices"]);
+        let pubkeys = pick(&v["pubkeys"], &case["key_indices"]);
+        let tweaks = pick(&v["tweaks"], &case["tweak_indices"]);
+        let error = tweaked_context(&pubkeys, &tweaks, &case["is_xonly"]).unwrap_err();
+        match case["error"].as_str().unwrap() {
+            // Keys 3 to 5 are the invalid ones
+            "pubkey" => {
+                let signer = key_indices.iter().position(|i| (3..=5).contains(i)).unwrap();
+                assert_eq!(error, MusigError::InvalidPublicKey(signer));
+            }
+            "tweak" => assert_eq!(error, MusigError::InvalidTweak),
+            other => panic!("unexpected error type {other}"),
+        }
+    }
+}
+
+#[test]
+fn test_nonce_gen_vectors() {
+    for case in vectors()["nonce_gen"]["test_cases"].as_array().unwrap() {
+        let secret_key = case["sk"].as_str().map(|_| private_key(&case["sk"]));
+        let aggregate_key = case["aggpk"].as_str().map(|_| XOnlyPublicKey::parse(&bytes::<32>(&case["aggpk"])).unwrap());
+        let msg = case["msg"].as_str().map(|m| hex::decode(m).unwrap());
+        let extra_in = case["extra_in"].as_str().map(|e| hex::decode(e).unwrap());
+        let (secnonce, pubnonce) = nonce_gen(
+            bytes(&case["rand_"]),
+            secret_key.as_ref(),
+            &bytes(&case["pk"]),
+            aggregate_key.as_ref(),
+            msg.as_deref(),
+            extra_in.as_deref(),
+        );
+        assert_eq!(secnonce.to_bytes(), bytes::<97>(&case["expected_secnonce"]));
+        assert_eq!(pubnonce.to_bytes(), bytes::<66>(&case["expected_pubnonce"]));
+    }
+}
+
+#[test]
+fn test_nonce_agg_vectors() {
+    let v = &vectors()["nonce_agg"];
+    let pnonces = |case: &Value| -> Vec<PubNonce> {
+        pick::<66>(&v["pnonces"], &case["pnonce_indices"]).into_iter().map(PubNonce::from_bytes).collect()
+    };
+    for case in v["valid_test_cases"].as_array().unwrap() {
+        let aggnonce = nonce_agg(&pnonces(case)).unwrap();
+        assert_eq!(aggnonce.to_bytes(), bytes::<66>(&case["expected"]));
+    }
+    for case in v["error_test_cases"].as_array().unwrap() {
+        let signer = case["signer"].as_u64().unwrap() as usize;
+        assert_eq!(nonce_agg(&pnonces(case)).unwrap_err(), MusigError::InvalidPubNonce(signer));
+    }
+}
+
+#[test]
+fn test_sign_verify_vectors() {
+    let v = &vectors()["sign_verify"];
+    let secret_key = private_key(&v["sk"]);
+    let msg = hex::decode(v["msgs"][0].as_str().unwrap()).unwrap();
+    let secnonce = |i: usize| SecNonce::from_bytes(bytes(&v["secnonces"][i]));
+    let aggnonce = |i: usize| AggNonce::from_bytes(bytes(&v["aggnonces"][i]));
+
+    for case in v["valid_test_cases"].as_array().unwrap() {
+        let pubkeys = pick::<33>(&v["pubkeys"], &case["key_indices"]);
+        let pubnonces: Vec<PubNonce> = pick::<66>(&v["pnonces"], &case["nonce_indices"]).into_iter().map(PubNonce::from_bytes).collect();
+        let aggnonce = aggnonce(case["aggnonce_index"].as_u64().unwrap() as usize);
+        assert_eq!(nonce_agg(&pubnonces).unwrap(), aggnonce);
+
+        let session = Session::new(&KeyAggContext::new(&pubkeys).unwrap(), &aggnonce, &msg).unwrap();
+        let psig = session.sign(secnonce(0), &secret_key).unwrap();
+        assert_eq!(psig, bytes::<32>(&case["expected"]));
+
+        let signer = case["signer_index"].as_u64().unwrap() as usize;
+        assert!(session.partial_sig_verify(&psig, &pubnonces[signer], &pubkeys[signer]));
+    }
+
+    for case in v["sign_error_test_cases"].as_array().unwrap() {
+        let pubkeys = pick::<33>(&v["pubkeys"], &case["key_indices"]);
+        let aggnonce = aggnonce(case["aggnonce_index"].as_u64().unwrap() as usize);
+        let secnonce = secnonce(case["secnonce_index"].as_u64().unwrap() as usize);
+        let result = KeyAggContext::new(&pubkeys)
+            .and_then(|context| Session::new(&context, &aggnonce, &msg))
+            .and_then(|session| session.sign(secnonce, &secret_key));
+        let error = result.unwrap_err();
+        match case["error"].as_str().unwrap() {
+            "pubkey" => assert!(matches!(error, MusigError::PublicKeyNotFound | MusigError::InvalidPublicKey(_))),
+            "aggnonce" => assert_eq!(error, MusigError::InvalidAggNonce),
+            "secnonce" => assert_eq!(error, MusigError::InvalidSecNonce),
+            other => panic!("unexpected error type {other}"),
+        }
+    }
+
+    for case in v["verify_fail_test_cases"].as_array().unwrap() {
+        let pubkeys = pick::<33>(&v["pubkeys"], &case["key_indices"]);
+        let pubnonces: Vec<PubNonce> = pick::<66>(&v["pnonces"], &case["nonce_indices"]).into_iter().map(PubNonce::from_bytes).collect();
+        let session = Session::new(&KeyAggContext::new(&pubkeys).unwrap(), &nonce_agg(&pubnonces).unwrap(), &msg).unwrap();
+        let signer = case["signer_index"].as_u64().unwrap() as usize;
+        assert!(!session.partial_sig_verify(&bytes(&case["sig"]), &pubnonces[signer], &pubkeys[signer]));
+    }
+
+    for case in v["verify_error_test_cases"].as_array().unwrap() {
+        let pubkeys = pick::<33>(&v["pubkeys"], &case["key_indices"]);
+        let pubnonces: Vec<PubNonce> = pick::<66>(&v["pnonces"], &case["nonce_indices"]).into_iter().map(PubNonce::from_bytes).collect();
+        match case["error"].as_str().unwrap() {
+            "pubkey" => assert_eq!(KeyAggContext::new(&pubkeys).unwrap_err(), MusigError::InvalidPublicKey(0)),
+            "pubnonce" => assert_eq!(nonce_agg(&pubnonces).unwrap_err(), MusigError::InvalidPubNonce(0)),
+            other => panic!("unexpected error type {other}"),
+        }
+    }
+}
+
+#[test]
+fn test_tweak_vectors() {
+    let v = &vectors()["tweak"];
+    let secret_key = private_key(&v["sk"]);
+    let aggnonce = AggNonce::from_bytes(bytes(&v["aggnonce"]));
+    let msg = hex::decode(v["msg"].as_str().unwrap()).unwrap();
+
+    for case in v["valid_test_cases"].as_array().unwrap() {
+        let pubkeys = pick::<33>(&v["pubkeys"], &case["key_indices"]);
+        let pubnonces: Vec<PubNonce> = pick::<66>(&v["pnonces"], &case["nonce_indices"]).into_iter().map(PubNonce::from_bytes).collect();
+        assert_eq!(nonce_agg(&pubnonces).unwrap(), aggnonce);
+        let tweaks = pick(&v["tweaks"], &case["tweak_indices"]);
+        let context = tweaked_context(&pubkeys, &tweaks, &case["is_xonly"]).unwrap();
+
+        let session = Session::new(&context, &aggnonce, &msg).unwrap();
+        let psig = session.sign(SecNonce::from_bytes(bytes(&v["secnonce"])), &secret_key).unwrap();
+        assert_eq!(psig, bytes::<32>(&case["expected"]));
+        let signer = case["signer_index"].as_u64().unwrap() as usize;
+        assert!(session.partial_sig_verify(&psig, &pubnonces[signer], &pubkeys[signer]));
+    }
+    for case in v["error_test_cases"].as_array().unwrap() {
+        let pubkeys = pick::<33>(&v["pubkeys"], &case["key_indices"]);
+        let tweaks = pick(&v["tweaks"], &case["tweak_indices"]);
+        assert_eq!(tweaked_context(&pubkeys, &tweaks, &case["is_xonly"]).unwrap_err(), MusigError::InvalidTweak);
+    }
+}
+
+#[test]
+fn test_sig_agg_vectors() {
+    let v = &vectors()["sig_agg"];
+    let msg = hex::decode(v["msg"].as_str().unwrap()).unwrap();
+    for case in v["valid_test_cases"].as_array().unwrap() {
+        let pubkeys = pick::<33>(&v["pubkeys"], &case["key_indices"]);
+        let tweaks = pick(&v["tweaks"], &case["tweak_indices"]);
+        let context = tweaked_context(&pubkeys, &tweaks, &case["is_xonly"]).unwrap();
+        let session = Session::new(&context, &AggNonce::from_bytes(bytes(&case["aggnonce"])), &msg).unwrap();
+
+        let signature = session.partial_sig_agg(&pick(&v["psigs"], &case["psig_indices"])).unwrap();
+        assert_eq!(signature.serialize(), bytes::<64>(&case["expected"]).to_vec());
+        assert!(context.aggregate_public_key().verify_schnorr(&msg, &signature));
+    }
+    for case in v["error_test_cases"].as_array().unwrap() {
+        let pubkeys = pick::<33>(&v["pubkeys"], &case["key_indices"]);
+        let tweaks = pick(&v["tweaks"], &case["tweak_indices"]);
+        let context = tweaked_context(&pubkeys, &tweaks, &case["is_xonly"]).unwrap();
+        let session = Session::new(&context, &AggNonce::from_bytes(bytes(&case["aggnonce"])), &msg).unwrap();
+        let index = case["invalid_sig_index"].as_u64().unwrap() as usize;
+        assert_eq!(
+            session.partial_sig_agg(&pick(&v["psigs"], &case["psig_indices"])).unwrap_err(),
+            MusigError::InvalidPartialSignature(index)
+        );
+    }
+}
+
+#[test]
+fn test_musig_taproot_3_of_3() {
+    let secret_keys: Vec<PrivateKey> = (1..=3u32).map(|i| PrivateKey::new(U256::from(1000 * i + 7))).collect();
+    let pubkeys: Vec<[u8; 33]> = (1..=3u32)
+        .map(|i| PrivateKey::new(U256::from(1000 * i + 7)).point().sec(true).try_into().unwrap())
+        .collect();
+    let pubkeys = key_sort(&pubkeys);
+    let mut context = KeyAggContext::new(&pubkeys).unwrap();
+    let internal_key = context.aggregate_public_key();
+    context.apply_taproot_tweak(None).unwrap();
+
+    // The tweaked aggregate key is the taproot output key of the internal key
+    let (output_key, _) = tweak_public_key(&internal_key, None).unwrap();
+    assert_eq!(context.aggregate_public_key(), output_key);
+
+    let msg = hash256(b"spend from the vault");
+    let mut secnonces = vec![];
+    let mut pubnonces = vec![];
+    for (i, secret_key) in secret_keys.iter().enumerate() {
+        let pubkey: [u8; 33] = PrivateKey::new(U256::from(1000 * (i as u32 + 1) + 7)).point().sec(true).try_into().unwrap();
+        let rand = hash256(&[i as u8]).try_into().unwrap();
+        let (secnonce, pubnonce) = nonce_gen(rand, Some(secret_key), &pubkey, Some(&output_key), Some(&msg), None);
+        secnonces.push((pubkey, secnonce));
+        pubnonces.push((pubkey, pubnonce));
+    }
+    let aggnonce = nonce_agg(&pubnonces.iter().map(|(_, nonce)| *nonce).collect::<Vec<_>>()).unwrap();
+    let session = Session::new(&context, &aggnonce, &msg).unwrap();
+
+    let mut psigs = vec![];
+    for ((pubkey, secnonce), secret_key) in secnonces.into_iter().zip(&secret_keys) {
+        let psig = session.sign(secnonce, secret_key).unwrap();
+        let (_, pubnonce) = pubnonces.iter().find(|(key, _)| *key == pubkey).unwrap();
+        assert!(session.partial_sig_verify(&psig, pubnonce, &pubkey));
+        psigs.push(psig);
+    }
+    let signature = session.partial_sig_agg(&psigs).unwrap();
+    assert!(output_key.verify_schnorr(&msg, &signature));
+
+    // Missing a partial signature gives an invalid signature
+    let signature = session.partial_sig_agg(&psigs[..2]).unwrap();
+    assert!(!output_key.verify_schnorr(&msg, &signature));
+}
@@ -1,6 +1,6 @@
 use std::io::Cursor;
 
-use programming_bitcoin::{ecc::signature::Signature, script::script::Script, utils::varint::encode_varint};
+use programming_bitcoin::{ecc::{batch_verifier::BatchVerifier, signature::{DerMode, Signature}}, script::script::{Command, Script}, utils::varint::encode_varint};
 
 // TODO test the parse (and serialize?) methods
 
@@ -9,25 +9,104 @@ fn evaluate_p2pk() {
     let z = hex::decode("7c076ff316692a3d7eb3c3bb0f8b1488cf72e1afcd929e29307032997a838a3d").unwrap();
     // let mut stream = Cursor::new(z);
     let raw_sec = hex::decode("04887387e452b8eacc4acfde10d9aaf7f6d9a0f975aabb10d006e4da568744d06c61de6d95231cd89026e286df3b6ae4a894a3378e393e93a0f45b666329a0ae34").unwrap();
-    let pubkey_commands = vec![raw_sec, vec![0xac]]; // SEC + OP_CHECKSIG (172)
+    let pubkey_commands = vec![Command::Data(raw_sec), Command::Op(0xac)]; // SEC + OP_CHECKSIG (172)
     let script_pubkey = Script::new(pubkey_commands);
 
     let raw_sig = hex::decode("3045022000eff69ef2b1bd93a66ed5219add4fb51e11a840f404876325a1e8ffe0529a2c022100c7207fee197d27c618aea621406f6bf5ef6fca38681d82b2f06fddbdce6feab601").unwrap();
-    let script_sig = Script::new(vec![raw_sig]);
+    let script_sig = Script::new(vec![Command::Data(raw_sig)]);
     let combined_script = script_sig.concat(script_pubkey);
 
     let result = combined_script.evaluate(z, None);
     assert!(result);
 }
 
+#[test]
+fn evaluate_p2pk_strict() {
+    // The signature from evaluate_p2pk is valid DER but has a high s
+    let z = hex::decode("7c076ff316692a3d7eb3c3bb0f8b1488cf72e1afcd929e29307032997a838a3d").unwrap();
+    let raw_sec = hex::decode("04887387e452b8eacc4acfde10d9aaf7f6d9a0f975aabb10d006e4da568744d06c61de6d95231cd89026e286df3b6ae4a894a3378e393e93a0f45b666329a0ae34").unwrap();
+    let raw_sig = hex::decode("3045022000eff69ef2b1bd93a66ed5219add4fb51e11a840f404876325a1e8ffe0529a2c022100c7207fee197d27c618aea621406f6bf5ef6fca38681d82b2f06fddbdce6feab601").unwrap();
+    let script_pubkey = Script::new(vec![Command::Data(raw_sec), Command::Op(0xac)]);
+    let p2pk = |sig: Vec<u8>| Script::new(vec![Command::Data(sig)]).concat(script_pubkey.clone());
+
+    assert!(!p2pk(raw_sig.clone()).evaluate_with_mode(z.clone(), None, DerMode::Strict));
+
+    // Normalizing s makes it acceptable
+    let signature = Signature::parse_der(&raw_sig[..raw_sig.len() - 1], DerMode::Strict).unwrap();
+    let low_s = [signature.normalize_s().der(), vec![0x01]].concat();
+    assert!(p2pk(low_s.clone()).evaluate_with_mode(z.clone(), None, DerMode::Strict));
+
+    // An undefined sighash type is rejected in strict mode only
+    let mut undefined_sighash = low_s.clone();
+    *undefined_sighash.last_mut().unwrap() = 0x04;
+    assert!(!p2pk(undefined_sighash.clone()).evaluate_with_mode(z.clone(), None, DerMode::Strict));
+    assert!(p2pk(undefined_sighash).evaluate_with_mode(z.clone(), None, DerMode::Lax));
+
+    // So is a long form sequence length, which fails the check instead of panicking
+    let mut long_form = vec![0x30, 0x81, low_s[1]];
+    long_form.extend_from_slice(&low_s[2..]);
+    assert!(!p2pk(long_form.clone()).evaluate_with_mode(z.clone(), None, DerMode::Strict));
+    assert!(p2pk(long_form).evaluate_with_mode(z.clone(), None, DerMode::Lax));
+
+    // Garbage fails the check in either mode
+    assert!(!p2pk(vec![0x30, 0x01]).evaluate_with_mode(z, None, DerMode::Lax));
+}
+
+#[test]
+fn evaluate_deferred_checksig() {
+    let z = hex::decode("7c076ff316692a3d7eb3c3bb0f8b1488cf72e1afcd929e29307032997a838a3d").unwrap();
+    let raw_sec = hex::decode("04887387e452b8eacc4acfde10d9aaf7f6d9a0f975aabb10d006e4da568744d06c61de6d95231cd89026e286df3b6ae4a894a3378e393e93a0f45b666329a0ae34").unwrap();
+    let raw_sig = hex::decode("3045022000eff69ef2b1bd93a66ed5219add4fb51e11a840f404876325a1e8ffe0529a2c022100c7207fee197d27c618aea621406f6bf5ef6fca38681d82b2f06fddbdce6feab601").unwrap();
+
+    // A final OP_CHECKSIG is left to the batch
+    let p2pk = Script::new(vec![Command::Data(raw_sig.clone()), Command::Data(raw_sec.clone()), Command::Op(0xac)]);
+    let mut batch = BatchVerifier::new();
+    assert!(p2pk.clone().evaluate_deferred(z.clone(), None, DerMode::Lax, &mut batch));
+    assert_eq!(batch.len(), 1);
+    assert_eq!(batch.verify(), Ok(()));
+    let mut batch = BatchVerifier::new();
+    assert!(p2pk.evaluate_deferred(vec![0; 32], None, DerMode::Lax, &mut batch));
+    assert_eq!(batch.verify(), Err(0));
+
+    // A check whose result later opcodes use (here OP_CHECKSIG OP_0 OP_EQUAL)
+    // is verified straight away, so the script evaluates as without a batch
+    let must_fail = Script::new(vec![
+        Command::Data(raw_sig),
+        Command::Data(raw_sec),
+        Command::Op(0xac),
+        Command::Op(0x00),
+        Command::Op(0x87),
+    ]);
+    for z in [z, vec![0; 32]] {
+        let mut batch = BatchVerifier::new();
+        let expected = must_fail.clone().evaluate_with_mode(z.clone(), None, DerMode::Lax);
+        assert_eq!(must_fail.clone().evaluate_deferred(z, None, DerMode::Lax, &mut batch), expected);
+        assert!(batch.is_empty());
+    }
+}
+
+#[test]
+fn evaluate_p2pk_invalid_pubkey() {
+    // An unparsable key makes OP_CHECKSIG fail instead of panicking
+    let z = hex::decode("7c076ff316692a3d7eb3c3bb0f8b1488cf72e1afcd929e29307032997a838a3d").unwrap();
+    let raw_sig = hex::decode("3045022000eff69ef2b1bd93a66ed5219add4fb51e11a840f404876325a1e8ffe0529a2c022100c7207fee197d27c618aea621406f6bf5ef6fca38681d82b2f06fddbdce6feab601").unwrap();
+    let mut off_curve = hex::decode("04887387e452b8eacc4acfde10d9aaf7f6d9a0f975aabb10d006e4da568744d06c61de6d95231cd89026e286df3b6ae4a894a3378e393e93a0f45b666329a0ae34").unwrap();
+    off_curve[64] ^= 1;
+    let bad_keys = [vec![], vec![0x05; 33], off_curve[..33].to_vec(), off_curve];
+    for bad_key in bad_keys {
+        let combined_script = Script::new(vec![Command::Data(raw_sig.clone())]).concat(Script::new(vec![Command::Data(bad_key), Command::Op(0xac)]));
+        assert!(!combined_script.evaluate(z.clone(), None));
+    }
+}
+
 #[test]
 fn test_script_display() {
     let raw_sec = hex::decode("04887387e452b8eacc4acfde10d9aaf7f6d9a0f975aabb10d006e4da568744d06c61de6d95231cd89026e286df3b6ae4a894a3378e393e93a0f45b666329a0ae34").unwrap();
-    let pubkey_commands = vec![raw_sec, vec![0xac]]; // SEC + OP_CHECKSIG (172)
+    let pubkey_commands = vec![Command::Data(raw_sec), Command::Op(0xac)]; // SEC + OP_CHECKSIG (172)
     let script_pubkey = Script::new(pubkey_commands);
 
     let raw_sig = hex::decode("3045022000eff69ef2b1bd93a66ed5219add4fb51e11a840f404876325a1e8ffe0529a2c022100c7207fee197d27c618aea621406f6bf5ef6fca38681d82b2f06fddbdce6feab601").unwrap();
-    let script_sig = Script::new(vec![raw_sig]);
+    let script_sig = Script::new(vec![Command::Data(raw_sig)]);
     let combined_script = script_sig.concat(script_pubkey);
     println!("{}", combined_script);
 }
@@ -51,17 +130,17 @@ fn test_sig_from_bytes() {
 #[test]
 fn evaluate_basic_script() {
     let pubkey_commands = vec![
-        vec![0x76], // 118 - OP_DUP
-        vec![0x76], // 118 - OP_DUP
-        vec![0x95], // 149 - OP_MUL
-        vec![0x93], // 147 - OP_ADD
-        vec![0x56], // 86 - OP_6
-        vec![0x87], // 136 - OP_EQUAL
+        Command::Op(0x76), // 118 - OP_DUP
+        Command::Op(0x76), // 118 - OP_DUP
+        Command::Op(0x95), // 149 - OP_MUL
+        Command::Op(0x93), // 147 - OP_ADD
+        Command::Op(0x56), // 86 - OP_6
+        Command::Op(0x87), // 136 - OP_EQUAL
     ];
 
     let dummy_z = vec![0];
     let script_pubkey = Script::new(pubkey_commands);
-    let script_sig_commands: Vec<Vec<u8>> = vec![vec![0x52]];
+    let script_sig_commands: Vec<Command> = vec![Command::Op(0x52)];
     let script_sig = Script::new(script_sig_commands);
     let combined_script = script_sig.concat(script_pubkey.clone());
     let result = combined_script.evaluate(dummy_z, None);
@@ -71,8 +150,8 @@ fn evaluate_basic_script() {
 #[test]
 fn test_is_p2wsh_script_pubkey() {
     let commands = vec![
-        vec![0x00],  // OP_0
-        vec![0; 32]  // 32-byte witness program (SHA256 hash)
+        Command::Op(0x00),  // OP_0
+        Command::Data(vec![0; 32])  // 32-byte witness program (SHA256 hash)
     ];
     let script = Script::new(commands);
     assert!(script.is_p2wsh_script_pubkey());
@@ -81,8 +160,8 @@ fn test_is_p2wsh_script_pubkey() {
 #[test]
 fn test_is_p2wpkh_script_pubkey() {
     let commands = vec![
-        vec![0x00],  // OP_0
-        vec![0; 20]  // 20-byte pubkey hash
+        Command::Op(0x00),  // OP_0
+        Command::Data(vec![0; 20])  // 20-byte pubkey hash
     ];
     let script = Script::new(commands);
     assert!(script.is_p2wpkh_script_pubkey());
@@ -98,6 +177,27 @@ fn test_parse_p2wsh() {
     println!("{}", witness_script);
 }
 
+#[test]
+fn test_one_byte_push() {
+    // OP_SIZE <32> OP_EQUALVERIFY, where 32 is pushed as a single byte
+    let raw = hex::decode("82012088").unwrap();
+    let mut serialized = encode_varint(raw.len() as u64);
+    serialized.extend_from_slice(&raw);
+    let script = Script::parse(&mut Cursor::new(serialized)).unwrap();
+    assert_eq!(script.commands, vec![Command::Op(0x82), Command::Data(vec![0x20]), Command::Op(0x88)]);
+    assert_eq!(script.raw_serialize(), raw);
+
+    // A push of 0x64 is data, not OP_NOTIF
+    let mut serialized = encode_varint(2);
+    serialized.extend_from_slice(&[0x01, 0x64]);
+    let script = Script::parse(&mut Cursor::new(serialized)).unwrap();
+    assert_eq!(script.commands, vec![Command::Data(vec![0x64])]);
+    assert_eq!(script.raw_serialize(), [0x01, 0x64]);
+    assert!(script.clone().evaluate(vec![0], None));
+    assert!(!Script::new(vec![Command::Op(0x64)]).evaluate(vec![0], None));
+    assert_eq!(Script::new(vec![Command::Op(0x64)]).raw_serialize(), [0x64]);
+}
+
 // fn test_p2wsh_evaluation() {}
 
 // #[test]
@@ -0,0 +1,190 @@
+use std::io::Cursor;
+use primitive_types::U256;
+use programming_bitcoin::ecc::private_key::PrivateKey;
+use programming_bitcoin::address::address::address_to_script_pubkey;
+use programming_bitcoin::sign_message::bip322::{self, message_hash, sign_full, sign_simple, verify_full, verify_full_with_lookup, verify_simple};
+use programming_bitcoin::sign_message::legacy::{sign_message, signed_message_hash, verify_message, MessageAddressType};
+use programming_bitcoin::script::script::Script;
+use programming_bitcoin::transactions::{tx::Tx, tx_input::TxInput};
+use programming_bitcoin::utils::base58::decode_base58;
+use programming_bitcoin::utils::base64::{decode_base64, encode_base64};
+use programming_bitcoin::utils::bech32::encode_segwit_address;
+use programming_bitcoin::utils::hash160::hash160;
+
+// Private key and address from the BIP322 test vectors
+const BIP322_WIF: &str = "L3VFeEujGtevx9w18HD1fhRbCH67Az2dpCymeRE1SoPK6XQtaN2k";
+const BIP322_ADDRESS: &str = "bc1q9vza2e8x573nczrlzms0wvx3gsqjx7vavgkx0l";
+
+fn bip322_private_key() -> PrivateKey {
+    // WIF payload is the 32 byte secret followed by the compression flag
+    let payload = decode_base58(BIP322_WIF).unwrap();
+    PrivateKey::new(U256::from_big_endian(&payload[..32]))
+}
+
+#[test]
+fn test_base64_round_trip() {
+    for (raw, encoded) in [("", ""), ("f", "Zg=="), ("fo", "Zm8="), ("foo", "Zm9v"), ("foobar", "Zm9vYmFy")] {
+        assert_eq!(encode_base64(raw.as_bytes()), encoded);
+        assert_eq!(decode_base64(encoded).unwrap(), raw.as_bytes());
+    }
+    assert!(decode_base64("Zg=").is_err());
+    assert!(decode_base64("Z===").is_err());
+    assert!(decode_base64("Zm9v!A==").is_err());
+}
+
+#[test]
+fn test_signed_message_hash() {
+    let mut hash = signed_message_hash("test");
+    hash.reverse();
+    assert_eq!(hex::encode(hash), "a6f87fe6d58a032c320ff8d1541656f0282c2c7bfcc69d61af4c8e8ed528e49c");
+}
+
+#[test]
+fn test_verify_legacy_message() {
+    // Vector from rust-bitcoin's sign_message tests
+    let secret = decode_base64("UuOGDsfLPr4HIMKQX0ipjJeRaj1geCq3yPUF2COP5ME=").unwrap();
+    let private_key = PrivateKey::new(U256::from_big_endian(&secret));
+    let public_key = decode_base64("A1FTfMEntPpAty3qkEo0q2Dc1FEycI10a3jmwEFy+Qr6").unwrap();
+    assert_eq!(PrivateKey::new(U256::from_big_endian(&secret)).point().sec(true), public_key);
+
+    let message = "rust-bitcoin MessageSignature test";
+    let signature = "IAM2qX24tYx/bdBTIgVLhD8QEAjrPlJpmjB4nZHdRYGIBa4DmVulAcwjPnWe6Q5iEwXH6F0pUCJP/ZeHPWS1h1o=";
+    let address = PrivateKey::new(U256::from_big_endian(&secret)).point().address(true, false);
+    assert!(verify_message(&address, signature, message).unwrap());
+    assert!(!verify_message(&address, signature, "a different message from what was signed").unwrap());
+
+    // RFC 6979 nonces make the signature deterministic
+    assert_eq!(sign_message(&private_key, message, MessageAddressType::P2pkh), signature);
+}
+
+#[test]
+fn test_sign_and_verify_legacy_message() {
+    let private_key = bip322_private_key();
+    let sec = bip322_private_key().point().sec(true);
+    let h160 = hash160(&sec);
+    let p2pkh = bip322_private_key().point().address(true, false);
+    let p2wpkh = encode_segwit_address("bc", 0, &h160);
+    assert_eq!(p2wpkh, BIP322_ADDRESS);
+    let message = "Hello World";
+
+    let signature = sign_message(&private_key, message, MessageAddressType::P2wpkh);
+    assert!(decode_base64(&signature).unwrap()[0] >= 39);
+    assert!(verify_message(&p2wpkh, &signature, message).unwrap());
+    // A P2WPKH header does not prove ownership of the P2PKH address
+    assert!(!verify_message(&p2pkh, &signature, message).unwrap());
+
+    let signature = sign_message(&private_key, message, MessageAddressType::P2pkh);
+    assert!(verify_message(&p2pkh, &signature, message).unwrap());
+    assert!(verify_message(&p2wpkh, &signature, message).unwrap());
+
+    let signature = sign_message(&private_key, message, MessageAddressType::P2pkhUncompressed);
+    let uncompressed = bip322_private_key().point().address(false, false);
+    assert!(verify_message(&uncompressed, &signature, message).unwrap());
+    assert!(!verify_message(&p2pkh, &signature, message).unwrap());
+}
+
+#[test]
+fn test_bip322_message_hash() {
+    assert_eq!(hex::encode(message_hash(b"")), "c90c269c4f8fcbe6880f72a721ddfbf1914268a794cbb21cfafee13770ae19f1");
+    assert_eq!(hex::encode(message_hash(b"Hello World")), "f0eb03b1a75ac6d9847f55c624a99169b5dccba2a31f5b23bea77ba270de0a7a");
+}
+
+#[test]
+fn test_bip322_transaction_hashes() {
+    let (script_pubkey, _) = address_to_script_pubkey(BIP322_ADDRESS).unwrap();
+    let vectors = [
+        ("", "c5680aa69bb8d860bf82d4e9cd3504b55dde018de765a91bb566283c545a99a7", "1e9654e951a5ba44c8604c4de6c67fd78a27e81dcadcfe1edf638ba3aaebaed6"),
+        ("Hello World", "b79d196740ad5217771c1098fc4a4b51e0535c32236c71f1ea4d61a2d603352b", "88737ae86f2077145f93cc4b153ae9a1cb8d56afa511988c149c5c8c9d93bddf"),
+    ];
+    for (message, to_spend_id, to_sign_id) in vectors {
+        let to_spend = bip322::to_spend(&script_pubkey, message.as_bytes());
+        assert_eq!(to_spend.id(), to_spend_id);
+        let to_sign = bip322::to_sign(&to_spend, Script::new(vec![]), Some(vec![]));
+        assert_eq!(to_sign.id(), to_sign_id);
+    }
+}
+
+#[test]
+fn test_bip322_simple_vectors() {
+    let vectors = [
+        ("", "AkcwRAIgM2gBAQqvZX15ZiysmKmQpDrG83avLIT492QBzLnQIxYCIBaTpOaD20qRlEylyxFSeEA2ba9YOixpX8z46TSDtS40ASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI="),
+        ("Hello World", "AkcwRAIgZRfIY3p7/DoVTty6YZbWS71bc5Vct9p9Fia83eRmw2QCICK/ENGfwLtptFluMGs2KsqoNSk89pO7F29zJLUx9a/sASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI="),
+    ];
+    for (message, signature) in vectors {
+        assert!(verify_simple(BIP322_ADDRESS, message.as_bytes(), signature).unwrap());
+    }
+    // The "" signature does not sign "Hello World"
+    assert!(!verify_simple(BIP322_ADDRESS, b"Hello World", vectors[0].1).unwrap());
+}
+
+#[test]
+fn test_bip322_sign_and_verify() {
+    let private_key = bip322_private_key();
+    let message = b"Hello World";
+
+    let signature = sign_simple(&private_key, BIP322_ADDRESS, message).unwrap();
+    assert!(verify_simple(BIP322_ADDRESS, message, &signature).unwrap());
+    assert!(!verify_simple(BIP322_ADDRESS, b"Goodbye", &signature).unwrap());
+
+    let sec = bip322_private_key().point().sec(true);
+    let h160 = hash160(&sec);
+    let p2pkh = bip322_private_key().point().address(true, false);
+    let redeem_script = Script::p2wpkh_script(h160.clone()).serialize()[1..].to_vec();
+    let p2sh_p2wpkh = programming_bitcoin::address::address::h160_to_p2sh_address(&hex::encode(hash160(&redeem_script)), false);
+    for address in [BIP322_ADDRESS, p2pkh.as_str(), p2sh_p2wpkh.as_str()] {
+        let signature = sign_full(&private_key, address, message).unwrap();
+        assert!(verify_full(address, message, &signature).unwrap(), "{address}");
+        assert!(!verify_full(address, b"Goodbye", &signature).unwrap(), "{address}");
+    }
+
+    // Another key cannot sign for the address
+    assert!(sign_full(&PrivateKey::new(U256::from(12345)), BIP322_ADDRESS, message).is_err());
+}
+
+#[test]
+fn test_bip322_verify_full_malformed() {
+    let private_key = bip322_private_key();
+    let message = b"Hello World";
+    let raw = decode_base64(&sign_full(&private_key, BIP322_ADDRESS, message).unwrap()).unwrap();
+    let proof = |tx: &Tx| encode_base64(&tx.serialize());
+
+    // Truncated or extended proofs are errors
+    for length in [0, 3, 10, 60, raw.len() - 1] {
+        assert!(verify_full(BIP322_ADDRESS, message, &encode_base64(&raw[..length])).is_err());
+    }
+    assert!(verify_full(BIP322_ADDRESS, message, &encode_base64(&[raw.clone(), vec![0]].concat())).is_err());
+    // A witness item longer than the proof
+    let tx = Tx::parse(&mut Cursor::new(raw.clone()), false);
+    let witness_length = bip322::encode_witness(tx.tx_ins[0].witness.as_ref().unwrap()).len();
+    let mut long_item = raw[..raw.len() - 4 - witness_length].to_vec();
+    long_item.extend_from_slice(&[0x01, 0xfe, 0xff, 0xff, 0xff, 0x7f, 0, 0, 0, 0]);
+    assert!(verify_full(BIP322_ADDRESS, message, &encode_base64(&long_item)).is_err());
+
+    // Inputs that don't fit the script they spend fail verification
+    let mut no_witness = tx.clone();
+    no_witness.tx_ins[0].witness = Some(vec![]);
+    assert!(!verify_full(BIP322_ADDRESS, message, &proof(&no_witness)).unwrap());
+
+    let redeem_script = Script::p2wpkh_script(hash160(&bip322_private_key().point().sec(true))).serialize()[1..].to_vec();
+    let p2sh_p2wpkh = programming_bitcoin::address::address::h160_to_p2sh_address(&hex::encode(hash160(&redeem_script)), false);
+    let mut no_redeem_script = Tx::parse(&mut Cursor::new(decode_base64(&sign_full(&private_key, &p2sh_p2wpkh, message).unwrap()).unwrap()), false);
+    no_redeem_script.tx_ins[0].script_sig = Script::new(vec![]);
+    assert!(!verify_full(&p2sh_p2wpkh, message, &proof(&no_redeem_script)).unwrap());
+
+    // A proof of funds input whose prevout can't be found
+    let mut unknown_funds = tx.clone();
+    unknown_funds.tx_ins.push(TxInput::new([0x42; 32], [0; 4], Script::new(vec![]), [0; 4], Some(vec![]), None));
+    let not_found = |_: &TxInput, _: bool| Err("not found".to_string());
+    assert!(!verify_full_with_lookup(BIP322_ADDRESS, message, &proof(&unknown_funds), &not_found).unwrap());
+
+    // The lookup is asked for the address' network
+    let testnet_address = encode_segwit_address("tb", 0, &hash160(&bip322_private_key().point().sec(true)));
+    let testnet_proof = sign_full(&private_key, &testnet_address, message).unwrap();
+    let mut testnet_funds = Tx::parse(&mut Cursor::new(decode_base64(&testnet_proof).unwrap()), true);
+    testnet_funds.tx_ins.push(TxInput::new([0x42; 32], [0; 4], Script::new(vec![]), [0; 4], Some(vec![]), None));
+    let testnet_only = |_: &TxInput, testnet: bool| {
+        assert!(testnet);
+        Err("not found".to_string())
+    };
+    assert!(!verify_full_with_lookup(&testnet_address, message, &proof(&testnet_funds), &testnet_only).unwrap());
+}
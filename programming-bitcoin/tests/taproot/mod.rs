@@ -0,0 +1,152 @@
+use std::io::Cursor;
+use primitive_types::U256;
+use programming_bitcoin::ecc::private_key::PrivateKey;
+use programming_bitcoin::ecc::xonly_public_key::XOnlyPublicKey;
+use programming_bitcoin::script::script::{Command, Script};
+use programming_bitcoin::taproot::control_block::ControlBlock;
+use programming_bitcoin::taproot::spend_info::TaprootSpendInfo;
+use programming_bitcoin::taproot::error::TaprootError;
+use programming_bitcoin::taproot::tap_tree::{TapLeaf, TapTree, TAPSCRIPT_LEAF_VERSION};
+use programming_bitcoin::taproot::tweak::{tap_tweak_hash, tweak_private_key, tweak_public_key};
+use programming_bitcoin::utils::bech32::decode_segwit_address;
+use programming_bitcoin::utils::varint::encode_varint;
+use serde_json::Value;
+
+// https://github.com/bitcoin/bips/blob/master/bip-0341/wallet-test-vectors.json
+const BIP341_VECTORS: &str = include_str!("bip341_wallet_test_vectors.json");
+
+fn vectors() -> Value {
+    serde_json::from_str(BIP341_VECTORS).unwrap()
+}
+
+fn parse_script(raw: &[u8]) -> Script {
+    let mut bytes = encode_varint(raw.len() as u64);
+    bytes.extend_from_slice(raw);
+    Script::parse(&mut Cursor::new(bytes)).unwrap()
+}
+
+// The vectors give a tree as either a single leaf object or a two element array
+fn parse_tree(value: &Value) -> TapTree {
+    match value {
+        Value::Array(children) => TapTree::branch(parse_tree(&children[0]), parse_tree(&children[1])),
+        leaf => {
+            let script = parse_script(&hex::decode(leaf["script"].as_str().unwrap()).unwrap());
+            let leaf_version = leaf["leafVersion"].as_u64().unwrap() as u8;
+            TapTree::Leaf(TapLeaf::new_with_version(script, leaf_version).unwrap())
+        }
+    }
+}
+
+fn hex_or_none(value: &Value) -> Option<Vec<u8>> {
+    value.as_str().map(|s| hex::decode(s).unwrap())
+}
+
+#[test]
+fn test_bip341_script_pubkey_vectors() {
+    let vectors = vectors();
+    for vector in vectors["scriptPubKey"].as_array().unwrap() {
+        let given = &vector["given"];
+        let intermediary = &vector["intermediary"];
+        let expected = &vector["expected"];
+
+        let internal_key = XOnlyPublicKey::parse(&hex::decode(given["internalPubkey"].as_str().unwrap()).unwrap()).unwrap();
+        let tree = if given["scriptTree"].is_null() { None } else { Some(parse_tree(&given["scriptTree"])) };
+        let spend_info = TaprootSpendInfo::new(internal_key, tree.clone()).unwrap();
+
+        assert_eq!(spend_info.merkle_root(), hex_or_none(&intermediary["merkleRoot"]));
+        let tweak = tap_tweak_hash(&internal_key, spend_info.merkle_root().as_deref());
+        assert_eq!(hex::encode(tweak), intermediary["tweak"].as_str().unwrap());
+        assert_eq!(hex::encode(spend_info.output_key().serialize()), intermediary["tweakedPubkey"].as_str().unwrap());
+
+        let script_pubkey = spend_info.script_pubkey();
+        assert!(script_pubkey.is_p2tr_script_pubkey());
+        // Drop the length prefix that serialize adds
+        assert_eq!(hex::encode(&script_pubkey.serialize()[1..]), expected["scriptPubKey"].as_str().unwrap());
+        assert_eq!(spend_info.address(false), expected["bip350Address"].as_str().unwrap());
+
+        let tree = match tree {
+            Some(tree) => tree,
+            None => continue,
+        };
+        let leaves = tree.leaves_with_paths();
+        let leaf_hashes = intermediary["leafHashes"].as_array().unwrap();
+        let control_blocks = expected["scriptPathControlBlocks"].as_array().unwrap();
+        assert_eq!(leaves.len(), leaf_hashes.len());
+        for (i, (leaf, _)) in leaves.iter().enumerate() {
+            assert_eq!(hex::encode(leaf.leaf_hash()), leaf_hashes[i].as_str().unwrap());
+
+            let control_block = spend_info.control_block(leaf).unwrap();
+            let serialized = control_block.serialize();
+            assert_eq!(hex::encode(&serialized), control_blocks[i].as_str().unwrap());
+            assert!(control_block.verify(&spend_info.output_key(), leaf));
+
+            let parsed = ControlBlock::parse(&serialized).unwrap();
+            assert_eq!(parsed, control_block);
+        }
+    }
+}
+
+#[test]
+fn test_bip341_key_path_spending_vectors() {
+    let vectors = vectors();
+    for input in vectors["keyPathSpending"][0]["inputSpending"].as_array().unwrap() {
+        let given = &input["given"];
+        let intermediary = &input["intermediary"];
+
+        let secret = U256::from_big_endian(&hex::decode(given["internalPrivkey"].as_str().unwrap()).unwrap());
+        let private_key = PrivateKey::new(secret);
+        assert_eq!(hex::encode(private_key.xonly_public_key().serialize()), intermediary["internalPubkey"].as_str().unwrap());
+
+        let merkle_root = hex_or_none(&given["merkleRoot"]);
+        let tweak = tap_tweak_hash(&private_key.xonly_public_key(), merkle_root.as_deref());
+        assert_eq!(hex::encode(tweak), intermediary["tweak"].as_str().unwrap());
+
+        let tweaked = tweak_private_key(&private_key, merkle_root.as_deref()).unwrap();
+        let expected_secret = U256::from_big_endian(&hex::decode(intermediary["tweakedPrivkey"].as_str().unwrap()).unwrap());
+        let expected = PrivateKey::new(expected_secret);
+        assert_eq!(tweaked.xonly_public_key(), expected.xonly_public_key());
+
+        // The tweaked private key must sign for the tweaked public key
+        let (output_key, _) = tweak_public_key(&private_key.xonly_public_key(), merkle_root.as_deref()).unwrap();
+        assert_eq!(tweaked.xonly_public_key(), output_key);
+
+        // The vectors sign with all-zero auxiliary randomness
+        let sig_hash = hex::decode(intermediary["sigHash"].as_str().unwrap()).unwrap();
+        let signature = tweaked.sign_schnorr(&sig_hash, [0u8; 32]);
+        let witness = input["expected"]["witness"][0].as_str().unwrap();
+        assert_eq!(hex::encode(signature.serialize()), &witness[..128]);
+        assert!(output_key.verify_schnorr(&sig_hash, &signature));
+    }
+}
+
+#[test]
+fn test_p2tr_script() {
+    let key = hex::decode("53a1f6e454df1aa2776a2814a721372d6258050de330b3c6d10ee8f4e0dda343").unwrap();
+    let script = Script::p2tr_script(key.clone());
+    assert!(script.is_p2tr_script_pubkey());
+    assert_eq!(script.commands, vec![Command::Op(0x51), Command::Data(key)]);
+}
+
+#[test]
+fn test_bip350_address_round_trip() {
+    let address = "bc1p2wsldez5mud2yam29q22wgfh9439spgduvct83k3pm50fcxa5dps59h4z5";
+    let (version, program) = decode_segwit_address("bc", address).unwrap();
+    assert_eq!(version, 1);
+    assert_eq!(hex::encode(&program), "53a1f6e454df1aa2776a2814a721372d6258050de330b3c6d10ee8f4e0dda343");
+
+    // A version 1 program with a bech32 (not bech32m) checksum is invalid
+    assert!(decode_segwit_address("bc", "bc1pw508d6qejxtdg4y5r3zarvary0c5xw7kw508d6qejxtdg4y5r3zarvary0c5xw7k7grplx").is_err());
+    // BIP173 example P2WPKH address
+    let (version, program) = decode_segwit_address("bc", "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4").unwrap();
+    assert_eq!(version, 0);
+    assert_eq!(hex::encode(program), "751e76e8199196d454941c45d1b3a323f1433bd6");
+}
+
+#[test]
+fn test_leaf_version() {
+    let script = Script::new(vec![Command::Op(0x51)]);
+    assert_eq!(TapLeaf::new(script.clone()).leaf_version(), TAPSCRIPT_LEAF_VERSION);
+    assert_eq!(TapLeaf::new_with_version(script.clone(), 0xfa).unwrap().leaf_version(), 0xfa);
+    // The lowest bit belongs to the output key parity in the control block
+    assert_eq!(TapLeaf::new_with_version(script, 0xc1).err(), Some(TaprootError::InvalidLeafVersion(0xc1)));
+}
@@ -5,3 +5,10 @@ pub mod transactions;
 pub mod blocks;
 pub mod network;
 pub mod spv;
+pub mod taproot;
+pub mod sign_message;
+pub mod musig;
+pub mod frost;
+pub mod hd;
+pub mod descriptor;
+pub mod miniscript;
@@ -1,5 +1,11 @@
 use std::io::Cursor;
-use programming_bitcoin::{ecc::{point::Point, signature::Signature}, script::script::Script, transactions::{tx::Tx, tx_fetcher::TxFetcher, tx_input::TxInput, tx_output::TxOutput}, utils::{base58::decode_base58, hash256::hash256, sig_hash_type::SigHashType}};
+use programming_bitcoin::{ecc::{point::Point, signature::Signature}, script::script::{Command, Script}, transactions::{tx::Tx, tx_fetcher::TxFetcher, tx_input::TxInput, tx_output::TxOutput}, utils::{base58::decode_base58, hash256::hash256, sig_hash_type::SigHashType}};
+use programming_bitcoin::transactions::input_signing_data::InputSigningData;
+use programming_bitcoin::transactions::nonce_audit::{audit_transactions, audit_transactions_with_lookup, InputRef, InputSignature};
+use programming_bitcoin::ecc::scalar::Scalar;
+use crate::ecc::sign_with_nonce;
+use programming_bitcoin::{address::address::address_to_script_pubkey, ecc::{private_key::PrivateKey, signature::DerMode}, sign_message::bip322, utils::{base64::decode_base64, bech32::encode_segwit_address, hash160::hash160}};
+use primitive_types::U256;
 use tokio::task;
 
 // add tests here for parsing the individual components of the tx - version, inputs, outputs, locktime (and testnet?)
@@ -280,3 +286,171 @@ fn test_verify_p2sh_p2wsh_input() {
     let result = tx.verify_input(SigHashType::SigHashAll, 0);
     assert!(result);
 }
+
+#[test]
+fn test_verify_batched() {
+    // BIP322 to_sign txs can be verified offline, as their prevout (to_spend)
+    // is given to the input
+    let private_key = PrivateKey::new(U256::from(12345));
+    let sec = PrivateKey::new(U256::from(12345)).point().sec(true);
+    let p2pkh = PrivateKey::new(U256::from(12345)).point().address(true, false);
+    let p2wpkh = encode_segwit_address("bc", 0, &hash160(&sec));
+    let message = b"batched";
+    for address in [p2pkh, p2wpkh] {
+        let signature = bip322::sign_full(&private_key, &address, message).unwrap();
+        let (script_pubkey, _) = address_to_script_pubkey(&address).unwrap();
+        let to_spend = bip322::to_spend(&script_pubkey, message);
+
+        let mut tx = Tx::parse(&mut Cursor::new(decode_base64(&signature).unwrap()), false);
+        tx.tx_ins[0].prev_output = Some(to_spend.tx_outs[0].clone());
+        assert_eq!(tx.verify_batched(DerMode::Strict), Ok(()));
+
+        // Signed for a different message, so z no longer matches
+        let other = bip322::sign_full(&private_key, &address, b"other").unwrap();
+        let other = Tx::parse(&mut Cursor::new(decode_base64(&other).unwrap()), false);
+        let mut tampered = tx.clone();
+        tampered.tx_ins[0].script_sig = other.tx_ins[0].script_sig.clone();
+        tampered.tx_ins[0].witness = other.tx_ins[0].witness.clone();
+        assert_eq!(tampered.verify_batched(DerMode::Strict), Err("Invalid signature for input 0".to_string()));
+    }
+}
+
+#[test]
+fn test_witness_round_trip() {
+    // An empty item and a single zero byte are different items, and every
+    // item is written with its length
+    let witness = vec![vec![], vec![0], vec![0x30; 72]];
+    let input = TxInput::new([0x11; 32], [0; 4], Script::new(vec![]), [0xff; 4], Some(witness.clone()), None);
+    let output = TxOutput::new(1000, Script::p2wpkh_script(vec![0x22; 20]));
+    let raw = Tx::new(2, vec![input], vec![output], 0, false, true).serialize();
+    assert!(hex::encode(&raw).contains(&format!("0300010048{}", "30".repeat(72))));
+    let tx = Tx::parse(&mut Cursor::new(raw.clone()), false);
+    assert_eq!(tx.tx_ins[0].witness, Some(witness));
+    assert_eq!(tx.serialize(), raw);
+}
+
+// An unsigned input spending an output that is given to it, so that it can be
+// signed and verified offline
+fn spend_output(prev_tx_id: [u8; 32], index: u32, output: TxOutput) -> TxInput {
+    TxInput::new(prev_tx_id, index.to_le_bytes(), Script::new(vec![]), [0xff; 4], None, None).with_prev_output(output)
+}
+
+#[test]
+fn test_sign_input_low_r() {
+    let key_hex = "0000000000000000000000000000000000000000000000000000000000bee75e";
+    let sec = PrivateKey::from_hex(key_hex).unwrap().point().sec(true);
+
+    for amount in 40_000..40_008 {
+        let unsigned_input = spend_output([0x11; 32], 0, TxOutput::new(50_000, Script::p2pkh_script(hash160(&sec))));
+        let outputs = vec![TxOutput::new(amount, Script::p2pkh_script(hash160(&sec)))];
+        let unsigned_tx = Tx::new(1, vec![unsigned_input.clone()], outputs.clone(), 0, true, false);
+
+        let signed_input = unsigned_tx.sign_input_low_r(0, key_hex, SigHashType::SigHashAll, unsigned_input.clone());
+        // DER signature plus the sighash byte
+        assert!(signed_input.script_sig.commands[0].data().unwrap().len() <= 71);
+        let signed_tx = Tx::new(1, vec![signed_input.clone()], outputs.clone(), 0, true, false);
+        assert!(signed_tx.verify_input(SigHashType::SigHashAll, 0));
+
+        let data = InputSigningData::new(0, key_hex.to_string(), SigHashType::SigHashAll, unsigned_input).with_low_r();
+        let signed_inputs = unsigned_tx.sign_multiple_inputs(vec![data]);
+        assert_eq!(signed_inputs[0].script_sig.commands, signed_input.script_sig.commands);
+    }
+}
+
+#[test]
+fn test_nonce_reuse_audit() {
+    // A key that has been paid to P2PKH and P2WPKH
+    let secret = Scalar::reduce(U256::from(0xc0ffee_u64));
+    let key_hex = hex::encode(secret.to_bytes());
+    let sec = PrivateKey::new(secret.to_u256()).point().sec(true);
+    let outputs = [
+        TxOutput::new(50_000, Script::p2pkh_script(hash160(&sec))),
+        TxOutput::new(50_000, Script::p2pkh_script(hash160(&sec))),
+        TxOutput::new(50_000, Script::p2wpkh_script(hash160(&sec))),
+    ];
+    let spend = |index: u32| spend_output([0x22; 32], index, outputs[index as usize].clone());
+    let pay_to = |amount: u64| vec![TxOutput::new(amount, Script::p2pkh_script(vec![0x33; 20]))];
+
+    // The P2PKH and P2WPKH spends are signed with the same nonce
+    let k = Scalar::reduce(U256::from(0x5eed_u64));
+    let unsigned = Tx::new(1, vec![spend(0)], pay_to(40_000), 0, true, false);
+    let z = unsigned.sig_hash(&SigHashType::SigHashAll, 0, false);
+    let sig = [sign_with_nonce(secret, k, &z).normalize_s().der(), vec![0x01]].concat();
+    let input = TxInput { script_sig: Script::new(vec![Command::Data(sig), Command::Data(sec.clone())]), ..spend(0) };
+    let legacy_tx = Tx::new(1, vec![input], pay_to(40_000), 0, true, false);
+
+    let unsigned = Tx::new(1, vec![spend(2)], pay_to(45_000), 0, true, true);
+    let z = unsigned.sig_hash_bip143(0, None, None);
+    let sig = [sign_with_nonce(secret, k, &z).normalize_s().der(), vec![0x01]].concat();
+    let input = spend(2).set_witness(Some(vec![sig, sec.clone()]));
+    let segwit_tx = Tx::new(1, vec![input], pay_to(45_000), 0, true, true);
+    assert!(segwit_tx.verify());
+
+    // An honest spend of the other output
+    let unsigned = Tx::new(1, vec![spend(1)], pay_to(30_000), 0, true, false);
+    let honest_input = unsigned.sign_input(0, &key_hex, SigHashType::SigHashAll, spend(1));
+    let honest_tx = Tx::new(1, vec![honest_input], pay_to(30_000), 0, true, false);
+
+    assert!(audit_transactions(std::slice::from_ref(&honest_tx)).is_clean());
+
+    let report = audit_transactions(&[legacy_tx.clone(), honest_tx.clone(), segwit_tx.clone(), legacy_tx.clone()]);
+    assert!(!report.is_clean());
+    assert!(report.skipped.is_empty());
+    assert!(report.shared_nonces.is_empty());
+    assert!(report.high_s.is_empty());
+    assert_eq!(report.nonce_reuse.len(), 1);
+    let finding = &report.nonce_reuse[0];
+    assert_eq!(finding.public_key, sec);
+    assert_eq!(finding.private_key.as_ref().unwrap().to_hex(), key_hex);
+    let mut expected = vec![InputRef { txid: legacy_tx.id(), index: 0 }, InputRef { txid: segwit_tx.id(), index: 0 }];
+    expected.sort();
+    assert_eq!(finding.inputs, expected);
+    assert_eq!(report.affected_inputs(), expected);
+
+    // The signatures come out with the sighashes that were signed
+    let extracted = InputSignature::from_input(&segwit_tx, 0).unwrap();
+    assert_eq!(extracted.z, z);
+    assert_eq!(extracted.signature.r(), finding.r);
+
+    // A high s is flagged on its own
+    let z = unsigned.sig_hash(&SigHashType::SigHashAll, 0, false);
+    let low_s = PrivateKey::new(secret.to_u256()).sign(z);
+    let high_s = Signature::new(low_s.r(), (-Scalar::reduce(low_s.s())).to_u256());
+    let input = TxInput { script_sig: Script::new(vec![Command::Data([high_s.der(), vec![0x01]].concat()), Command::Data(sec.clone())]), ..spend(1) };
+    let malleated_tx = Tx::new(1, vec![input], pay_to(30_000), 0, true, false);
+    let report = audit_transactions(std::slice::from_ref(&malleated_tx));
+    assert_eq!(report.high_s, vec![InputRef { txid: malleated_tx.id(), index: 0 }]);
+    assert!(report.nonce_reuse.is_empty());
+
+    // Inputs whose sighash can't be computed are skipped rather than audited
+    // with the wrong z: SIGHASH_SINGLE, a legacy input of a segwit tx, and a
+    // signature that doesn't verify
+    let signed_with_k = |tx: &Tx, sig_hash_type: u8, index: u32| {
+        let z = tx.sig_hash(&SigHashType::SigHashAll, 0, false);
+        let sig = [sign_with_nonce(secret, k, &z).normalize_s().der(), vec![sig_hash_type]].concat();
+        TxInput { script_sig: Script::new(vec![Command::Data(sig), Command::Data(sec.clone())]), ..spend(index) }
+    };
+    let unsigned = Tx::new(1, vec![spend(1)], pay_to(20_000), 0, true, false);
+    let single_tx = Tx::new(1, vec![signed_with_k(&unsigned, 0x03, 1)], pay_to(20_000), 0, true, false);
+    let unsigned = Tx::new(1, vec![spend(1)], pay_to(25_000), 0, true, false);
+    let mixed_input = signed_with_k(&unsigned, 0x01, 1).set_witness(Some(vec![]));
+    let mixed_tx = Tx::new(1, vec![mixed_input], pay_to(25_000), 0, true, true);
+    let unsigned = Tx::new(1, vec![spend(1)], pay_to(35_000), 0, true, false);
+    let forged_tx = Tx::new(1, vec![signed_with_k(&unsigned, 0x01, 1)], pay_to(10_000), 0, true, false);
+    let report = audit_transactions(&[legacy_tx.clone(), single_tx.clone(), mixed_tx.clone(), forged_tx.clone()]);
+    assert!(report.is_clean());
+    let mut expected = vec![
+        InputRef { txid: single_tx.id(), index: 0 },
+        InputRef { txid: mixed_tx.id(), index: 0 },
+        InputRef { txid: forged_tx.id(), index: 0 },
+    ];
+    expected.sort();
+    assert_eq!(report.skipped, expected);
+
+    // An input whose prevout can't be found is skipped
+    let unknown = TxInput::new([0x44; 32], [0; 4], Script::new(vec![Command::Data(vec![0x30; 72]), Command::Data(sec.clone())]), [0xff; 4], None, None);
+    let unknown_tx = Tx::new(1, vec![unknown], pay_to(30_000), 0, true, false);
+    let not_found = |_: &TxInput, _: bool| Err("not found".to_string());
+    let report = audit_transactions_with_lookup(std::slice::from_ref(&unknown_tx), &not_found);
+    assert_eq!(report.skipped, vec![InputRef { txid: unknown_tx.id(), index: 0 }]);
+}
@@ -1,4 +1,4 @@
-use programming_bitcoin::utils::{base58::decode_base58, hash160::hash160, hash256::hash256, rng};
+use programming_bitcoin::utils::{base58::{decode_base58, decode_base58_checksum, encode_base58, encode_base58_checksum}, hash160::hash160, hash256::hash256, rng};
 
 #[test]
 fn random_u256() {
@@ -41,3 +41,14 @@ fn test_hash256_rev() {
     // hash.into_iter().rev().collect() // reverse to get little endian
     println!("Hash256 result (BE): {}", hex::encode(result));
 }
+
+#[test]
+fn test_encode_base58() {
+    assert_eq!(encode_base58(&[]), "");
+    assert_eq!(encode_base58(&[0, 0, 1]), "112");
+    assert_eq!(encode_base58(&hex::decode("7c076ff316692a3d7eb3c3bb0f8b1488cf72e1afcd929e29307032997a838a3d").unwrap()), "9MA8fRQrT4u8Zj8ZRd6MAiiyaxb2Y1CMpvVkHQu5hVM6");
+    // Longer than 32 bytes
+    let payload: Vec<u8> = (0..=64).collect();
+    let encoded = encode_base58_checksum(&payload);
+    assert_eq!(decode_base58_checksum(&encoded).unwrap(), payload);
+}